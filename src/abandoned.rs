@@ -0,0 +1,85 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::forge;
+use crate::setup::Plugin;
+use crate::setup::Setup;
+use crate::state::State;
+use crate::time;
+
+/// How often forges are asked which plugins are archived. Unauthenticated
+/// requests to GitHub's API are limited to 60 an hour, so asking on every
+/// update would soon exhaust them.
+const ARCHIVE_CHECK_INTERVAL: u64 = 24 * 60 * 60;
+
+/// Warns about the plugins that look abandoned: those archived upstream, and
+/// those whose upstream got no commits for longer than `after`. Which plugins
+/// are archived is remembered in `state`, and only asked again once a day.
+pub fn warnings(
+    setup: &Setup,
+    plugins: &[Plugin],
+    after: Duration,
+    now: u64,
+    state: &mut State,
+) -> Vec<String> {
+    let plugins: Vec<&Plugin> = plugins
+        .iter()
+        .filter(|plugin| plugin.is_checkout() && plugin.is_installed())
+        .collect();
+
+    let due = state
+        .last_archive_check
+        .is_none_or(|checked| now.saturating_sub(checked) >= ARCHIVE_CHECK_INTERVAL);
+
+    if due {
+        state.archived = thread::scope(|s| {
+            let checks: Vec<_> = plugins
+                .iter()
+                .map(|plugin| {
+                    s.spawn(|| {
+                        forge::is_archived(setup, &plugin.location)
+                            .unwrap_or(false)
+                            .then(|| plugin.name.clone())
+                    })
+                })
+                .collect();
+
+            checks
+                .into_iter()
+                .filter_map(|check| check.join().ok().flatten())
+                .collect()
+        });
+
+        state.archived.sort();
+        state.last_archive_check = Some(now);
+    }
+
+    let mut warnings = Vec::new();
+
+    for plugin in plugins {
+        if state.archived.contains(&plugin.name) {
+            warnings.push(format!(
+                "{}'s repository is archived upstream; consider dropping it",
+                plugin.name
+            ));
+            continue;
+        }
+
+        let Ok(commit) = plugin.last_upstream_commit() else {
+            continue;
+        };
+
+        let idle = Duration::from_secs(now.saturating_sub(commit));
+
+        if idle > after {
+            warnings.push(format!(
+                "{} got no upstream commits for {}; it may be abandoned",
+                plugin.name,
+                time::describe_duration(idle)
+            ));
+        }
+    }
+
+    warnings.sort();
+    warnings
+}
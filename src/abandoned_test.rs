@@ -0,0 +1,64 @@
+use std::fs;
+use std::time::Duration;
+
+use crate::abandoned;
+use crate::runner_test::FakeRunner;
+use crate::setup::Plugin;
+use crate::setup::Setup;
+use crate::setup_test::pinned_plugin;
+use crate::state::State;
+
+const DAY: u64 = 24 * 60 * 60;
+const NOW: u64 = 1_700_000_000;
+
+/// An installed plugin hosted on GitHub, whose newest commit is `age` old.
+fn plugin(dir: &tempfile::TempDir, name: &str, age: u64) -> Plugin {
+    let repository_path = dir.path().join(name);
+    fs::create_dir_all(&repository_path).unwrap();
+    fs::write(
+        repository_path.join("commit-time.txt"),
+        format!("{}\n", NOW - age),
+    )
+    .unwrap();
+
+    let mut plugin = pinned_plugin(repository_path, dir.path().join("link"), "abc");
+    plugin.name = name.into();
+    plugin.location = format!("https://github.com/someone/{name}");
+    plugin
+}
+
+#[test]
+fn warnings() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let setup = Setup {
+        runner: FakeRunner::new().runner(),
+        ..Setup::default()
+    };
+
+    let plugins = [
+        plugin(&temp_dir, "lively", 10 * DAY),
+        plugin(&temp_dir, "dormant", 400 * DAY),
+        plugin(&temp_dir, "archived-plugin", 10 * DAY),
+    ];
+
+    let mut state = State::default();
+    let year = Duration::from_secs(365 * DAY);
+
+    let expected = vec![
+        "archived-plugin's repository is archived upstream; consider dropping it".to_string(),
+        "dormant got no upstream commits for 1 year; it may be abandoned".to_string(),
+    ];
+
+    assert_eq!(
+        abandoned::warnings(&setup, &plugins, year, NOW, &mut state),
+        expected
+    );
+    assert_eq!(state.archived, vec!["archived-plugin"]);
+    assert_eq!(state.last_archive_check, Some(NOW));
+
+    // Forges aren't asked again within a day, so what they told is kept.
+    state.archived = vec!["lively".into()];
+    let warnings = abandoned::warnings(&setup, &plugins, year, NOW + 3600, &mut state);
+    assert!(warnings[1].starts_with("lively's repository is archived"));
+    assert_eq!(state.last_archive_check, Some(NOW));
+}
@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::os::unix;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::setup::Setup;
+use crate::wizard::Entry;
+
+/// Where a plugin not managed by Almoxarife was found.
+#[derive(Debug, PartialEq)]
+pub enum Origin {
+    /// A checkout inside Kakoune's autoload directory.
+    Autoload,
+    /// A symlink inside Kakoune's autoload directory pointing to a directory
+    /// elsewhere.
+    AutoloadLink(PathBuf),
+    /// A checkout inside the data directory left from a plugin not present in
+    /// the configuration file.
+    DataDir,
+}
+
+/// A plugin that can be adopted.
+#[derive(Debug, PartialEq)]
+pub struct Candidate {
+    pub name: String,
+    pub path: PathBuf,
+    pub location: String,
+    pub origin: Origin,
+}
+
+impl Candidate {
+    pub fn entry(&self) -> Entry {
+        Entry {
+            name: self.name.clone(),
+            location: self.location.clone(),
+            config: String::new(),
+        }
+    }
+
+    /// Moves the checkout into the data directory (or, for symlinks, removes
+    /// them from the autoload directory), so that it can be managed from now
+    /// on.
+    pub fn adopt(&self, setup: &Setup) -> io::Result<()> {
+        match &self.origin {
+            Origin::Autoload => {
+                let destination = setup.almoxarife_data_dir.join(&self.name);
+
+                // `rename` doesn't work across file systems.
+                if fs::rename(&self.path, &destination).is_err() {
+                    copy_dir(&self.path, &destination, &[])?;
+                    fs::remove_dir_all(&self.path)?;
+                }
+
+                Ok(())
+            }
+
+            Origin::AutoloadLink(_) => fs::remove_file(&self.path),
+            Origin::DataDir => Ok(()),
+        }
+    }
+}
+
+/// Looks for git checkouts in the autoload and data directories not managed by
+/// Almoxarife. `managed` has the names of the plugins in the configuration
+/// file.
+pub fn candidates(setup: &Setup, managed: &HashSet<&str>) -> io::Result<Vec<Candidate>> {
+    let mut candidates = Vec::new();
+
+    if let Ok(entries) = fs::read_dir(&setup.autoload_dir) {
+        for entry in entries {
+            let path = entry?.path();
+
+            if path == setup.autoload_plugins_dir || !path.join(".git").exists() {
+                continue;
+            }
+
+            let name = plugin_name(&path);
+
+            if managed.contains(name.as_str()) {
+                continue;
+            }
+
+            let candidate = if path.is_symlink() {
+                let target = fs::canonicalize(&path)?;
+
+                Candidate {
+                    name,
+                    location: target.to_string_lossy().into(),
+                    path,
+                    origin: Origin::AutoloadLink(target),
+                }
+            } else {
+                let Some(location) = remote_location(setup, &path) else {
+                    continue;
+                };
+
+                Candidate {
+                    name,
+                    location,
+                    path,
+                    origin: Origin::Autoload,
+                }
+            };
+
+            candidates.push(candidate);
+        }
+    }
+
+    if let Ok(entries) = fs::read_dir(&setup.almoxarife_data_dir) {
+        for entry in entries {
+            let entry = entry?;
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if managed.contains(name.as_str()) {
+                continue;
+            }
+
+            let Some(location) = remote_location(setup, &path) else {
+                continue;
+            };
+
+            candidates.push(Candidate {
+                name,
+                path,
+                location,
+                origin: Origin::DataDir,
+            });
+        }
+    }
+
+    candidates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(candidates)
+}
+
+/// The name a plugin checked out at `path` will have in the configuration file.
+fn plugin_name(path: &Path) -> String {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    name.strip_suffix(".kak").unwrap_or(&name).to_string()
+}
+
+/// The URL of the `origin` remote of the checkout at `path`, in the format of a
+/// plugin location.
+fn remote_location(setup: &Setup, path: &Path) -> Option<String> {
+    let output = setup
+        .runner
+        .output(
+            Command::new("git")
+                .args(["remote", "get-url", "origin"])
+                .current_dir(path),
+            None,
+        )
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    let url = String::from_utf8_lossy(&output.stdout);
+    Some(location_from_url(url.trim()))
+}
+
+/// Almoxarife appends `.git` to locations when cloning them.
+pub fn location_from_url(url: &str) -> String {
+    url.strip_suffix(".git").unwrap_or(url).to_string()
+}
+
+/// Copies the directory `from` into `to`, except for the entries named in
+/// `exclude`.
+pub fn copy_dir(from: &Path, to: &Path, exclude: &[&str]) -> io::Result<()> {
+    fs::create_dir_all(to)?;
+
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+
+        if exclude
+            .iter()
+            .any(|excluded| entry.file_name() == *excluded)
+        {
+            continue;
+        }
+
+        let file_type = entry.file_type()?;
+        let destination = to.join(entry.file_name());
+
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &destination, exclude)?;
+        } else if file_type.is_symlink() {
+            unix::fs::symlink(fs::read_link(entry.path())?, destination)?;
+        } else {
+            fs::copy(entry.path(), destination)?;
+        }
+    }
+
+    Ok(())
+}
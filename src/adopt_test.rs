@@ -0,0 +1,138 @@
+use std::collections::HashSet;
+use std::fs;
+use std::os::unix;
+use std::path::Path;
+
+use crate::adopt;
+use crate::adopt::Candidate;
+use crate::adopt::Origin;
+use crate::runner_test::FakeRunner;
+use crate::setup::Setup;
+
+fn fake_checkout(path: &Path, remote: Option<&str>) {
+    fs::create_dir_all(path.join(".git")).unwrap();
+
+    if let Some(remote) = remote {
+        fs::write(path.join(".git/almoxarife-test-remote"), remote).unwrap();
+    }
+}
+
+#[test]
+fn candidates() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let autoload_dir = temp_dir.path().join("autoload");
+    let data_dir = temp_dir.path().join("data");
+    let code_dir = temp_dir.path().join("code");
+
+    let setup = Setup {
+        autoload_dir: autoload_dir.clone(),
+        autoload_plugins_dir: autoload_dir.join("almoxarife"),
+        almoxarife_data_dir: data_dir.clone(),
+        runner: FakeRunner::new().runner(),
+        ..Default::default()
+    };
+
+    fake_checkout(
+        &autoload_dir.join("auto-pairs.kak"),
+        Some("https://github.com/alexherbo2/auto-pairs.kak.git\n"),
+    );
+    // Managed plugins are not candidates.
+    fake_checkout(
+        &autoload_dir.join("luar"),
+        Some("https://github.com/gustavo-hms/luar"),
+    );
+    // Neither are directories without a remote.
+    fake_checkout(&autoload_dir.join("scratch"), None);
+    fs::create_dir_all(autoload_dir.join("almoxarife")).unwrap();
+
+    fake_checkout(&code_dir.join("my-scripts"), None);
+    unix::fs::symlink(code_dir.join("my-scripts"), autoload_dir.join("my-scripts")).unwrap();
+
+    fake_checkout(
+        &data_dir.join("peneira"),
+        Some("https://github.com/gustavo-hms/peneira"),
+    );
+
+    let managed: HashSet<_> = ["luar"].into();
+    let candidates = adopt::candidates(&setup, &managed).unwrap();
+
+    assert_eq!(
+        candidates,
+        [
+            Candidate {
+                name: "auto-pairs".into(),
+                path: autoload_dir.join("auto-pairs.kak"),
+                location: "https://github.com/alexherbo2/auto-pairs.kak".into(),
+                origin: Origin::Autoload,
+            },
+            Candidate {
+                name: "my-scripts".into(),
+                path: autoload_dir.join("my-scripts"),
+                location: fs::canonicalize(code_dir.join("my-scripts"))
+                    .unwrap()
+                    .to_string_lossy()
+                    .into(),
+                origin: Origin::AutoloadLink(
+                    fs::canonicalize(code_dir.join("my-scripts")).unwrap()
+                ),
+            },
+            Candidate {
+                name: "peneira".into(),
+                path: data_dir.join("peneira"),
+                location: "https://github.com/gustavo-hms/peneira".into(),
+                origin: Origin::DataDir,
+            },
+        ]
+    );
+}
+
+#[test]
+fn adopt_moves_checkout() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let autoload_dir = temp_dir.path().join("autoload");
+    let data_dir = temp_dir.path().join("data");
+    fs::create_dir_all(&data_dir).unwrap();
+
+    let setup = Setup {
+        autoload_dir: autoload_dir.clone(),
+        almoxarife_data_dir: data_dir.clone(),
+        ..Default::default()
+    };
+
+    fake_checkout(&autoload_dir.join("auto-pairs.kak"), None);
+    fs::write(autoload_dir.join("auto-pairs.kak/auto-pairs.kak"), "").unwrap();
+
+    let candidate = Candidate {
+        name: "auto-pairs".into(),
+        path: autoload_dir.join("auto-pairs.kak"),
+        location: "https://github.com/alexherbo2/auto-pairs.kak".into(),
+        origin: Origin::Autoload,
+    };
+
+    candidate.adopt(&setup).unwrap();
+
+    assert!(!autoload_dir.join("auto-pairs.kak").exists());
+    assert!(data_dir.join("auto-pairs/auto-pairs.kak").exists());
+}
+
+#[test]
+fn adopt_removes_link() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let autoload_dir = temp_dir.path().join("autoload");
+    let code_dir = temp_dir.path().join("code/my-scripts");
+    fs::create_dir_all(&autoload_dir).unwrap();
+    fs::create_dir_all(&code_dir).unwrap();
+    unix::fs::symlink(&code_dir, autoload_dir.join("my-scripts")).unwrap();
+
+    let candidate = Candidate {
+        name: "my-scripts".into(),
+        path: autoload_dir.join("my-scripts"),
+        location: code_dir.to_string_lossy().into(),
+        origin: Origin::AutoloadLink(code_dir.clone()),
+    };
+
+    candidate.adopt(&Setup::default()).unwrap();
+
+    assert!(!autoload_dir.join("my-scripts").exists());
+    assert!(code_dir.exists());
+}
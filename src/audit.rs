@@ -0,0 +1,293 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+
+use crate::kakscript;
+use crate::kakscript::ShellBlock;
+use crate::setup::Plugin;
+
+/// Shell keywords, which come before the commands they control.
+const KEYWORDS: &[&str] = &[
+    "if", "then", "elif", "else", "fi", "do", "done", "while", "until", "!", "time", "exec",
+    "command", "nohup",
+];
+
+/// Words starting what isn't a command, up to the next separator.
+const NOT_COMMANDS: &[&str] = &["for", "case", "esac", "in", "function"];
+
+/// Commands built into POSIX shells, which run nothing else.
+const BUILTINS: &[&str] = &[
+    ".", ":", "[", "[[", "alias", "break", "cd", "continue", "echo", "eval", "exit", "export",
+    "false", "getopts", "local", "printf", "pwd", "read", "readonly", "return", "set", "shift",
+    "test", "trap", "true", "type", "umask", "unset", "wait",
+];
+
+/// Everything the Kakoune scripts of `plugin` can run, as lines to print:
+/// where each `%sh` block is, what runs it, and its code, followed by the
+/// external commands those blocks call.
+///
+/// This is not a shell parser: commands are told apart by the words
+/// starting each line or following a separator, which misses commands built
+/// at run time, like `$cmd`, and may take arguments for commands.
+pub fn audit(plugin: &Plugin) -> io::Result<Vec<String>> {
+    let root = &plugin.repository_path;
+    let mut lines = Vec::new();
+    let mut commands = BTreeSet::new();
+    let mut count = 0;
+
+    for script in kakscript::kak_files(root)? {
+        let source = fs::read_to_string(&script)?;
+        let file = script
+            .strip_prefix(root)
+            .unwrap_or(&script)
+            .to_string_lossy();
+
+        for block in kakscript::shell_blocks(&source) {
+            count += 1;
+            lines.push(format!("{file}:{}, {}:", block.line, context(&block)));
+            lines.extend(indented(&block.code));
+            commands.extend(shell_commands(&block.code));
+        }
+    }
+
+    if count == 0 {
+        return Ok(vec![format!("{} runs no shell code", plugin.name)]);
+    }
+
+    let places = if count == 1 { "place" } else { "places" };
+    lines.insert(
+        0,
+        format!("{} runs shell code in {count} {places}:", plugin.name),
+    );
+
+    if commands.is_empty() {
+        lines.push("External commands: none".to_string());
+    } else {
+        let commands: Vec<String> = commands.into_iter().collect();
+        lines.push(format!("External commands: {}", commands.join(", ")));
+    }
+
+    Ok(lines)
+}
+
+/// What becomes of the output of `block`, told by the command running it.
+fn context(block: &ShellBlock) -> String {
+    let command = block.before.split_whitespace().next().unwrap_or_default();
+
+    match command {
+        "" => "as a value".to_string(),
+        "nop" => "in nop, for its side effects only".to_string(),
+        "evaluate-commands" | "eval" => "in evaluate-commands, its output run".to_string(),
+        command => format!("in {command}"),
+    }
+}
+
+/// The lines of `code`, without the blank ones around them, indented below
+/// the line telling where they are.
+fn indented(code: &str) -> Vec<String> {
+    let lines: Vec<&str> = code
+        .lines()
+        .skip_while(|line| line.trim().is_empty())
+        .collect();
+
+    let end = lines
+        .iter()
+        .rposition(|line| !line.trim().is_empty())
+        .map_or(0, |i| i + 1);
+
+    let margin = lines[..end]
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    lines[..end]
+        .iter()
+        .map(|line| {
+            let line = line.get(margin..).unwrap_or(line.trim_start());
+            format!("    {}", line.trim_end())
+        })
+        .collect()
+}
+
+/// The external commands `code` calls, leaving out builtins and the
+/// functions it defines.
+pub fn shell_commands(code: &str) -> BTreeSet<String> {
+    let mut commands = BTreeSet::new();
+    let mut functions = BTreeSet::new();
+    let mut depth = 0;
+
+    let code = unquoted(code);
+    let mut segment = String::new();
+
+    for c in code.chars() {
+        match c {
+            ';' | '|' | '&' | '\n' | '`' | '{' | '}' => {
+                commands.extend(command(&segment));
+                segment.clear();
+            }
+
+            '(' => {
+                // `name()` defines a function, and `$(` opens a command
+                // substitution, whose command comes after.
+                let words: Vec<&str> = segment.split_whitespace().collect();
+
+                if let [name] = words[..]
+                    && !name.ends_with('$')
+                {
+                    functions.insert(name.to_string());
+                }
+
+                depth += 1;
+                segment.clear();
+            }
+
+            ')' => {
+                // Unbalanced, it ends a pattern of `case`.
+                if depth > 0 {
+                    depth -= 1;
+                    commands.extend(command(&segment));
+                }
+
+                segment.clear();
+            }
+
+            c => segment.push(c),
+        }
+    }
+
+    commands.extend(command(&segment));
+    commands.retain(|command| !functions.contains(command));
+    commands
+}
+
+/// The command `segment`, a simple command, starts with, if it's external.
+fn command(segment: &str) -> Option<String> {
+    let mut words = segment
+        .split_whitespace()
+        .skip_while(|word| KEYWORDS.contains(word) || is_assignment(word));
+
+    let word = words.next()?;
+
+    let plausible = word
+        .chars()
+        .all(|c| c.is_alphanumeric() || "_-./+".contains(c))
+        && !word.starts_with(['-', '.'])
+        && !word.chars().all(|c| c.is_ascii_digit());
+
+    if !plausible || NOT_COMMANDS.contains(&word) || BUILTINS.contains(&word) {
+        return None;
+    }
+
+    Some(word.to_string())
+}
+
+fn is_assignment(word: &str) -> bool {
+    word.split_once('=').is_some_and(|(name, _)| {
+        !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    })
+}
+
+/// `code` with quoted text, comments and here-documents blanked out, since
+/// they hold no commands to run. Quotes become a single `x`, keeping the words
+/// they were part of.
+fn unquoted(code: &str) -> String {
+    let chars: Vec<char> = code.chars().collect();
+    let mut out = String::with_capacity(code.len());
+    let mut heredocs = Vec::new();
+    let mut at_word_start = true;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        match c {
+            '\\' => {
+                i += 1;
+                out.push('x');
+            }
+
+            '\'' | '"' => {
+                i += 1;
+
+                while i < chars.len() && chars[i] != c {
+                    if chars[i] == '\\' && c == '"' {
+                        i += 1;
+                    }
+
+                    i += 1;
+                }
+
+                out.push('x');
+            }
+
+            '#' if at_word_start => {
+                while i + 1 < chars.len() && chars[i + 1] != '\n' {
+                    i += 1;
+                }
+            }
+
+            '<' if chars.get(i + 1) == Some(&'<') => {
+                i += 2;
+
+                if chars.get(i) == Some(&'-') {
+                    i += 1;
+                }
+
+                while chars.get(i) == Some(&' ') {
+                    i += 1;
+                }
+
+                let mut delimiter = String::new();
+
+                while let Some(&next) = chars.get(i) {
+                    if next.is_whitespace() || ";|&".contains(next) {
+                        break;
+                    }
+
+                    if !"'\"\\".contains(next) {
+                        delimiter.push(next);
+                    }
+
+                    i += 1;
+                }
+
+                heredocs.push(delimiter);
+                out.push_str("<< x");
+                at_word_start = false;
+                continue;
+            }
+
+            // The bodies of the here-documents opened in the line follow it,
+            // one after the other.
+            '\n' if !heredocs.is_empty() => {
+                out.push('\n');
+
+                for delimiter in heredocs.drain(..) {
+                    loop {
+                        let start = (i + 1).min(chars.len());
+                        let end = chars[start..]
+                            .iter()
+                            .position(|&c| c == '\n')
+                            .map_or(chars.len(), |n| start + n);
+
+                        let line: String = chars[start..end].iter().collect();
+                        i = end;
+
+                        if line.trim() == delimiter || end == chars.len() {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            c => out.push(c),
+        }
+
+        at_word_start = c.is_whitespace() || ";|&()".contains(c);
+        i += 1;
+    }
+
+    out
+}
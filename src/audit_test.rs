@@ -0,0 +1,73 @@
+use std::collections::BTreeSet;
+use std::fs;
+
+use crate::audit;
+use crate::setup_test::pinned_plugin;
+
+#[test]
+fn audit_shell_blocks() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+    let plugin = pinned_plugin(repository_path.clone(), link_path, "ghijkl");
+
+    fs::create_dir_all(repository_path.join("rc")).unwrap();
+    fs::write(
+        repository_path.join("rc/phantom.kak"),
+        "declare-option str phantom_version %sh{ git describe }
+define-command phantom-update %{
+    nop %sh{
+        curl -s https://example.com/update | tar x
+    }
+    evaluate-commands %sh{
+        printf 'echo %s' \"$kak_selection\"
+    }
+}
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        audit::audit(&plugin).unwrap(),
+        vec![
+            "kakoune-phantom-selection runs shell code in 3 places:",
+            "rc/phantom.kak:1, in declare-option:",
+            "    git describe",
+            "rc/phantom.kak:3, in nop, for its side effects only:",
+            "    curl -s https://example.com/update | tar x",
+            "rc/phantom.kak:6, in evaluate-commands, its output run:",
+            "    printf 'echo %s' \"$kak_selection\"",
+            "External commands: curl, git, tar",
+        ]
+    );
+
+    fs::write(repository_path.join("rc/phantom.kak"), "echo hi").unwrap();
+    assert_eq!(
+        audit::audit(&plugin).unwrap(),
+        vec!["kakoune-phantom-selection runs no shell code"]
+    );
+}
+
+#[test]
+fn shell_commands() {
+    let code = r#"
+        # git is only mentioned here
+        helper() { sed -n 1p; }
+        if command -v fzf >/dev/null; then
+            FZF_DEFAULT_OPTS=--reverse fzf | helper
+        fi
+        case "$1" in
+            start) kak-lsp --kakoune -s "$kak_session" & ;;
+        esac
+        printf '%s\n' "rm -rf /; not run" $(date +%s) `whoami`
+        cat <<EOF
+        set-option buffer filetype lua
+        EOF
+        exec jq . < "$file"
+    "#;
+
+    assert_eq!(
+        audit::shell_commands(code),
+        BTreeSet::from(["cat", "date", "fzf", "jq", "kak-lsp", "sed", "whoami"].map(String::from))
+    );
+}
@@ -0,0 +1,121 @@
+use std::ffi::OsString;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::time::Duration;
+use std::time::SystemTime;
+
+/// Locks older than this were left by a process that died while storing a
+/// file, and are taken over.
+const STALE_LOCK: Duration = Duration::from_secs(10 * 60);
+
+/// The cached copy of the file at `url` with the SHA-256 checksum `sha256`,
+/// kept in the cache directory `dir`, if there's one. Files are only stored
+/// whole, so there's no need to wait for a lock to read them, though they're
+/// still worth checking against their checksum: the cache may be shared with
+/// other machines.
+pub fn lookup(dir: &Path, url: &str, sha256: &str) -> Option<PathBuf> {
+    let entry = entry(dir, url, sha256);
+    entry.is_file().then_some(entry)
+}
+
+/// Stores a copy of `file`, downloaded from `url` and matching `sha256`, in
+/// the cache directory `dir`. The copy is renamed into place once complete,
+/// while holding a lock file, so that machines sharing the cache, as over
+/// NFS, neither read half written files nor write the same one at once. If
+/// another one is already storing it, there's nothing left to do.
+pub fn store(dir: &Path, url: &str, sha256: &str, file: &Path) -> io::Result<()> {
+    let entry = entry(dir, url, sha256);
+
+    if let Some(parent) = entry.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let Some(_lock) = Lock::acquire(&sibling(&entry, "lock"))? else {
+        return Ok(());
+    };
+
+    let temporary = sibling(&entry, "tmp");
+
+    fs::copy(file, &temporary)
+        .and_then(|_| fs::rename(&temporary, &entry))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temporary);
+        })
+}
+
+/// Where the file at `url` with checksum `sha256` is kept: a directory per
+/// checksum, and in it a file per URL with that content, so that both have to
+/// match.
+fn entry(dir: &Path, url: &str, sha256: &str) -> PathBuf {
+    let name: String = url
+        .bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'-' | b'_' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect();
+
+    dir.join(sha256.to_lowercase()).join(name)
+}
+
+/// `entry` with `extension` appended to its name.
+fn sibling(entry: &Path, extension: &str) -> PathBuf {
+    let mut name = OsString::from(entry.file_name().unwrap_or_default());
+    name.push(".");
+    name.push(extension);
+    entry.with_file_name(name)
+}
+
+/// A lock file, removed when dropped.
+struct Lock(PathBuf);
+
+impl Lock {
+    /// Creates the lock file at `path`, unless another process holds it.
+    fn acquire(path: &Path) -> io::Result<Option<Lock>> {
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => {
+                // For whoever finds it left behind.
+                let _ = writeln!(file, "{}", process::id());
+                Ok(Some(Lock(path.into())))
+            }
+
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => {
+                if !is_stale(path) {
+                    return Ok(None);
+                }
+
+                fs::remove_file(path)?;
+
+                match OpenOptions::new().write(true).create_new(true).open(path) {
+                    Ok(_) => Ok(Some(Lock(path.into()))),
+                    // Someone else took it over first.
+                    Err(error) if error.kind() == io::ErrorKind::AlreadyExists => Ok(None),
+                    Err(error) => Err(error),
+                }
+            }
+
+            Err(error) => Err(error),
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn is_stale(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > STALE_LOCK)
+}
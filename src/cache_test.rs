@@ -0,0 +1,80 @@
+use std::fs;
+use std::fs::File;
+use std::time::Duration;
+use std::time::SystemTime;
+
+use crate::cache;
+
+const URL: &str = "https://example.com/raw/smarttab.kak?v=2";
+const SHA256: &str = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+
+#[test]
+fn store_and_lookup() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().join("cache");
+    let file = temp_dir.path().join("smarttab.kak");
+    fs::write(&file, "define-command smarttab %{}").unwrap();
+
+    assert_eq!(cache::lookup(&dir, URL, SHA256), None);
+
+    cache::store(&dir, URL, SHA256, &file).unwrap();
+    let entry = cache::lookup(&dir, URL, SHA256).unwrap();
+    assert_eq!(
+        entry,
+        dir.join(SHA256)
+            .join("https%3A%2F%2Fexample.com%2Fraw%2Fsmarttab.kak%3Fv%3D2")
+    );
+    assert_eq!(
+        fs::read_to_string(&entry).unwrap(),
+        "define-command smarttab %{}"
+    );
+
+    // Both the URL and the checksum have to match.
+    assert_eq!(
+        cache::lookup(&dir, "https://example.com/smarttab.kak", SHA256),
+        None
+    );
+    assert_eq!(cache::lookup(&dir, URL, &"0".repeat(64)), None);
+    assert_eq!(
+        cache::lookup(&dir, URL, &SHA256.to_uppercase()),
+        Some(entry)
+    );
+
+    // Nothing but the entry is left behind.
+    assert_eq!(fs::read_dir(dir.join(SHA256)).unwrap().count(), 1);
+}
+
+#[test]
+fn store_while_locked() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dir = temp_dir.path().join("cache");
+    let file = temp_dir.path().join("smarttab.kak");
+    fs::write(&file, "define-command smarttab %{}").unwrap();
+
+    // Another machine is storing the file already.
+    cache::store(&dir, URL, SHA256, &file).unwrap();
+    let entry = cache::lookup(&dir, URL, SHA256).unwrap();
+    fs::remove_file(&entry).unwrap();
+    let lock = entry.with_file_name(format!(
+        "{}.lock",
+        entry.file_name().unwrap().to_string_lossy()
+    ));
+    fs::write(&lock, "1234\n").unwrap();
+
+    cache::store(&dir, URL, SHA256, &file).unwrap();
+    assert_eq!(cache::lookup(&dir, URL, SHA256), None);
+    assert!(lock.exists());
+
+    // Unless it died long ago.
+    let long_ago = SystemTime::now() - Duration::from_secs(3600);
+    File::options()
+        .write(true)
+        .open(&lock)
+        .unwrap()
+        .set_modified(long_ago)
+        .unwrap();
+
+    cache::store(&dir, URL, SHA256, &file).unwrap();
+    assert_eq!(cache::lookup(&dir, URL, SHA256), Some(entry));
+    assert!(!lock.exists());
+}
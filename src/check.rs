@@ -0,0 +1,88 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::edit;
+use crate::setup::Config;
+
+/// Something in the configuration with no effect, likely a leftover.
+#[derive(Debug, PartialEq)]
+pub struct Smell {
+    /// The file it's in.
+    pub path: PathBuf,
+    /// The line it's at, counting from 1, if it could be found.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl Display for Smell {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let path = self.path.to_string_lossy();
+
+        match self.line {
+            Some(line) => write!(f, "{path}:{line}: {}", self.message),
+            None => write!(f, "{path}: {}", self.message),
+        }
+    }
+}
+
+/// The smells of `config`, read from `source`, the text of the configuration
+/// file at `path`, and of the `al dev` overrides in `overrides_path`, in the
+/// order they appear.
+pub fn smells(config: &Config, source: &str, path: &Path, overrides_path: &Path) -> Vec<Smell> {
+    let at = |name: &str, field: Option<&str>, message: String| Smell {
+        path: path.into(),
+        line: edit::line_of(source, name, field),
+        message,
+    };
+
+    let mut smells: Vec<Smell> = config
+        .unused_configs()
+        .into_iter()
+        .map(|name| {
+            at(
+                name,
+                Some("config"),
+                format!("{name} is disabled, so its config never runs"),
+            )
+        })
+        .chain(config.skipped_children().into_iter().map(|(name, parent)| {
+            at(
+                name,
+                None,
+                format!(
+                    "{name} is left out along with {parent}, which is disabled; \
+                             disable {name} too, or move it out of {parent}"
+                ),
+            )
+        }))
+        .collect();
+
+    smells.sort_by_key(|smell| smell.line);
+
+    let names = config.plugin_names();
+    let overrides = fs::read_to_string(overrides_path).unwrap_or_default();
+
+    smells.extend(
+        config
+            .overrides
+            .0
+            .keys()
+            .filter(|name| !names.contains(name.as_str()))
+            .map(|name| Smell {
+                path: overrides_path.into(),
+                line: overrides
+                    .lines()
+                    .position(|line| line.starts_with(&format!("{name}:")))
+                    .map(|i| i + 1),
+                message: format!(
+                    "{name} is overridden by al dev, but isn't in the configuration file; \
+                     run al dev --reset {name}"
+                ),
+            }),
+    );
+
+    smells
+}
@@ -0,0 +1,72 @@
+use std::fs;
+use std::path::Path;
+
+use crate::check;
+use crate::dev::Overrides;
+use crate::setup::Setup;
+
+const CONFIG: &str = "luar:
+  location: https://github.com/gustavo-hms/luar
+  disabled: true
+  config: set-option global luar_interpreter luajit
+
+  peneira:
+    location: https://github.com/gustavo-hms/peneira
+
+    peneira-filters:
+      location: https://github.com/gustavo-hms/peneira-filters
+
+auto-pairs:
+  location: https://github.com/alexherbo2/auto-pairs.kak
+  disabled: true
+";
+
+#[test]
+fn smells() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let overrides = temp_dir.path().join("dev.yaml");
+    fs::write(
+        &overrides,
+        "auto-pairs: /home/me/auto-pairs\nsearch-highlighter: /home/me/search\n",
+    )
+    .unwrap();
+
+    let setup = Setup::default();
+    let mut config = setup.config_from_buffer(CONFIG.as_bytes()).unwrap();
+    config.overrides = Overrides::load(&overrides).unwrap();
+    let path = Path::new("almoxarife.yaml");
+    let smells: Vec<String> = check::smells(&config, CONFIG, path, &overrides)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    let overrides = overrides.to_string_lossy();
+    assert_eq!(
+        smells,
+        [
+            "almoxarife.yaml:4: luar is disabled, so its config never runs".to_string(),
+            "almoxarife.yaml:6: peneira is left out along with luar, which is disabled; \
+             disable peneira too, or move it out of luar"
+                .into(),
+            "almoxarife.yaml:9: peneira-filters is left out along with luar, which is \
+             disabled; disable peneira-filters too, or move it out of luar"
+                .into(),
+            format!(
+                "{overrides}:2: search-highlighter is overridden by al dev, but isn't in the \
+                 configuration file; run al dev --reset search-highlighter"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn no_smells() {
+    let setup = Setup::default();
+    let source = "luar:\n  location: https://github.com/gustavo-hms/luar\n";
+    let config = setup.config_from_buffer(source.as_bytes()).unwrap();
+
+    assert_eq!(
+        check::smells(&config, source, Path::new("almoxarife.yaml"), Path::new("")),
+        []
+    );
+}
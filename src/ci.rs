@@ -0,0 +1,226 @@
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+
+use crate::report::Change;
+use crate::report::Outcome;
+use crate::report::Reporter;
+use crate::report::Revisions;
+use crate::setup::PluginError;
+
+/// A report for CI systems, written after the update besides the usual one.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Format {
+    /// A JUnit XML file, with one test case per plugin.
+    Junit(PathBuf),
+    /// GitHub Actions workflow commands, annotating failures and warnings.
+    Github,
+}
+
+impl Format {
+    /// Parses `junit=<path>` or `github`.
+    pub fn parse(text: &str) -> Option<Format> {
+        match text.split_once('=') {
+            Some(("junit", path)) if !path.is_empty() => Some(Format::Junit(path.into())),
+            None if text == "github" => Some(Format::Github),
+            _ => None,
+        }
+    }
+}
+
+/// Forwards everything to another reporter, recording what CI reports need.
+pub struct Recorder<'a> {
+    inner: &'a dyn Reporter,
+    statuses: Mutex<Vec<(String, Outcome)>>,
+    warnings: Mutex<Vec<String>>,
+}
+
+impl<'a> Recorder<'a> {
+    pub fn new(inner: &'a dyn Reporter) -> Self {
+        Recorder {
+            inner,
+            statuses: Mutex::new(Vec::new()),
+            warnings: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// The statuses of the plugins, sorted by name, and the warnings, in the
+    /// order they were reported.
+    pub fn into_records(self) -> (Vec<(String, Outcome)>, Vec<String>) {
+        let mut statuses = self
+            .statuses
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        statuses.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let warnings = self
+            .warnings
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        (statuses, warnings)
+    }
+}
+
+impl Reporter for Recorder<'_> {
+    fn status(
+        &self,
+        name: &str,
+        outcome: Outcome,
+        revisions: Revisions,
+        elapsed: Option<Duration>,
+    ) {
+        self.statuses
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push((name.to_string(), outcome));
+
+        self.inner.status(name, outcome, revisions, elapsed);
+    }
+
+    fn building(&self, name: &str) {
+        self.inner.building(name);
+    }
+
+    fn changes(&self, changes: &[Change]) {
+        self.inner.changes(changes);
+    }
+
+    fn timings(&self, timings: &[(String, Duration)]) {
+        self.inner.timings(timings);
+    }
+
+    fn skipped(&self, reason: &str) {
+        self.inner.skipped(reason);
+    }
+
+    fn warnings(&self, warnings: &[String]) {
+        self.warnings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(warnings);
+
+        self.inner.warnings(warnings);
+    }
+
+    fn error(&self, message: &str) {
+        self.inner.error(message);
+    }
+
+    fn progress(&self, message: &str) {
+        self.inner.progress(message);
+    }
+}
+
+/// A JUnit XML document with a test case per plugin. Failed plugins carry the
+/// error, and disabled ones are skipped. `failure` is an error that kept the
+/// update from even starting, like an invalid configuration file, reported as
+/// a failed `setup` test case.
+pub fn junit(
+    statuses: &[(String, Outcome)],
+    errors: &[PluginError],
+    failure: Option<&str>,
+) -> String {
+    let mut cases = String::new();
+
+    if let Some(failure) = failure {
+        cases.push_str(&failed_case("setup", failure));
+    }
+
+    for (name, outcome) in statuses {
+        match outcome {
+            Outcome::Failed => {
+                let description = errors
+                    .iter()
+                    .filter(|error| error.plugin() == name)
+                    .map(PluginError::description)
+                    .collect::<Vec<_>>()
+                    .join("\n");
+
+                cases.push_str(&failed_case(name, &description));
+            }
+
+            Outcome::Disabled | Outcome::ParentFailed | Outcome::Interrupted | Outcome::TimedOut => cases.push_str(&format!(
+                "    <testcase classname=\"almoxarife\" name=\"{}\">\n      <skipped/>\n    </testcase>\n",
+                escape(name)
+            )),
+
+            _ => cases.push_str(&format!(
+                "    <testcase classname=\"almoxarife\" name=\"{}\"/>\n",
+                escape(name)
+            )),
+        }
+    }
+
+    let tests = statuses.len() + usize::from(failure.is_some());
+    let failures = statuses
+        .iter()
+        .filter(|(_, outcome)| *outcome == Outcome::Failed)
+        .count()
+        + usize::from(failure.is_some());
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>
+<testsuites>
+  <testsuite name=\"almoxarife\" tests=\"{tests}\" failures=\"{failures}\">
+{cases}  </testsuite>
+</testsuites>
+"
+    )
+}
+
+fn failed_case(name: &str, description: &str) -> String {
+    let first_line = description.lines().next().unwrap_or_default();
+
+    format!(
+        "    <testcase classname=\"almoxarife\" name=\"{}\">\n      <failure message=\"{}\">{}</failure>\n    </testcase>\n",
+        escape(name),
+        escape(first_line),
+        escape(description)
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// GitHub Actions workflow commands annotating every failure and warning.
+pub fn github_annotations(
+    errors: &[PluginError],
+    warnings: &[String],
+    failure: Option<&str>,
+) -> String {
+    let failure =
+        failure.map(|failure| format!("::error title=almoxarife::{}\n", command_data(failure)));
+
+    let errors = errors.iter().map(|error| {
+        format!(
+            "::error title={}::{}\n",
+            command_property(&format!("almoxarife: {}", error.plugin())),
+            command_data(&error.description())
+        )
+    });
+
+    let warnings = warnings
+        .iter()
+        .map(|warning| format!("::warning title=almoxarife::{}\n", command_data(warning)));
+
+    failure.into_iter().chain(errors).chain(warnings).collect()
+}
+
+/// Escapes the message of a workflow command.
+fn command_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes a property of a workflow command.
+fn command_property(text: &str) -> String {
+    command_data(text).replace(':', "%3A").replace(',', "%2C")
+}
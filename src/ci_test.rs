@@ -0,0 +1,108 @@
+use crate::ci;
+use crate::ci::Format;
+use crate::ci::Recorder;
+use crate::report::Outcome;
+use crate::report::Porcelain;
+use crate::report::Reporter;
+use crate::report::Revisions;
+use crate::setup::PluginError;
+
+#[test]
+fn parse_formats() {
+    assert_eq!(
+        Format::parse("junit=reports/al.xml"),
+        Some(Format::Junit("reports/al.xml".into()))
+    );
+    assert_eq!(Format::parse("github"), Some(Format::Github));
+    assert_eq!(Format::parse("junit="), None);
+    assert_eq!(Format::parse("github=yes"), None);
+    assert_eq!(Format::parse("tap"), None);
+}
+
+#[test]
+fn record_and_forward() {
+    let porcelain = Porcelain::with_writers(Vec::new(), Vec::new());
+    let recorder = Recorder::new(&porcelain);
+
+    recorder.status("peneira", Outcome::Updated, Revisions::new("abcdef"), None);
+    recorder.status("luar", Outcome::Failed, Revisions::default(), None);
+    recorder.warnings(&["luar has no .kak files".into()]);
+
+    let (statuses, warnings) = recorder.into_records();
+    assert_eq!(
+        statuses,
+        vec![
+            ("luar".into(), Outcome::Failed),
+            ("peneira".into(), Outcome::Updated)
+        ]
+    );
+    assert_eq!(warnings, vec!["luar has no .kak files"]);
+
+    let (out, err) = porcelain.into_writers();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "peneira\tupdated\t-\tabcdef\t-\t-\nluar\tfailed\t-\t-\t-\t-\n"
+    );
+    assert_eq!(
+        String::from_utf8(err).unwrap(),
+        "warning: luar has no .kak files\n"
+    );
+}
+
+#[test]
+fn junit() {
+    let statuses = vec![
+        ("luar".into(), Outcome::Failed),
+        ("peneira".into(), Outcome::Unchanged),
+        ("search".into(), Outcome::Disabled),
+    ];
+    let errors = vec![PluginError::Pull(
+        "luar".into(),
+        "git exited with status 1: <remote> hung up".into(),
+    )];
+
+    assert_eq!(
+        ci::junit(&statuses, &errors, None),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="almoxarife" tests="3" failures="1">
+    <testcase classname="almoxarife" name="luar">
+      <failure message="could not update: git exited with status 1: &lt;remote&gt; hung up">could not update: git exited with status 1: &lt;remote&gt; hung up</failure>
+    </testcase>
+    <testcase classname="almoxarife" name="peneira"/>
+    <testcase classname="almoxarife" name="search">
+      <skipped/>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+    );
+
+    assert_eq!(
+        ci::junit(&[], &[], Some("couldn't open almoxarife.yaml")),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites>
+  <testsuite name="almoxarife" tests="1" failures="1">
+    <testcase classname="almoxarife" name="setup">
+      <failure message="couldn't open almoxarife.yaml">couldn't open almoxarife.yaml</failure>
+    </testcase>
+  </testsuite>
+</testsuites>
+"#
+    );
+}
+
+#[test]
+fn github_annotations() {
+    let errors = vec![PluginError::Clone(
+        "luar".into(),
+        "git exited with status 128: repository not found\n".into(),
+    )];
+
+    assert_eq!(
+        ci::github_annotations(&errors, &["100% of luar's maps conflict".into()], None),
+        "::error title=almoxarife%3A luar::could not clone: git exited with status 128: repository not found%0A
+::warning title=almoxarife::100%25 of luar's maps conflict
+"
+    );
+}
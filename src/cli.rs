@@ -0,0 +1,764 @@
+use std::mem;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::ci;
+use crate::graph;
+use crate::schedule::Cadence;
+use crate::time;
+
+/// How long a plugin's upstream may go without commits before `al stats`
+/// flags it as possibly abandoned, unless told otherwise.
+const STALE_AFTER: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// What Almoxarife was asked to do.
+#[derive(Debug, Default, PartialEq)]
+pub enum Command {
+    /// Install, update and remove plugins according to the configuration file.
+    #[default]
+    Update,
+    Adopt,
+    /// List the shell code a plugin's Kakoune scripts run, and the commands
+    /// it calls.
+    Audit(String),
+    /// Write the installed plugins as git bundles to a single archive.
+    Bundle(PathBuf),
+    /// Report what in the configuration has no effect.
+    Check,
+    /// Point a plugin to a local checkout, or back to its upstream source
+    /// when there's no checkout.
+    Dev {
+        name: String,
+        checkout: Option<PathBuf>,
+    },
+    /// Run a command inside the checkout of a plugin.
+    Exec {
+        name: String,
+        command: Vec<String>,
+    },
+    Freeze,
+    /// Link the plugins in `sources` into `out` and write `almoxarife.kak`
+    /// there, using nothing but the given configuration file.
+    Generate {
+        config: PathBuf,
+        sources: PathBuf,
+        out: PathBuf,
+    },
+    /// Print the graph of the plugins, their children and their
+    /// dependencies.
+    Graph(graph::Format),
+    /// Print what's known about a plugin, along with the first section of its
+    /// README when `readme`.
+    Info {
+        name: String,
+        readme: bool,
+    },
+    /// Compact the object stores of the checkouts.
+    Maintain,
+    /// Convert the configuration file to the current format version.
+    Migrate,
+    /// Delete the checkouts of the disabled plugins.
+    PruneDisabled,
+    /// List the plugins in the configuration file and where they come from.
+    List,
+    /// Open the web page of a plugin, or its checkout in Kakoune when `edit`.
+    Open {
+        name: String,
+        edit: bool,
+    },
+    /// Print where a plugin is, or its generated config.
+    Path {
+        name: String,
+        query: PathQuery,
+    },
+    /// Recreate links and `almoxarife.kak` from the existing checkouts.
+    Relink,
+    /// Restore the plugins exported by `al vendor` or `al bundle` from the given
+    /// directory or archive.
+    Restore(PathBuf),
+    /// Put back the `almoxarife.kak` (or `plugins.kak`) from before the last
+    /// update.
+    RestoreScript,
+    Schema,
+    /// Replace the `al` executable by the latest release.
+    SelfUpdate,
+    /// Schedule updates with the given cadence, or remove the schedule.
+    Schedule(Option<Cadence>),
+    /// Print aggregate numbers about the plugins, flagging those whose
+    /// upstream got no commits for longer than `stale_after`.
+    Stats {
+        stale_after: Duration,
+    },
+    /// Report the checkouts that drifted from what the configuration file
+    /// pins and the last update left, resetting them when `fix`.
+    Verify {
+        fix: bool,
+    },
+    /// Export the installed plugins to a directory.
+    Vendor {
+        dir: PathBuf,
+        full_history: bool,
+    },
+    Which(String),
+    /// Explain why a plugin is installed, and what would break without it.
+    Why(String),
+    Version,
+    Help,
+}
+
+/// What `al path` prints about a plugin.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PathQuery {
+    /// The path to its checkout.
+    #[default]
+    Repo,
+    /// The path to its link inside `autoload`.
+    Link,
+    /// The snippet it gets in `almoxarife.kak`.
+    Config,
+}
+
+/// The parsed command line.
+#[derive(Debug, Default, PartialEq)]
+pub struct Args {
+    pub command: Command,
+    /// Open the configuration file before updating plugins.
+    pub edit_config: bool,
+    /// Send a desktop notification summarizing the update.
+    pub notify: bool,
+    /// Update again whenever the configuration file changes.
+    pub watch: bool,
+    /// Only print warnings and errors.
+    pub quiet: bool,
+    /// Also print the plugins that took the longest, and git's output for
+    /// the failures Almoxarife explains.
+    pub verbose: bool,
+    /// The update wasn't explicitly asked for, so it respects
+    /// `auto_update_min_interval`.
+    pub auto: bool,
+    /// Print plugin statuses in the stable `--porcelain` format.
+    pub porcelain: bool,
+    /// Kakoune's configuration directory, overriding the default one.
+    pub kak_config_dir: Option<PathBuf>,
+    /// A separate Kakoune configuration tree to manage instead of the usual
+    /// one.
+    pub target: Option<PathBuf>,
+    /// A directory taking every write, for when Kakoune's configuration
+    /// directory is read-only.
+    pub writable_root: Option<PathBuf>,
+    /// A named set of plugins, with a configuration file of its own, loaded
+    /// in the Kakoune sessions asking for it.
+    pub workspace: Option<String>,
+    /// List plugins grouped by the outcome of the update, instead of as
+    /// they're done.
+    pub group_by_status: bool,
+    /// Replace the files in the way of the plugins' links, instead of
+    /// reporting them.
+    pub force_adopt: bool,
+    /// A report for CI systems to write after the update.
+    pub report: Option<ci::Format>,
+    /// Where to write metrics for node-exporter's textfile collector after
+    /// the update.
+    pub metrics_file: Option<PathBuf>,
+    /// How long the update may take before the plugins not done yet are
+    /// skipped.
+    pub max_time: Option<Duration>,
+    /// Run the build commands of every plugin, even unchanged ones.
+    pub rebuild: bool,
+    /// Only fetch the plugins the last update, interrupted or with failures,
+    /// didn't get done with.
+    pub resume: bool,
+    /// Print commit messages whole, instead of fitting them to the
+    /// terminal's width.
+    pub no_wrap: bool,
+    /// Print the Updates section straight to the terminal, even when it
+    /// doesn't fit on the screen.
+    pub no_pager: bool,
+}
+
+pub const HELP: &str = "A plugin manager for the Kakoune editor.
+
+Usage: al [OPTIONS]
+       al <COMMAND>
+
+Commands:
+ adopt
+        Add plugins installed by hand to the configuration file.
+
+ audit <name>
+        List every `%sh` block the Kakoune scripts of the plugin <name> run,
+        with its code and what runs it, and the external commands those
+        blocks call, to review what the plugin can execute.
+
+ bundle <archive>
+        Write every installed plugin, as a git bundle at its current revision,
+        to the tar file <archive>, along with the configuration file pinning
+        each plugin to that revision. Restore it with `al restore <archive>` on
+        a machine without network access.
+
+ check
+        Report what in the configuration file has no effect, with its line: the
+        config of disabled plugins, plugins left out because a plugin they're
+        nested under is disabled, and plugins overridden by `al dev` that
+        aren't in the configuration file anymore.
+
+ dev <name> <path>
+        Use the checkout at <path> for the plugin <name>, instead of its
+        location, until `al dev --reset <name>`. The override is recorded apart
+        from the configuration file, which is left untouched.
+
+ exec <name> -- <command>...
+        Run <command> inside the checkout of the plugin <name>, as in
+        `al exec peneira -- git log -5` or `al exec peneira -- $SHELL`.
+
+ freeze
+        Pin every installed plugin to its current revision, recording it in the
+        configuration file.
+
+ generate <almoxarife.yaml> <sources> <out>
+        Link the plugins found in <sources>, each in a directory named after it,
+        into <out> and write almoxarife.kak there, without fetching anything
+        nor touching anything outside <out>. Meant for sandboxed builds, like
+        Nix's: <out> replaces the almoxarife directory inside autoload.
+
+ graph [--format dot|mermaid]
+        Print the graph of the plugins, with an arrow from each parent to its
+        children, labeled with the hook loading them, and a dashed one from
+        each plugin to its dependencies, in Graphviz's dot format (the
+        default) or Mermaid's, as in `al graph | dot -Tsvg > plugins.svg`.
+
+ info [--readme] <name>
+        Print where the plugin <name> comes from, where its checkout is, the
+        revision it's at and how long ago it got its last upstream commit. With
+        --readme, also print the first section of its README as plain text,
+        which usually tells how to use it.
+
+ list
+        List the plugins in the configuration file and where they come from,
+        marking the ones pointed to a local checkout by `al dev`, along with
+        how long ago the installed ones got their last upstream commit.
+
+ maintain
+        Compact the git object stores of the installed plugins, with
+        `git gc --auto`, to keep them small and fast. Set
+        `maintenance_interval` to do it periodically after updates.
+
+ migrate
+        Convert the configuration file, in place, to the format version this
+        Almoxarife reads, marking it with a `version:` key.
+
+ open [--edit] <name>
+        Open the web page of the plugin <name>, found from its location, with
+        $BROWSER (or the system's default browser), to read its documentation.
+        With --edit, open a Kakoune client inside its checkout instead, to hack
+        on it.
+
+ path [--repo|--link|--config] <name>
+        Print the path to the checkout of the plugin <name> (the default), the
+        path to its link inside autoload, or the snippet configuring it in
+        almoxarife.kak, for use in scripts and %sh{} blocks, as in
+        `cd $(al path peneira)`.
+
+ prune-disabled
+        Delete the checkouts of the disabled plugins, to free their disk space.
+        They're cloned again once enabled. Set `prune_disabled_after` to do it
+        on updates, for plugins disabled for some time.
+
+ relink
+        Recreate the links in Kakoune's autoload directory and almoxarife.kak
+        from the plugins already installed, without fetching anything. Useful
+        after moving Kakoune's configuration directory or restoring a backup.
+
+ restore <dir|archive>
+        Install the plugins exported by `al vendor` to <dir>, or by `al bundle`
+        to <archive>, along with the configuration file, if there's none yet.
+
+ restore-script
+        Put back the almoxarife.kak (or plugins.kak, with the source
+        integration) from before the last update that changed it. The last 3
+        are kept, and each restore goes one further back.
+
+ schedule [hourly|daily|weekly]
+        Update plugins periodically (daily by default) with a systemd user timer
+        or, on macOS, a launchd agent. Use `al schedule --remove` to stop.
+
+ schema
+        Print a JSON Schema describing the configuration file.
+
+ self-update
+        Replace the `al` executable by the latest release, if it's newer, after
+        checking the download against its published checksum.
+
+ stats [--stale-after <duration>]
+        Print how many plugins there are, how much disk they take, when the
+        last update was and what came of it, and which plugins got no upstream
+        commits for longer than <duration> (like 26w or 180d; a year by
+        default), which may have been abandoned.
+
+ verify [--fix]
+        Check that every checkout is what the configuration file and the last
+        update left: present, without changed files, at the revision its
+        plugin is pinned to, and without commits of its own. With --fix,
+        reset the checkouts that drifted to that state, throwing away their
+        changes and commits.
+
+ vendor [--full-history] <dir>
+        Export the installed plugins to <dir>, to be restored with `al restore`
+        on a machine without network access. Plugins are copied without their
+        git history, unless --full-history is given.
+
+ which <name>
+        Tell which plugin defines a command or an option, or maps a key.
+
+ why <name>
+        Tell why the plugin <name> is installed: whether it's in the
+        configuration file, as a child of another plugin or not, or brought in
+        by the manifest of a plugin depending on it. Also list the plugins that
+        would break without it.
+
+Options:
+ --auto
+        Mark the update as automatic (started by a timer or by Kakoune, for
+        instance): if the last successful update is more recent than
+        `auto_update_min_interval`, nothing is fetched.
+
+ -c, --config
+        Open the configuration file before updating plugins.
+
+ --force-adopt
+        Replace the files and directories found where plugins are linked, like
+        copies of plugins installed by hand, instead of reporting them as
+        conflicts.
+
+ --group-by status
+        List the plugins once the update is done, grouped by what happened to
+        them: updated, installed, removed, unchanged, local, disabled, skipped
+        and failed, each group under a header. Easier to scan than the plugins in
+        the order they're done, when there are many of them.
+
+ --kak-config-dir <dir>
+        Use <dir> as Kakoune's configuration directory, instead of
+        $KAKOUNE_CONFIG_DIR or ~/.config/kak.
+
+ --target <dir>
+        Manage a separate Kakoune configuration tree in <dir>, to try a new set
+        of plugins without touching the usual configuration: the configuration
+        file is <dir>/almoxarife.yaml, plugins are linked into <dir>/autoload,
+        and their checkouts are kept apart from the usual ones. Run Kakoune
+        with KAKOUNE_CONFIG_DIR=<dir> to use it.
+
+ --writable-root <dir>
+        Write everything to <dir>, for when Kakoune's configuration directory
+        is read-only (managed by a dotfiles tool, or on a shared host, say).
+        Plugins are then sourced from <dir>/plugins.kak, which your kakrc has to
+        source; Almoxarife tells you the line to add.
+
+ --workspace <name>
+        Manage the workspace <name>, a set of plugins only some Kakoune sessions
+        need, like a heavy one for writing prose: its configuration file is
+        almoxarife-<name>.yaml, next to almoxarife.yaml, and its plugins are
+        kept apart from the usual ones. They aren't loaded on startup; run
+        almoxarife-workspace <name> in Kakoune to load them.
+
+ --max-time <duration>
+        Give up on the plugins not done after <duration> (like 90s or 2m), so
+        that a login script or a CI job is never held up for long. Skipped
+        plugins keep their current revision and their configuration, and are
+        picked up by the next update.
+
+ --rebuild
+        Run the build commands of the plugins' manifests even for plugins whose
+        revision and build commands didn't change since their last successful
+        build, which are otherwise left alone.
+
+ --resume
+        Pick up where the last update left off, when it was interrupted or some
+        plugins failed, as when the network went away: only the plugins it
+        didn't get done with are fetched, and the report covers the whole
+        update.
+
+ --no-wrap
+        Print the commit messages of the Updates section whole, instead of
+        wrapping them to the terminal's width and cutting the long ones short.
+
+ --no-pager
+        Print the Updates section straight to the terminal. Otherwise, when it
+        doesn't fit on the screen, it goes through $PAGER (less by default), as
+        git does. Watch mode never pages.
+
+ --report junit=<path>|github
+        Besides the usual output, write a report for CI systems: a JUnit XML
+        file at <path>, with a test case per plugin, or GitHub Actions
+        annotations for every failure and warning.
+
+ --metrics-file <path>
+        After the update, write metrics about it to <path>, in the format
+        node-exporter's textfile collector reads: when it finished, how long it
+        took, whether it succeeded and how many plugins were installed, updated
+        and failed. Use a path ending in .prom inside the collector's directory.
+
+ -q, --quiet
+        Only print warnings and errors.
+
+ --notify
+        Send a desktop notification summarizing the update. Useful for
+        unattended runs.
+
+ --porcelain
+        Print one line per plugin, with the tab separated fields name, status,
+        old revision, new revision, the milliseconds managing the plugin took
+        and the tag of plugins just installed (`-` when unknown). Unlike the default
+        output, this format is guaranteed not to change, so it's the one to use
+        in scripts.
+
+ --verbose
+        Also print the plugins that took the longest to install or update, to
+        spot the ones worth freezing or dropping, and how long ago the
+        unchanged ones got their last upstream commit. Failures of git that
+        Almoxarife explains, like a repository not found, also come with git's
+        own output.
+
+ -w, --watch
+        Keep running, updating plugins whenever the configuration file changes.
+        If every plugin is already installed, nothing is fetched: only
+        almoxarife.kak is regenerated.
+
+ -v, --version
+        Print Almoxarife version.
+
+ -h, --help
+        Print this help message.
+
+Running al without any extra option will update your plugins according to the
+configuration file.";
+
+/// Options taking a value.
+const VALUE_OPTIONS: [&str; 10] = [
+    "--format",
+    "--group-by",
+    "--kak-config-dir",
+    "--max-time",
+    "--target",
+    "--writable-root",
+    "--workspace",
+    "--report",
+    "--metrics-file",
+    "--stale-after",
+];
+
+/// Parses the command line arguments, not including the program name.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut positionals = Vec::new();
+    let mut remove = false;
+    let mut reset = false;
+    let mut full_history = false;
+    let mut edit = false;
+    let mut readme = false;
+    let mut fix = false;
+    let mut queries = Vec::new();
+    let mut stale_after = None;
+    let mut graph_format = None;
+    // Everything after `--`, which isn't taken as options.
+    let mut trailing = Vec::new();
+
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            trailing.extend(args.by_ref());
+            break;
+        }
+
+        // Options taking a value accept both `--option value` and
+        // `--option=value`.
+        let (option, inline) = match arg.split_once('=') {
+            Some((option, value)) if VALUE_OPTIONS.contains(&option) => (option, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+
+        let mut value = |what: &str| -> Result<String, String> {
+            inline
+                .map(String::from)
+                .or_else(|| args.next())
+                .ok_or_else(|| format!("{option} needs {what}"))
+        };
+
+        match option {
+            "--kak-config-dir" => parsed.kak_config_dir = Some(value("a directory")?.into()),
+            "--target" => parsed.target = Some(value("a directory")?.into()),
+            "--writable-root" => parsed.writable_root = Some(value("a directory")?.into()),
+            "--workspace" => parsed.workspace = Some(value("a name")?),
+            "--metrics-file" => parsed.metrics_file = Some(value("a path")?.into()),
+            "--stale-after" => {
+                stale_after = Some(time::parse_duration(&value("a duration")?)?);
+            }
+            "--format" => {
+                let format = value("a format")?;
+
+                graph_format = Some(
+                    graph::Format::parse(&format)
+                        .ok_or_else(|| format!("unknown format {format}; use dot or mermaid"))?,
+                );
+            }
+            "--max-time" => {
+                let max_time = time::parse_duration(&value("a duration")?)?;
+
+                if max_time.is_zero() {
+                    return Err("--max-time must be longer than 0s".to_string());
+                }
+
+                parsed.max_time = Some(max_time);
+            }
+            "--group-by" => {
+                let grouping = value("a grouping")?;
+
+                if grouping != "status" {
+                    return Err(format!("unknown grouping {grouping}; use status"));
+                }
+
+                parsed.group_by_status = true;
+            }
+            "--report" => {
+                let format = value("a format")?;
+
+                parsed.report = Some(ci::Format::parse(&format).ok_or_else(|| {
+                    format!("unknown report {format}; use junit=<path> or github")
+                })?);
+            }
+
+            "-c" | "--config" => parsed.edit_config = true,
+            "--auto" => parsed.auto = true,
+            "--force-adopt" => parsed.force_adopt = true,
+            "--rebuild" => parsed.rebuild = true,
+            "--resume" => parsed.resume = true,
+            "--no-wrap" => parsed.no_wrap = true,
+            "--no-pager" => parsed.no_pager = true,
+            "--notify" => parsed.notify = true,
+            "--porcelain" => parsed.porcelain = true,
+            "-w" | "--watch" => parsed.watch = true,
+            "-q" | "--quiet" => parsed.quiet = true,
+            "--verbose" => parsed.verbose = true,
+            "--remove" => remove = true,
+            "--reset" => reset = true,
+            "--full-history" => full_history = true,
+            "--edit" => edit = true,
+            "--readme" => readme = true,
+            "--fix" => fix = true,
+            "--repo" => queries.push(PathQuery::Repo),
+            "--link" => queries.push(PathQuery::Link),
+            "-v" | "--version" => parsed.command = Command::Version,
+            "-h" | "--help" => parsed.command = Command::Help,
+            option if option.starts_with('-') => return Err(format!("unknown option {option}")),
+            _ => positionals.push(arg.clone()),
+        }
+    }
+
+    // Asking for help or for the version takes precedence over any command.
+    if parsed.command != Command::Update {
+        return Ok(parsed);
+    }
+
+    if parsed.group_by_status && parsed.porcelain {
+        return Err("--group-by can't be used with --porcelain".to_string());
+    }
+
+    if parsed.metrics_file.is_some() && parsed.watch {
+        return Err("--metrics-file can't be used with --watch".to_string());
+    }
+
+    let mut positionals = positionals.into_iter();
+
+    let command = positionals.next();
+
+    parsed.command = match command.as_deref() {
+        // Without a command, plugins are updated, which options meant for
+        // other commands make no sense with.
+        None => Command::Update,
+        Some("adopt") => Command::Adopt,
+        Some("audit") => Command::Audit(
+            positionals
+                .next()
+                .ok_or_else(|| "usage: al audit <name>".to_string())?,
+        ),
+        Some("check") => Command::Check,
+        Some("bundle") => Command::Bundle(
+            positionals
+                .next()
+                .ok_or_else(|| "usage: al bundle <archive>".to_string())?
+                .into(),
+        ),
+        Some("dev") => {
+            let usage = || "usage: al dev <name> <path> or al dev --reset <name>".to_string();
+            let name = positionals.next().ok_or_else(usage)?;
+
+            let checkout = if reset {
+                None
+            } else {
+                Some(positionals.next().ok_or_else(usage)?.into())
+            };
+
+            Command::Dev { name, checkout }
+        }
+        Some("exec") => {
+            let usage = || "usage: al exec <name> -- <command>...".to_string();
+            let name = positionals.next().ok_or_else(usage)?;
+            let command: Vec<String> = positionals.by_ref().chain(trailing.drain(..)).collect();
+
+            if command.is_empty() {
+                return Err(usage());
+            }
+
+            Command::Exec { name, command }
+        }
+        Some("freeze") => Command::Freeze,
+        Some("generate") => {
+            let usage = || "usage: al generate <almoxarife.yaml> <sources> <out>".to_string();
+
+            Command::Generate {
+                config: positionals.next().ok_or_else(usage)?.into(),
+                sources: positionals.next().ok_or_else(usage)?.into(),
+                out: positionals.next().ok_or_else(usage)?.into(),
+            }
+        }
+        Some("graph") => Command::Graph(graph_format.take().unwrap_or_default()),
+        Some("info") => Command::Info {
+            name: positionals
+                .next()
+                .ok_or_else(|| "usage: al info [--readme] <name>".to_string())?,
+            readme,
+        },
+        Some("list") => Command::List,
+        Some("migrate") => Command::Migrate,
+        Some("path") => {
+            let name = positionals
+                .next()
+                .ok_or_else(|| "usage: al path [--repo|--link|--config] <name>".to_string())?;
+
+            // With path, --config asks for the plugin's config instead of
+            // opening the configuration file.
+            if mem::take(&mut parsed.edit_config) {
+                queries.push(PathQuery::Config);
+            }
+
+            if queries.len() > 1 {
+                return Err("use only one of --repo, --link and --config".to_string());
+            }
+
+            Command::Path {
+                name,
+                query: queries.pop().unwrap_or_default(),
+            }
+        }
+        Some("maintain") => Command::Maintain,
+        Some("open") => Command::Open {
+            name: positionals
+                .next()
+                .ok_or_else(|| "usage: al open [--edit] <name>".to_string())?,
+            edit,
+        },
+        Some("prune-disabled") => Command::PruneDisabled,
+        Some("relink") => Command::Relink,
+        Some("restore") => Command::Restore(
+            positionals
+                .next()
+                .ok_or_else(|| "usage: al restore <dir|archive>".to_string())?
+                .into(),
+        ),
+        Some("restore-script") => Command::RestoreScript,
+        Some("schema") => Command::Schema,
+        Some("self-update") => Command::SelfUpdate,
+        Some("schedule") if remove => Command::Schedule(None),
+        Some("schedule") => {
+            let cadence = positionals.next().unwrap_or("daily".to_string());
+
+            Command::Schedule(Some(Cadence::parse(&cadence).ok_or_else(|| {
+                format!("unknown cadence {cadence}; use hourly, daily or weekly")
+            })?))
+        }
+        Some("stats") => Command::Stats {
+            stale_after: stale_after.take().unwrap_or(STALE_AFTER),
+        },
+        Some("verify") => Command::Verify { fix },
+        Some("vendor") => Command::Vendor {
+            dir: positionals
+                .next()
+                .ok_or_else(|| "usage: al vendor [--full-history] <dir>".to_string())?
+                .into(),
+            full_history,
+        },
+        Some("which") => Command::Which(
+            positionals
+                .next()
+                .ok_or_else(|| "usage: al which <name>".to_string())?,
+        ),
+        Some("why") => Command::Why(
+            positionals
+                .next()
+                .ok_or_else(|| "usage: al why <name>".to_string())?,
+        ),
+        Some(command) => return Err(format!("unknown command {command}")),
+    };
+
+    if remove && parsed.command != Command::Schedule(None) {
+        return Err("--remove can only be used with schedule".to_string());
+    }
+
+    if reset && !matches!(parsed.command, Command::Dev { .. }) {
+        return Err("--reset can only be used with dev".to_string());
+    }
+
+    if !queries.is_empty() {
+        return Err("--repo and --link can only be used with path".to_string());
+    }
+
+    if stale_after.is_some() {
+        return Err("--stale-after can only be used with stats".to_string());
+    }
+
+    if graph_format.is_some() {
+        return Err("--format can only be used with graph".to_string());
+    }
+
+    if parsed.group_by_status && !matches!(parsed.command, Command::Update | Command::Relink) {
+        return Err("--group-by can only be used when updating or relinking".to_string());
+    }
+
+    if parsed.rebuild && parsed.command != Command::Update {
+        return Err("--rebuild can only be used when updating".to_string());
+    }
+
+    if parsed.resume && parsed.command != Command::Update {
+        return Err("--resume can only be used when updating".to_string());
+    }
+
+    if parsed.max_time.is_some() && parsed.command != Command::Update {
+        return Err("--max-time can only be used when updating".to_string());
+    }
+
+    if parsed.metrics_file.is_some() && parsed.command != Command::Update {
+        return Err("--metrics-file can only be used when updating".to_string());
+    }
+
+    if edit && !matches!(parsed.command, Command::Open { .. }) {
+        return Err("--edit can only be used with open".to_string());
+    }
+
+    if readme && !matches!(parsed.command, Command::Info { .. }) {
+        return Err("--readme can only be used with info".to_string());
+    }
+
+    if fix && !matches!(parsed.command, Command::Verify { .. }) {
+        return Err("--fix can only be used with verify".to_string());
+    }
+
+    if full_history && !matches!(parsed.command, Command::Vendor { .. }) {
+        return Err("--full-history can only be used with vendor".to_string());
+    }
+
+    if let Some(extra) = positionals.next().or_else(|| trailing.into_iter().next()) {
+        return Err(format!("unexpected argument {extra}"));
+    }
+
+    Ok(parsed)
+}
@@ -0,0 +1,470 @@
+use std::time::Duration;
+
+use crate::ci;
+use crate::cli;
+use crate::cli::Args;
+use crate::cli::Command;
+use crate::cli::PathQuery;
+use crate::graph;
+use crate::schedule::Cadence;
+
+fn parse(args: &[&str]) -> Result<Args, String> {
+    cli::parse(args.iter().map(|arg| arg.to_string()))
+}
+
+#[test]
+fn parse_no_arguments() {
+    assert_eq!(parse(&[]), Ok(Args::default()));
+}
+
+#[test]
+fn parse_options() {
+    assert_eq!(
+        parse(&["--notify", "-c", "--watch", "--auto"]),
+        Ok(Args {
+            command: Command::Update,
+            edit_config: true,
+            notify: true,
+            watch: true,
+            quiet: false,
+            verbose: false,
+            auto: true,
+            porcelain: false,
+            kak_config_dir: None,
+            target: None,
+            writable_root: None,
+            workspace: None,
+            group_by_status: false,
+            force_adopt: false,
+            report: None,
+            metrics_file: None,
+            max_time: None,
+            rebuild: false,
+            resume: false,
+            no_wrap: false,
+            no_pager: false,
+        })
+    );
+
+    assert_eq!(parse(&["schema", "--help"]).unwrap().command, Command::Help);
+    assert_eq!(parse(&["-v"]).unwrap().command, Command::Version);
+    assert_eq!(
+        parse(&["self-update"]).unwrap().command,
+        Command::SelfUpdate
+    );
+    assert_eq!(parse(&["migrate"]).unwrap().command, Command::Migrate);
+    assert_eq!(parse(&["check"]).unwrap().command, Command::Check);
+    assert_eq!(parse(&["maintain"]).unwrap().command, Command::Maintain);
+    assert_eq!(
+        parse(&["open", "peneira"]).unwrap().command,
+        Command::Open {
+            name: "peneira".into(),
+            edit: false
+        }
+    );
+    assert_eq!(
+        parse(&["open", "--edit", "peneira"]).unwrap().command,
+        Command::Open {
+            name: "peneira".into(),
+            edit: true
+        }
+    );
+    assert_eq!(
+        parse(&["open"]),
+        Err("usage: al open [--edit] <name>".into())
+    );
+    assert_eq!(
+        parse(&["info", "--readme", "peneira"]).unwrap().command,
+        Command::Info {
+            name: "peneira".into(),
+            readme: true
+        }
+    );
+    assert_eq!(
+        parse(&["info"]),
+        Err("usage: al info [--readme] <name>".into())
+    );
+    assert_eq!(
+        parse(&["verify", "--fix"]).unwrap().command,
+        Command::Verify { fix: true }
+    );
+    assert_eq!(
+        parse(&["list", "--fix"]),
+        Err("--fix can only be used with verify".into())
+    );
+    assert_eq!(
+        parse(&["list", "--readme"]),
+        Err("--readme can only be used with info".into())
+    );
+    assert_eq!(
+        parse(&["list", "--edit"]),
+        Err("--edit can only be used with open".into())
+    );
+    assert_eq!(
+        parse(&["prune-disabled"]).unwrap().command,
+        Command::PruneDisabled
+    );
+    assert_eq!(
+        parse(&["restore-script"]).unwrap().command,
+        Command::RestoreScript
+    );
+    assert!(parse(&["--porcelain"]).unwrap().porcelain);
+    assert!(parse(&["--verbose"]).unwrap().verbose);
+    assert!(parse(&["--force-adopt"]).unwrap().force_adopt);
+    assert_eq!(
+        parse(&["--max-time", "2m"]).unwrap().max_time,
+        Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+        parse(&["--max-time", "0s"]),
+        Err("--max-time must be longer than 0s".into())
+    );
+    assert_eq!(
+        parse(&["list", "--max-time", "90s"]),
+        Err("--max-time can only be used when updating".into())
+    );
+    assert!(parse(&["--group-by", "status"]).unwrap().group_by_status);
+    assert!(
+        parse(&["relink", "--group-by=status"])
+            .unwrap()
+            .group_by_status
+    );
+    assert_eq!(
+        parse(&["--group-by", "name"]),
+        Err("unknown grouping name; use status".into())
+    );
+    assert_eq!(
+        parse(&["list", "--group-by", "status"]),
+        Err("--group-by can only be used when updating or relinking".into())
+    );
+    assert_eq!(
+        parse(&["--porcelain", "--group-by", "status"]),
+        Err("--group-by can't be used with --porcelain".into())
+    );
+    assert_eq!(
+        parse(&["--report", "junit=al.xml"]).unwrap().report,
+        Some(ci::Format::Junit("al.xml".into()))
+    );
+    assert_eq!(
+        parse(&["--report=github"]).unwrap().report,
+        Some(ci::Format::Github)
+    );
+    assert_eq!(
+        parse(&["--auto", "--metrics-file", "al.prom"])
+            .unwrap()
+            .metrics_file,
+        Some("al.prom".into())
+    );
+    assert_eq!(
+        parse(&["list", "--metrics-file", "al.prom"]),
+        Err("--metrics-file can only be used when updating".into())
+    );
+    assert_eq!(
+        parse(&["--watch", "--metrics-file=al.prom"]),
+        Err("--metrics-file can't be used with --watch".into())
+    );
+    assert_eq!(
+        parse(&["--writable-root", "/tmp/al"])
+            .unwrap()
+            .writable_root,
+        Some("/tmp/al".into())
+    );
+    assert_eq!(
+        parse(&["--kak-config-dir", "/tmp/kak"])
+            .unwrap()
+            .kak_config_dir,
+        Some("/tmp/kak".into())
+    );
+    assert_eq!(
+        parse(&["--kak-config-dir=/tmp/kak", "relink"])
+            .unwrap()
+            .kak_config_dir,
+        Some("/tmp/kak".into())
+    );
+}
+
+#[test]
+fn parse_commands() {
+    assert_eq!(parse(&["adopt"]).unwrap().command, Command::Adopt);
+    assert_eq!(parse(&["relink"]).unwrap().command, Command::Relink);
+    assert_eq!(parse(&["list"]).unwrap().command, Command::List);
+    assert_eq!(
+        parse(&["dev", "peneira", "~/src/peneira"]).unwrap().command,
+        Command::Dev {
+            name: "peneira".into(),
+            checkout: Some("~/src/peneira".into())
+        }
+    );
+    assert_eq!(
+        parse(&["dev", "--reset", "peneira"]).unwrap().command,
+        Command::Dev {
+            name: "peneira".into(),
+            checkout: None
+        }
+    );
+    assert_eq!(
+        parse(&["bundle", "plugins.tar"]).unwrap().command,
+        Command::Bundle("plugins.tar".into())
+    );
+    assert_eq!(
+        parse(&["vendor", "--full-history", "/mnt/usb"])
+            .unwrap()
+            .command,
+        Command::Vendor {
+            dir: "/mnt/usb".into(),
+            full_history: true
+        }
+    );
+    assert_eq!(
+        parse(&["restore", "/mnt/usb"]).unwrap().command,
+        Command::Restore("/mnt/usb".into())
+    );
+    assert_eq!(
+        parse(&["schedule"]).unwrap().command,
+        Command::Schedule(Some(Cadence::Daily))
+    );
+    assert_eq!(
+        parse(&["schedule", "weekly"]).unwrap().command,
+        Command::Schedule(Some(Cadence::Weekly))
+    );
+    assert_eq!(
+        parse(&["schedule", "--remove"]).unwrap().command,
+        Command::Schedule(None)
+    );
+    assert_eq!(
+        parse(&["which", "peneira-files"]).unwrap().command,
+        Command::Which("peneira-files".into())
+    );
+    assert_eq!(
+        parse(&["why", "luar"]).unwrap().command,
+        Command::Why("luar".into())
+    );
+    assert_eq!(
+        parse(&["audit", "kak-lsp"]).unwrap().command,
+        Command::Audit("kak-lsp".into())
+    );
+    assert_eq!(
+        parse(&["graph"]).unwrap().command,
+        Command::Graph(graph::Format::Dot)
+    );
+    assert_eq!(
+        parse(&["graph", "--format=mermaid"]).unwrap().command,
+        Command::Graph(graph::Format::Mermaid)
+    );
+    assert_eq!(
+        parse(&["exec", "kak-lsp", "--", "git", "log", "-5"])
+            .unwrap()
+            .command,
+        Command::Exec {
+            name: "kak-lsp".into(),
+            command: vec!["git".into(), "log".into(), "-5".into()],
+        }
+    );
+    assert_eq!(
+        parse(&["stats"]).unwrap().command,
+        Command::Stats {
+            stale_after: Duration::from_secs(365 * 24 * 60 * 60)
+        }
+    );
+    assert_eq!(
+        parse(&["stats", "--stale-after", "26w"]).unwrap().command,
+        Command::Stats {
+            stale_after: Duration::from_secs(26 * 7 * 24 * 60 * 60)
+        }
+    );
+    assert_eq!(
+        parse(&["path", "peneira"]).unwrap().command,
+        Command::Path {
+            name: "peneira".into(),
+            query: PathQuery::Repo,
+        }
+    );
+    assert_eq!(
+        parse(&["path", "--link", "peneira"]).unwrap().command,
+        Command::Path {
+            name: "peneira".into(),
+            query: PathQuery::Link,
+        }
+    );
+
+    let args = parse(&["path", "peneira", "--config"]).unwrap();
+    assert_eq!(
+        args.command,
+        Command::Path {
+            name: "peneira".into(),
+            query: PathQuery::Config,
+        }
+    );
+    assert!(!args.edit_config);
+    assert_eq!(
+        parse(&["exec", "peneira", "ls"]).unwrap().command,
+        Command::Exec {
+            name: "peneira".into(),
+            command: vec!["ls".into()],
+        }
+    );
+    assert_eq!(
+        parse(&["generate", "almoxarife.yaml", "/nix/store/plugins", "out"])
+            .unwrap()
+            .command,
+        Command::Generate {
+            config: "almoxarife.yaml".into(),
+            sources: "/nix/store/plugins".into(),
+            out: "out".into(),
+        }
+    );
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(
+        parse(&["--target=~/kak-testing"]).unwrap().target,
+        Some("~/kak-testing".into())
+    );
+    assert_eq!(
+        parse(&["--workspace", "writing"]).unwrap().workspace,
+        Some("writing".into())
+    );
+    assert_eq!(
+        parse(&["--workspace"]),
+        Err("--workspace needs a name".into())
+    );
+    assert_eq!(
+        parse(&["--notify=yes"]),
+        Err("unknown option --notify=yes".into())
+    );
+    assert_eq!(
+        parse(&["--full-history", "adopt"]),
+        Err("--full-history can only be used with vendor".into())
+    );
+    assert_eq!(
+        parse(&["dev", "peneira"]),
+        Err("usage: al dev <name> <path> or al dev --reset <name>".into())
+    );
+    assert_eq!(
+        parse(&["--reset", "relink"]),
+        Err("--reset can only be used with dev".into())
+    );
+    assert_eq!(
+        parse(&["vendor"]),
+        Err("usage: al vendor [--full-history] <dir>".into())
+    );
+    assert_eq!(
+        parse(&["--report", "html"]),
+        Err("unknown report html; use junit=<path> or github".into())
+    );
+    assert_eq!(parse(&["--report"]), Err("--report needs a format".into()));
+    assert_eq!(
+        parse(&["--kak-config-dir"]),
+        Err("--kak-config-dir needs a directory".into())
+    );
+    assert_eq!(
+        parse(&["--frobnicate"]),
+        Err("unknown option --frobnicate".into())
+    );
+    assert_eq!(
+        parse(&["frobnicate"]),
+        Err("unknown command frobnicate".into())
+    );
+    assert_eq!(parse(&["which"]), Err("usage: al which <name>".into()));
+    assert_eq!(parse(&["why"]), Err("usage: al why <name>".into()));
+    assert_eq!(parse(&["audit"]), Err("usage: al audit <name>".into()));
+    assert!(parse(&["--rebuild"]).unwrap().rebuild);
+    assert_eq!(
+        parse(&["relink", "--rebuild"]),
+        Err("--rebuild can only be used when updating".into())
+    );
+    assert!(parse(&["--resume"]).unwrap().resume);
+    assert_eq!(
+        parse(&["list", "--resume"]),
+        Err("--resume can only be used when updating".into())
+    );
+    assert_eq!(
+        parse(&["graph", "--format", "svg"]),
+        Err("unknown format svg; use dot or mermaid".into())
+    );
+    assert_eq!(
+        parse(&["list", "--format", "dot"]),
+        Err("--format can only be used with graph".into())
+    );
+    assert_eq!(
+        parse(&["generate", "almoxarife.yaml"]),
+        Err("usage: al generate <almoxarife.yaml> <sources> <out>".into())
+    );
+    assert_eq!(
+        parse(&["exec", "peneira", "--"]),
+        Err("usage: al exec <name> -- <command>...".into())
+    );
+    assert_eq!(
+        parse(&["--stale-after=2d", "list"]),
+        Err("--stale-after can only be used with stats".into())
+    );
+    assert_eq!(
+        parse(&["path", "--repo", "--link", "peneira"]),
+        Err("use only one of --repo, --link and --config".into())
+    );
+    assert_eq!(
+        parse(&["--link", "list"]),
+        Err("--repo and --link can only be used with path".into())
+    );
+    assert_eq!(
+        parse(&["list", "--", "-5"]),
+        Err("unexpected argument -5".into())
+    );
+    assert_eq!(
+        parse(&["schema", "json"]),
+        Err("unexpected argument json".into())
+    );
+}
+
+#[test]
+fn parse_options_for_other_commands_when_updating() {
+    assert_eq!(
+        parse(&["--remove"]),
+        Err("--remove can only be used with schedule".into())
+    );
+    assert_eq!(
+        parse(&["--reset"]),
+        Err("--reset can only be used with dev".into())
+    );
+    assert_eq!(
+        parse(&["--fix"]),
+        Err("--fix can only be used with verify".into())
+    );
+    assert_eq!(
+        parse(&["--edit"]),
+        Err("--edit can only be used with open".into())
+    );
+    assert_eq!(
+        parse(&["--readme"]),
+        Err("--readme can only be used with info".into())
+    );
+    assert_eq!(
+        parse(&["--full-history"]),
+        Err("--full-history can only be used with vendor".into())
+    );
+    assert_eq!(
+        parse(&["--stale-after", "30d"]),
+        Err("--stale-after can only be used with stats".into())
+    );
+    assert_eq!(
+        parse(&["--format", "dot"]),
+        Err("--format can only be used with graph".into())
+    );
+    assert_eq!(
+        parse(&["--repo"]),
+        Err("--repo and --link can only be used with path".into())
+    );
+    assert_eq!(
+        parse(&["--link"]),
+        Err("--repo and --link can only be used with path".into())
+    );
+
+    // Options for updating are still fine.
+    let args = parse(&["--max-time", "90s", "--metrics-file", "al.prom"]).unwrap();
+    assert_eq!(args.command, Command::Update);
+    assert_eq!(
+        parse(&["--", "extra"]),
+        Err("unexpected argument extra".into())
+    );
+}
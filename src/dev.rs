@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// Plugins temporarily pointed to a local checkout by `al dev`, from their
+/// names to the checkouts. They're kept apart from `almoxarife.yaml`, which
+/// goes on describing the upstream sources.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Overrides(pub BTreeMap<String, PathBuf>);
+
+impl Overrides {
+    /// Loads the overrides from `path`. A missing file means there are none.
+    pub fn load(path: &Path) -> io::Result<Overrides> {
+        match fs::read_to_string(path) {
+            Ok(yaml) => serde_yaml::from_str(&yaml).map_err(io::Error::other),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Overrides::default()),
+            Err(error) => Err(error),
+        }
+    }
+
+    /// Saves the overrides to `path`, removing the file if there are none
+    /// left.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if self.0.is_empty() {
+            return match fs::remove_file(path) {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+                _ => Ok(()),
+            };
+        }
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let yaml = serde_yaml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, yaml)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PathBuf> {
+        self.0.get(name)
+    }
+}
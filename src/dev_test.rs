@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use tempfile::TempDir;
+
+use crate::dev::Overrides;
+
+#[test]
+fn save_and_load() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("state/dev.yaml");
+
+    // No file means no overrides.
+    assert_eq!(Overrides::load(&path).unwrap(), Overrides::default());
+
+    let mut overrides = Overrides::default();
+    overrides
+        .0
+        .insert("peneira".into(), PathBuf::from("/home/me/src/peneira"));
+    overrides.save(&path).unwrap();
+
+    let loaded = Overrides::load(&path).unwrap();
+    assert_eq!(
+        loaded.get("peneira"),
+        Some(&PathBuf::from("/home/me/src/peneira"))
+    );
+
+    // Once the last override is gone, so is the file.
+    Overrides::default().save(&path).unwrap();
+    assert!(!path.exists());
+}
@@ -0,0 +1,82 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::setup::Setup;
+
+/// Commits the configuration file to the git repository `repo`, where the
+/// user keeps their dotfiles, if it changed since the last commit there, and
+/// pushes the commit when `push` is set. Only the configuration file is
+/// committed: anything else the user may have staged is left alone. Returns
+/// whether there was anything to commit.
+pub fn commit(setup: &Setup, repo: &Path, push: bool, message: &str) -> Result<bool, String> {
+    let repo = setup.expand_home(repo);
+    let root = fs::canonicalize(&repo)
+        .map_err(|e| format!("couldn't open {}: {e}", repo.to_string_lossy()))?;
+
+    // The configuration file is usually linked from the repository.
+    let config = fs::canonicalize(&setup.almoxarife_yaml_path).map_err(|e| {
+        format!(
+            "couldn't open {}: {e}",
+            setup.almoxarife_yaml_path.to_string_lossy()
+        )
+    })?;
+
+    let file = config.strip_prefix(&root).map_err(|_| {
+        format!(
+            "{} isn't inside {}",
+            config.to_string_lossy(),
+            root.to_string_lossy()
+        )
+    })?;
+
+    let git = |args: &[&str]| -> Result<Vec<u8>, String> {
+        let output = setup
+            .runner
+            .output(Command::new("git").current_dir(&root).args(args), None)
+            .map_err(|e| format!("couldn't run git: {e}"))?;
+
+        if !output.status.success() {
+            return Err(format!(
+                "git {} failed in {}: {}",
+                args[0],
+                root.to_string_lossy(),
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            ));
+        }
+
+        Ok(output.stdout)
+    };
+
+    let file = file.to_string_lossy();
+    let status = git(&["status", "--porcelain", "--", &file])?;
+
+    if status.is_empty() {
+        return Ok(false);
+    }
+
+    git(&["add", "--", &file])?;
+    git(&["commit", "--quiet", "-m", message, "--", &file])?;
+
+    if push {
+        git(&["push", "--quiet"])?;
+    }
+
+    Ok(true)
+}
+
+/// A commit message made of `subject` and, if any, a line for each of
+/// `changes`.
+pub fn message(subject: &str, changes: &[String]) -> String {
+    let mut message = subject.to_string();
+
+    if !changes.is_empty() {
+        message.push_str("\n\n");
+
+        for change in changes {
+            message.push_str(&format!("- {change}\n"));
+        }
+    }
+
+    message
+}
@@ -0,0 +1,77 @@
+use std::fs;
+use std::os::unix;
+
+use crate::dotfiles;
+use crate::runner_test::FakeRunner;
+use crate::setup::Setup;
+
+#[test]
+fn commit_config() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repo = temp_dir.path().join("dotfiles");
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    fs::create_dir_all(repo.join("kak")).unwrap();
+    fs::write(repo.join("kak/almoxarife.yaml"), "luar:\n").unwrap();
+
+    // The configuration file is linked from the repository.
+    let config = temp_dir.path().join("almoxarife.yaml");
+    unix::fs::symlink(repo.join("kak/almoxarife.yaml"), &config).unwrap();
+
+    let fake = FakeRunner::new();
+    fake.set("HOME", temp_dir.path().to_string_lossy().into());
+    let setup = Setup {
+        almoxarife_yaml_path: config.clone(),
+        runner: fake.runner(),
+        ..Setup::default()
+    };
+
+    let message = dotfiles::message(
+        "Update Kakoune plugins",
+        &["luar: installed at abcdef".into()],
+    );
+    assert_eq!(
+        message,
+        "Update Kakoune plugins\n\n- luar: installed at abcdef\n"
+    );
+
+    assert_eq!(
+        dotfiles::commit(&setup, "~/dotfiles".as_ref(), false, &message),
+        Ok(true)
+    );
+    assert_eq!(
+        fs::read_to_string(repo.join(".git/almoxarife-test-commit")).unwrap(),
+        message
+    );
+    assert!(!repo.join(".git/almoxarife-test-pushed").exists());
+
+    // Nothing changed since.
+    assert_eq!(dotfiles::commit(&setup, &repo, true, &message), Ok(false));
+
+    fs::write(&config, "luar:\nperu:\n").unwrap();
+    assert_eq!(dotfiles::commit(&setup, &repo, true, "Update"), Ok(true));
+    assert!(repo.join(".git/almoxarife-test-pushed").exists());
+
+    fs::write(&config, "luar:\n").unwrap();
+    fake.reply("git", &["push"], 12, "", "can't push");
+    let error = dotfiles::commit(&setup, &repo, true, "Update").unwrap_err();
+    assert!(error.starts_with("git push failed"), "{error}");
+}
+
+#[test]
+fn config_outside_repo() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repo = temp_dir.path().join("dotfiles");
+    fs::create_dir_all(repo.join(".git")).unwrap();
+
+    let config = temp_dir.path().join("almoxarife.yaml");
+    fs::write(&config, "luar:\n").unwrap();
+
+    let setup = Setup {
+        almoxarife_yaml_path: config,
+        runner: FakeRunner::new().runner(),
+        ..Setup::default()
+    };
+
+    let error = dotfiles::commit(&setup, &repo, false, "Update").unwrap_err();
+    assert!(error.contains("isn't inside"), "{error}");
+}
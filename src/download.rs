@@ -0,0 +1,330 @@
+use std::ffi::OsString;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(not(feature = "http"))]
+use std::process::Command;
+use std::time::Instant;
+
+use sha2::Digest;
+use sha2::Sha256;
+
+use crate::cache;
+#[cfg(feature = "http")]
+use crate::http;
+use crate::runner::Runner;
+
+/// How many redirects a download follows before giving up.
+pub const MAX_REDIRECTS: u32 = 10;
+
+/// curl's exit code when the server won't resume a transfer, as when the file
+/// changed since it was started.
+#[cfg(not(feature = "http"))]
+const CANNOT_RESUME: i32 = 33;
+
+/// A file downloaded over HTTP(S), with `curl` or, when built with the `http`
+/// feature, a built-in client.
+pub struct Download<'a> {
+    pub url: &'a str,
+    pub runner: &'a Runner,
+    pub deadline: Option<Instant>,
+    /// The proxy to go through, if not the default one. An empty proxy is a
+    /// direct connection, as for git.
+    pub proxy: Option<&'a str>,
+    /// The SHA-256 checksum the file must have, in hexadecimal.
+    pub sha256: Option<&'a str>,
+    /// A directory, possibly shared with other machines, keeping the files
+    /// with a checksum once downloaded.
+    pub cache: Option<&'a Path>,
+}
+
+/// What a download did.
+#[derive(Debug, PartialEq)]
+pub enum Fetched {
+    /// The server told the file didn't change since the last download.
+    Unchanged,
+    Downloaded,
+}
+
+/// What a single request for the file did.
+#[derive(Debug, PartialEq)]
+pub enum Transfer {
+    /// The file is complete, along with its SHA-256 checksum if it was
+    /// computed on the way.
+    Complete { sha256: Option<String> },
+    /// The file didn't change since the download whose ETag was sent.
+    NotModified,
+    /// The server won't send the rest of an interrupted transfer.
+    CannotResume,
+}
+
+impl Download<'_> {
+    /// Downloads the file to `destination`, unless the server tells, from the
+    /// ETag the last download saved in `etag`, that it didn't change.
+    ///
+    /// The file is only replaced once complete and checked against its
+    /// checksum. An interrupted transfer is kept next to `destination`, and
+    /// the next download picks it up where it stopped, unless the file changed
+    /// upstream in the meantime. Redirects from HTTPS never lead to plain
+    /// HTTP.
+    ///
+    /// Files with a checksum are taken from the cache, when there's one and
+    /// it has them, and stored there otherwise.
+    pub fn to(&self, destination: &Path, etag: &Path) -> Result<Fetched, String> {
+        let partial = sibling(destination, "download");
+        let partial_etag = sibling(destination, "download-etag");
+
+        if self.copy_cached(&partial, destination)? {
+            // Neither the ETag of the last download nor an interrupted one
+            // say anything about this copy.
+            let _ = fs::remove_file(etag);
+            let _ = fs::remove_file(&partial_etag);
+            return Ok(Fetched::Downloaded);
+        }
+
+        // Only a transfer whose ETag is known can be safely resumed.
+        let resume = partial.exists() && partial_etag.exists();
+
+        if !resume {
+            discard(&partial, &partial_etag);
+        }
+
+        let mut transfer = self.transfer(&partial, &partial_etag, etag, resume);
+
+        if resume && transfer == Ok(Transfer::CannotResume) {
+            discard(&partial, &partial_etag);
+            transfer = self.transfer(&partial, &partial_etag, etag, false);
+        }
+
+        let sha256 = match transfer.map_err(|e| format!("couldn't download {}: {e}", self.url))? {
+            Transfer::Complete { sha256 } => sha256,
+            // Nothing is written when the file is unchanged.
+            Transfer::NotModified => {
+                discard(&partial, &partial_etag);
+                return Ok(Fetched::Unchanged);
+            }
+            Transfer::CannotResume => {
+                discard(&partial, &partial_etag);
+                return Err(format!("couldn't download {}: cannot resume", self.url));
+            }
+        };
+
+        if let Some(expected) = self.sha256 {
+            let actual = match sha256 {
+                Some(actual) => actual,
+                None => sha256_of(&partial)
+                    .map_err(|e| format!("couldn't compute the checksum of {}: {e}", self.url))?,
+            };
+
+            if !actual.eq_ignore_ascii_case(expected) {
+                discard(&partial, &partial_etag);
+
+                return Err(format!(
+                    "{} doesn't match its sha256 checksum: expected {expected}, got {actual}",
+                    self.url
+                ));
+            }
+
+            // A cache that can't be written to only means other machines
+            // download the file too.
+            if let Some(dir) = self.cache {
+                let _ = cache::store(dir, self.url, expected, &partial);
+            }
+        }
+
+        save(&partial, destination)?;
+
+        // Servers not sending ETags leave nothing to compare next time.
+        if fs::rename(&partial_etag, etag).is_err() {
+            let _ = fs::remove_file(etag);
+        }
+
+        Ok(Fetched::Downloaded)
+    }
+
+    /// Copies the file from the cache to `destination`, through `partial`,
+    /// telling whether it was there. Copies not matching their checksum are
+    /// passed over.
+    fn copy_cached(&self, partial: &Path, destination: &Path) -> Result<bool, String> {
+        let (Some(dir), Some(sha256)) = (self.cache, self.sha256) else {
+            return Ok(false);
+        };
+
+        let Some(entry) = cache::lookup(dir, self.url, sha256) else {
+            return Ok(false);
+        };
+
+        // Checked on the way, since the cache may be shared with other
+        // machines.
+        let copied = File::open(&entry).and_then(|mut file| {
+            let mut hashing = Hashing::new(File::create(partial)?);
+            io::copy(&mut file, &mut hashing)?;
+            Ok(hashing.sha256())
+        });
+
+        if !copied.is_ok_and(|actual| actual.eq_ignore_ascii_case(sha256)) {
+            let _ = fs::remove_file(partial);
+            return Ok(false);
+        }
+
+        save(partial, destination)?;
+        Ok(true)
+    }
+
+    /// Requests the file into `partial`, saving its ETag to `partial_etag`,
+    /// unless it still has the one in `etag`. A `resume`d transfer asks for
+    /// the rest of `partial` only.
+    #[cfg(feature = "http")]
+    fn transfer(
+        &self,
+        partial: &Path,
+        partial_etag: &Path,
+        etag: &Path,
+        resume: bool,
+    ) -> Result<Transfer, String> {
+        http::get(self, partial, partial_etag, etag, resume)
+    }
+
+    #[cfg(not(feature = "http"))]
+    fn transfer(
+        &self,
+        partial: &Path,
+        partial_etag: &Path,
+        etag: &Path,
+        resume: bool,
+    ) -> Result<Transfer, String> {
+        let mut command = Command::new("curl");
+        command
+            .args(["--silent", "--show-error", "--fail", "--location"])
+            .arg("--max-redirs")
+            .arg(MAX_REDIRECTS.to_string())
+            .args(["--write-out", "%{http_code}"])
+            .arg("--etag-compare")
+            .arg(etag)
+            .arg("--etag-save")
+            .arg(partial_etag);
+
+        if self.url.starts_with("https://") {
+            command.args(["--proto-redir", "=https"]);
+        }
+
+        if resume {
+            let resumed_etag = fs::read_to_string(partial_etag).unwrap_or_default();
+
+            // The server sends the whole file again if it changed, which curl
+            // refuses to append.
+            command
+                .args(["--continue-at", "-"])
+                .arg("--header")
+                .arg(format!("If-Range: {}", resumed_etag.trim()));
+        }
+
+        match self.proxy {
+            Some("") => {
+                command.args(["--noproxy", "*"]);
+            }
+            Some(proxy) => {
+                command.args(["--proxy", proxy]);
+            }
+            None => {}
+        }
+
+        command.arg("--output").arg(partial).arg(self.url);
+
+        let output = self
+            .runner
+            .output(&mut command, self.deadline)
+            .map_err(|e| e.to_string())?;
+
+        if output.status.code() == Some(CANNOT_RESUME) {
+            return Ok(Transfer::CannotResume);
+        }
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(stderr.trim().to_string());
+        }
+
+        if String::from_utf8_lossy(&output.stdout).trim() == "304" {
+            Ok(Transfer::NotModified)
+        } else {
+            Ok(Transfer::Complete { sha256: None })
+        }
+    }
+}
+
+/// A writer computing the SHA-256 checksum of what goes through it.
+pub struct Hashing<W> {
+    writer: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> Hashing<W> {
+    pub fn new(writer: W) -> Self {
+        Hashing {
+            writer,
+            hasher: Sha256::new(),
+        }
+    }
+
+    /// Hashes what `reader` holds without writing it, as the part of a file
+    /// already written by an interrupted transfer.
+    pub fn skip(&mut self, reader: &mut impl io::Read) -> io::Result<()> {
+        io::copy(reader, &mut self.hasher)?;
+        Ok(())
+    }
+
+    /// The checksum, in lowercase hexadecimal.
+    pub fn sha256(self) -> String {
+        format!("{:x}", self.hasher.finalize())
+    }
+}
+
+impl<W: Write> Write for Hashing<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.writer.write(buf)?;
+        self.hasher.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// The SHA-256 checksum of the file at `path`, in lowercase hexadecimal.
+pub fn sha256_of(path: &Path) -> io::Result<String> {
+    let mut hashing = Hashing::new(io::sink());
+    hashing.skip(&mut File::open(path)?)?;
+    Ok(hashing.sha256())
+}
+
+/// Renames the complete download `partial` into `destination`.
+fn save(partial: &Path, destination: &Path) -> Result<(), String> {
+    fs::rename(partial, destination).map_err(|e| {
+        format!(
+            "couldn't save {}: {e}",
+            destination
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+        )
+    })
+}
+
+/// A hidden file next to `destination`, like `.smarttab.kak.download`.
+fn sibling(destination: &Path, extension: &str) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(destination.file_name().unwrap_or_default());
+    name.push(".");
+    name.push(extension);
+    destination.with_file_name(name)
+}
+
+fn discard(partial: &Path, partial_etag: &Path) {
+    let _ = fs::remove_file(partial);
+    let _ = fs::remove_file(partial_etag);
+}
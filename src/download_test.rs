@@ -0,0 +1,210 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::sync::Arc;
+
+use crate::cache;
+use crate::download;
+use crate::download::Download;
+use crate::download::Fetched;
+use crate::runner::Runner;
+use crate::runner_test::FakeRunner;
+
+const URL: &str = "https://gist.githubusercontent.com/someone/abc/raw/smarttab.kak";
+const SCRIPT: &str = "define-command smarttab %{}";
+
+fn download<'a>(runner: &'a Runner, sha256: Option<&'a str>) -> Download<'a> {
+    Download {
+        url: URL,
+        runner,
+        deadline: None,
+        proxy: None,
+        sha256,
+        cache: None,
+    }
+}
+
+fn fake() -> Arc<FakeRunner> {
+    let fake = FakeRunner::new();
+    fake.set("ALMOXARIFE_TEST_DOWNLOAD", SCRIPT.into());
+    fake.set("ALMOXARIFE_TEST_ETAG", "\"1\"".into());
+    fake
+}
+
+#[test]
+fn download_and_compare_etag() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("smarttab.kak");
+    let etag = temp_dir.path().join(".etag");
+    let fake = fake();
+    let runner = fake.runner();
+
+    assert_eq!(
+        download(&runner, None).to(&file, &etag),
+        Ok(Fetched::Downloaded)
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), SCRIPT);
+    assert_eq!(fs::read_to_string(&etag).unwrap(), "\"1\"\n");
+
+    assert_eq!(
+        download(&runner, None).to(&file, &etag),
+        Ok(Fetched::Unchanged)
+    );
+    assert_eq!(leftovers(temp_dir.path()), ["smarttab.kak", ".etag"]);
+}
+
+#[test]
+fn resume_interrupted_download() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("smarttab.kak");
+    let etag = temp_dir.path().join(".etag");
+    let fake = fake();
+    let runner = fake.runner();
+
+    fake.set("ALMOXARIFE_TEST_DOWNLOAD_CUT", "10".into());
+    let error = download(&runner, None).to(&file, &etag).unwrap_err();
+    assert!(error.contains("transfer closed"), "{error}");
+    assert!(!file.exists());
+
+    fake.unset("ALMOXARIFE_TEST_DOWNLOAD_CUT");
+    assert_eq!(
+        download(&runner, None).to(&file, &etag),
+        Ok(Fetched::Downloaded)
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), SCRIPT);
+    assert!(temp_dir.path().join("resumed.txt").exists());
+}
+
+#[test]
+fn restart_download_changed_upstream() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("smarttab.kak");
+    let etag = temp_dir.path().join(".etag");
+    let fake = fake();
+    let runner = fake.runner();
+
+    fake.set("ALMOXARIFE_TEST_DOWNLOAD_CUT", "10".into());
+    assert!(download(&runner, None).to(&file, &etag).is_err());
+
+    // The server won't resume a file that changed, so it's downloaded whole.
+    fake.unset("ALMOXARIFE_TEST_DOWNLOAD_CUT");
+    fake.set("ALMOXARIFE_TEST_DOWNLOAD", "define-command tab %{}".into());
+    fake.set("ALMOXARIFE_TEST_ETAG", "\"2\"".into());
+    assert_eq!(
+        download(&runner, None).to(&file, &etag),
+        Ok(Fetched::Downloaded)
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), "define-command tab %{}");
+    assert!(!temp_dir.path().join("resumed.txt").exists());
+}
+
+#[test]
+fn check_sha256() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("smarttab.kak");
+    let etag = temp_dir.path().join(".etag");
+    let fake = fake();
+    let runner = fake.runner();
+
+    let sha256 = "0000000000000000000000000000000000000000000000000000000000000000";
+    let error = download(&runner, Some(sha256))
+        .to(&file, &etag)
+        .unwrap_err();
+    assert!(
+        error.starts_with(&format!(
+            "{URL} doesn't match its sha256 checksum: expected {sha256}, got "
+        )),
+        "{error}"
+    );
+    assert!(!file.exists());
+    assert!(leftovers(temp_dir.path()).is_empty());
+
+    let actual = error.rsplit(' ').next().unwrap().to_uppercase();
+    assert_eq!(
+        download(&runner, Some(&actual)).to(&file, &etag),
+        Ok(Fetched::Downloaded)
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), SCRIPT);
+}
+
+/// The files left in `dir`, sorted by name, hidden ones last.
+fn leftovers(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+
+    names.sort_by_key(|name| (name.starts_with('.'), name.clone()));
+    names
+}
+
+#[test]
+fn shared_cache() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let cache_dir = temp_dir.path().join("cache");
+    let fake = fake();
+    let runner = fake.runner();
+
+    let sha256 = sha256_of(temp_dir.path(), SCRIPT);
+    let download = Download {
+        cache: Some(&cache_dir),
+        ..download(&runner, Some(&sha256))
+    };
+
+    let first = temp_dir.path().join("first");
+    fs::create_dir(&first).unwrap();
+    assert_eq!(
+        download.to(&first.join("smarttab.kak"), &first.join(".etag")),
+        Ok(Fetched::Downloaded)
+    );
+
+    // Another machine sharing the cache doesn't download it again.
+    fake.set("ALMOXARIFE_TEST_DOWNLOAD_FAIL", "1".into());
+    let second = temp_dir.path().join("second");
+    fs::create_dir(&second).unwrap();
+    assert_eq!(
+        download.to(&second.join("smarttab.kak"), &second.join(".etag")),
+        Ok(Fetched::Downloaded)
+    );
+    assert_eq!(
+        fs::read_to_string(second.join("smarttab.kak")).unwrap(),
+        SCRIPT
+    );
+    assert_eq!(leftovers(&second), ["smarttab.kak"]);
+
+    // A corrupted copy is passed over.
+    let entry = cache::lookup(&cache_dir, URL, &sha256).unwrap();
+    fs::write(&entry, "define-command evil %{}").unwrap();
+    let error = download
+        .to(&second.join("smarttab.kak"), &second.join(".etag"))
+        .unwrap_err();
+    assert!(error.contains("404"), "{error}");
+}
+
+/// The checksum of `text`, as `sha256sum` computes it.
+fn sha256_of(dir: &Path, text: &str) -> String {
+    let path = dir.join("checksummed");
+    fs::write(&path, text).unwrap();
+    let output = Command::new("sha256sum").arg(&path).output().unwrap();
+    fs::remove_file(&path).unwrap();
+
+    String::from_utf8_lossy(&output.stdout)[..64].to_string()
+}
+
+#[test]
+fn compute_checksums() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("smarttab.kak");
+    fs::write(&path, SCRIPT).unwrap();
+
+    assert_eq!(
+        download::sha256_of(&path).unwrap(),
+        sha256_of(temp_dir.path(), SCRIPT)
+    );
+
+    fs::write(&path, "abc").unwrap();
+    assert_eq!(
+        download::sha256_of(&path).unwrap(),
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+    );
+}
@@ -0,0 +1,142 @@
+//! Format-preserving edits of `almoxarife.yaml`, so that comments, ordering
+//! and quoting chosen by the user survive commands that update the file.
+
+/// A line of the configuration file, as far as editing is concerned.
+struct Line<'a> {
+    indentation: usize,
+    /// The key, if the line starts a mapping entry.
+    key: Option<&'a str>,
+    /// Whether the value is a block scalar (`|` or `>`), whose lines must
+    /// not be taken as keys.
+    starts_block: bool,
+}
+
+fn parse_line(line: &str) -> Option<Line<'_>> {
+    let trimmed = line.trim_start();
+
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+
+    let (key, value) = match trimmed.split_once(':') {
+        Some((key, value)) if value.is_empty() || value.starts_with([' ', '\t']) => {
+            (Some(key.trim()), value.trim())
+        }
+        _ => (None, ""),
+    };
+
+    // Drop trailing comments.
+    let value = value.split(" #").next().unwrap_or_default().trim();
+
+    Some(Line {
+        indentation: line.len() - trimmed.len(),
+        key,
+        starts_block: value.starts_with(['|', '>']),
+    })
+}
+
+/// The range of lines (start inclusive, end exclusive) of the body of the
+/// plugin named `name`, along with the indentation of its fields.
+fn plugin_body(lines: &[&str], name: &str) -> Option<(usize, usize, usize)> {
+    let mut block_indentation: Option<usize> = None;
+    let mut found: Option<(usize, usize)> = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        let Some(parsed) = parse_line(line) else {
+            continue;
+        };
+
+        if let Some(indentation) = block_indentation {
+            if parsed.indentation > indentation {
+                continue;
+            }
+
+            block_indentation = None;
+        }
+
+        if let Some((start, indentation)) = found
+            && parsed.indentation <= indentation
+        {
+            return body_with_fields_indentation(lines, start, i);
+        }
+
+        if parsed.starts_block {
+            block_indentation = Some(parsed.indentation);
+        }
+
+        if found.is_none() && parsed.key == Some(name) {
+            found = Some((i + 1, parsed.indentation));
+        }
+    }
+
+    let (start, _) = found?;
+    body_with_fields_indentation(lines, start, lines.len())
+}
+
+fn body_with_fields_indentation(
+    lines: &[&str],
+    start: usize,
+    end: usize,
+) -> Option<(usize, usize, usize)> {
+    let indentation = lines[start..end]
+        .iter()
+        .find_map(|line| parse_line(line))?
+        .indentation;
+
+    Some((start, end, indentation))
+}
+
+/// Sets a single-line `field` of the plugin `name` to `value`, replacing the
+/// current value if any. New fields are written after `location`. Returns
+/// `None` if there's no such plugin.
+pub fn set_field(source: &str, name: &str, field: &str, value: &str) -> Option<String> {
+    let lines: Vec<&str> = source.lines().collect();
+    let (start, end, indentation) = plugin_body(&lines, name)?;
+
+    let fields: Vec<usize> = (start..end)
+        .filter(|&i| parse_line(lines[i]).is_some_and(|line| line.indentation == indentation))
+        .collect();
+
+    let key_of = |i: usize| parse_line(lines[i]).and_then(|line| line.key);
+    let new_line = format!("{}{field}: {value}", " ".repeat(indentation));
+    let mut edited: Vec<String> = lines.iter().map(|line| line.to_string()).collect();
+
+    if let Some(&i) = fields.iter().find(|&&i| key_of(i) == Some(field)) {
+        edited[i] = new_line;
+    } else {
+        let after = fields
+            .iter()
+            .find(|&&i| key_of(i) == Some("location"))
+            .copied()
+            .unwrap_or(start - 1);
+
+        edited.insert(after + 1, new_line);
+    }
+
+    let mut edited = edited.join("\n");
+
+    if source.ends_with('\n') {
+        edited.push('\n');
+    }
+
+    Some(edited)
+}
+
+/// The number, counting from 1, of the line where the plugin `name` starts,
+/// or where its `field` is, if given. Returns `None` if there's no such plugin
+/// or field.
+pub fn line_of(source: &str, name: &str, field: Option<&str>) -> Option<usize> {
+    let lines: Vec<&str> = source.lines().collect();
+    let (start, end, indentation) = plugin_body(&lines, name)?;
+
+    let Some(field) = field else {
+        return Some(start);
+    };
+
+    (start..end)
+        .find(|&i| {
+            parse_line(lines[i])
+                .is_some_and(|line| line.indentation == indentation && line.key == Some(field))
+        })
+        .map(|i| i + 1)
+}
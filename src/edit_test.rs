@@ -0,0 +1,68 @@
+use crate::edit;
+
+const CONFIG: &str = "# My plugins
+luar:
+  location: https://github.com/gustavo-hms/luar # Lua support
+  config: |
+    peneira:
+      not a plugin
+  peneira:
+    location: https://github.com/gustavo-hms/peneira
+    rev: 0123456
+
+auto-pairs:
+    config: set-option global auto_pairs ( ) { }
+    location: 'https://github.com/alexherbo2/auto-pairs.kak'
+";
+
+#[test]
+fn set_field_inserts_after_location() {
+    let edited = edit::set_field(CONFIG, "auto-pairs", "rev", "abcdef").unwrap();
+    assert_eq!(
+        edited,
+        "# My plugins
+luar:
+  location: https://github.com/gustavo-hms/luar # Lua support
+  config: |
+    peneira:
+      not a plugin
+  peneira:
+    location: https://github.com/gustavo-hms/peneira
+    rev: 0123456
+
+auto-pairs:
+    config: set-option global auto_pairs ( ) { }
+    location: 'https://github.com/alexherbo2/auto-pairs.kak'
+    rev: abcdef
+"
+    );
+}
+
+#[test]
+fn set_field_replaces_value_of_child() {
+    let edited = edit::set_field(CONFIG, "peneira", "rev", "abcdef").unwrap();
+    assert_eq!(edited, CONFIG.replace("rev: 0123456", "rev: abcdef"));
+}
+
+#[test]
+fn set_field_of_parent_ignores_children() {
+    let edited = edit::set_field(CONFIG, "luar", "rev", "abcdef").unwrap();
+    assert_eq!(
+        edited,
+        CONFIG.replace("# Lua support\n", "# Lua support\n  rev: abcdef\n")
+    );
+}
+
+#[test]
+fn set_field_unknown_plugin() {
+    assert_eq!(edit::set_field(CONFIG, "kak-lsp", "rev", "abcdef"), None);
+}
+
+#[test]
+fn line_of_plugin_and_field() {
+    assert_eq!(edit::line_of(CONFIG, "luar", None), Some(2));
+    assert_eq!(edit::line_of(CONFIG, "luar", Some("config")), Some(4));
+    assert_eq!(edit::line_of(CONFIG, "peneira", Some("rev")), Some(9));
+    assert_eq!(edit::line_of(CONFIG, "auto-pairs", Some("rev")), None);
+    assert_eq!(edit::line_of(CONFIG, "kak-lsp", None), None);
+}
@@ -0,0 +1,143 @@
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::setup::Setup;
+
+/// Parses sizes like `500K`, `100M` or `2G`. A plain number is taken as bytes.
+pub fn parse_size(text: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid size {text:?}; use something like 500K, 100M or 2G");
+    let text = text.trim();
+
+    let (number, unit) = match text.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((i, _)) => text.split_at(i),
+        None => (text, ""),
+    };
+
+    let unit = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => return Err(invalid()),
+    };
+
+    let value: u64 = number.parse().map_err(|_| invalid())?;
+    Ok(value * unit)
+}
+
+/// Describes a size in the largest unit that fits, like `400 MB`.
+pub fn describe_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{bytes} bytes"),
+        1024..1048576 => format!("{} KB", bytes >> 10),
+        1048576..1073741824 => format!("{} MB", bytes >> 20),
+        _ => format!("{} GB", bytes >> 30),
+    }
+}
+
+/// The API endpoint describing the repository at `location`, for the forges
+/// known to report repository sizes and whether repositories are archived.
+pub fn api_url(location: &str) -> Option<String> {
+    let path = location
+        .strip_prefix("https://")
+        .or_else(|| location.strip_prefix("http://"))
+        .or_else(|| location.strip_prefix("git@"))?;
+
+    let (host, path) = path.split_once(['/', ':'])?;
+    let path = path.trim_end_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    let (owner, repository) = path.split_once('/')?;
+
+    if owner.is_empty() || repository.is_empty() || repository.contains('/') {
+        return None;
+    }
+
+    match host {
+        "github.com" => Some(format!("https://api.github.com/repos/{owner}/{repository}")),
+        "codeberg.org" => Some(format!(
+            "https://codeberg.org/api/v1/repos/{owner}/{repository}"
+        )),
+        _ => None,
+    }
+}
+
+/// The web page of the repository at `location`, as most forges serve it at
+/// the repository's own URL. Repositories cloned over ssh or git get their
+/// https page. Local paths and `file://` URLs have none.
+pub fn web_page(location: &str) -> Option<String> {
+    let (scheme, host, path) = match location.split_once("://") {
+        Some(("file", _)) => return None,
+
+        Some((protocol, rest)) => {
+            let (authority, path) = rest.split_once('/')?;
+            let host = authority.rsplit('@').next()?;
+
+            match protocol {
+                "http" | "https" => (protocol, host, path),
+                // The port of an ssh server isn't the one of its web server.
+                _ => ("https", host.split(':').next()?, path),
+            }
+        }
+
+        // The scp-like syntax, as in `git@github.com:user/repo`.
+        None => {
+            let (authority, path) = location.split_once(':')?;
+            ("https", authority.rsplit('@').next()?, path)
+        }
+    };
+
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+
+    if host.is_empty() || path.is_empty() {
+        return None;
+    }
+
+    Some(format!("{scheme}://{host}/{path}"))
+}
+
+#[derive(Deserialize)]
+struct Repository {
+    /// In kilobytes, both for GitHub and Gitea.
+    size: u64,
+    #[serde(default)]
+    archived: bool,
+}
+
+/// The approximate size, in bytes, of the repository at `location`, if its
+/// forge tells. Any failure only means the size is unknown.
+pub fn repository_size(setup: &Setup, location: &str) -> Option<u64> {
+    Some(repository(setup, location)?.size << 10)
+}
+
+/// Whether the repository at `location` is archived, if its forge tells.
+pub fn is_archived(setup: &Setup, location: &str) -> Option<bool> {
+    Some(repository(setup, location)?.archived)
+}
+
+fn repository(setup: &Setup, location: &str) -> Option<Repository> {
+    let url = api_url(location)?;
+
+    let output = setup
+        .runner
+        .output(
+            Command::new("curl").args([
+                "--silent",
+                "--fail",
+                "--location",
+                "--max-time",
+                "10",
+                &url,
+            ]),
+            None,
+        )
+        .ok()?;
+
+    if !output.status.success() {
+        return None;
+    }
+
+    // JSON is valid YAML, which spares a dependency.
+    serde_yaml::from_slice(&output.stdout).ok()
+}
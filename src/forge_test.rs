@@ -0,0 +1,90 @@
+use crate::forge::api_url;
+use crate::forge::describe_size;
+use crate::forge::parse_size;
+use crate::forge::repository_size;
+use crate::forge::web_page;
+use crate::runner_test::FakeRunner;
+use crate::setup::Setup;
+
+#[test]
+fn parse_sizes() {
+    assert_eq!(parse_size("2048"), Ok(2048));
+    assert_eq!(parse_size("500K"), Ok(500 * 1024));
+    assert_eq!(parse_size("100M"), Ok(100 * 1024 * 1024));
+    assert_eq!(parse_size("100mb"), Ok(100 * 1024 * 1024));
+    assert_eq!(parse_size("2G"), Ok(2 * 1024 * 1024 * 1024));
+    assert!(parse_size("M").is_err());
+    assert!(parse_size("100T").is_err());
+    assert!(parse_size("1.5G").is_err());
+}
+
+#[test]
+fn describe_sizes() {
+    assert_eq!(describe_size(512), "512 bytes");
+    assert_eq!(describe_size(120 * 1024), "120 KB");
+    assert_eq!(describe_size(400 * 1024 * 1024), "400 MB");
+    assert_eq!(describe_size(3 * 1024 * 1024 * 1024), "3 GB");
+}
+
+#[test]
+fn web_pages() {
+    let expected = Some("https://github.com/gustavo-hms/peneira".to_string());
+
+    assert_eq!(web_page("https://github.com/gustavo-hms/peneira"), expected);
+    assert_eq!(
+        web_page("https://github.com/gustavo-hms/peneira.git/"),
+        expected
+    );
+    assert_eq!(web_page("git@github.com:gustavo-hms/peneira.git"), expected);
+    assert_eq!(
+        web_page("ssh://git@github.com:22/gustavo-hms/peneira"),
+        expected
+    );
+    assert_eq!(
+        web_page("http://git.example.com:8080/kak/peneira"),
+        Some("http://git.example.com:8080/kak/peneira".into())
+    );
+    assert_eq!(web_page("file:///srv/mirrors/peneira.git"), None);
+    assert_eq!(web_page("/home/me/kak-scripts"), None);
+    assert_eq!(web_page("https://github.com/"), None);
+}
+
+#[test]
+fn api_urls() {
+    assert_eq!(
+        api_url("https://github.com/gustavo-hms/peneira").as_deref(),
+        Some("https://api.github.com/repos/gustavo-hms/peneira")
+    );
+    assert_eq!(
+        api_url("git@github.com:gustavo-hms/peneira.git").as_deref(),
+        Some("https://api.github.com/repos/gustavo-hms/peneira")
+    );
+    assert_eq!(
+        api_url("https://codeberg.org/someone/plugin/").as_deref(),
+        Some("https://codeberg.org/api/v1/repos/someone/plugin")
+    );
+    assert_eq!(api_url("https://git.sr.ht/~someone/plugin"), None);
+    assert_eq!(api_url("https://github.com/gustavo-hms"), None);
+    assert_eq!(api_url("/home/me/kak-scripts"), None);
+}
+
+#[test]
+fn repository_sizes() {
+    let setup = Setup {
+        runner: FakeRunner::new().runner(),
+        ..Setup::default()
+    };
+
+    assert_eq!(
+        repository_size(&setup, "https://github.com/someone/huge-grammars"),
+        Some(400 * 1024 * 1024)
+    );
+    assert_eq!(
+        repository_size(&setup, "https://github.com/gustavo-hms/peneira"),
+        Some(120 * 1024)
+    );
+    assert_eq!(
+        repository_size(&setup, "https://git.sr.ht/~someone/plugin"),
+        None
+    );
+}
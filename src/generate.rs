@@ -0,0 +1,102 @@
+use std::fs;
+use std::path::PathBuf;
+
+use crate::order;
+use crate::report::Outcome;
+use crate::report::Reporter;
+use crate::report::Revisions;
+use crate::setup::Context;
+use crate::setup::Integration;
+use crate::setup::Kak;
+use crate::setup::PluginError;
+use crate::setup::Setup;
+use crate::setup::SetupError;
+use crate::setup::Status;
+
+/// Generates the links and `almoxarife.kak` (or `plugins.kak`) from plugin
+/// sources fetched beforehand, for sandboxed builds like Nix's. Nothing is
+/// fetched and nothing is written outside the output directory. Plugins are
+/// handled one after the other, in name order, so that the same sources always
+/// give the same output.
+///
+/// Returns the errors of the plugins that couldn't be linked.
+pub fn generate(setup: &Setup, reporter: &dyn Reporter) -> Result<Vec<PluginError>, SetupError> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let integration = config.settings.integration;
+    let mut disabled_plugins = config.disabled_plugins();
+    disabled_plugins.sort();
+
+    let out = &setup.autoload_plugins_dir;
+
+    if out.symlink_metadata().is_ok() {
+        fs::remove_dir_all(out).context("couldn't clean the output directory")?;
+    }
+
+    fs::create_dir_all(out).context("couldn't create the output directory")?;
+
+    let mut plugins = config.active_plugins();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let repositories: Vec<PathBuf> = plugins
+        .iter()
+        .map(|plugin| plugin.repository_path.clone())
+        .collect();
+
+    let order = order::sort(&plugins);
+    let mut kak = Kak::with_buffer();
+    kak.write_prelude(None, None, None, None)?;
+
+    for disabled in disabled_plugins {
+        reporter.status(&disabled, Outcome::Disabled, Revisions::default(), None);
+    }
+
+    let mut errors = Vec::new();
+
+    for plugin in plugins {
+        let result = match integration {
+            Integration::Autoload => plugin.activate(),
+            Integration::Source => plugin.inspect(),
+        };
+
+        match result {
+            Ok(Status::Local { name, config }) => {
+                kak.write_config(&name, config);
+                reporter.status(&name, Outcome::Local, Revisions::default(), None);
+            }
+
+            Ok(Status::Unchanged { name, config, .. }) => {
+                kak.write_config(&name, config);
+                reporter.status(&name, Outcome::Unchanged, Revisions::default(), None);
+            }
+
+            // Only installing or updating a plugin gives other statuses.
+            Ok(_) => (),
+
+            Err(error) => {
+                reporter.status(error.plugin(), Outcome::Failed, Revisions::default(), None);
+                errors.push(error);
+            }
+        }
+    }
+
+    kak.close(&order)?;
+
+    match integration {
+        Integration::Autoload => setup.write_almoxarife_kak(&kak)?,
+
+        Integration::Source => {
+            let repositories: Vec<_> = repositories
+                .iter()
+                .map(PathBuf::as_path)
+                .filter(|path| path.exists())
+                .collect();
+
+            setup.write_plugins_kak(&repositories, &kak)?;
+        }
+    }
+
+    Ok(errors)
+}
@@ -0,0 +1,98 @@
+use std::fs;
+use std::path::Path;
+
+use tempfile::TempDir;
+
+use crate::generate::generate;
+use crate::report::Porcelain;
+use crate::setup::Setup;
+
+const CONFIG: &str = "peneira:
+    location: https://github.com/gustavo-hms/peneira
+    config: set-option global peneira_files_command 'rg --files'
+luar:
+    location: https://github.com/gustavo-hms/luar
+    config: set-option global luar_interpreter luajit
+kak-lsp:
+    location: https://github.com/kakoune-lsp/kakoune-lsp
+    disabled: true
+";
+
+fn sources(root: &Path) -> std::path::PathBuf {
+    let sources = root.join("sources");
+
+    for plugin in ["peneira", "luar"] {
+        fs::create_dir_all(sources.join(plugin)).unwrap();
+        fs::write(
+            sources.join(plugin).join(format!("{plugin}.kak")),
+            format!("provide-module {plugin} %{{}}"),
+        )
+        .unwrap();
+    }
+
+    fs::write(root.join("almoxarife.yaml"), CONFIG).unwrap();
+    sources
+}
+
+#[test]
+fn generate_links_and_kak_file() {
+    let root = TempDir::new().unwrap();
+    let sources = sources(root.path());
+    let out = root.path().join("out");
+    let setup = Setup::for_generation(&root.path().join("almoxarife.yaml"), &sources, &out);
+
+    let porcelain = Porcelain::with_writers(Vec::new(), Vec::new());
+    let errors = generate(&setup, &porcelain).unwrap();
+    assert!(errors.is_empty());
+
+    let (out_buffer, _) = porcelain.into_writers();
+    assert_eq!(
+        String::from_utf8(out_buffer).unwrap(),
+        "kak-lsp\tdisabled\t-\t-\t-\t-\nluar\tunchanged\t-\t-\t-\t-\npeneira\tunchanged\t-\t-\t-\t-\n"
+    );
+
+    assert_eq!(
+        fs::read_link(out.join("luar")).unwrap(),
+        sources.join("luar")
+    );
+    assert_eq!(
+        fs::read_link(out.join("peneira")).unwrap(),
+        sources.join("peneira")
+    );
+
+    let kak = fs::read_to_string(out.join("almoxarife.kak")).unwrap();
+    let luar = kak.find("require-module luar").unwrap();
+    let peneira = kak.find("require-module peneira").unwrap();
+    // As in the configuration file.
+    assert!(peneira < luar);
+
+    // Generating again gives the same output.
+    let porcelain = Porcelain::with_writers(Vec::new(), Vec::new());
+    generate(&setup, &porcelain).unwrap();
+    assert_eq!(fs::read_to_string(out.join("almoxarife.kak")).unwrap(), kak);
+
+    // Nothing is written outside the output directory.
+    let mut entries: Vec<_> = fs::read_dir(root.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+
+    entries.sort();
+    assert_eq!(entries, ["almoxarife.yaml", "out", "sources"]);
+}
+
+#[test]
+fn generate_missing_source() {
+    let root = TempDir::new().unwrap();
+    let sources = sources(root.path());
+    fs::remove_dir_all(sources.join("luar")).unwrap();
+    let out = root.path().join("out");
+    let setup = Setup::for_generation(&root.path().join("almoxarife.yaml"), &sources, &out);
+
+    let porcelain = Porcelain::with_writers(Vec::new(), Vec::new());
+    let errors = generate(&setup, &porcelain).unwrap();
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].plugin(), "luar");
+    assert!(out.join("peneira").exists());
+}
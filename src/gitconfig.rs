@@ -0,0 +1,86 @@
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use crate::runner::Runner;
+
+/// The parts of the user's own git configuration, in the system and global
+/// files, that change where plugins are fetched from. git follows them by
+/// itself; Almoxarife reads them to know what git will do, and to stay out of
+/// their way.
+#[derive(Debug, Default, PartialEq)]
+pub struct GitConfig {
+    /// The `url.<base>.insteadOf` rules, from the prefix replaced to `base`.
+    pub url_rewrites: BTreeMap<String, String>,
+    /// The `http.proxy` setting, which git prefers to the proxy of the
+    /// environment.
+    pub proxy: Option<String>,
+}
+
+impl GitConfig {
+    /// Reads the configuration by running `git`. Without git, or without any
+    /// of the settings, the configuration is empty.
+    pub fn load(runner: &Runner) -> GitConfig {
+        let output = runner.output(
+            Command::new("git").args([
+                "config",
+                "--show-scope",
+                "--get-regexp",
+                r"^(url\..*\.insteadof|http\.proxy)$",
+            ]),
+            None,
+        );
+
+        match output {
+            Ok(output) => GitConfig::parse(&String::from_utf8_lossy(&output.stdout)),
+            Err(_) => GitConfig::default(),
+        }
+    }
+
+    /// Parses the output of `git config --show-scope --get-regexp`. Only the
+    /// system and global scopes are kept: the configuration of the repository
+    /// Almoxarife happens to run from says nothing about plugins.
+    pub fn parse(output: &str) -> GitConfig {
+        let mut config = GitConfig::default();
+
+        for line in output.lines() {
+            let Some((scope, setting)) = line.split_once('\t') else {
+                continue;
+            };
+
+            if scope != "system" && scope != "global" {
+                continue;
+            }
+
+            let Some((key, value)) = setting.split_once(' ') else {
+                continue;
+            };
+
+            // Lines come in the order git reads them, so later ones win, as
+            // they do for git.
+            if key == "http.proxy" {
+                config.proxy = Some(value.to_string());
+            } else if let Some(base) = key
+                .strip_prefix("url.")
+                .and_then(|key| key.strip_suffix(".insteadof"))
+            {
+                config
+                    .url_rewrites
+                    .insert(value.to_string(), base.to_string());
+            }
+        }
+
+        config
+    }
+}
+
+/// The URL git fetches `url` from, following `rewrites` the way git's
+/// `insteadOf` does: the longest matching prefix wins.
+pub fn rewrite_url(rewrites: &BTreeMap<String, String>, url: &str) -> String {
+    rewrites
+        .iter()
+        .filter(|(from, _)| url.starts_with(from.as_str()))
+        .max_by_key(|(from, _)| from.len())
+        .map_or(url.to_string(), |(from, to)| {
+            format!("{to}{}", &url[from.len()..])
+        })
+}
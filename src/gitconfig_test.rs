@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use crate::gitconfig;
+use crate::gitconfig::GitConfig;
+use crate::runner_test::FakeRunner;
+
+#[test]
+fn parse_git_config() {
+    let output = "\
+system\thttp.proxy http://proxy.example.com:3128
+global\turl.https://mirror.internal/github/.insteadof https://github.com/
+global\turl.git@github.com:.insteadof gh:
+local\turl.https://elsewhere.com/.insteadof https://gitlab.com/
+global\thttp.proxy socks5://localhost:1080
+global\turl.broken.insteadof
+";
+
+    assert_eq!(
+        GitConfig::parse(output),
+        GitConfig {
+            url_rewrites: BTreeMap::from([
+                (
+                    "https://github.com/".into(),
+                    "https://mirror.internal/github/".into()
+                ),
+                ("gh:".into(), "git@github.com:".into()),
+            ]),
+            proxy: Some("socks5://localhost:1080".into()),
+        }
+    );
+
+    assert_eq!(GitConfig::parse(""), GitConfig::default());
+}
+
+#[test]
+fn load_git_config() {
+    let fake = FakeRunner::new();
+    fake.reply(
+        "git",
+        &["config", "--show-scope"],
+        0,
+        "global\turl.https://mirror.internal/github/.insteadof https://github.com/\n",
+        "",
+    );
+
+    let config = GitConfig::load(&fake.runner());
+    assert_eq!(
+        config.url_rewrites,
+        BTreeMap::from([(
+            "https://github.com/".into(),
+            "https://mirror.internal/github/".into()
+        )])
+    );
+    assert_eq!(config.proxy, None);
+
+    fake.reply("git", &["config"], 1, "", "");
+    let config = GitConfig::load(&fake.runner());
+    assert_eq!(config, GitConfig::default());
+}
+
+#[test]
+fn url_rewrites() {
+    let rewrites = BTreeMap::from([
+        (
+            "https://github.com/".to_string(),
+            "https://mirror.internal/github/".to_string(),
+        ),
+        (
+            "https://github.com/gustavo-hms/".to_string(),
+            "https://mirror.internal/mine/".to_string(),
+        ),
+    ]);
+
+    assert_eq!(
+        gitconfig::rewrite_url(&rewrites, "https://github.com/gustavo-hms/luar"),
+        "https://mirror.internal/mine/luar"
+    );
+    assert_eq!(
+        gitconfig::rewrite_url(
+            &rewrites,
+            "https://github.com/occivink/kakoune-phantom-selection"
+        ),
+        "https://mirror.internal/github/occivink/kakoune-phantom-selection"
+    );
+    assert_eq!(
+        gitconfig::rewrite_url(&rewrites, "https://gitlab.com/me/plugin"),
+        "https://gitlab.com/me/plugin"
+    );
+}
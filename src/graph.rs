@@ -0,0 +1,121 @@
+use crate::setup::Plugin;
+
+/// The formats `al graph` writes the plugins in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Format {
+    /// Graphviz's, as in `al graph | dot -Tsvg`.
+    #[default]
+    Dot,
+    /// Mermaid's, which GitHub and many wikis render from Markdown.
+    Mermaid,
+}
+
+impl Format {
+    pub fn parse(text: &str) -> Option<Format> {
+        match text {
+            "dot" => Some(Format::Dot),
+            "mermaid" => Some(Format::Mermaid),
+            _ => None,
+        }
+    }
+}
+
+/// How two plugins are tied together.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Edge {
+    /// The second is a child of the first, configured once the first one's
+    /// module is loaded.
+    Child,
+    /// The first depends on the second, according to its manifest.
+    Dependency,
+}
+
+/// The graph of `plugins`, by name, in `format`: an arrow from each parent
+/// to its children, labeled with the hook loading them, and a dashed one from
+/// each plugin to its dependencies.
+pub fn render(plugins: &[Plugin], format: Format) -> String {
+    let mut plugins: Vec<&Plugin> = plugins.iter().collect();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let index = |name: &str| plugins.iter().position(|plugin| plugin.name == name);
+
+    let mut edges = Vec::new();
+
+    for (i, plugin) in plugins.iter().enumerate() {
+        if let Some(parent) = plugin.parent.as_deref().and_then(index) {
+            edges.push((parent, i, Edge::Child));
+        }
+
+        // Dependencies disabled, and so missing, are left out.
+        for dependency in plugin.manifest().dependencies.keys() {
+            if let Some(dependency) = index(dependency) {
+                edges.push((i, dependency, Edge::Dependency));
+            }
+        }
+    }
+
+    edges.sort_by_key(|&(from, to, _)| (from, to));
+
+    match format {
+        Format::Dot => dot(&plugins, &edges),
+        Format::Mermaid => mermaid(&plugins, &edges),
+    }
+}
+
+fn dot(plugins: &[&Plugin], edges: &[(usize, usize, Edge)]) -> String {
+    let quote = |name: &str| format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+    let mut dot = String::from("digraph plugins {\n    rankdir=LR;\n");
+
+    for plugin in plugins {
+        let style = if plugin.required_by.is_some() {
+            " [style=dashed]"
+        } else {
+            ""
+        };
+
+        dot.push_str(&format!("    {}{style};\n", quote(&plugin.name)));
+    }
+
+    for &(from, to, edge) in edges {
+        let attributes = match edge {
+            Edge::Child => format!("label={}", quote(&loaded_after(plugins[from]))),
+            Edge::Dependency => "label=\"depends on\", style=dashed".to_string(),
+        };
+
+        dot.push_str(&format!(
+            "    {} -> {} [{attributes}];\n",
+            quote(&plugins[from].name),
+            quote(&plugins[to].name)
+        ));
+    }
+
+    dot.push_str("}\n");
+    dot
+}
+
+fn mermaid(plugins: &[&Plugin], edges: &[(usize, usize, Edge)]) -> String {
+    // Names may have characters Mermaid doesn't take in identifiers, so
+    // nodes are numbered and labeled with them.
+    let quote = |text: &str| format!("\"{}\"", text.replace('"', "#quot;"));
+    let mut mermaid = String::from("graph LR\n");
+
+    for (i, plugin) in plugins.iter().enumerate() {
+        mermaid.push_str(&format!("    n{i}[{}]\n", quote(&plugin.name)));
+    }
+
+    for &(from, to, edge) in edges {
+        let arrow = match edge {
+            Edge::Child => format!("-->|{}|", quote(&loaded_after(plugins[from]))),
+            Edge::Dependency => "-.->|depends on|".to_string(),
+        };
+
+        mermaid.push_str(&format!("    n{from} {arrow} n{to}\n"));
+    }
+
+    mermaid
+}
+
+/// The hook children of `parent` are configured from.
+fn loaded_after(parent: &Plugin) -> String {
+    format!("ModuleLoaded {}", parent.name)
+}
@@ -0,0 +1,59 @@
+use std::fs;
+
+use crate::graph;
+use crate::graph::Format;
+use crate::setup::Setup;
+
+#[test]
+fn render_graph() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let peneira = temp_dir.path().join("peneira");
+    fs::create_dir_all(&peneira).unwrap();
+    fs::write(
+        peneira.join("almoxarife.toml"),
+        "dependencies.luar = 'https://github.com/gustavo-hms/luar'",
+    )
+    .unwrap();
+
+    let file = b"
+peneira:
+    location: https://github.com/gustavo-hms/peneira
+
+    peneira-filters:
+        location: https://github.com/gustavo-hms/peneira-filters
+";
+
+    let setup = Setup {
+        almoxarife_data_dir: temp_dir.path().into(),
+        ..Default::default()
+    };
+
+    let plugins = setup
+        .config_from_buffer(file.as_slice())
+        .unwrap()
+        .active_plugins();
+
+    assert_eq!(
+        graph::render(&plugins, Format::Dot),
+        r#"digraph plugins {
+    rankdir=LR;
+    "luar" [style=dashed];
+    "peneira";
+    "peneira-filters";
+    "peneira" -> "luar" [label="depends on", style=dashed];
+    "peneira" -> "peneira-filters" [label="ModuleLoaded peneira"];
+}
+"#
+    );
+
+    assert_eq!(
+        graph::render(&plugins, Format::Mermaid),
+        r#"graph LR
+    n0["luar"]
+    n1["peneira"]
+    n2["peneira-filters"]
+    n1 -.->|depends on| n0
+    n1 -->|"ModuleLoaded peneira"| n2
+"#
+    );
+}
@@ -0,0 +1,56 @@
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::setup::Setup;
+
+/// Shell commands run around updates, set under the top level `hooks` key.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Hooks {
+    /// Run before anything is fetched. If it fails, the update is called off.
+    pub before_all: Option<String>,
+    /// Run once the update is done, even if some plugins failed.
+    pub after_all: Option<String>,
+    /// Run for each plugin installed or updated.
+    pub after_plugin_update: Option<String>,
+}
+
+/// Runs the `hook` named `name` with `sh -c`, with `env` added to the
+/// environment, along with `ALMOXARIFE_HOOK` set to `name` and
+/// `ALMOXARIFE_BIN` to the directory plugins' executables go. The hook's output
+/// is only shown when it fails, so that it doesn't get in the way of the
+/// update's.
+pub fn run(setup: &Setup, name: &str, hook: &str, env: &[(&str, String)]) -> Result<(), String> {
+    let mut command = Command::new("sh");
+    command
+        .arg("-c")
+        .arg(hook)
+        .env("ALMOXARIFE_HOOK", name)
+        .env("ALMOXARIFE_BIN", setup.bin_dir())
+        .envs(env.iter().map(|(var, value)| (var, value)));
+
+    let output = setup
+        .runner
+        .output(&mut command, None)
+        .map_err(|e| format!("couldn't run the {name} hook: {e}"))?;
+
+    if output.status.success() {
+        return Ok(());
+    }
+
+    let status = match output.status.code() {
+        Some(code) => format!("exited with status {code}"),
+        None => "was killed".to_string(),
+    };
+
+    let mut error = format!("the {name} hook {status}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    if !stderr.trim().is_empty() {
+        error.push_str(": ");
+        error.push_str(stderr.trim_end());
+    }
+
+    Err(error)
+}
@@ -0,0 +1,70 @@
+use std::fs;
+
+use crate::hooks;
+use crate::hooks::Hooks;
+use crate::runner_test::FakeRunner;
+use crate::setup::Setup;
+
+#[test]
+fn parse_hooks() {
+    let setup = Setup::default();
+    let file = b"
+hooks:
+    before_all: git -C ~/dotfiles pull
+    after_plugin_update: make -C \"$ALMOXARIFE_PLUGIN_DIR\"
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(
+        config.settings.hooks,
+        Hooks {
+            before_all: Some("git -C ~/dotfiles pull".into()),
+            after_all: None,
+            after_plugin_update: Some("make -C \"$ALMOXARIFE_PLUGIN_DIR\"".into()),
+        }
+    );
+
+    let file = b"
+hooks:
+    after_every_plugin: true
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    assert!(setup.config_from_buffer(file.as_slice()).is_err());
+}
+
+#[test]
+fn run_hook() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let out = temp_dir.path().join("out");
+    let setup = Setup {
+        runner: FakeRunner::new().runner(),
+        ..Setup::default()
+    };
+
+    let hook = format!(
+        "printf '%s %s %s' \"$ALMOXARIFE_HOOK\" \"$ALMOXARIFE_PLUGIN\" \"$ALMOXARIFE_BIN\" > {}",
+        out.to_string_lossy()
+    );
+
+    hooks::run(
+        &setup,
+        "after_plugin_update",
+        &hook,
+        &[("ALMOXARIFE_PLUGIN", "luar".into())],
+    )
+    .unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&out).unwrap(),
+        "after_plugin_update luar ~/.local/share/almoxarife/bin"
+    );
+
+    assert_eq!(
+        hooks::run(&setup, "before_all", "echo not today >&2; exit 3", &[]),
+        Err("the before_all hook exited with status 3: not today".into())
+    );
+}
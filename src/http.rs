@@ -0,0 +1,151 @@
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::Path;
+use std::time::Instant;
+
+use ureq::AgentBuilder;
+use ureq::Proxy;
+
+use crate::download::Download;
+use crate::download::Hashing;
+use crate::download::MAX_REDIRECTS;
+use crate::download::Transfer;
+
+/// Requests the file of `download` into `partial`, without curl, the way
+/// [`Download::to`] expects: its ETag goes to `partial_etag`, the one in
+/// `etag` is sent to be told the file didn't change, and a `resume`d
+/// transfer only asks for what `partial` lacks, as long as the file is still
+/// the one it started with.
+pub fn get(
+    download: &Download,
+    partial: &Path,
+    partial_etag: &Path,
+    etag: &Path,
+    resume: bool,
+) -> Result<Transfer, String> {
+    let mut agent = AgentBuilder::new()
+        .redirects(MAX_REDIRECTS)
+        .https_only(download.url.starts_with("https://"));
+
+    if let Some(proxy) = proxy(download) {
+        let proxy = Proxy::new(&proxy).map_err(|e| format!("invalid proxy {proxy}: {e}"))?;
+        agent = agent.proxy(proxy);
+    }
+
+    let mut request = agent.build().get(download.url);
+
+    if let Some(deadline) = download.deadline {
+        request = request.timeout(deadline.saturating_duration_since(Instant::now()));
+    }
+
+    if let Ok(etag) = fs::read_to_string(etag) {
+        request = request.set("If-None-Match", etag.trim());
+    }
+
+    if resume {
+        let offset = fs::metadata(partial).map_err(|e| e.to_string())?.len();
+        let resumed_etag = fs::read_to_string(partial_etag).unwrap_or_default();
+
+        // The server sends the whole file again if it changed.
+        request = request
+            .set("Range", &format!("bytes={offset}-"))
+            .set("If-Range", resumed_etag.trim());
+    }
+
+    let response = match request.call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(416, _)) => return Ok(Transfer::CannotResume),
+        Err(ureq::Error::Status(status, _)) => {
+            return Err(format!("the requested URL returned error: {status}"));
+        }
+        Err(e) => return Err(e.to_string()),
+    };
+
+    let append = match response.status() {
+        304 => return Ok(Transfer::NotModified),
+        206 if resume => true,
+        200 => false,
+        status => return Err(format!("unexpected response: {status}")),
+    };
+
+    // Saved before the body, so that an interrupted transfer can be resumed.
+    match response.header("ETag") {
+        Some(etag) => fs::write(partial_etag, format!("{etag}\n")).map_err(|e| e.to_string())?,
+        None if !append => {
+            let _ = fs::remove_file(partial_etag);
+        }
+        None => {}
+    }
+
+    let mut file = if append {
+        OpenOptions::new().append(true).open(partial)
+    } else {
+        File::create(partial)
+    }
+    .map(Hashing::new)
+    .map_err(|e| e.to_string())?;
+
+    // The checksum covers what an interrupted transfer already wrote.
+    if append {
+        File::open(partial)
+            .and_then(|mut written| file.skip(&mut written))
+            .map_err(|e| e.to_string())?;
+    }
+
+    io::copy(&mut response.into_reader(), &mut file)
+        .map_err(|e| format!("transfer closed: {e}"))?;
+
+    Ok(Transfer::Complete {
+        sha256: Some(file.sha256()),
+    })
+}
+
+/// The proxy `download` goes through. Unless given one, it's taken from the
+/// environment, like curl does: from `https_proxy`, `http_proxy` or
+/// `all_proxy`, except for hosts in `no_proxy`.
+pub fn proxy(download: &Download) -> Option<String> {
+    match download.proxy {
+        Some("") => return None,
+        Some(proxy) => return Some(proxy.to_string()),
+        None => {}
+    }
+
+    let var = |name: &str| {
+        download
+            .runner
+            .var(name)
+            .or_else(|| download.runner.var(&name.to_uppercase()))
+            .filter(|value| !value.is_empty())
+    };
+
+    let (scheme, rest) = download.url.split_once("://")?;
+    let authority = rest.split('/').next().unwrap_or_default();
+    let host_and_port = authority.rsplit('@').next().unwrap_or_default();
+    let host = host_and_port.split(':').next().unwrap_or_default();
+
+    let bypassed = var("no_proxy").is_some_and(|hosts| {
+        hosts.split(',').map(str::trim).any(|entry| {
+            let entry = entry.trim_start_matches('.');
+            entry == "*"
+                || (!entry.is_empty() && (host == entry || host.ends_with(&format!(".{entry}"))))
+        })
+    });
+
+    if bypassed {
+        return None;
+    }
+
+    // curl doesn't take HTTP_PROXY, which CGI scripts get from requests.
+    let scheme_proxy = if scheme == "http" {
+        download
+            .runner
+            .var("http_proxy")
+            .filter(|value| !value.is_empty())
+    } else {
+        var(&format!("{scheme}_proxy"))
+    };
+
+    scheme_proxy.or_else(|| var("all_proxy"))
+}
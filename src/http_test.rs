@@ -0,0 +1,272 @@
+use std::fs;
+use std::io::BufRead;
+use std::io::BufReader;
+use std::io::Write;
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::sync::mpsc::Receiver;
+use std::thread;
+
+use crate::download::Download;
+use crate::download::Fetched;
+use crate::http;
+use crate::runner::Runner;
+use crate::runner_test::FakeRunner;
+
+const SCRIPT: &str = "define-command smarttab %{}";
+
+/// Answers the requests made to the returned URL with `responses`, one per
+/// connection, handing out the headers of each request.
+fn serve(responses: Vec<String>) -> (String, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/smarttab.kak", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for response in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut request = String::new();
+            let mut reader = BufReader::new(&stream);
+
+            while reader.read_line(&mut request).unwrap() > 2 {}
+
+            sender.send(request.to_lowercase()).unwrap();
+            (&stream).write_all(response.as_bytes()).unwrap();
+        }
+    });
+
+    (url, receiver)
+}
+
+fn response(status: &str, headers: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {status}\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// A response cut after the first `n` bytes of `SCRIPT`.
+fn cut(n: usize) -> String {
+    let response = response("200 OK", "ETag: \"1\"\r\n", SCRIPT);
+    response[..response.len() - SCRIPT.len() + n].to_string()
+}
+
+/// The checksum of `SCRIPT`.
+const SHA256: &str = "0515470d2efa0776a8b895ec716c4227344d51004e6679155eb42bdd043707b4";
+
+fn download<'a>(url: &'a str, runner: &'a Runner) -> Download<'a> {
+    Download {
+        url,
+        runner,
+        deadline: None,
+        proxy: None,
+        sha256: None,
+        cache: None,
+    }
+}
+
+fn runner() -> Runner {
+    FakeRunner::new().runner()
+}
+
+#[test]
+fn download_and_compare_etag() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("smarttab.kak");
+    let etag = temp_dir.path().join(".etag");
+    let runner = runner();
+
+    let (url, requests) = serve(vec![
+        response("200 OK", "ETag: \"1\"\r\n", SCRIPT),
+        response("304 Not Modified", "", ""),
+    ]);
+
+    assert_eq!(
+        download(&url, &runner).to(&file, &etag),
+        Ok(Fetched::Downloaded)
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), SCRIPT);
+    assert_eq!(fs::read_to_string(&etag).unwrap(), "\"1\"\n");
+    assert!(!requests.recv().unwrap().contains("if-none-match"));
+
+    assert_eq!(
+        download(&url, &runner).to(&file, &etag),
+        Ok(Fetched::Unchanged)
+    );
+    assert!(
+        requests
+            .recv()
+            .unwrap()
+            .contains("if-none-match: \"1\"\r\n")
+    );
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn resume_interrupted_download() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("smarttab.kak");
+    let etag = temp_dir.path().join(".etag");
+    let runner = runner();
+
+    let (url, requests) = serve(vec![
+        cut(10),
+        response("206 Partial Content", "ETag: \"1\"\r\n", &SCRIPT[10..]),
+    ]);
+
+    let error = download(&url, &runner).to(&file, &etag).unwrap_err();
+    assert!(error.contains("transfer closed"), "{error}");
+    assert!(!file.exists());
+
+    assert_eq!(
+        download(&url, &runner).to(&file, &etag),
+        Ok(Fetched::Downloaded)
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), SCRIPT);
+
+    let resumed = requests.iter().nth(1).unwrap();
+    assert!(resumed.contains("range: bytes=10-\r\n"), "{resumed}");
+    assert!(resumed.contains("if-range: \"1\"\r\n"), "{resumed}");
+}
+
+#[test]
+fn check_resumed_download() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("smarttab.kak");
+    let etag = temp_dir.path().join(".etag");
+    let runner = runner();
+
+    let (url, _requests) = serve(vec![
+        cut(10),
+        response("206 Partial Content", "ETag: \"1\"\r\n", &SCRIPT[10..]),
+        cut(10),
+        response(
+            "206 Partial Content",
+            "ETag: \"1\"\r\n",
+            "define-command evil",
+        ),
+    ]);
+
+    let checked = Download {
+        sha256: Some(SHA256),
+        ..download(&url, &runner)
+    };
+
+    checked.to(&file, &etag).unwrap_err();
+    assert_eq!(checked.to(&file, &etag), Ok(Fetched::Downloaded));
+    assert_eq!(fs::read_to_string(&file).unwrap(), SCRIPT);
+
+    fs::remove_file(&file).unwrap();
+    fs::remove_file(&etag).unwrap();
+    checked.to(&file, &etag).unwrap_err();
+    let error = checked.to(&file, &etag).unwrap_err();
+    assert!(
+        error.contains("doesn't match its sha256 checksum"),
+        "{error}"
+    );
+    assert!(!file.exists());
+}
+
+#[test]
+fn restart_download_changed_upstream() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("smarttab.kak");
+    let etag = temp_dir.path().join(".etag");
+    let runner = runner();
+
+    let (url, _requests) = serve(vec![
+        cut(10),
+        response("200 OK", "ETag: \"2\"\r\n", "define-command tab %{}"),
+    ]);
+
+    download(&url, &runner).to(&file, &etag).unwrap_err();
+    assert_eq!(
+        download(&url, &runner).to(&file, &etag),
+        Ok(Fetched::Downloaded)
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), "define-command tab %{}");
+    assert_eq!(fs::read_to_string(&etag).unwrap(), "\"2\"\n");
+}
+
+#[test]
+fn follow_redirects() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("smarttab.kak");
+    let etag = temp_dir.path().join(".etag");
+    let runner = runner();
+
+    let (url, requests) = serve(vec![
+        response("302 Found", "Location: /moved/smarttab.kak\r\n", ""),
+        response("200 OK", "", SCRIPT),
+    ]);
+
+    assert_eq!(
+        download(&url, &runner).to(&file, &etag),
+        Ok(Fetched::Downloaded)
+    );
+    assert_eq!(fs::read_to_string(&file).unwrap(), SCRIPT);
+    assert!(!etag.exists());
+    assert!(
+        requests
+            .iter()
+            .nth(1)
+            .unwrap()
+            .starts_with("get /moved/smarttab.kak ")
+    );
+}
+
+#[test]
+fn fail_on_http_errors() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let file = temp_dir.path().join("smarttab.kak");
+    let runner = runner();
+
+    let (url, _requests) = serve(vec![response("404 Not Found", "", "Not Found")]);
+
+    let error = download(&url, &runner)
+        .to(&file, &temp_dir.path().join(".etag"))
+        .unwrap_err();
+    assert!(error.contains("404"), "{error}");
+    assert!(!file.exists());
+}
+
+#[test]
+fn proxies() {
+    let fake = FakeRunner::new();
+    let runner = fake.runner();
+    let url = "https://gist.githubusercontent.com/someone/abc/raw/smarttab.kak";
+    let proxy = |url| http::proxy(&download(url, &runner));
+
+    assert_eq!(proxy(url), None);
+
+    fake.set("ALL_PROXY", "socks5://localhost:1080".into());
+    assert_eq!(proxy(url).as_deref(), Some("socks5://localhost:1080"));
+
+    fake.set("https_proxy", "http://localhost:3128".into());
+    assert_eq!(proxy(url).as_deref(), Some("http://localhost:3128"));
+
+    fake.set("HTTP_PROXY", "http://localhost:8080".into());
+    assert_eq!(
+        proxy("http://example.com/smarttab.kak").as_deref(),
+        Some("socks5://localhost:1080")
+    );
+
+    fake.set("no_proxy", "localhost, .githubusercontent.com".into());
+    assert_eq!(proxy(url), None);
+    assert_eq!(
+        proxy("https://example.com/smarttab.kak").as_deref(),
+        Some("http://localhost:3128")
+    );
+
+    let direct = Download {
+        proxy: Some(""),
+        ..download("https://example.com/smarttab.kak", &runner)
+    };
+    assert_eq!(http::proxy(&direct), None);
+
+    let given = Download {
+        proxy: Some("socks5://proxy:1080"),
+        ..download(url, &runner)
+    };
+    assert_eq!(http::proxy(&given).as_deref(), Some("socks5://proxy:1080"));
+}
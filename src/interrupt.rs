@@ -0,0 +1,48 @@
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+/// Whether the user asked to stop the current run.
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Catches Ctrl-C until the returned guard is dropped, the time of a run.
+/// Meanwhile Ctrl-C asks the run to stop instead of killing Almoxarife
+/// outright, so that it can leave things as they were. Ctrl-C reaches the git
+/// processes of the plugins being fetched too, which stop on their own; a
+/// second Ctrl-C quits right away.
+pub fn catch() -> Guard {
+    REQUESTED.store(false, Ordering::SeqCst);
+
+    // SAFETY: the handler only touches an atomic and calls `_exit`, both safe
+    // to use from a signal handler.
+    unsafe {
+        libc::signal(libc::SIGINT, handle as *const () as libc::sighandler_t);
+    }
+
+    Guard
+}
+
+pub struct Guard;
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        // SAFETY: restoring the default disposition is always sound.
+        unsafe {
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+        }
+
+        REQUESTED.store(false, Ordering::SeqCst);
+    }
+}
+
+extern "C" fn handle(_: libc::c_int) {
+    if REQUESTED.swap(true, Ordering::SeqCst) {
+        // SAFETY: `_exit` is async-signal-safe.
+        unsafe { libc::_exit(130) }
+    }
+}
+
+/// Whether the run was asked to stop. Work not yet started should be left
+/// alone.
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
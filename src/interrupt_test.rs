@@ -0,0 +1,17 @@
+use crate::interrupt;
+
+#[test]
+fn catch_interruptions() {
+    let guard = interrupt::catch();
+    assert!(!interrupt::requested());
+
+    // SAFETY: SIGINT is handled while the guard lives.
+    unsafe {
+        libc::raise(libc::SIGINT);
+    }
+
+    assert!(interrupt::requested());
+
+    drop(guard);
+    assert!(!interrupt::requested());
+}
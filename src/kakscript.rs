@@ -0,0 +1,365 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fmt::Formatter;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// Something a Kakoune script makes available to the editor.
+#[derive(Debug, PartialEq)]
+pub enum Declaration {
+    Command(String),
+    Option(String),
+    Mapping {
+        scope: String,
+        mode: String,
+        key: String,
+    },
+}
+
+impl Declaration {
+    /// Whether this declaration is the command or option with the given name,
+    /// or the mapping of the given key.
+    pub fn provides(&self, name: &str) -> bool {
+        match self {
+            Declaration::Command(command) => command == name,
+            Declaration::Option(option) => option == name,
+            Declaration::Mapping { key, .. } => key == name,
+        }
+    }
+}
+
+impl Display for Declaration {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Declaration::Command(name) => write!(f, "define-command {name}"),
+            Declaration::Option(name) => write!(f, "declare-option {name}"),
+            Declaration::Mapping { scope, mode, key } => write!(f, "map {scope} {mode} {key}"),
+        }
+    }
+}
+
+/// Switches of `define-command` that take an argument.
+const COMMAND_SWITCHES_WITH_ARGUMENT: &[&str] = &[
+    "-params",
+    "-docstring",
+    "-shell-completion",
+    "-shell-script-completion",
+    "-shell-script-candidates",
+];
+
+/// Finds the declarations made by a Kakoune script, along with the (1-based)
+/// line where each one happens.
+///
+/// This is not a full parser: it only looks at lines starting with a command,
+/// which is how virtually every plugin declares things.
+pub fn declarations(source: &str) -> Vec<(usize, Declaration)> {
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| Some((i + 1, declaration(line)?)))
+        .collect()
+}
+
+fn declaration(line: &str) -> Option<Declaration> {
+    let words = words(line);
+    let (command, args) = words.split_first()?;
+
+    match command.as_str() {
+        "define-command" | "def" => {
+            let mut args = args.iter();
+
+            while let Some(arg) = args.next() {
+                if COMMAND_SWITCHES_WITH_ARGUMENT.contains(&arg.as_str()) {
+                    args.next();
+                } else if !arg.starts_with('-') {
+                    return Some(Declaration::Command(arg.clone()));
+                }
+            }
+
+            None
+        }
+
+        "declare-option" | "decl" => {
+            let [_kind, name, ..] = skip_switches(args) else {
+                return None;
+            };
+
+            Some(Declaration::Option(name.clone()))
+        }
+
+        "map" => {
+            let [scope, mode, key, ..] = skip_switches(args) else {
+                return None;
+            };
+
+            Some(Declaration::Mapping {
+                scope: scope.clone(),
+                mode: mode.clone(),
+                key: key.clone(),
+            })
+        }
+
+        _ => None,
+    }
+}
+
+/// The modules a Kakoune script provides, as in `provide-module lsp %{`.
+pub fn modules(source: &str) -> Vec<String> {
+    source
+        .lines()
+        .filter_map(|line| {
+            let words = words(line);
+            let (command, args) = words.split_first()?;
+
+            if command != "provide-module" {
+                return None;
+            }
+
+            skip_switches(args).first().cloned()
+        })
+        .collect()
+}
+
+/// Skips the leading switches of `declare-option` and `map`, of which only
+/// `-docstring` takes an argument.
+fn skip_switches(args: &[String]) -> &[String] {
+    match args.split_first() {
+        Some((switch, rest)) if switch == "-docstring" => {
+            skip_switches(rest.get(1..).unwrap_or_default())
+        }
+        Some((switch, rest)) if switch.starts_with('-') => skip_switches(rest),
+        _ => args,
+    }
+}
+
+/// Splits a line of kakscript into words, honouring quotes. It stops at the
+/// first comment or `%` expansion, since we are only interested in the words
+/// preceding a command body.
+fn words(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut chars = line.trim_start().chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+            }
+
+            '#' | '%' => break,
+
+            '\'' | '"' => {
+                chars.next();
+                let mut word = String::new();
+
+                while let Some(d) = chars.next() {
+                    if d != c {
+                        word.push(d);
+                    } else if chars.peek() == Some(&c) {
+                        // A doubled quote is an escaped quote.
+                        word.push(d);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+
+                words.push(word);
+            }
+
+            _ => {
+                let mut word = String::new();
+
+                while let Some(&d) = chars.peek() {
+                    if d == ' ' || d == '\t' {
+                        break;
+                    }
+
+                    word.push(d);
+                    chars.next();
+                }
+
+                words.push(word);
+            }
+        }
+    }
+
+    words
+}
+
+/// A key bound globally by more than one plugin in the same mode.
+#[derive(Debug, PartialEq)]
+pub struct MappingConflict {
+    pub mode: String,
+    pub key: String,
+    pub plugins: Vec<String>,
+}
+
+impl Display for MappingConflict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} is mapped in {} mode by {}",
+            self.key,
+            self.mode,
+            self.plugins.join(", ")
+        )
+    }
+}
+
+/// Finds keys mapped in the global scope by the config of more than one plugin.
+/// Each item of `configs` is a pair of plugin name and config.
+pub fn mapping_conflicts<'a>(
+    configs: impl IntoIterator<Item = (&'a str, &'a str)>,
+) -> Vec<MappingConflict> {
+    let mut mappings: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+
+    for (plugin, config) in configs {
+        for (_, declaration) in declarations(config) {
+            if let Declaration::Mapping { scope, mode, key } = declaration
+                && scope == "global"
+            {
+                mappings.entry((mode, key)).or_default().push(plugin.into());
+            }
+        }
+    }
+
+    mappings
+        .into_iter()
+        .filter_map(|((mode, key), mut plugins)| {
+            plugins.sort();
+            plugins.dedup();
+
+            if plugins.len() < 2 {
+                return None;
+            }
+
+            Some(MappingConflict { mode, key, plugins })
+        })
+        .collect()
+}
+
+/// A `%sh` expansion of a Kakoune script, whose code runs in a shell.
+#[derive(Debug, PartialEq)]
+pub struct ShellBlock {
+    /// The (1-based) line where the block starts.
+    pub line: usize,
+    /// What comes before the block on that line, like `evaluate-commands`.
+    pub before: String,
+    /// The code between the delimiters.
+    pub code: String,
+}
+
+/// Finds the `%sh` expansions of a Kakoune script, leaving out commented
+/// lines. Like Kakoune, nested pairs of the block's delimiters are skipped
+/// over, and quotes are of no concern.
+pub fn shell_blocks(source: &str) -> Vec<ShellBlock> {
+    let bytes = source.as_bytes();
+    let mut blocks = Vec::new();
+    let mut line = 1;
+    let mut line_start = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\n' => {
+                line += 1;
+                line_start = i + 1;
+            }
+
+            b'#' if source[line_start..i].trim().is_empty() => {
+                // The newline ending the comment is handled as any other.
+                while i + 1 < bytes.len() && bytes[i + 1] != b'\n' {
+                    i += 1;
+                }
+            }
+
+            b'%' if source[i..].starts_with("%sh") && i + 3 < bytes.len() => {
+                let open = bytes[i + 3];
+                let close = match open {
+                    b'{' => b'}',
+                    b'(' => b')',
+                    b'[' => b']',
+                    b'<' => b'>',
+                    b'|' => b'|',
+                    _ => {
+                        i += 1;
+                        continue;
+                    }
+                };
+
+                let start = i + 4;
+                let mut end = start;
+                let mut depth = 1;
+
+                while end < bytes.len() {
+                    if bytes[end] == close {
+                        depth -= 1;
+
+                        if depth == 0 {
+                            break;
+                        }
+                    } else if bytes[end] == open {
+                        depth += 1;
+                    }
+
+                    end += 1;
+                }
+
+                let code = &source[start..end];
+
+                blocks.push(ShellBlock {
+                    line,
+                    before: source[line_start..i].trim().to_string(),
+                    code: code.to_string(),
+                });
+
+                if let Some(last) = code.rfind('\n') {
+                    line += code.matches('\n').count();
+                    line_start = start + last + 1;
+                }
+
+                i = end + 1;
+                continue;
+            }
+
+            _ => (),
+        }
+
+        i += 1;
+    }
+
+    blocks
+}
+
+/// Lists the `.kak` files inside `dir`, recursively, skipping hidden
+/// directories like `.git`.
+pub fn kak_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            files.extend(kak_files(&path)?);
+        } else if path.extension().is_some_and(|extension| extension == "kak") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Reads the script at `path`, replacing anything that isn't valid UTF-8, so
+/// that a stray Latin-1 comment doesn't hide the rest of the file.
+pub fn read(path: &Path) -> io::Result<String> {
+    let bytes = fs::read(path)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
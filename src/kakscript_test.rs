@@ -0,0 +1,174 @@
+use std::fs;
+
+use crate::kakscript;
+use crate::kakscript::Declaration;
+use crate::kakscript::MappingConflict;
+use crate::kakscript::ShellBlock;
+
+#[test]
+fn declarations() {
+    let source = r#"
+declare-option -hidden str phantom_selections
+decl -docstring "the number of selections" int phantom_count 0
+
+define-command -params 0..1 -docstring 'add a ''phantom'' selection' phantom-selection-add-selection %{
+    # define-command not-a-command
+    nop
+}
+
+def -hidden phantom-selection-iterate %{ nop }
+map global normal f ': phantom-selection-add-selection<ret>'
+map -docstring 'clear phantoms' global user <a-f> ': phantom-selection-clear<ret>'
+provide-module phantom-selection %{}
+"#;
+
+    assert_eq!(
+        kakscript::declarations(source),
+        [
+            (2, Declaration::Option("phantom_selections".into())),
+            (3, Declaration::Option("phantom_count".into())),
+            (
+                5,
+                Declaration::Command("phantom-selection-add-selection".into())
+            ),
+            (10, Declaration::Command("phantom-selection-iterate".into())),
+            (
+                11,
+                Declaration::Mapping {
+                    scope: "global".into(),
+                    mode: "normal".into(),
+                    key: "f".into()
+                }
+            ),
+            (
+                12,
+                Declaration::Mapping {
+                    scope: "global".into(),
+                    mode: "user".into(),
+                    key: "<a-f>".into()
+                }
+            ),
+        ]
+    );
+}
+
+#[test]
+fn modules() {
+    let source = "provide-module lsp %{
+    provide-module -override 'lsp-snippets' %{}
+}
+# provide-module commented
+provide-module
+";
+
+    assert_eq!(kakscript::modules(source), ["lsp", "lsp-snippets"]);
+}
+
+#[test]
+fn declarations_incomplete_lines() {
+    let source = "define-command -hidden\ndeclare-option str\nmap global normal\n";
+    assert_eq!(kakscript::declarations(source), []);
+}
+
+#[test]
+fn kak_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let rc = temp_dir.path().join("rc");
+    let git = temp_dir.path().join(".git");
+    fs::create_dir_all(&rc).unwrap();
+    fs::create_dir_all(&git).unwrap();
+
+    fs::write(temp_dir.path().join("plugin.kak"), "").unwrap();
+    fs::write(rc.join("extra.kak"), "").unwrap();
+    fs::write(rc.join("README.md"), "").unwrap();
+    fs::write(git.join("hook.kak"), "").unwrap();
+
+    assert_eq!(
+        kakscript::kak_files(temp_dir.path()).unwrap(),
+        [temp_dir.path().join("plugin.kak"), rc.join("extra.kak")]
+    );
+}
+
+#[test]
+fn read_invalid_utf8() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let script = temp_dir.path().join("plugin.kak");
+    fs::write(&script, b"# caf\xe9\ndefine-command phantom-add %{}\n").unwrap();
+
+    let source = kakscript::read(&script).unwrap();
+    assert_eq!(
+        kakscript::declarations(&source),
+        [(2, Declaration::Command("phantom-add".into()))]
+    );
+}
+
+#[test]
+fn shell_blocks() {
+    let source = "declare-option str version %sh{ git describe }
+# nop %sh{ commented out }
+define-command update %{
+    nop %sh(
+        curl $(echo url)
+    )
+    echo %sh|date|
+}
+";
+
+    assert_eq!(
+        kakscript::shell_blocks(source),
+        [
+            ShellBlock {
+                line: 1,
+                before: "declare-option str version".into(),
+                code: " git describe ".into(),
+            },
+            ShellBlock {
+                line: 4,
+                before: "nop".into(),
+                code: "\n        curl $(echo url)\n    ".into(),
+            },
+            ShellBlock {
+                line: 7,
+                before: "echo".into(),
+                code: "date".into(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn mapping_conflicts() {
+    let configs = [
+        (
+            "peneira-filters",
+            "map global normal <c-p> ': peneira-filters-mode<ret>'",
+        ),
+        (
+            "fzf",
+            "map global normal <c-p> ': fzf-mode<ret>'\nmap global user f ': fzf<ret>'",
+        ),
+        (
+            "kakoune-phantom-selection",
+            "map global normal f ': phantom-selection-add-selection<ret>'\nmap global normal f ': phantom-selection-add-selection<ret>'",
+        ),
+        (
+            "kak-lsp",
+            "hook global WinSetOption filetype=rust %{\n    map window normal <c-p> ': lsp-hover<ret>'\n}",
+        ),
+    ];
+
+    let conflicts = kakscript::mapping_conflicts(configs);
+    assert_eq!(
+        conflicts,
+        [MappingConflict {
+            mode: "normal".into(),
+            key: "<c-p>".into(),
+            plugins: vec!["fzf".into(), "peneira-filters".into()],
+        }]
+    );
+
+    assert_eq!(
+        conflicts[0].to_string(),
+        "<c-p> is mapped in normal mode by fzf, peneira-filters"
+    );
+}
@@ -1,214 +1,2890 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
 use std::env;
 use std::error;
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::fmt::Formatter;
 use std::fs;
-use std::fs::File;
+use std::io;
+use std::io::IsTerminal;
+use std::io::Write;
 use std::mem;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process;
-use std::process::Command;
 use std::result;
 use std::sync::mpsc;
 use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 
+use cli::Args;
+use cli::Command;
+use cli::PathQuery;
 use colorized::Color;
 use colorized::Colors;
+use gitconfig::GitConfig;
+use hooks::Hooks;
+use metrics::Metrics;
 
+use prompt::Prompt;
+use report::Change;
+use report::Outcome;
+use report::Pager;
+use report::Porcelain;
+use report::Reporter;
+use report::Revisions;
+use report::Terminal;
+use report::Transcript;
+use setup::Config;
+use setup::Integration;
 use setup::Kak;
 use setup::Plugin;
+use setup::Sandbox;
 use setup::Setup;
 use setup::Status;
+use state::Build;
+use state::Done;
+use state::Install;
+use state::State;
+use throttle::BuildLimits;
+use throttle::HostLimits;
+use throttle::Limits;
+use wizard::Wizard;
 
 use crate::setup::PluginError;
 
+mod abandoned;
+#[cfg(test)]
+mod abandoned_test;
+mod adopt;
+#[cfg(test)]
+mod adopt_test;
+mod audit;
+#[cfg(test)]
+mod audit_test;
+mod cache;
+#[cfg(test)]
+mod cache_test;
+mod check;
+#[cfg(test)]
+mod check_test;
+mod ci;
+#[cfg(test)]
+mod ci_test;
+mod cli;
+#[cfg(test)]
+mod cli_test;
+mod dev;
+#[cfg(test)]
+mod dev_test;
+mod dotfiles;
+#[cfg(test)]
+mod dotfiles_test;
+mod download;
+// The tests download through the fake curl.
+#[cfg(all(test, not(feature = "http")))]
+mod download_test;
+mod edit;
+#[cfg(test)]
+mod edit_test;
+#[cfg(test)]
+mod fakes_test;
+mod forge;
+#[cfg(test)]
+mod forge_test;
+mod generate;
+#[cfg(test)]
+mod generate_test;
+mod gitconfig;
+#[cfg(test)]
+mod gitconfig_test;
+mod graph;
+#[cfg(test)]
+mod graph_test;
+mod hooks;
+#[cfg(test)]
+mod hooks_test;
+#[cfg(feature = "http")]
+mod http;
+#[cfg(all(test, feature = "http"))]
+mod http_test;
+mod interrupt;
+#[cfg(test)]
+mod interrupt_test;
+mod kakscript;
+#[cfg(test)]
+mod kakscript_test;
+mod maintenance;
+#[cfg(test)]
+mod maintenance_test;
+mod manifest;
+#[cfg(test)]
+mod manifest_test;
+mod metrics;
+#[cfg(test)]
+mod metrics_test;
+mod migrate;
+#[cfg(test)]
+mod migrate_test;
+mod notify;
+#[cfg(test)]
+mod notify_test;
+mod order;
+#[cfg(test)]
+mod order_test;
+mod policy;
+#[cfg(test)]
+mod policy_test;
+mod prompt;
+mod readme;
+#[cfg(test)]
+mod readme_test;
+mod report;
+#[cfg(test)]
+mod report_test;
+mod review;
+#[cfg(test)]
+mod review_test;
+mod runner;
+#[cfg(test)]
+mod runner_test;
+mod schedule;
+#[cfg(test)]
+mod schedule_test;
+mod schema;
+#[cfg(test)]
+mod schema_test;
+mod self_update;
+#[cfg(test)]
+mod self_update_test;
+mod session;
+#[cfg(test)]
+mod session_test;
 mod setup;
 #[cfg(test)]
 mod setup_test;
+mod source;
+#[cfg(test)]
+mod source_test;
+mod state;
+#[cfg(test)]
+mod state_test;
+mod stats;
+#[cfg(test)]
+mod stats_test;
+mod throttle;
+#[cfg(test)]
+mod throttle_test;
+mod time;
+#[cfg(test)]
+mod time_test;
+mod vendor;
+#[cfg(test)]
+mod vendor_test;
+mod verify;
+#[cfg(test)]
+mod verify_test;
+mod why;
+#[cfg(test)]
+mod why_test;
+mod wizard;
+#[cfg(test)]
+mod wizard_test;
+
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+fn main() -> Result<()> {
+    let args = cli::parse(env::args().skip(1)).map_err(Error::Message)?;
+    let verbose = args.verbose;
+
+    run(args).map_err(|error| match error {
+        Error::Plugins(errors) => plugins_error(errors, verbose),
+        error => error,
+    })
+}
+
+/// The error for the plugins that failed. Explained git failures leave git's
+/// own output out, unless `verbose`.
+fn plugins_error(errors: Vec<PluginError>, verbose: bool) -> Error {
+    if verbose {
+        Error::Plugins(errors)
+    } else {
+        Error::Plugins(
+            errors
+                .into_iter()
+                .map(PluginError::without_git_output)
+                .collect(),
+        )
+    }
+}
+
+fn run(args: Args) -> Result<()> {
+    // Generating must not depend on anything but its arguments, so it's
+    // handled before the usual setup.
+    if let Command::Generate {
+        config,
+        sources,
+        out,
+    } = &args.command
+    {
+        let sources = fs::canonicalize(sources)
+            .context(&format!("couldn't find {}", sources.to_string_lossy()))?;
+
+        let setup = Setup::for_generation(config, &sources, out);
+        let errors = generate::generate(&setup, new_reporter(&args).as_ref())
+            .context("couldn't generate the plugins' files")?;
+
+        return if errors.is_empty() {
+            Ok(())
+        } else {
+            eprintln!();
+            Err(Error::Plugins(errors))
+        };
+    }
+
+    let mut setup = Setup::new();
+
+    if let Some(target) = &args.target {
+        setup.set_target(target);
+    }
+
+    if let Some(dir) = &args.kak_config_dir {
+        setup.set_kak_config_dir(dir);
+    }
+
+    if let Some(root) = &args.writable_root {
+        setup.set_writable_root(root);
+    }
+
+    if let Some(workspace) = &args.workspace {
+        setup.set_workspace(workspace);
+    }
+
+    // Isolation must be in place before anything runs, git included, so the
+    // configuration file is read ahead for it. Errors in it are reported by
+    // whatever reads it next.
+    let isolation = setup.open_config_file().ok().and_then(|config| {
+        let settings = config.settings;
+        settings.isolate_env.then_some(settings.pass_env)
+    });
+
+    if let Some(pass) = isolation {
+        setup.isolate(pass);
+    }
+
+    setup.force_adopt = args.force_adopt;
+    setup.rebuild = args.rebuild;
+    setup.resume = args.resume;
+    setup.commit_age = args.verbose;
+    setup.builds = State::load(&setup.state_path).builds;
+    setup.deadline = args.max_time.map(|max_time| Instant::now() + max_time);
+    setup.git_config = GitConfig::load(&setup.runner);
+
+    match args.command {
+        Command::Update => (),
+
+        Command::Generate { .. } => unreachable!("handled before the setup"),
+
+        Command::Version => {
+            println!("Almoxarife {VERSION}");
+            return Ok(());
+        }
+
+        Command::Help => {
+            println!("{}", cli::HELP);
+            return Ok(());
+        }
+
+        Command::Schema => {
+            println!("{}", schema::json_schema());
+            return Ok(());
+        }
+
+        Command::Adopt => return adopt(&setup),
+        Command::Audit(name) => return audit(&setup, &name),
+        Command::Check => return check(&setup),
+
+        Command::Freeze => return freeze(&setup),
+
+        Command::Relink => return relink(&setup, new_reporter(&args).as_ref()),
+
+        Command::Verify { fix } => return verify(&setup, fix),
+
+        Command::Vendor { dir, full_history } => return vendor(&setup, &dir, full_history),
+
+        Command::Bundle(archive) => return bundle(&setup, &archive),
+
+        Command::Dev {
+            ref name,
+            ref checkout,
+        } => {
+            let reporter = new_reporter(&args);
+            return dev(&setup, name, checkout.as_deref(), reporter.as_ref());
+        }
+
+        Command::Exec { name, command } => return exec(&setup, &name, &command),
+
+        Command::List => return list(&setup),
+
+        Command::Info { name, readme } => return info(&setup, &name, readme),
+
+        Command::Open { name, edit } => return open(&setup, &name, edit),
+
+        Command::Path { name, query } => return path(&setup, &name, query),
+
+        Command::Maintain => return maintain(&setup, new_reporter(&args).as_ref()),
+
+        Command::Migrate => return migrate(&setup),
+
+        Command::PruneDisabled => return prune_disabled(&setup, new_reporter(&args).as_ref()),
+
+        Command::RestoreScript => return restore_script(&setup),
+
+        Command::Restore(ref dir) => {
+            let restored = vendor::restore(&setup, dir).context(&format!(
+                "couldn't restore plugins from {}",
+                dir.to_string_lossy()
+            ))?;
+
+            println!("Restored {} plugins.", restored.len());
+            return relink(&setup, new_reporter(&args).as_ref());
+        }
+
+        Command::Which(name) => return which(&setup, &name),
+
+        Command::Why(name) => return why(&setup, &name),
+
+        Command::Graph(format) => return graph(&setup, format),
+
+        Command::Stats { stale_after } => return stats(&setup, stale_after),
+
+        Command::SelfUpdate => {
+            match self_update::self_update(&setup, VERSION).context("couldn't update al")? {
+                self_update::Outcome::UpToDate => println!("Almoxarife {VERSION} is up to date."),
+                self_update::Outcome::Updated(version) => {
+                    println!("Updated Almoxarife from {VERSION} to {version}.")
+                }
+            }
+
+            return Ok(());
+        }
+
+        Command::Schedule(None) => {
+            schedule::remove(&setup).context("couldn't remove the scheduled updates")?;
+            println!("Scheduled updates removed.");
+            return Ok(());
+        }
+
+        Command::Schedule(Some(cadence)) => {
+            let files = schedule::install(&setup, cadence).context("couldn't schedule updates")?;
+
+            for file in files {
+                println!("Wrote {}", file.to_string_lossy());
+            }
+
+            println!("Plugins will be updated {}.", cadence.name());
+            return Ok(());
+        }
+    }
+
+    if args.edit_config {
+        let status = setup
+            .runner
+            .status(process::Command::new("kak").arg(&setup.almoxarife_yaml_path))
+            .context("couldn't run Kakoune")?;
+
+        match status.code() {
+            None | Some(0) => (),
+            Some(_) => process::exit(1),
+        }
+    }
+
+    if !setup.almoxarife_yaml_path.exists() && io::stdin().is_terminal() && !first_run(&setup)? {
+        return Ok(());
+    }
+
+    let reporter = new_reporter(&args);
+    let reporter = reporter.as_ref();
+
+    if args.watch {
+        return watch(&setup, &args, reporter);
+    }
+
+    if args.auto
+        && let Some(recent) = recent_update(&setup)
+    {
+        reporter.skipped(&recent);
+        return Ok(());
+    }
+
+    let recorder = ci::Recorder::new(reporter);
+    let start = Instant::now();
+
+    let summary = match update(&setup, false, &recorder) {
+        Ok(summary) => summary,
+
+        Err(error) => {
+            if let Some(path) = &args.metrics_file {
+                write_metrics(path, Metrics::default(), start)?;
+            }
+
+            if args.notify {
+                notify_or_warn(&setup, reporter, &format!("Update failed: {error}"), true);
+            }
+
+            if let Some(format) = &args.report {
+                write_ci_report(format, recorder, &[], Some(&error.to_string()))?;
+            }
+
+            return Err(error);
+        }
+    };
+
+    if let Some(format) = &args.report {
+        write_ci_report(format, recorder, &summary.errors, None)?;
+    }
+
+    if let Some(path) = &args.metrics_file {
+        let metrics = Metrics {
+            installed: summary.installed,
+            updated: summary.updated,
+            failed: summary.errors.len(),
+            success: summary.errors.is_empty(),
+            ..Metrics::default()
+        };
+
+        write_metrics(path, metrics, start)?;
+    }
+
+    if args.notify {
+        let message = notify::message(summary.installed, summary.updated, summary.errors.len());
+        notify_or_warn(&setup, reporter, &message, !summary.errors.is_empty());
+    }
+
+    if !summary.errors.is_empty() {
+        eprintln!();
+        Err(Error::Plugins(summary.errors))
+    } else {
+        Ok(())
+    }
+}
+
+fn write_ci_report(
+    format: &ci::Format,
+    recorder: ci::Recorder,
+    errors: &[PluginError],
+    failure: Option<&str>,
+) -> Result<()> {
+    let (statuses, warnings) = recorder.into_records();
+
+    match format {
+        ci::Format::Junit(path) => {
+            fs::write(path, ci::junit(&statuses, errors, failure))
+                .context(&format!("couldn't write {}", path.to_string_lossy()))?;
+        }
+
+        ci::Format::Github => print!("{}", ci::github_annotations(errors, &warnings, failure)),
+    }
+
+    Ok(())
+}
+
+/// Writes the metrics of an update started at `start` and just finished.
+fn write_metrics(path: &Path, metrics: Metrics, start: Instant) -> Result<()> {
+    let metrics = Metrics {
+        timestamp: time::now(),
+        duration: start.elapsed(),
+        ..metrics
+    };
+
+    metrics
+        .write(path)
+        .context(&format!("couldn't write {}", path.to_string_lossy()))
+}
+
+fn new_reporter(args: &Args) -> Box<dyn Reporter> {
+    if args.porcelain {
+        Box::new(Porcelain::new())
+    } else {
+        Box::new(
+            Terminal::new(args.quiet)
+                .verbose(args.verbose)
+                .group_by_status(args.group_by_status)
+                .width(report::terminal_width().filter(|_| !args.no_wrap))
+                // A pager would hold the watch up until quit.
+                .pager(Pager::from_env().filter(|_| !args.no_pager && !args.watch)),
+        )
+    }
+}
+
+/// How often the configuration file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Updates plugins every time the configuration file changes. If all plugins
+/// are already installed, the update happens offline, only regenerating
+/// `almoxarife.kak`.
+fn watch(setup: &Setup, args: &Args, reporter: &dyn Reporter) -> Result<()> {
+    let modified = || {
+        fs::metadata(&setup.almoxarife_yaml_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+
+    let mut last_modified = modified();
+
+    // Starting to watch is an automatic update too, so it respects
+    // `auto_update_min_interval`.
+    let recent = recent_update(setup).filter(|_| all_installed(setup));
+
+    if let Some(recent) = &recent {
+        reporter.skipped(recent);
+    }
+
+    run_watched_update(setup, args, reporter, recent.is_some());
+
+    loop {
+        reporter.progress(&format!(
+            "Watching {} for changes...",
+            setup.almoxarife_yaml_path.to_string_lossy()
+        ));
+
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current = modified();
+
+            if current != last_modified {
+                last_modified = current;
+                break;
+            }
+        }
+
+        // Editors usually write files in several steps, so we wait for the
+        // file to settle before reading it.
+        loop {
+            thread::sleep(WATCH_INTERVAL);
+            let current = modified();
+
+            if current == last_modified {
+                break;
+            }
+
+            last_modified = current;
+        }
+
+        run_watched_update(setup, args, reporter, all_installed(setup));
+    }
+}
+
+fn all_installed(setup: &Setup) -> bool {
+    setup.open_config_file().is_ok_and(|config| {
+        config
+            .active_plugins()
+            .iter()
+            .all(|plugin| plugin.is_installed())
+    })
+}
+
+/// Describes the last successful update if it happened less than
+/// `auto_update_min_interval` ago.
+fn recent_update(setup: &Setup) -> Option<String> {
+    let interval = setup
+        .open_config_file()
+        .ok()?
+        .settings
+        .auto_update_min_interval?;
+
+    let state = State::load(&setup.state_path);
+    let elapsed = state.recent_run(time::now(), interval)?;
+
+    Some(format!(
+        "the last one was {} ago ({})",
+        time::describe_duration(elapsed),
+        state.last_result
+    ))
+}
+
+/// Runs an update for the watch mode, where errors are reported but don't stop
+/// watching.
+fn run_watched_update(setup: &Setup, args: &Args, reporter: &dyn Reporter, offline: bool) {
+    if offline {
+        reporter.progress("Regenerating almoxarife.kak");
+    }
+
+    match update(setup, offline, reporter) {
+        Ok(summary) => {
+            if args.notify {
+                let message =
+                    notify::message(summary.installed, summary.updated, summary.errors.len());
+                notify_or_warn(setup, reporter, &message, !summary.errors.is_empty());
+            }
+
+            if !summary.errors.is_empty() {
+                reporter.error(&plugins_error(summary.errors, args.verbose).to_string());
+            }
+        }
+
+        Err(error) => {
+            if args.notify {
+                notify_or_warn(setup, reporter, &format!("Update failed: {error}"), true);
+            }
+
+            reporter.error(&error.to_string());
+        }
+    }
+}
+
+fn notify_or_warn(setup: &Setup, reporter: &dyn Reporter, message: &str, failed: bool) {
+    if let Err(error) = notify::send(&setup.runner, message, failed) {
+        reporter.warnings(&[format!("couldn't send notification: {error}")]);
+    }
+}
+
+/// Updates plugins. When `offline`, only the plugins already installed are
+/// activated, and nothing is fetched nor removed.
+fn update(setup: &Setup, offline: bool, reporter: &dyn Reporter) -> Result<Summary> {
+    let _interrupt = interrupt::catch();
+
+    // Kept for `almoxarife-report`.
+    let transcript = Transcript::new(reporter);
+    let reporter: &dyn Reporter = &transcript;
+
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let integration = setup.integration(config.settings.integration);
+    let clone_size_warning = config.settings.clone_size_warning;
+    let review = config.settings.review && !offline;
+    let abandoned_after = config.settings.abandoned_after.filter(|_| !offline);
+    let maintenance_interval = config.settings.maintenance_interval.filter(|_| !offline);
+    let dotfiles_repo = config.settings.dotfiles_repo.clone().filter(|_| !offline);
+    let dotfiles_push = config.settings.dotfiles_push;
+
+    // Activating plugins doesn't reach the network.
+    let limits = Limits {
+        hosts: HostLimits::new(if offline {
+            None
+        } else {
+            config.settings.max_connections_per_host
+        }),
+        builds: BuildLimits::new(config.settings.max_parallel_builds),
+        sandbox: Sandbox::default(),
+    };
+
+    match integration {
+        Integration::Autoload => setup.create_dirs(),
+        Integration::Source => setup.create_dirs_for_sourcing(),
+    }
+    .context("couldn't setup Almoxarife")?;
+
+    // Hooks run around updates, which activating plugins isn't.
+    let hooks = if offline {
+        Hooks::default()
+    } else {
+        config.settings.hooks.clone()
+    };
+
+    if let Some(hook) = &hooks.before_all {
+        hooks::run(setup, "before_all", hook, &[]).map_err(Error::Message)?;
+    }
+
+    let disabled_plugins = config.disabled_plugins();
+    let mut removed_plugins = if offline {
+        Vec::new()
+    } else {
+        config
+            .removed_plugins()
+            .context("couldn't delete directories of removed plugins")?
+    };
+
+    if !offline && let Some(after) = config.settings.prune_disabled_after {
+        let state = State::load(&setup.state_path);
+
+        removed_plugins.extend(
+            state
+                .disabled_for(&disabled_plugins, after, time::now())
+                .into_iter()
+                .map(|name| setup.almoxarife_data_dir.join(name))
+                .filter(|path| path.exists()),
+        );
+    }
+
+    let mut plugins = config.active_plugins();
+
+    let conflicts = manifest::conflicts(&plugins);
+
+    if !conflicts.is_empty() {
+        return Err(Error::Message(format!(
+            "plugins that can't be enabled together:\n{}",
+            conflicts.join("\n")
+        )));
+    }
+
+    let mut warnings: Vec<String> = kakscript::mapping_conflicts(
+        plugins
+            .iter()
+            .map(|plugin| (plugin.name.as_str(), plugin.config.as_str())),
+    )
+    .iter()
+    .map(|conflict| conflict.to_string())
+    .collect();
+
+    if !offline && let Some(threshold) = clone_size_warning {
+        plugins = check_clone_sizes(setup, plugins, threshold, &mut warnings)?;
+    }
+
+    if review {
+        plugins = review_installs(plugins, &mut warnings, reporter)?;
+    }
+
+    // Kept to check, once they're fetched, whether they look abandoned, and
+    // to compact them.
+    let watched: Vec<Plugin> = match (abandoned_after, maintenance_interval) {
+        (None, None) => Vec::new(),
+        _ => plugins.clone(),
+    };
+
+    for disabled in &disabled_plugins {
+        reporter.status(disabled, Outcome::Disabled, Revisions::default(), None);
+    }
+
+    // With `--resume`, the plugins the interrupted update got done with are
+    // only activated.
+    let progress = if setup.resume && !offline {
+        State::load(&setup.state_path).progress
+    } else {
+        BTreeMap::new()
+    };
+
+    let activate: PluginAction = match integration {
+        Integration::Autoload => Plugin::activate,
+        Integration::Source => |plugin| plugin.inspect(),
+    };
+
+    // Plugins just installed or updated may depend on plugins their
+    // manifests are the first to tell about. Those are fetched once the
+    // others are, round after round, until no new ones turn up. Returns the
+    // dependencies fetched.
+    let manage = |plugins: Vec<Plugin>,
+                  kak: &mut Kak<Vec<u8>>,
+                  action: PluginAction|
+     -> Result<(Summary, Vec<Plugin>)> {
+        let mut known = plugins.clone();
+        let mut added = Vec::new();
+
+        let (resumed, plugins): (Vec<Plugin>, Vec<Plugin>) = plugins
+            .into_iter()
+            .partition(|plugin| progress.contains_key(&plugin.name) && plugin.is_installed());
+
+        let (mut summary, changes) = replay(resumed, &progress, kak, activate, reporter)?;
+
+        summary.add(manage_plugins(
+            plugins,
+            removed_plugins,
+            Carried { warnings, changes },
+            kak,
+            action,
+            &limits,
+            reporter,
+        )?);
+
+        while !offline && summary.interrupted == 0 {
+            let dependencies = config.dependencies(&known);
+
+            if dependencies.is_empty() {
+                break;
+            }
+
+            // Dependencies declined are known too, so that they aren't asked
+            // about again.
+            known.extend(dependencies.iter().cloned());
+            let mut round_warnings = Vec::new();
+
+            let dependencies = if review {
+                review_installs(dependencies, &mut round_warnings, reporter)?
+            } else {
+                dependencies
+            };
+
+            for dependency in &dependencies {
+                reporter.progress(&format!(
+                    "Installing {}, a dependency of {}",
+                    dependency.name,
+                    dependency.required_by.as_deref().unwrap_or_default()
+                ));
+            }
+
+            added.extend(dependencies.iter().cloned());
+
+            let round = manage_plugins(
+                dependencies,
+                Vec::new(),
+                Carried {
+                    warnings: round_warnings,
+                    ..Carried::default()
+                },
+                kak,
+                action,
+                &limits,
+                reporter,
+            )?;
+
+            summary.add(round);
+        }
+
+        Ok((summary, added))
+    };
+
+    let mut managed = plugins.clone();
+
+    let (summary, dependencies) = match integration {
+        Integration::Autoload => {
+            let mut link_paths: Vec<PathBuf> = plugins
+                .iter()
+                .flat_map(Plugin::link_paths)
+                .cloned()
+                .collect();
+
+            // The links of the plugins this run installs, or the one it
+            // resumes did, undone if it's interrupted.
+            let mut new_links: Vec<PathBuf> = plugins
+                .iter()
+                .filter(|plugin| {
+                    plugin.reviewed
+                        || !plugin.is_installed()
+                        || progress
+                            .get(&plugin.name)
+                            .is_some_and(|done| done.old_revision.is_none())
+                })
+                .flat_map(Plugin::link_paths)
+                .cloned()
+                .collect();
+
+            let mut kak = Kak::with_buffer();
+            kak.write_prelude(
+                setup.status_path().as_deref(),
+                setup.report_path().as_deref(),
+                Some(&setup.bin_dir()),
+                Some(&setup.workspaces_dir()),
+            )?;
+
+            let action: PluginAction = if offline {
+                Plugin::activate
+            } else {
+                Plugin::manage
+            };
+
+            let (summary, dependencies) = manage(plugins, &mut kak, action)?;
+            let links: Vec<PathBuf> = dependencies
+                .iter()
+                .flat_map(Plugin::link_paths)
+                .cloned()
+                .collect();
+            link_paths.extend(links.iter().cloned());
+            new_links.extend(links);
+
+            if summary.interrupted > 0 {
+                if !offline {
+                    record_progress(setup, &summary, reporter);
+                }
+
+                for link in new_links {
+                    if link.symlink_metadata().is_ok_and(|m| m.is_symlink()) {
+                        let _ = fs::remove_file(link);
+                    }
+                }
+
+                return Err(interrupted(&summary, &setup.almoxarife_kak));
+            }
+
+            kak.close(&order::sort(&[managed.as_slice(), &dependencies].concat()))?;
+
+            setup
+                .write_almoxarife_kak(&kak)
+                .context("couldn't configure plugins")?;
+
+            // Links of plugins that failed are kept, leaving them as they
+            // were before the update.
+            setup
+                .remove_stale_links(&link_paths.iter().map(PathBuf::as_path).collect())
+                .context("couldn't remove the links of removed plugins")?;
+
+            (summary, dependencies)
+        }
+
+        // Plugins are sourced from plugins.kak rather than linked, and it can
+        // only be written once we know what files each plugin has.
+        Integration::Source => {
+            let mut repositories: Vec<_> = plugins
+                .iter()
+                .map(|plugin| plugin.repository_path.clone())
+                .collect();
+
+            let mut kak = Kak::with_buffer();
+
+            // Workspaces are loaded into a running session, which already has
+            // everything the prelude defines.
+            if setup.workspace.is_some() {
+                kak.write_workspace_prelude()?;
+            } else {
+                kak.write_prelude(
+                    setup.status_path().as_deref(),
+                    setup.report_path().as_deref(),
+                    Some(&setup.bin_dir()),
+                    Some(&setup.workspaces_dir()),
+                )?;
+            }
+
+            let action: PluginAction = if offline {
+                |plugin| plugin.inspect()
+            } else {
+                |plugin| plugin.fetch()
+            };
+
+            let (summary, dependencies) = manage(plugins, &mut kak, action)?;
+            repositories.extend(
+                dependencies
+                    .iter()
+                    .map(|plugin| plugin.repository_path.clone()),
+            );
+
+            if summary.interrupted > 0 {
+                if !offline {
+                    record_progress(setup, &summary, reporter);
+                }
+
+                return Err(interrupted(&summary, &setup.plugins_kak));
+            }
+
+            kak.close(&order::sort(&[managed.as_slice(), &dependencies].concat()))?;
+
+            let repositories: Vec<_> = repositories
+                .iter()
+                .map(PathBuf::as_path)
+                .filter(|path| path.exists())
+                .collect();
+
+            setup
+                .write_plugins_kak(&repositories, &kak)
+                .context("couldn't configure plugins")?;
+
+            (summary, dependencies)
+        }
+    };
+
+    if let Some(line) = setup.missing_source_line() {
+        reporter.warnings(&[format!(
+            "plugins are written to {}; add `{line}` to your kakrc to load them",
+            setup.plugins_kak.to_string_lossy()
+        )]);
+    }
+
+    managed.extend(dependencies);
+
+    let checkouts: HashMap<String, PathBuf> = managed
+        .iter()
+        .map(|plugin| (plugin.name.clone(), plugin.repository_path.clone()))
+        .collect();
+
+    if !offline {
+        reporter.warnings(&manifest::warnings(setup, &managed));
+        reporter.warnings(&setup::nesting_warnings(&managed));
+    }
+
+    if let Some(hook) = &hooks.after_plugin_update {
+        run_after_plugin_update(setup, hook, &summary.fetched, &checkouts, reporter);
+    }
+
+    if !summary.after_update_kak.is_empty() {
+        send_after_update_kak(setup, &summary.after_update_kak, reporter);
+    }
+
+    if !summary.history.is_empty()
+        && let Err(error) = state::record(&setup.history_path(), time::now(), &summary.history)
+    {
+        reporter.warnings(&[format!("couldn't record the history log: {error}")]);
+    }
+
+    if !offline && let Some(path) = setup.status_path() {
+        let status = notify::summary(summary.installed, summary.updated, summary.errors.len());
+        publish_status(setup, &path, &status, reporter);
+    }
+
+    if !offline
+        && let Some(path) = setup.report_path()
+        && let Err(error) = fs::write(&path, transcript.text())
+    {
+        reporter.warnings(&[format!(
+            "couldn't write {}: {error}",
+            path.to_string_lossy()
+        )]);
+    }
+
+    if !offline {
+        let now = time::now();
+        let mut state = State::load(&setup.state_path);
+        state.last_run = Some(now);
+        state.last_run_result =
+            notify::message(summary.installed, summary.updated, summary.errors.len());
+
+        if summary.errors.is_empty() {
+            state.last_successful_run = Some(now);
+            state.last_result = state.last_run_result.clone();
+        }
+
+        state.track_disabled(&disabled_plugins, now);
+
+        // Failures, as when the network goes away, leave the rest for
+        // `--resume`.
+        state.progress = if summary.errors.is_empty() {
+            BTreeMap::new()
+        } else {
+            summary.progress()
+        };
+
+        state.builds.extend(summary.builds.iter().cloned());
+        state.builds.retain(|name, _| checkouts.contains_key(name));
+
+        for plugin in &summary.fetched {
+            if plugin.old_revision.is_none() {
+                let install = Install {
+                    revision: plugin.new_revision.clone(),
+                    tag: plugin.tag.clone(),
+                    at: now,
+                };
+
+                state.installs.insert(plugin.name.clone(), install);
+            }
+        }
+
+        state
+            .installs
+            .retain(|name, _| checkouts.contains_key(name));
+
+        if let Some(interval) = maintenance_interval
+            && state.maintenance_due(now, interval)
+        {
+            reporter.progress("Compacting repositories");
+
+            let warnings: Vec<String> = maintenance::maintain(&watched)
+                .iter()
+                .map(|error| format!("{}: {}", error.plugin(), error.description()))
+                .collect();
+
+            reporter.warnings(&warnings);
+            state.last_maintenance = Some(now);
+        }
+
+        if let Some(after) = abandoned_after {
+            reporter.warnings(&abandoned::warnings(
+                setup, &watched, after, now, &mut state,
+            ));
+        }
+
+        if let Err(error) = state.save(&setup.state_path) {
+            reporter.warnings(&[format!("couldn't save Almoxarife's state: {error}")]);
+        }
+    }
+
+    if let Some(repo) = &dotfiles_repo
+        && summary.errors.is_empty()
+    {
+        let changes: Vec<String> = summary
+            .fetched
+            .iter()
+            .map(|plugin| match &plugin.old_revision {
+                Some(old) => format!(
+                    "{}: updated from {old} to {}",
+                    plugin.name, plugin.new_revision
+                ),
+                None => match &plugin.tag {
+                    Some(tag) if *tag != plugin.new_revision => format!(
+                        "{}: installed at {} ({tag})",
+                        plugin.name, plugin.new_revision
+                    ),
+                    _ => format!("{}: installed at {}", plugin.name, plugin.new_revision),
+                },
+            })
+            .collect();
+
+        let message = dotfiles::message("Update Kakoune plugins", &changes);
+
+        if let Err(error) = dotfiles::commit(setup, repo, dotfiles_push, &message) {
+            reporter.warnings(&[format!("couldn't commit almoxarife.yaml: {error}")]);
+        }
+    }
+
+    if let Some(hook) = &hooks.after_all {
+        let names = |outcomes: &[Outcome]| {
+            summary
+                .fetched
+                .iter()
+                .filter(|plugin| outcomes.contains(&plugin.outcome))
+                .map(|plugin| plugin.name.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        let env = [
+            (
+                "ALMOXARIFE_INSTALLED",
+                names(&[Outcome::Installed, Outcome::InstalledWithoutKakFiles]),
+            ),
+            (
+                "ALMOXARIFE_UPDATED",
+                names(&[Outcome::Updated, Outcome::Rewritten]),
+            ),
+            (
+                "ALMOXARIFE_FAILED",
+                summary
+                    .errors
+                    .iter()
+                    .map(PluginError::plugin)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            ),
+        ];
+
+        if let Err(error) = hooks::run(setup, "after_all", hook, &env) {
+            reporter.warnings(&[error]);
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Runs the `after_plugin_update` hook for each plugin installed or updated,
+/// in the order they were done. A failing hook is only worth a warning: the
+/// plugin itself is up to date.
+fn run_after_plugin_update(
+    setup: &Setup,
+    hook: &str,
+    fetched: &[Fetched],
+    checkouts: &HashMap<String, PathBuf>,
+    reporter: &dyn Reporter,
+) {
+    let warnings: Vec<String> = fetched
+        .iter()
+        .filter_map(|plugin| {
+            let checkout = checkouts.get(&plugin.name)?;
+            let env = [
+                ("ALMOXARIFE_PLUGIN", plugin.name.clone()),
+                ("ALMOXARIFE_PLUGIN_DIR", checkout.to_string_lossy().into()),
+                ("ALMOXARIFE_STATUS", plugin.outcome.label().into()),
+                (
+                    "ALMOXARIFE_OLD_REVISION",
+                    plugin.old_revision.clone().unwrap_or_default(),
+                ),
+                ("ALMOXARIFE_NEW_REVISION", plugin.new_revision.clone()),
+            ];
+
+            let error = hooks::run(setup, "after_plugin_update", hook, &env).err()?;
+            Some(format!("{}: {error}", plugin.name))
+        })
+        .collect();
+
+    reporter.warnings(&warnings);
+}
+
+/// Sends the `after_update_kak` commands of the updated plugins to every
+/// running Kakoune session. Failing to do so is only worth a warning: the
+/// changes take effect on the next start anyway.
+fn send_after_update_kak(setup: &Setup, commands: &[String], reporter: &dyn Reporter) {
+    let sessions = match session::sessions(setup) {
+        Ok(sessions) => sessions,
+        Err(error) => {
+            reporter.warnings(&[format!("couldn't list Kakoune sessions: {error}")]);
+            return;
+        }
+    };
+
+    let script = session::after_update_script(commands.iter().map(String::as_str));
+
+    let warnings: Vec<String> = sessions
+        .iter()
+        .filter_map(|session| {
+            let error = session::send(setup, session, &script).err()?;
+            Some(format!(
+                "couldn't send commands to session {session}: {error}"
+            ))
+        })
+        .collect();
+
+    reporter.warnings(&warnings);
+}
+
+/// Sets `almoxarife_status` to `status`, in the script `almoxarife.kak`
+/// sources at `path` and in every running Kakoune session. Sessions not
+/// knowing the option yet, or the lack of any, aren't worth a warning.
+fn publish_status(setup: &Setup, path: &Path, status: &str, reporter: &dyn Reporter) {
+    let script = session::status_script(status);
+
+    let written = path
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(path, &script));
+
+    if let Err(error) = written {
+        reporter.warnings(&[format!(
+            "couldn't write {}: {error}",
+            path.to_string_lossy()
+        )]);
+    }
+
+    for session in session::sessions(setup).unwrap_or_default() {
+        let _ = session::send(setup, &session, &script);
+    }
+}
+
+/// The outcome of managing the plugins.
+#[derive(Default)]
+struct Summary {
+    installed: usize,
+    updated: usize,
+    errors: Vec<PluginError>,
+    /// The `after_update_kak` commands of the updated plugins.
+    after_update_kak: Vec<String>,
+    /// Events worth keeping in the history log.
+    history: Vec<String>,
+    /// How many plugins were left alone because the run was interrupted.
+    interrupted: usize,
+    /// The plugins installed or updated, in the order they were done.
+    fetched: Vec<Fetched>,
+    /// The plugins built successfully, along with what they were built from.
+    builds: Vec<(String, Build)>,
+    /// The plugins installed, updated or found unchanged, and what was done
+    /// to them, kept for `--resume` if the run doesn't get through.
+    done: Vec<(String, Done)>,
+}
+
+impl Summary {
+    /// Adds up the summary of a further round of plugins to this one.
+    fn add(&mut self, round: Summary) {
+        self.installed += round.installed;
+        self.updated += round.updated;
+        self.errors.extend(round.errors);
+        self.after_update_kak.extend(round.after_update_kak);
+        self.history.extend(round.history);
+        self.interrupted += round.interrupted;
+        self.fetched.extend(round.fetched);
+        self.builds.extend(round.builds);
+        self.done.extend(round.done);
+    }
+
+    /// What the run got done with, for `--resume` to leave alone.
+    fn progress(&self) -> BTreeMap<String, Done> {
+        let mut progress: BTreeMap<String, Done> = self.done.iter().cloned().collect();
+
+        for (name, build) in &self.builds {
+            if let Some(done) = progress.get_mut(name) {
+                done.build = Some(build.clone());
+            }
+        }
+
+        progress
+    }
+}
+
+/// A plugin installed or updated by a run.
+struct Fetched {
+    name: String,
+    outcome: Outcome,
+    /// Unset for plugins just installed.
+    old_revision: Option<String>,
+    new_revision: String,
+    /// The tag pointing at `new_revision`, only looked for on installs.
+    tag: Option<String>,
+}
+
+/// The error for a run interrupted with Ctrl-C. Nothing is written past that
+/// point, so Kakoune keeps loading plugins through the previous `script`.
+fn interrupted(summary: &Summary, script: &Path) -> Error {
+    let done = summary.installed + summary.updated;
+
+    Error::Message(format!(
+        "interrupted, leaving {} plugins as they were ({done} installed or updated before); {} \
+         was kept as it was, and the next run picks up the rest",
+        summary.interrupted,
+        script.to_string_lossy()
+    ))
+}
+
+/// Warns about the plugins about to be cloned whose repositories are larger
+/// than `threshold`. Run from a terminal, it asks whether to clone each of them,
+/// leaving out the ones the user declines.
+fn check_clone_sizes(
+    setup: &Setup,
+    plugins: Vec<Plugin>,
+    threshold: u64,
+    warnings: &mut Vec<String>,
+) -> Result<Vec<Plugin>> {
+    let interactive = io::stdin().is_terminal();
+    let mut kept = Vec::with_capacity(plugins.len());
+
+    for plugin in plugins {
+        if plugin.is_local || plugin.is_installed() {
+            kept.push(plugin);
+            continue;
+        }
+
+        let Some(size) =
+            forge::repository_size(setup, &plugin.location).filter(|&size| size > threshold)
+        else {
+            kept.push(plugin);
+            continue;
+        };
+
+        let size = forge::describe_size(size);
+        let advice = format!(
+            "if you don't need its whole history, clone it yourself with `git clone --depth 1` \
+             (or `--filter=blob:none`, for a partial clone) into {}",
+            plugin.repository_path.to_string_lossy()
+        );
+
+        if interactive {
+            // The prompt goes to stderr, keeping stdout for the report.
+            let mut prompt = Prompt::new(io::stdin().lock(), io::stderr());
+            let question = format!("{} is about {size}. Clone it anyway?", plugin.name);
+
+            if !prompt
+                .confirm(&question)
+                .context("couldn't read the answer")?
+            {
+                warnings.push(format!("{} wasn't cloned; {advice}", plugin.name));
+                continue;
+            }
+        } else {
+            warnings.push(format!("{} is about {size}; {advice}", plugin.name));
+        }
+
+        kept.push(plugin);
+    }
+
+    Ok(kept)
+}
+
+/// Clones the plugins not installed yet, for the user to look at what they'd
+/// run before accepting them, and keeps the ones accepted. The others are
+/// deleted. Nothing can be asked without a terminal, so new plugins are then
+/// left out.
+fn review_installs(
+    plugins: Vec<Plugin>,
+    warnings: &mut Vec<String>,
+    reporter: &dyn Reporter,
+) -> Result<Vec<Plugin>> {
+    let interactive = io::stdin().is_terminal();
+    let mut kept = Vec::with_capacity(plugins.len());
+
+    for mut plugin in plugins {
+        if plugin.is_local || plugin.is_installed() {
+            kept.push(plugin);
+            continue;
+        }
+
+        if !interactive {
+            warnings.push(format!(
+                "{} awaits review, so it wasn't installed; run al in a terminal to review it",
+                plugin.name
+            ));
+            continue;
+        }
+
+        reporter.progress(&format!("Cloning {} for review", plugin.name));
+
+        // Failures are left for the update to retry and report.
+        if plugin.fetch().is_err() || !plugin.is_installed() {
+            kept.push(plugin);
+            continue;
+        }
+
+        let summary = review::summary(&plugin).context("couldn't look into the plugin")?;
+
+        // The prompt goes to stderr, keeping stdout for the report.
+        let mut prompt = Prompt::new(io::stdin().lock(), io::stderr());
+        writeln!(prompt.output, "\n{}\n", summary.join("\n")).context("couldn't write")?;
+
+        let accepted = prompt
+            .confirm(&format!("Install {}?", plugin.name))
+            .context("couldn't read the answer")?;
+
+        if accepted {
+            plugin.reviewed = true;
+            kept.push(plugin);
+        } else {
+            fs::remove_dir_all(&plugin.repository_path).context(&format!(
+                "couldn't delete {}",
+                plugin.repository_path.to_string_lossy()
+            ))?;
+
+            warnings.push(format!(
+                "{} wasn't installed, since you declined it; disable it to stop being asked",
+                plugin.name
+            ));
+        }
+    }
+
+    Ok(kept)
+}
+
+/// What to do with each plugin: install or update it, or just activate it.
+type PluginAction = fn(Plugin) -> result::Result<Status, PluginError>;
+
+/// What a round of plugins reports at its end besides what it finds itself:
+/// the warnings found beforehand, and the changes of the plugins `--resume`
+/// replays.
+#[derive(Default)]
+struct Carried {
+    warnings: Vec<String>,
+    changes: Vec<Change>,
+}
+
+fn manage_plugins<W: Write>(
+    plugins: Vec<Plugin>,
+    removed_plugins: Vec<PathBuf>,
+    carried: Carried,
+    kak: &mut Kak<W>,
+    action: PluginAction,
+    limits: &Limits,
+    reporter: &dyn Reporter,
+) -> Result<Summary> {
+    let Carried {
+        mut warnings,
+        mut changes,
+    } = carried;
+    let (sender, receiver) = mpsc::channel();
+    let mut errors = Vec::new();
+    let mut installed = 0;
+    let mut updated = 0;
+    let mut interrupted = 0;
+    let mut timed_out = 0;
+    let mut fetched = Vec::new();
+    let mut builds = Vec::new();
+    let mut timings = Vec::new();
+    let mut done = Vec::new();
+
+    let mut after_update_commands: HashMap<String, String> = plugins
+        .iter()
+        .filter(|plugin| !plugin.after_update_kak.trim().is_empty())
+        .map(|plugin| (plugin.name.clone(), plugin.after_update_kak.clone()))
+        .collect();
+
+    let mut after_update_kak = Vec::new();
+    let mut history = Vec::new();
+    let mut renamed_branches = Vec::new();
+
+    let following_tags: HashSet<String> = plugins
+        .iter()
+        .filter(|plugin| plugin.follows_tags())
+        .map(|plugin| plugin.name.clone())
+        .collect();
+
+    // Children only load once their parents do, so they wait for their
+    // parents to be managed, and are skipped if their parents fail. Those
+    // whose parents aren't being managed at all go on their own.
+    let names: HashSet<String> = plugins.iter().map(|plugin| plugin.name.clone()).collect();
+    let mut children: HashMap<String, Vec<Plugin>> = HashMap::new();
+    let mut roots = Vec::new();
+
+    for plugin in plugins {
+        match &plugin.parent {
+            Some(parent) if names.contains(parent) => {
+                children.entry(parent.clone()).or_default().push(plugin)
+            }
+            _ => roots.push(plugin),
+        }
+    }
+
+    thread::scope(|s| -> Result<()> {
+        let spawn = |plugin: Plugin| {
+            let sender = sender.clone();
+
+            s.spawn(move || {
+                // Waiting for other plugins on the same host, or for other
+                // builds, doesn't count as time spent on this one.
+                let permit = limits.hosts.acquire(&plugin.location);
+                let start = Instant::now();
+
+                let result = if interrupt::requested() {
+                    Ok(Status::Interrupted {
+                        name: plugin.name.clone(),
+                    })
+                } else {
+                    action(plugin.clone())
+                };
+
+                drop(permit);
+                let mut elapsed = start.elapsed();
+
+                // Builds take turns apart from fetches, freeing the host for
+                // the next plugin meanwhile.
+                let pending_build = match &result {
+                    Ok(status) => plugin.pending_build(status),
+                    Err(_) => None,
+                };
+
+                let (result, built) = match (result, pending_build) {
+                    (Ok(status), Some(build)) => {
+                        let _permit = limits.builds.acquire();
+                        reporter.building(&plugin.name);
+
+                        let start = Instant::now();
+                        let result = plugin.build(status, &limits.sandbox);
+                        elapsed += start.elapsed();
+
+                        // Builds killed past the deadline leave the plugin
+                        // skipped.
+                        let built = matches!(&result, Ok(status) if !matches!(status, Status::Skipped { .. }));
+                        (result, built.then_some(build))
+                    }
+                    (result, _) => (result, None),
+                };
+
+                // Sending only fails if the results are no longer awaited.
+                let _ = sender.send((result, elapsed, built));
+            });
+        };
+
+        let mut pending = roots.len() + removed_plugins.len();
+
+        for plugin in roots {
+            spawn(plugin);
+        }
+
+        for removed in removed_plugins {
+            let sender = sender.clone();
+
+            s.spawn(move || {
+                let start = Instant::now();
+                let result = setup::remove_repository(&removed);
+                let _ = sender.send((result, start.elapsed(), None));
+            });
+        }
+
+        while pending > 0 {
+            let Ok((result, elapsed, built)) = receiver.recv() else {
+                break;
+            };
+
+            pending -= 1;
+
+            let name = match &result {
+                Ok(status) => status.name(),
+                Err(error) => error.plugin(),
+            }
+            .to_string();
+
+            if let Some(build) = built {
+                builds.push((name.clone(), build));
+            }
+
+            // Children of a plugin that was never installed would wait for
+            // it in vain.
+            let descend = match &result {
+                Ok(Status::Skipped { config, .. }) => config.is_some(),
+                result => result.is_ok(),
+            };
+
+            if descend {
+                for child in children.remove(&name).unwrap_or_default() {
+                    pending += 1;
+                    spawn(child);
+                }
+            }
+
+            timings.push((name.clone(), elapsed));
+            let elapsed = Some(elapsed);
+
+            match result {
+                Ok(Status::Installed {
+                    name,
+                    config,
+                    revision,
+                    tag,
+                }) => {
+                    installed += 1;
+                    kak.write_config(&name, config);
+                    reporter.status(
+                        &name,
+                        Outcome::Installed,
+                        Revisions {
+                            tag: tag.as_deref(),
+                            ..Revisions::new(&revision)
+                        },
+                        elapsed,
+                    );
+                    done.push((
+                        name.clone(),
+                        Done {
+                            new_revision: revision.clone(),
+                            tag: tag.clone(),
+                            ..Done::default()
+                        },
+                    ));
+                    fetched.push(Fetched {
+                        name,
+                        outcome: Outcome::Installed,
+                        old_revision: None,
+                        new_revision: revision,
+                        tag,
+                    });
+                }
+
+                Ok(Status::NoKakFiles {
+                    name,
+                    config,
+                    revision,
+                    tag,
+                }) => {
+                    installed += 1;
+                    kak.write_config(&name, config);
+                    reporter.status(
+                        &name,
+                        Outcome::InstalledWithoutKakFiles,
+                        Revisions {
+                            tag: tag.as_deref(),
+                            ..Revisions::new(&revision)
+                        },
+                        elapsed,
+                    );
+                    warnings.push(format!(
+                        "{name} has no .kak files; check whether its location is right"
+                    ));
+                    done.push((
+                        name.clone(),
+                        Done {
+                            new_revision: revision.clone(),
+                            tag: tag.clone(),
+                            ..Done::default()
+                        },
+                    ));
+                    fetched.push(Fetched {
+                        name,
+                        outcome: Outcome::InstalledWithoutKakFiles,
+                        old_revision: None,
+                        new_revision: revision,
+                        tag,
+                    });
+                }
+
+                Ok(Status::Unchanged {
+                    name,
+                    config,
+                    revision,
+                    renamed_branch,
+                    last_commit,
+                }) => {
+                    kak.write_config(&name, config);
+
+                    if let Some((old, new)) = renamed_branch {
+                        renamed_branches.push((name.clone(), old, new));
+                    }
+
+                    let revisions = Revisions {
+                        old: revision.as_deref(),
+                        new: revision.as_deref(),
+                        tag: None,
+                        last_commit_age: last_commit
+                            .map(|commit| Duration::from_secs(time::now().saturating_sub(commit))),
+                    };
+
+                    reporter.status(&name, Outcome::Unchanged, revisions, elapsed);
+
+                    if let Some(revision) = revision {
+                        done.push((
+                            name,
+                            Done {
+                                old_revision: Some(revision.clone()),
+                                new_revision: revision,
+                                ..Done::default()
+                            },
+                        ));
+                    }
+                }
+
+                Ok(Status::Updated {
+                    name,
+                    log,
+                    config,
+                    old_revision,
+                    new_revision,
+                    history_rewritten,
+                    breaking_changes,
+                    renamed_branch,
+                }) => {
+                    updated += 1;
+                    kak.write_config(&name, config);
+
+                    if let Some((old, new)) = renamed_branch {
+                        renamed_branches.push((name.clone(), old, new));
+                    }
+
+                    let revisions = Revisions {
+                        old: Some(&old_revision),
+                        new: Some(&new_revision),
+                        tag: None,
+                        last_commit_age: None,
+                    };
+
+                    let outcome = if history_rewritten {
+                        history.push(format!(
+                            "{name}: upstream history was rewritten; reset from {old_revision} to {new_revision}"
+                        ));
+
+                        Outcome::Rewritten
+                    } else {
+                        Outcome::Updated
+                    };
+
+                    reporter.status(&name, outcome, revisions, elapsed);
+                    fetched.push(Fetched {
+                        name: name.clone(),
+                        outcome,
+                        old_revision: Some(old_revision.clone()),
+                        new_revision: new_revision.clone(),
+                        tag: None,
+                    });
+                    done.push((
+                        name.clone(),
+                        Done {
+                            old_revision: Some(old_revision.clone()),
+                            new_revision: new_revision.clone(),
+                            log: log.clone(),
+                            ..Done::default()
+                        },
+                    ));
+
+                    if !breaking_changes.is_empty() {
+                        warnings.push(format!(
+                            "{name} may have breaking changes ({}); read about them before restarting Kakoune",
+                            breaking_changes.join(", ")
+                        ));
+                    }
+
+                    if let Some(commands) = after_update_commands.remove(&name) {
+                        after_update_kak.push(commands);
+                    }
+
+                    let versions = following_tags
+                        .contains(&name)
+                        .then(|| (old_revision.clone(), new_revision.clone()));
+
+                    // With `log_paths`, none of the new commits may be worth
+                    // listing.
+                    if !log.trim().is_empty() || versions.is_some() {
+                        changes.push(Change {
+                            name,
+                            log,
+                            versions,
+                        });
+                    }
+                }
+
+                Ok(Status::Local { name, config }) => {
+                    kak.write_config(&name, config);
+                    reporter.status(&name, Outcome::Local, Revisions::default(), elapsed);
+                }
+
+                Ok(Status::Deleted { name }) => {
+                    reporter.status(&name, Outcome::Removed, Revisions::default(), elapsed);
+                }
+
+                Ok(Status::Interrupted { name }) => {
+                    interrupted += 1;
+                    reporter.status(&name, Outcome::Interrupted, Revisions::default(), None);
+                }
+
+                Ok(Status::Skipped { name, config }) => {
+                    timed_out += 1;
+                    reporter.status(&name, Outcome::TimedOut, Revisions::default(), elapsed);
+
+                    match config {
+                        Some(config) => kak.write_config(&name, config),
+                        None => {
+                            timed_out +=
+                                skip_descendants(&name, &mut children, Outcome::TimedOut, reporter)
+                        }
+                    }
+                }
+
+                // Failures are expected from the git processes Ctrl-C stops.
+                Err(_) if interrupt::requested() => {
+                    interrupted += 1;
+                    reporter.status(&name, Outcome::Interrupted, Revisions::default(), None);
+                    interrupted +=
+                        skip_descendants(&name, &mut children, Outcome::Interrupted, reporter);
+                }
+
+                Err(error) => {
+                    reporter.status(
+                        error.plugin(),
+                        Outcome::Failed,
+                        Revisions::default(),
+                        elapsed,
+                    );
+                    errors.push(error);
+                    skip_descendants(&name, &mut children, Outcome::ParentFailed, reporter);
+                }
+            }
+        }
+
+        Ok(())
+    })?;
+
+    for (name, old, new) in renamed_branches {
+        warnings.push(format!(
+            "{name} renamed its default branch from {old} to {new} upstream; the checkout follows {new} now"
+        ));
+        history.push(format!(
+            "{name}: upstream renamed the default branch from {old} to {new}"
+        ));
+    }
+
+    if timed_out > 0 {
+        warnings.push(format!(
+            "{timed_out} plugins ran past --max-time and were skipped; the next update picks them up"
+        ));
+    }
+
+    warnings.extend(limits.sandbox.warning());
+
+    reporter.changes(&changes);
+    reporter.timings(&timings);
+    reporter.warnings(&warnings);
+
+    Ok(Summary {
+        installed,
+        updated,
+        errors,
+        after_update_kak,
+        history,
+        interrupted,
+        fetched,
+        builds,
+        done,
+    })
+}
+
+/// Activates the plugins an interrupted update got done with, with `activate`,
+/// reporting what that update did to them as if this run had, so that
+/// `--resume` ends with the report of the whole update. Nothing is fetched nor
+/// built again. Returns the changes to report along with the others.
+fn replay<W: Write>(
+    plugins: Vec<Plugin>,
+    progress: &BTreeMap<String, Done>,
+    kak: &mut Kak<W>,
+    activate: PluginAction,
+    reporter: &dyn Reporter,
+) -> Result<(Summary, Vec<Change>)> {
+    let mut summary = Summary::default();
+    let mut changes = Vec::new();
+
+    for plugin in plugins {
+        let Some(done) = progress.get(&plugin.name).cloned() else {
+            continue;
+        };
+
+        let name = plugin.name.clone();
+        let after_update_kak = plugin.after_update_kak.clone();
+        let follows_tags = plugin.follows_tags();
+
+        let config = match activate(plugin) {
+            Ok(Status::Unchanged { config, .. }) | Ok(Status::Local { config, .. }) => config,
+            Ok(_) => String::new(),
+            Err(error) => {
+                reporter.status(&name, Outcome::Failed, Revisions::default(), None);
+                summary.errors.push(error);
+                continue;
+            }
+        };
+
+        kak.write_config(&name, config);
+
+        let outcome = match &done.old_revision {
+            None => {
+                summary.installed += 1;
+                Outcome::Installed
+            }
+            Some(old) if *old == done.new_revision => Outcome::Unchanged,
+            Some(_) => {
+                summary.updated += 1;
+                Outcome::Updated
+            }
+        };
+
+        let revisions = Revisions {
+            old: done.old_revision.as_deref(),
+            tag: done.tag.as_deref(),
+            ..Revisions::new(&done.new_revision)
+        };
+
+        reporter.status(&name, outcome, revisions, None);
+
+        if let Some(build) = &done.build {
+            summary.builds.push((name.clone(), build.clone()));
+        }
+
+        if outcome != Outcome::Unchanged {
+            summary.fetched.push(Fetched {
+                name: name.clone(),
+                outcome,
+                old_revision: done.old_revision.clone(),
+                new_revision: done.new_revision.clone(),
+                tag: done.tag.clone(),
+            });
+        }
+
+        if outcome == Outcome::Updated {
+            if !after_update_kak.trim().is_empty() {
+                summary.after_update_kak.push(after_update_kak);
+            }
+
+            let versions = follows_tags.then(|| {
+                (
+                    done.old_revision.clone().unwrap_or_default(),
+                    done.new_revision.clone(),
+                )
+            });
+
+            if !done.log.trim().is_empty() || versions.is_some() {
+                changes.push(Change {
+                    name: name.clone(),
+                    log: done.log.clone(),
+                    versions,
+                });
+            }
+        }
+
+        summary.done.push((name, done));
+    }
+
+    Ok((summary, changes))
+}
+
+/// Records what an update that didn't get through got done with, for
+/// `--resume` to pick up the rest.
+fn record_progress(setup: &Setup, summary: &Summary, reporter: &dyn Reporter) {
+    let mut state = State::load(&setup.state_path);
+    state.progress = summary.progress();
+
+    if let Err(error) = state.save(&setup.state_path) {
+        reporter.warnings(&[format!("couldn't save Almoxarife's state: {error}")]);
+    }
+}
+
+/// Reports every descendant of the plugin `parent` with `outcome`, leaving
+/// them out of the generated script: their configs would wait for a module
+/// that's never loaded. Returns how many there were.
+fn skip_descendants(
+    parent: &str,
+    children: &mut HashMap<String, Vec<Plugin>>,
+    outcome: Outcome,
+    reporter: &dyn Reporter,
+) -> usize {
+    let mut skipped = 0;
+
+    for child in children.remove(parent).unwrap_or_default() {
+        reporter.status(&child.name, outcome, Revisions::default(), None);
+        skipped += 1 + skip_descendants(&child.name, children, outcome, reporter);
+    }
+
+    skipped
+}
+
+/// Runs the wizard creating the configuration file. Returns whether the file
+/// was created.
+fn first_run(setup: &Setup) -> Result<bool> {
+    let yaml = Wizard::new(setup, io::stdin().lock(), io::stdout())
+        .run()
+        .context("couldn't run the setup wizard")?;
+
+    let Some(yaml) = yaml else {
+        return Ok(false);
+    };
+
+    if let Some(dir) = setup.almoxarife_yaml_path.parent() {
+        fs::create_dir_all(dir).context("couldn't create the configuration directory")?;
+    }
+
+    fs::write(&setup.almoxarife_yaml_path, yaml).context("couldn't write almoxarife.yaml")?;
+    println!(
+        "\nWrote {}. Installing plugins...\n",
+        setup.almoxarife_yaml_path.to_string_lossy()
+    );
+
+    Ok(true)
+}
+
+fn adopt(setup: &Setup) -> Result<()> {
+    // Adopting plugins is a good way of starting to use Almoxarife, so the
+    // configuration file may not exist yet.
+    let yaml = fs::read_to_string(&setup.almoxarife_yaml_path).unwrap_or_default();
+
+    let config = if yaml.trim().is_empty() {
+        None
+    } else {
+        Some(
+            setup
+                .open_config_file()
+                .context("couldn't open almoxarife.yaml")?,
+        )
+    };
+
+    let managed = config
+        .as_ref()
+        .map(|config| config.plugin_names())
+        .unwrap_or_default();
+
+    let candidates =
+        adopt::candidates(setup, &managed).context("couldn't look for unmanaged plugins")?;
+
+    if candidates.is_empty() {
+        println!("No unmanaged plugins found.");
+        return Ok(());
+    }
+
+    fs::create_dir_all(&setup.almoxarife_data_dir).context("couldn't create the data directory")?;
+
+    let mut prompt = Prompt::new(io::stdin().lock(), io::stdout());
+    let mut entries = Vec::new();
+
+    for candidate in candidates {
+        println!(
+            "\n{} ({}) found at {}",
+            candidate.name.color(Colors::GreenFg),
+            candidate.location,
+            candidate.path.to_string_lossy()
+        );
+
+        if !prompt
+            .confirm("Adopt it?")
+            .context("couldn't read answer")?
+        {
+            continue;
+        }
+
+        candidate
+            .adopt(setup)
+            .context(&format!("couldn't adopt {}", candidate.name))?;
+
+        entries.push(candidate.entry());
+    }
+
+    if entries.is_empty() {
+        return Ok(());
+    }
+
+    let mut yaml = yaml;
+
+    if !yaml.is_empty() && !yaml.ends_with("\n\n") {
+        yaml.push_str(if yaml.ends_with('\n') { "\n" } else { "\n\n" });
+    }
+
+    yaml.push_str(&wizard::render_config(&entries));
+
+    if let Some(dir) = setup.almoxarife_yaml_path.parent() {
+        fs::create_dir_all(dir).context("couldn't create the configuration directory")?;
+    }
+
+    fs::write(&setup.almoxarife_yaml_path, yaml).context("couldn't write almoxarife.yaml")?;
+    println!(
+        "\nAdded {} plugins to {}. Run `al` to activate them.",
+        entries.len(),
+        setup.almoxarife_yaml_path.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+/// Recreates the links to the plugins and `almoxarife.kak` from the existing
+/// checkouts, without fetching anything.
+/// Compacts the object stores of every installed plugin.
+fn maintain(setup: &Setup, reporter: &dyn Reporter) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let errors = maintenance::maintain(&config.active_plugins());
+
+    let mut state = State::load(&setup.state_path);
+    state.last_maintenance = Some(time::now());
+
+    if let Err(error) = state.save(&setup.state_path) {
+        reporter.warnings(&[format!("couldn't save Almoxarife's state: {error}")]);
+    }
+
+    if !errors.is_empty() {
+        eprintln!();
+        return Err(Error::Plugins(errors));
+    }
+
+    println!("Repositories compacted.");
+    Ok(())
+}
+
+/// Reports the checkouts that drifted from what the configuration file pins
+/// and the last update left, resetting them to it when `fix`. Drifting
+/// checkouts left as they are make it fail, for scripts to notice.
+fn verify(setup: &Setup, fix: bool) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let mut errors = Vec::new();
+    let mut drifted = 0;
+
+    for plugin in config.active_plugins() {
+        let drift = match verify::drift(&plugin) {
+            Ok(drift) => drift,
+            Err(error) => {
+                println!("{:>20} {}", plugin.name, "failed".color(Colors::RedFg));
+                errors.push(error);
+                continue;
+            }
+        };
+
+        if drift.is_empty() {
+            continue;
+        }
+
+        let description = drift
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        println!(
+            "{:>20} {}",
+            plugin.name,
+            description.color(Colors::YellowFg)
+        );
+
+        // Missing checkouts are cloned by the next update, not here.
+        if !fix || drift.contains(&verify::Drift::Missing) {
+            drifted += 1;
+            continue;
+        }
 
-const VERSION: &str = env!("CARGO_PKG_VERSION");
+        match verify::fix(&plugin) {
+            Ok(()) => println!("{:>20} {}", plugin.name, "reset".color(Colors::BlueFg)),
+            Err(error) => {
+                println!("{:>20} {}", plugin.name, "failed".color(Colors::RedFg));
+                errors.push(error);
+            }
+        }
+    }
 
-fn main() -> Result<()> {
-    let setup = Setup::new();
+    if !errors.is_empty() {
+        eprintln!();
+        return Err(Error::Plugins(errors));
+    }
+
+    match drifted {
+        0 => {
+            println!("Every checkout is as expected.");
+            Ok(())
+        }
+        _ if fix => Err(Error::Message(
+            "some plugins are missing; run al to install them".to_string(),
+        )),
+        1 => Err(Error::Message(
+            "a checkout drifted; run al verify --fix to reset it".to_string(),
+        )),
+        _ => Err(Error::Message(format!(
+            "{drifted} checkouts drifted; run al verify --fix to reset them"
+        ))),
+    }
+}
+
+/// Deletes the checkouts of every disabled plugin. They're cloned again once
+/// the plugins are enabled.
+fn prune_disabled(setup: &Setup, reporter: &dyn Reporter) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
 
-    match env::args().nth(1) {
-        Some(arg) if arg == "--config" || arg == "-c" => {
-            let status = Command::new("kak")
-                .arg(&setup.almoxarife_yaml_path)
-                .status()
-                .context("couldn't run Kakoune")?;
+    let mut disabled = config.disabled_plugins();
+    disabled.sort();
 
-            match status.code() {
-                None | Some(0) => (),
-                Some(_) => process::exit(1),
+    let checkouts: Vec<PathBuf> = disabled
+        .iter()
+        .map(|name| setup.almoxarife_data_dir.join(name))
+        .filter(|path| path.exists())
+        .collect();
+
+    if checkouts.is_empty() {
+        println!("No disabled plugin has a checkout.");
+        return Ok(());
+    }
+
+    let mut errors = Vec::new();
+
+    for checkout in checkouts {
+        match setup::remove_repository(&checkout) {
+            Ok(status) => {
+                reporter.status(status.name(), Outcome::Removed, Revisions::default(), None)
+            }
+
+            Err(error) => {
+                reporter.status(error.plugin(), Outcome::Failed, Revisions::default(), None);
+                errors.push(error);
             }
         }
+    }
 
-        Some(arg) if arg == "--version" || arg == "-v" => {
-            println!("Almoxarife {VERSION}");
-            return Ok(());
+    if !errors.is_empty() {
+        eprintln!();
+        Err(Error::Plugins(errors))
+    } else {
+        Ok(())
+    }
+}
+
+fn relink(setup: &Setup, reporter: &dyn Reporter) -> Result<()> {
+    let summary = update(setup, true, reporter)?;
+
+    if !summary.errors.is_empty() {
+        eprintln!();
+        Err(Error::Plugins(summary.errors))
+    } else {
+        Ok(())
+    }
+}
+
+/// Points the plugin `name` to `checkout` or, without one, back to its
+/// upstream source, and rebuilds the links accordingly.
+fn dev(setup: &Setup, name: &str, checkout: Option<&Path>, reporter: &dyn Reporter) -> Result<()> {
+    let mut config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    if !config.plugin_names().contains(name) {
+        return Err(Error::Message(format!(
+            "there's no plugin named {name} in almoxarife.yaml"
+        )));
+    }
+
+    let mut overrides = mem::take(&mut config.overrides);
+
+    let message = match checkout {
+        Some(checkout) => {
+            let checkout = fs::canonicalize(checkout)
+                .context(&format!("couldn't find {}", checkout.to_string_lossy()))?;
+
+            if !checkout.is_dir() {
+                return Err(Error::Message(format!(
+                    "{} isn't a directory",
+                    checkout.to_string_lossy()
+                )));
+            }
+
+            let message = format!(
+                "{name} now uses {}. Run `al dev --reset {name}` to go back to upstream.",
+                checkout.to_string_lossy()
+            );
+
+            overrides.0.insert(name.to_string(), checkout);
+            message
         }
 
-        Some(arg) if arg == "--help" || arg == "-h" => {
-            println!(
-                "A plugin manager for the Kakoune editor.
+        None => {
+            if overrides.0.remove(name).is_none() {
+                return Err(Error::Message(format!(
+                    "{name} isn't pointed to a checkout"
+                )));
+            }
+
+            format!("{name} uses its upstream source again.")
+        }
+    };
+
+    overrides
+        .save(&setup.dev_overrides_path)
+        .context("couldn't save the development overrides")?;
+
+    println!("{message}");
+    relink(setup, reporter)
+}
+
+/// Finds the enabled plugin `name` in the configuration file.
+fn find_plugin(config: Config, name: &str) -> Result<Plugin> {
+    let disabled = config
+        .disabled_plugins()
+        .iter()
+        .any(|plugin| plugin == name);
+
+    config
+        .active_plugins()
+        .into_iter()
+        .find(|plugin| plugin.name == name)
+        .ok_or_else(|| {
+            Error::Message(if disabled {
+                format!("{name} is disabled in almoxarife.yaml")
+            } else {
+                format!("there's no plugin named {name} in almoxarife.yaml")
+            })
+        })
+}
+
+/// Runs `command` inside the checkout of the plugin `name`, exiting with the
+/// command's status.
+fn exec(setup: &Setup, name: &str, command: &[String]) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let plugin = find_plugin(config, name)?;
+
+    if !plugin.is_installed() {
+        return Err(Error::Message(format!(
+            "{name} isn't installed yet; run al to install it"
+        )));
+    }
+
+    let Some((program, args)) = command.split_first() else {
+        return Ok(());
+    };
+
+    let status = setup
+        .runner
+        .status(
+            process::Command::new(program)
+                .args(args)
+                .current_dir(&plugin.repository_path),
+        )
+        .context(&format!("couldn't run {program}"))?;
+
+    match status.code() {
+        None | Some(0) => Ok(()),
+        Some(code) => process::exit(code),
+    }
+}
+
+/// Opens the web page of the plugin `name` or, when `edit`, a Kakoune client
+/// inside its checkout.
+fn open(setup: &Setup, name: &str, edit: bool) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let plugin = find_plugin(config, name)?;
+
+    if edit {
+        if !plugin.is_installed() {
+            return Err(Error::Message(format!(
+                "{name} isn't installed yet; run al to install it"
+            )));
+        }
+
+        let status = setup
+            .runner
+            .status(process::Command::new("kak").current_dir(&plugin.repository_path))
+            .context("couldn't run Kakoune")?;
+
+        return match status.code() {
+            None | Some(0) => Ok(()),
+            Some(code) => process::exit(code),
+        };
+    }
+
+    let Some(page) = forge::web_page(&plugin.location) else {
+        return Err(Error::Message(format!(
+            "{name} has no web page, since it's in {}; use al open --edit {name} to open it",
+            plugin.location
+        )));
+    };
+
+    // $BROWSER may list several browsers, to be tried in turn.
+    let browsers = setup.runner.var("BROWSER").unwrap_or_default();
+    let default = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+
+    let opened = browsers
+        .split(':')
+        .filter(|browser| !browser.is_empty())
+        .chain([default])
+        .any(|browser| {
+            setup
+                .runner
+                .status(process::Command::new(browser).arg(&page))
+                .is_ok_and(|status| status.success())
+        });
+
+    if !opened {
+        return Err(Error::Message(format!(
+            "couldn't open {page}; set BROWSER to the browser to open it with"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Prints what `query` asks about the plugin `name`, alone on its line, so
+/// that it can be used in command substitutions.
+fn path(setup: &Setup, name: &str, query: PathQuery) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
 
-Usage: al [OPTIONS]
+    let integration = setup.integration(config.settings.integration);
+    let plugin = find_plugin(config, name)?;
 
-Options:
- --config
-        Open the configuration file before updating plugins.
+    match query {
+        PathQuery::Repo => println!("{}", plugin.repository_path.to_string_lossy()),
 
- -v, --version
-        Print Almoxarife version.
+        PathQuery::Link if integration == Integration::Source => {
+            return Err(Error::Message(
+                "plugins aren't linked into autoload with the source integration".to_string(),
+            ));
+        }
+
+        PathQuery::Link => println!("{}", plugin.link_path.to_string_lossy()),
+        PathQuery::Config => print!("{}", plugin.config()),
+    }
+
+    Ok(())
+}
+
+fn stats(setup: &Setup, stale_after: Duration) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let state = State::load(&setup.state_path);
+    let now = time::now();
+    let disabled = config.disabled_plugins().len();
+    let plugins = config.active_plugins();
+
+    let mut stale: Vec<(String, u64)> = plugins
+        .iter()
+        .filter(|plugin| plugin.is_checkout() && plugin.is_installed())
+        .filter_map(|plugin| {
+            let time = plugin.last_upstream_commit().ok()?;
+            let stale = now.saturating_sub(time) > stale_after.as_secs();
+            stale.then(|| (plugin.name.clone(), time))
+        })
+        .collect();
+
+    stale.sort();
+
+    let stats = stats::Stats {
+        enabled: plugins.len(),
+        disabled,
+        local: plugins.iter().filter(|plugin| plugin.is_local).count(),
+        disk_usage: stats::disk_usage(&setup.almoxarife_data_dir),
+        last_run: state.last_run.map(|time| (time, state.last_run_result)),
+        last_successful_run: state.last_successful_run,
+        stale,
+    };
+
+    print!("{}", stats.render(now, stale_after));
+    Ok(())
+}
+
+/// Prints where the plugin `name` comes from and what state its checkout is
+/// in, followed by the first section of its README when `readme`.
+fn info(setup: &Setup, name: &str, readme: bool) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let plugin = find_plugin(config, name)?;
+    let label = |label: &str| format!("{label:>20}").color(Colors::BrightBlackFg);
+
+    println!("{} {}", label("source"), plugin.location);
+
+    if !plugin.is_installed() {
+        println!(
+            "{} not installed yet; run al to install it",
+            label("checkout")
+        );
+        return Ok(());
+    }
+
+    println!(
+        "{} {}",
+        label("checkout"),
+        plugin.repository_path.to_string_lossy()
+    );
 
- -h, --help
-        Print this help message.
+    if plugin.is_checkout() {
+        if let Ok(revision) = plugin.current_revision() {
+            println!("{} {revision}", label("revision"));
+        }
 
-Running al without any extra option will update your plugins according to the
-configuration file."
+        if let Ok(commit) = plugin.last_upstream_commit() {
+            let age = Duration::from_secs(time::now().saturating_sub(commit));
+            println!(
+                "{} {} ago",
+                label("last commit"),
+                time::describe_duration(age)
             );
-            return Ok(());
         }
+    }
+
+    if !readme {
+        return Ok(());
+    }
+
+    let Some(path) = readme::find(&plugin.repository_path) else {
+        return Err(Error::Message(format!("{name} has no README")));
+    };
+
+    let text =
+        fs::read_to_string(&path).context(&format!("couldn't read {}", path.to_string_lossy()))?;
+
+    println!("\n{}", readme::intro(&text));
+    Ok(())
+}
+
+fn list(setup: &Setup) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let overridden: HashSet<String> = config.overrides.0.keys().cloned().collect();
+    let mut plugins = config.active_plugins();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    let now = time::now();
+
+    for plugin in plugins {
+        let (label, color) = if overridden.contains(&plugin.name) {
+            ("dev", Colors::MagentaFg)
+        } else if plugin.is_local {
+            ("local", Colors::YellowFg)
+        } else if plugin.is_installed() {
+            ("installed", Colors::GreenFg)
+        } else {
+            ("missing", Colors::RedFg)
+        };
+
+        let mut notes = Vec::new();
+
+        if let Some(dependent) = &plugin.required_by {
+            notes.push(format!("dependency of {dependent}"));
+        }
+
+        // Helps telling plugins which may be unmaintained.
+        if label == "installed"
+            && let Ok(commit) = plugin.last_upstream_commit()
+        {
+            let age = Duration::from_secs(now.saturating_sub(commit));
+            notes.push(format!(
+                "last upstream commit {} ago",
+                time::describe_duration(age)
+            ));
+        }
+
+        let notes = if notes.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", notes.join(", "))
+        };
 
-        _ => (),
+        println!(
+            "{:>20} {} {}{notes}",
+            plugin.name,
+            format!("{label:<9}").color(color),
+            plugin.location
+        );
     }
 
+    Ok(())
+}
+
+/// Puts back the generated script from before the last update.
+fn restore_script(setup: &Setup) -> Result<()> {
     let config = setup
         .open_config_file()
         .context("couldn't open almoxarife.yaml")?;
 
-    setup.create_dirs().context("couldn't setup Almoxarife")?;
+    let script = match setup.integration(config.settings.integration) {
+        Integration::Autoload => &setup.almoxarife_kak,
+        Integration::Source => &setup.plugins_kak,
+    };
 
-    let kak = setup
-        .create_kak_file_with_prelude()
-        .context("couldn't configure plugins")?;
+    if !setup
+        .restore_script(script)
+        .context("couldn't restore the previous script")?
+    {
+        return Err(Error::Message(format!(
+            "there's no backup of {} to restore",
+            script.to_string_lossy()
+        )));
+    }
 
-    let disabled_plugins = config.disabled_plugins();
-    let removed_plugins = config
-        .removed_plugins()
-        .context("couldn't delete directories of removed plugins")?;
-
-    manage_plugins(
-        config.active_plugins(),
-        disabled_plugins,
-        removed_plugins,
-        kak,
-    )
+    println!(
+        "Restored the previous {}. It'll be regenerated on the next update.",
+        script.to_string_lossy()
+    );
+
+    Ok(())
 }
 
-fn manage_plugins(
-    plugins: Vec<Plugin>,
-    disabled_plugins: Vec<String>,
-    removed_plugins: Vec<PathBuf>,
-    mut kak: Kak<File>,
-) -> Result<()> {
-    for disabled in disabled_plugins {
-        println!("{disabled:>20} {}", "disabled".color(Colors::BrightBlackFg))
+/// Rewrites the configuration file in the current format version.
+fn migrate(setup: &Setup) -> Result<()> {
+    let path = &setup.almoxarife_yaml_path;
+    let source = fs::read_to_string(path).context("couldn't read almoxarife.yaml")?;
+    let (from, migrated) = migrate::migrate(&source).map_err(Error::Message)?;
+
+    if migrated == source {
+        println!(
+            "{} is already in format version {}.",
+            path.to_string_lossy(),
+            migrate::CURRENT_VERSION
+        );
+
+        return Ok(());
     }
 
-    let (sender, receiver) = mpsc::channel();
-    let mut errors = Vec::new();
-    let mut changes = Vec::new();
+    fs::write(path, migrated).context("couldn't write almoxarife.yaml")?;
 
-    thread::scope(|s| -> Result<()> {
-        for plugin in plugins {
-            let sender = sender.clone();
+    if from == migrate::CURRENT_VERSION {
+        println!(
+            "Marked {} as format version {from}.",
+            path.to_string_lossy()
+        );
+    } else {
+        println!(
+            "Migrated {} from format version {from} to {}.",
+            path.to_string_lossy(),
+            migrate::CURRENT_VERSION
+        );
+    }
 
-            s.spawn(move || {
-                let result = plugin.manage();
-                sender.send(result)
-            });
+    Ok(())
+}
+
+fn vendor(setup: &Setup, dir: &Path, full_history: bool) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let manifest = vendor::vendor(setup, &config.active_plugins(), dir, full_history).context(
+        &format!("couldn't export plugins to {}", dir.to_string_lossy()),
+    )?;
+
+    println!(
+        "Exported {} plugins to {}. Run `al restore {}` on the other machine.",
+        manifest.plugins.len(),
+        dir.to_string_lossy(),
+        dir.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+fn bundle(setup: &Setup, archive: &Path) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let manifest = vendor::bundle(setup, &config.active_plugins(), archive).context(&format!(
+        "couldn't bundle plugins to {}",
+        archive.to_string_lossy()
+    ))?;
+
+    println!(
+        "Bundled {} plugins to {}. Run `al restore {}` on the other machine.",
+        manifest.plugins.len(),
+        archive.to_string_lossy(),
+        archive.to_string_lossy()
+    );
+
+    Ok(())
+}
+
+/// Lists what in the configuration has no effect, failing if there's
+/// anything.
+fn check(setup: &Setup) -> Result<()> {
+    let source =
+        fs::read_to_string(&setup.almoxarife_yaml_path).context("couldn't open almoxarife.yaml")?;
+
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let smells = check::smells(
+        &config,
+        &source,
+        &setup.almoxarife_yaml_path,
+        &setup.dev_overrides_path,
+    );
+
+    for smell in &smells {
+        println!("{smell}");
+    }
+
+    match smells.len() {
+        0 => {
+            println!("Nothing to report.");
+            Ok(())
         }
+        1 => Err(Error::Message("found 1 problem".to_string())),
+        count => Err(Error::Message(format!("found {count} problems"))),
+    }
+}
 
-        for removed in removed_plugins {
-            let sender = sender.clone();
+fn freeze(setup: &Setup) -> Result<()> {
+    let mut yaml =
+        fs::read_to_string(&setup.almoxarife_yaml_path).context("couldn't open almoxarife.yaml")?;
 
-            s.spawn(move || {
-                let result = remove_dir(&removed);
-                sender.send(result)
-            });
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    let dotfiles_repo = config.settings.dotfiles_repo.clone();
+    let dotfiles_push = config.settings.dotfiles_push;
+    let mut errors = Vec::new();
+    let mut frozen = Vec::new();
+
+    for plugin in config.active_plugins() {
+        if !plugin.is_checkout() || !plugin.repository_path.exists() {
+            continue;
         }
 
-        mem::drop(sender);
+        let revision = match plugin.current_revision() {
+            Ok(revision) => revision,
+            Err(error) => {
+                println!("{:>20} {}", plugin.name, "failed".color(Colors::RedFg));
+                errors.push(error);
+                continue;
+            }
+        };
 
-        while let Ok(result) = receiver.recv() {
-            match result {
-                Ok(Status::Installed { name, config }) => {
-                    kak.write(config.as_bytes())?;
-                    println!("{name:>20} {}", "installed".color(Colors::GreenFg))
-                }
+        if let Some(edited) = edit::set_field(&yaml, &plugin.name, "rev", &revision) {
+            yaml = edited;
+            frozen.push(format!("{}: {revision}", plugin.name));
+            println!(
+                "{:>20} {} {}",
+                plugin.name,
+                "frozen".color(Colors::BlueFg),
+                revision.color(Colors::BrightBlackFg)
+            );
+        }
+    }
 
-                Ok(Status::Unchanged { name, config }) => {
-                    kak.write(config.as_bytes())?;
-                    println!("{name:>20} {}", "unchanged".color(Colors::BlueFg))
-                }
+    fs::write(&setup.almoxarife_yaml_path, yaml).context("couldn't write almoxarife.yaml")?;
 
-                Ok(Status::Updated { name, log, config }) => {
-                    kak.write(config.as_bytes())?;
-                    println!("{name:>20} {}", "updated".color(Colors::GreenFg));
+    if let Some(repo) = &dotfiles_repo
+        && errors.is_empty()
+    {
+        let message = dotfiles::message("Freeze Kakoune plugins", &frozen);
+        dotfiles::commit(setup, repo, dotfiles_push, &message)
+            .map_err(|error| Error::Message(format!("couldn't commit almoxarife.yaml: {error}")))?;
+    }
 
-                    let message: String = log
-                        .split("\n")
-                        .map(|line| match line.split_once(" ") {
-                            Some((revision, message)) => {
-                                format!("{} {message}\n", revision.color(Colors::BrightBlackFg))
-                            }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        eprintln!();
+        Err(Error::Plugins(errors))
+    }
+}
 
-                            None => line.to_string(),
-                        })
-                        .collect();
+fn which(setup: &Setup, name: &str) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
 
-                    changes.push(format!("{}:\n{message}", name.color(Colors::GreenFg)));
-                }
+    let mut found = false;
 
-                Ok(Status::Local { name, config }) => {
-                    kak.write(config.as_bytes())?;
-                    println!("{name:>20} {}", "local".color(Colors::YellowFg))
-                }
+    for plugin in config.active_plugins() {
+        // Plugins not yet installed have nothing to declare.
+        let Ok(files) = kakscript::kak_files(&plugin.repository_path) else {
+            continue;
+        };
 
-                Ok(Status::Deleted { name }) => {
-                    println!("{name:>20} {}", "removed".color(Colors::CyanFg))
-                }
+        for file in files {
+            let source = kakscript::read(&file)
+                .context(&format!("couldn't read {}", file.to_string_lossy()))?;
 
-                Err(error) => {
-                    println!("{:>20} {}", error.plugin(), "failed".color(Colors::RedFg));
-                    errors.push(error);
+            for (line, declaration) in kakscript::declarations(&source) {
+                if declaration.provides(name) {
+                    found = true;
+                    println!(
+                        "{:>20} {} {}:{line}",
+                        plugin.name,
+                        declaration.to_string().color(Colors::BlueFg),
+                        file.to_string_lossy()
+                    );
                 }
             }
         }
+    }
 
+    if found {
         Ok(())
-    })?;
+    } else {
+        Err(Error::Message(format!(
+            "no installed plugin declares {name}"
+        )))
+    }
+}
+
+/// Prints the graph of the active plugins in `format`.
+fn graph(setup: &Setup, format: graph::Format) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    print!("{}", graph::render(&config.active_plugins(), format));
+    Ok(())
+}
+
+/// Prints why the plugin `name` is installed, and what depends on it.
+fn audit(setup: &Setup, name: &str) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
 
-    kak.close()?;
+    let plugin = find_plugin(config, name)?;
 
-    if !changes.is_empty() {
-        println!("\nUpdates:\n");
-        println!("{}", changes.join("\n"));
+    if !plugin.is_installed() {
+        return Err(Error::Message(format!(
+            "{name} isn't installed yet; run al to install it"
+        )));
     }
 
-    if !errors.is_empty() {
-        eprintln!();
-        Err(Error::Plugins(errors))
-    } else {
-        Ok(())
+    let lines = audit::audit(&plugin).context(&format!("couldn't audit {name}"))?;
+
+    for line in lines {
+        println!("{line}");
     }
+
+    Ok(())
 }
 
-fn remove_dir(path: &Path) -> result::Result<Status, PluginError> {
-    let name = path
-        .file_name()
-        .unwrap_or_default()
-        .to_string_lossy()
-        .into();
+fn why(setup: &Setup, name: &str) -> Result<()> {
+    let config = setup
+        .open_config_file()
+        .context("couldn't open almoxarife.yaml")?;
+
+    if config
+        .disabled_plugins()
+        .iter()
+        .any(|disabled| disabled == name)
+    {
+        println!("{name} is disabled in almoxarife.yaml, so it isn't installed");
+        return Ok(());
+    }
+
+    let lines = why::explain(&config.active_plugins(), name).ok_or_else(|| {
+        Error::Message(format!(
+            "{name} isn't in almoxarife.yaml, nor a dependency of any plugin"
+        ))
+    })?;
 
-    match fs::remove_dir_all(path) {
-        Ok(_) => Ok(Status::Deleted { name }),
-        Err(e) => Err(PluginError::Delete(name, e.to_string())),
+    for line in lines {
+        println!("{line}");
     }
+
+    Ok(())
 }
 
 enum Error {
@@ -217,6 +2893,7 @@ enum Error {
         context: String,
     },
     Plugins(Vec<setup::PluginError>),
+    Message(String),
 }
 
 impl Display for Error {
@@ -228,6 +2905,8 @@ impl Display for Error {
                 let messages: Vec<_> = errors.iter().map(|e| e.to_string()).collect();
                 write!(f, "\n\n{}", messages.join("\n\n"))
             }
+
+            Error::Message(message) => write!(f, "{message}"),
         }
     }
 }
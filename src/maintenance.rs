@@ -0,0 +1,36 @@
+use std::num::NonZeroUsize;
+use std::thread;
+
+use crate::setup::Plugin;
+use crate::setup::PluginError;
+
+/// Compacts the object stores of the installed checkouts, with `git gc
+/// --auto`, which leaves alone the ones not worth it. Unlike fetching,
+/// compacting is bound by the processor and the disk, so only as many
+/// checkouts as there are processors are compacted at once.
+///
+/// Returns the errors of the checkouts that couldn't be compacted.
+pub fn maintain(plugins: &[Plugin]) -> Vec<PluginError> {
+    let plugins: Vec<&Plugin> = plugins
+        .iter()
+        .filter(|plugin| plugin.is_checkout() && plugin.is_installed())
+        .collect();
+
+    let jobs = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+
+    plugins
+        .chunks(jobs)
+        .flat_map(|chunk| {
+            thread::scope(|s| {
+                let runs: Vec<_> = chunk
+                    .iter()
+                    .map(|plugin| s.spawn(|| plugin.maintain()))
+                    .collect();
+
+                runs.into_iter()
+                    .filter_map(|run| run.join().ok()?.err())
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect()
+}
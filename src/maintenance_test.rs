@@ -0,0 +1,42 @@
+use std::fs;
+
+use crate::maintenance;
+use crate::runner_test::FakeRunner;
+use crate::setup::Plugin;
+use crate::setup_test::pinned_plugin;
+
+fn plugin(dir: &tempfile::TempDir, name: &str) -> Plugin {
+    let repository_path = dir.path().join(name);
+    let mut plugin = pinned_plugin(repository_path, dir.path().join("link"), "abc");
+    plugin.name = name.into();
+    plugin
+}
+
+#[test]
+fn maintain() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let installed = plugin(&temp_dir, "luar");
+    fs::create_dir_all(&installed.repository_path).unwrap();
+
+    let mut local = plugin(&temp_dir, "mine");
+    local.is_local = true;
+    fs::create_dir_all(&local.repository_path).unwrap();
+
+    let missing = plugin(&temp_dir, "peneira");
+
+    let mut failing = plugin(&temp_dir, "search");
+    let fake = FakeRunner::new();
+    fake.reply("git", &["gc"], 11, "", "can't compact the repository");
+    failing.runner = fake.runner();
+    fs::create_dir_all(&failing.repository_path).unwrap();
+
+    let errors = maintenance::maintain(&[installed, local, missing, failing]);
+
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].plugin(), "search");
+    assert!(errors[0].description().starts_with("could not maintain: "));
+
+    assert!(temp_dir.path().join("luar/gc-ran.txt").exists());
+    assert!(!temp_dir.path().join("mine/gc-ran.txt").exists());
+}
@@ -0,0 +1,328 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+use serde::Deserializer;
+
+use crate::setup::Plugin;
+use crate::setup::Setup;
+
+/// The file where plugin authors declare what users would otherwise have to
+/// know about their plugin.
+pub const MANIFEST: &str = "almoxarife.toml";
+
+/// What a plugin's `almoxarife.toml` declares. Keys Almoxarife doesn't know
+/// about are ignored, so that manifests written for newer versions still
+/// work.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Manifest {
+    /// The module the plugin provides, when it isn't named after the plugin.
+    pub module: Option<String>,
+    /// The plugins this one needs, by name, along with their locations.
+    pub dependencies: BTreeMap<String, String>,
+    /// The oldest Kakoune version the plugin works with, like `2024.05.18`.
+    pub kakoune: Option<String>,
+    /// Shell commands run inside the checkout, one after the other, after
+    /// every install and update.
+    #[serde(deserialize_with = "commands")]
+    pub build: Vec<String>,
+    /// Configuration run before the user's own, which comes later and so
+    /// wins.
+    pub config: String,
+    /// What the plugin offers to users asking for it with `features:`, by
+    /// name.
+    pub features: BTreeMap<String, Feature>,
+    /// The plugins this one breaks, or is broken by, when both are enabled.
+    pub conflicts: Vec<String>,
+}
+
+/// What a feature adds to the manifest declaring it, once enabled.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct Feature {
+    pub dependencies: BTreeMap<String, String>,
+    #[serde(deserialize_with = "commands")]
+    pub build: Vec<String>,
+    pub config: String,
+}
+
+impl Manifest {
+    /// Loads the manifest of the plugin checked out at `repository`, if it has
+    /// one.
+    pub fn load(repository: &Path) -> Result<Option<Manifest>, String> {
+        match fs::read_to_string(repository.join(MANIFEST)) {
+            Ok(source) => Manifest::parse(&source).map(Some),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(error.to_string()),
+        }
+    }
+
+    pub fn parse(source: &str) -> Result<Manifest, String> {
+        toml::from_str(source).map_err(|error| {
+            let message = error.message().trim().replace('\n', "; ");
+
+            match error.span() {
+                Some(span) => {
+                    let line = source[..span.start].matches('\n').count() + 1;
+                    format!("line {line}: {message}")
+                }
+                None => message,
+            }
+        })
+    }
+
+    /// The manifest with what `features` add to it, in that order, for the
+    /// ones it declares.
+    pub fn with_features(mut self, features: &[String]) -> Manifest {
+        for name in features {
+            let Some(feature) = self.features.get(name) else {
+                continue;
+            };
+
+            self.dependencies.extend(feature.dependencies.clone());
+            self.build.extend(feature.build.iter().cloned());
+
+            if !feature.config.is_empty() {
+                if !self.config.is_empty() && !self.config.ends_with('\n') {
+                    self.config.push('\n');
+                }
+
+                self.config.push_str(&feature.config);
+            }
+        }
+
+        self
+    }
+}
+
+/// A single command, or a list of them.
+fn commands<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged, expecting = "build must be a string or an array of strings")]
+    enum Commands {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Commands::deserialize(deserializer)? {
+        Commands::One(command) => vec![command],
+        Commands::Many(commands) => commands,
+    })
+}
+
+/// The pairs of `plugins` that can't be enabled together, according to the
+/// manifest of either one, each described in a line.
+pub fn conflicts(plugins: &[Plugin]) -> Vec<String> {
+    let names: HashSet<&str> = plugins.iter().map(|plugin| plugin.name.as_str()).collect();
+    let mut pairs = BTreeMap::new();
+
+    for plugin in plugins {
+        for other in plugin.manifest().conflicts {
+            if other == plugin.name || !names.contains(other.as_str()) {
+                continue;
+            }
+
+            let pair = if plugin.name < other {
+                (plugin.name.clone(), other)
+            } else {
+                (other, plugin.name.clone())
+            };
+
+            // When both manifests tell, the first one in alphabetical order
+            // is named.
+            pairs
+                .entry(pair)
+                .and_modify(|declared: &mut String| {
+                    if plugin.name < *declared {
+                        *declared = plugin.name.clone();
+                    }
+                })
+                .or_insert_with(|| plugin.name.clone());
+        }
+    }
+
+    pairs
+        .into_iter()
+        .map(|((a, b), declared)| {
+            format!("{a} and {b} break each other, says the {MANIFEST} of {declared}; disable one of them")
+        })
+        .collect()
+}
+
+/// What's worth telling about the manifests of `plugins`: manifests that
+/// can't be read, dependencies disabled in the configuration file or fetched
+/// from elsewhere than their dependents expect, dependency cycles, Kakoune
+/// versions too old and conflicts, from plugins just installed.
+pub fn warnings(setup: &Setup, plugins: &[Plugin]) -> Vec<String> {
+    let conflicts = conflicts(plugins);
+    let mut plugins: Vec<&Plugin> = plugins.iter().collect();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let locations: HashMap<&str, &Plugin> = plugins
+        .iter()
+        .map(|plugin| (plugin.name.as_str(), *plugin))
+        .collect();
+
+    let mut warnings = Vec::new();
+    let mut graph = BTreeMap::new();
+    let mut kakoune = None;
+
+    for plugin in &plugins {
+        let name = &plugin.name;
+
+        let manifest = match Manifest::load(&plugin.repository_path) {
+            Ok(Some(manifest)) => manifest,
+            // Features asked of an installed plugin without a manifest are
+            // worth telling about too.
+            Ok(None) if !plugin.features.is_empty() && plugin.repository_path.exists() => {
+                Manifest::default()
+            }
+            Ok(None) => continue,
+            Err(error) => {
+                warnings.push(format!("{name}: couldn't read its {MANIFEST}: {error}"));
+                continue;
+            }
+        };
+
+        for feature in &plugin.features {
+            if !manifest.features.contains_key(feature) {
+                let offered: Vec<&str> = manifest.features.keys().map(String::as_str).collect();
+
+                warnings.push(match offered[..] {
+                    [] => format!("{name} offers no features, so {feature} does nothing"),
+                    _ => format!(
+                        "{name} offers no feature {feature}; it offers {}",
+                        offered.join(", ")
+                    ),
+                });
+            }
+        }
+
+        let manifest = manifest.with_features(&plugin.features);
+
+        for (dependency, location) in &manifest.dependencies {
+            match locations.get(dependency.as_str()) {
+                None => warnings.push(format!(
+                    "{name} depends on {dependency} ({location}), which almoxarife.yaml disables"
+                )),
+
+                // Local checkouts are the user's own choice, like the ones of
+                // `al dev`.
+                Some(other) if !other.is_local && !same_location(&other.location, location) => {
+                    warnings.push(format!(
+                        "{name} depends on {dependency} from {location}, but it's fetched from {}",
+                        other.location
+                    ))
+                }
+
+                Some(_) => (),
+            }
+        }
+
+        graph.insert(name.as_str(), manifest.dependencies);
+
+        if let Some(needed) = &manifest.kakoune {
+            // Only asked once, and only if some plugin cares.
+            let installed = kakoune.get_or_insert_with(|| setup.kakoune_version());
+
+            if let Some(installed) = installed
+                && version_numbers(installed) < version_numbers(needed)
+            {
+                warnings.push(format!(
+                    "{name} needs Kakoune {needed} or later, but {installed} is installed"
+                ));
+            }
+        }
+    }
+
+    for cycle in cycles(&graph) {
+        warnings.push(format!("dependency cycle: {}", cycle.join(" → ")));
+    }
+
+    warnings.extend(conflicts);
+
+    warnings
+}
+
+/// Whether `a` and `b` are the same location, written in different ways.
+fn same_location(a: &str, b: &str) -> bool {
+    let normalize = |location: &str| {
+        let location = location.trim_end_matches('/');
+        location
+            .strip_suffix(".git")
+            .unwrap_or(location)
+            .to_string()
+    };
+
+    normalize(a) == normalize(b)
+}
+
+/// The cycles of the dependency `graph`, each one as the names along it,
+/// from its first plugin in alphabetical order back to that same plugin.
+fn cycles(graph: &BTreeMap<&str, BTreeMap<String, String>>) -> Vec<Vec<String>> {
+    fn visit<'a>(
+        name: &'a str,
+        graph: &'a BTreeMap<&str, BTreeMap<String, String>>,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+        cycles: &mut Vec<Vec<String>>,
+    ) {
+        if let Some(start) = path.iter().position(|visited| *visited == name) {
+            let mut cycle: Vec<String> = path[start..].iter().map(|n| n.to_string()).collect();
+            let first = (0..cycle.len()).min_by_key(|&i| &cycle[i]).unwrap_or(0);
+            cycle.rotate_left(first);
+            cycle.push(cycle[0].clone());
+
+            if !cycles.contains(&cycle) {
+                cycles.push(cycle);
+            }
+
+            return;
+        }
+
+        if done.contains(name) {
+            return;
+        }
+
+        path.push(name);
+
+        for dependency in graph.get(name).into_iter().flat_map(BTreeMap::keys) {
+            visit(dependency, graph, path, done, cycles);
+        }
+
+        path.pop();
+        done.insert(name);
+    }
+
+    let mut done = HashSet::new();
+    let mut cycles = Vec::new();
+
+    for name in graph.keys() {
+        visit(name, graph, &mut Vec::new(), &mut done, &mut cycles);
+    }
+
+    cycles
+}
+
+/// The numbers of a version like `v2024.05.18`, as in `Kakoune v2024.05.18`,
+/// in order. Anything after them, like the commit of a development build, is
+/// left out.
+fn version_numbers(version: &str) -> Vec<u64> {
+    let start = version
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(version.len());
+    let version = &version[start..];
+    let end = version
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(version.len());
+
+    version[..end]
+        .split('.')
+        .map_while(|number| number.parse().ok())
+        .collect()
+}
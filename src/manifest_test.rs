@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::manifest;
+use crate::manifest::Manifest;
+use crate::runner_test::FakeRunner;
+use crate::setup::Setup;
+use crate::setup_test::pinned_plugin;
+
+#[test]
+fn parse_manifest() {
+    let source = r#"
+# Read by Almoxarife.
+module = "peneira-core"
+kakoune = '2024.05.18'
+build = "make"
+conflicts = ["fzf.kak"]
+homepage = "https://example.com" # Unknown keys are left alone.
+keywords = ["fuzzy", 'finder']
+experimental = false
+
+config = """
+set-option global peneira_files_command \
+    "rg --files"
+map global user f ': peneira-files<ret>'
+"""
+
+[dependencies]
+luar = "https://github.com/gustavo-hms/luar"
+"kak.tree" = "https://example.com/kak.tree"
+"#;
+
+    assert_eq!(
+        Manifest::parse(source),
+        Ok(Manifest {
+            module: Some("peneira-core".into()),
+            dependencies: BTreeMap::from([
+                ("luar".into(), "https://github.com/gustavo-hms/luar".into()),
+                ("kak.tree".into(), "https://example.com/kak.tree".into()),
+            ]),
+            kakoune: Some("2024.05.18".into()),
+            build: vec!["make".into()],
+            config: "set-option global peneira_files_command \"rg --files\"\nmap global user f ': peneira-files<ret>'\n".into(),
+            features: BTreeMap::new(),
+            conflicts: vec!["fzf.kak".into()],
+        })
+    );
+
+    assert_eq!(Manifest::parse(""), Ok(Manifest::default()));
+}
+
+#[test]
+fn parse_manifest_errors() {
+    assert_eq!(
+        Manifest::parse("module = \"peneira\"\n\nbuild = make\n"),
+        Err("line 3: invalid string; expected `\"`, `'`".into())
+    );
+    assert_eq!(
+        Manifest::parse("conflicts = \"fzf.kak\"\n"),
+        Err("line 1: invalid type: string \"fzf.kak\", expected a sequence".into())
+    );
+    assert_eq!(
+        Manifest::parse("module = [\"peneira\"]\n"),
+        Err("line 1: invalid type: sequence, expected a string".into())
+    );
+    assert_eq!(
+        Manifest::parse("build = 1\n"),
+        Err("line 1: build must be a string or an array of strings".into())
+    );
+    assert_eq!(
+        Manifest::parse("module = \"a\"\nmodule = \"b\"\n"),
+        Err("line 2: duplicate key `module` in document root".into())
+    );
+    assert_eq!(
+        Manifest::parse("config = \"\"\"\nunterminated\n"),
+        Err("line 3: invalid multiline basic string".into())
+    );
+    assert_eq!(
+        Manifest::parse("module = \"a\" \"b\"\n"),
+        Err("line 1: expected newline, `#`".into())
+    );
+}
+
+#[test]
+fn parse_manifest_unknown_keys() {
+    let source = r#"
+module = "peneira-core"
+stars = 42
+ratio = 0.5
+released = 2024-05-18
+
+[[authors]]
+name = "Gustavo"
+
+[[authors]]
+name = "Someone"
+
+[features.fzf]
+build = "make fzf"
+priority = 1
+"#;
+
+    assert_eq!(
+        Manifest::parse(source),
+        Ok(Manifest {
+            module: Some("peneira-core".into()),
+            features: BTreeMap::from([(
+                "fzf".into(),
+                manifest::Feature {
+                    build: vec!["make fzf".into()],
+                    ..manifest::Feature::default()
+                }
+            )]),
+            ..Manifest::default()
+        })
+    );
+}
+
+#[test]
+fn manifest_in_config() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    fs::write(
+        repository_path.join("almoxarife.toml"),
+        "module = \"phantom-selection\"\nconfig = \"map global user f ': phantom-selection-add-selection<ret>'\"\n",
+    )
+    .unwrap();
+
+    let mut plugin = pinned_plugin(repository_path, link_path, "abc");
+    plugin.config = "set-option global phantom_color red".into();
+
+    assert_eq!(
+        plugin.config(),
+        "try %[ require-module phantom-selection ]
+map global user f ': phantom-selection-add-selection<ret>'
+set-option global phantom_color red
+"
+    );
+
+    // Children still wait for a module named after the plugin.
+    plugin.has_children = true;
+    plugin.config = String::new();
+
+    assert_eq!(
+        plugin.config(),
+        "try %[ require-module phantom-selection ]
+try %[ require-module kakoune-phantom-selection ] catch %[
+    provide-module kakoune-phantom-selection ''
+    require-module kakoune-phantom-selection
+]
+map global user f ': phantom-selection-add-selection<ret>'
+"
+    );
+}
+
+#[test]
+fn manifest_features() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let checkout = temp_dir.path().join("peneira");
+    fs::create_dir_all(&checkout).unwrap();
+    fs::write(
+        checkout.join("almoxarife.toml"),
+        r#"
+build = "make"
+config = "set-option global peneira_files_command 'rg --files'"
+
+[features.fzf]
+build = ["make fzf", "make fzf-preview"]
+config = "set-option global peneira_finder fzf"
+dependencies.fzf = "https://github.com/junegunn/fzf"
+
+[features.icons]
+config = "set-option global peneira_icons true"
+"#,
+    )
+    .unwrap();
+
+    let file = b"
+peneira:
+    location: https://github.com/gustavo-hms/peneira
+    features: [fzf]
+";
+
+    let setup = Setup {
+        almoxarife_data_dir: temp_dir.path().into(),
+        ..Setup::default()
+    };
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let plugins = config.active_plugins();
+    let [peneira, fzf] = &plugins[..] else {
+        panic!("expected peneira and fzf, got {plugins:?}");
+    };
+
+    assert_eq!(peneira.features, vec!["fzf"]);
+    assert_eq!(fzf.required_by.as_deref(), Some("peneira"));
+
+    let manifest = peneira.manifest();
+    assert_eq!(manifest.build, vec!["make", "make fzf", "make fzf-preview"]);
+    assert_eq!(
+        peneira.config(),
+        "try %[ require-module peneira ]
+set-option global peneira_files_command 'rg --files'
+set-option global peneira_finder fzf
+"
+    );
+
+    // Features nobody asked for add nothing.
+    let manifest = manifest.with_features(&[]);
+    assert_eq!(manifest.features.len(), 2);
+    assert_eq!(
+        Manifest::load(&checkout)
+            .unwrap()
+            .unwrap()
+            .with_features(&[])
+            .build,
+        vec!["make"]
+    );
+}
+
+#[test]
+fn manifest_conflicts() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let checkout = |name: &str, manifest: &str| {
+        let path = temp_dir.path().join(name);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("almoxarife.toml"), manifest).unwrap();
+
+        let mut plugin = pinned_plugin(path, temp_dir.path().join("links").join(name), "");
+        plugin.name = name.into();
+        plugin
+    };
+
+    let plugins = [
+        checkout("auto-pairs", "conflicts = ['kakoune-autopairs', 'kak-lsp']"),
+        checkout("kakoune-autopairs", "conflicts = ['auto-pairs']"),
+        checkout(
+            "kak-lsp",
+            "conflicts = ['kak-lsp', 'lsp-client-not-enabled']",
+        ),
+        checkout("kak-tree-sitter", "conflicts = ['kak-lsp']"),
+    ];
+
+    assert_eq!(
+        manifest::conflicts(&plugins),
+        vec![
+            "auto-pairs and kak-lsp break each other, says the almoxarife.toml of auto-pairs; disable one of them",
+            "auto-pairs and kakoune-autopairs break each other, says the almoxarife.toml of auto-pairs; disable one of them",
+            "kak-lsp and kak-tree-sitter break each other, says the almoxarife.toml of kak-tree-sitter; disable one of them",
+        ]
+    );
+
+    assert!(manifest::conflicts(&plugins[2..3]).is_empty());
+}
+
+#[test]
+fn manifest_warnings() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let checkout = |name: &str, location: &str, manifest: &str| {
+        let path = temp_dir.path().join(name);
+        fs::create_dir_all(&path).unwrap();
+
+        if !manifest.is_empty() {
+            fs::write(path.join("almoxarife.toml"), manifest).unwrap();
+        }
+
+        let mut plugin = pinned_plugin(path, temp_dir.path().join("links").join(name), "");
+        plugin.name = name.into();
+        plugin.location = location.into();
+        plugin
+    };
+
+    let mut plugins = [
+        checkout(
+            "peneira",
+            "https://github.com/gustavo-hms/peneira",
+            "kakoune = \"2024.01.01\"\n[dependencies]\nluar = \"https://github.com/gustavo-hms/luar\"\nfzf = \"https://github.com/junegunn/fzf\"\n",
+        ),
+        checkout(
+            "kak-lsp",
+            "https://github.com/kakoune-lsp/kakoune-lsp",
+            "kakoune = \"2099.01.01\"\ndependencies = { peneira = \"https://github.com/gustavo-hms/peneira.git\" }\n",
+        ),
+        checkout(
+            "luar",
+            "https://github.com/someone/luar",
+            "dependencies.kak-lsp = \"https://github.com/kakoune-lsp/kakoune-lsp\"\n",
+        ),
+        checkout("broken", "https://example.com/broken", "module = \n"),
+        checkout("plain", "https://example.com/plain", ""),
+    ];
+
+    plugins[4].features = vec!["fzf".into()];
+
+    let setup = Setup {
+        runner: FakeRunner::new().runner(),
+        ..Setup::default()
+    };
+
+    assert_eq!(
+        manifest::warnings(&setup, &plugins),
+        vec![
+            "broken: couldn't read its almoxarife.toml: line 1: invalid string; expected `\"`, `'`",
+            "kak-lsp needs Kakoune 2099.01.01 or later, but Kakoune v2024.05.18 is installed",
+            "peneira depends on fzf (https://github.com/junegunn/fzf), which almoxarife.yaml disables",
+            "peneira depends on luar from https://github.com/gustavo-hms/luar, but it's fetched from https://github.com/someone/luar",
+            "plain offers no features, so fzf does nothing",
+            "dependency cycle: kak-lsp → peneira → luar → kak-lsp",
+        ]
+    );
+}
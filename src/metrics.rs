@@ -0,0 +1,75 @@
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+/// What an update leaves for monitoring systems.
+#[derive(Debug, Default, PartialEq)]
+pub struct Metrics {
+    /// When the update finished, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub duration: Duration,
+    pub installed: usize,
+    pub updated: usize,
+    pub failed: usize,
+    /// Whether the update got through without errors, including those that
+    /// kept it from even starting.
+    pub success: bool,
+}
+
+impl Metrics {
+    /// The metrics in the text format of Prometheus, as node-exporter's
+    /// textfile collector reads them.
+    pub fn textfile(&self) -> String {
+        let metrics = [
+            (
+                "almoxarife_last_run_timestamp_seconds",
+                "When the last update finished.",
+                self.timestamp.to_string(),
+            ),
+            (
+                "almoxarife_last_run_duration_seconds",
+                "How long the last update took.",
+                format!("{:.3}", self.duration.as_secs_f64()),
+            ),
+            (
+                "almoxarife_last_run_success",
+                "Whether the last update got through without errors.",
+                u8::from(self.success).to_string(),
+            ),
+            (
+                "almoxarife_plugins_installed",
+                "Plugins installed by the last update.",
+                self.installed.to_string(),
+            ),
+            (
+                "almoxarife_plugins_updated",
+                "Plugins updated by the last update.",
+                self.updated.to_string(),
+            ),
+            (
+                "almoxarife_plugins_failed",
+                "Plugins the last update failed on.",
+                self.failed.to_string(),
+            ),
+        ];
+
+        metrics
+            .iter()
+            .map(|(name, help, value)| {
+                format!("# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}\n")
+            })
+            .collect()
+    }
+
+    /// Writes the metrics to `path`. The file is replaced at once, so the
+    /// collector never reads it half written.
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let mut temporary = OsString::from(path);
+        temporary.push(".tmp");
+
+        fs::write(&temporary, self.textfile())?;
+        fs::rename(&temporary, path)
+    }
+}
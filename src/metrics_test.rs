@@ -0,0 +1,55 @@
+use std::fs;
+use std::time::Duration;
+
+use crate::metrics::Metrics;
+
+#[test]
+fn textfile() {
+    let metrics = Metrics {
+        timestamp: 1_700_000_000,
+        duration: Duration::from_millis(12_345),
+        installed: 1,
+        updated: 3,
+        failed: 2,
+        success: false,
+    };
+
+    assert_eq!(
+        metrics.textfile(),
+        "# HELP almoxarife_last_run_timestamp_seconds When the last update finished.
+# TYPE almoxarife_last_run_timestamp_seconds gauge
+almoxarife_last_run_timestamp_seconds 1700000000
+# HELP almoxarife_last_run_duration_seconds How long the last update took.
+# TYPE almoxarife_last_run_duration_seconds gauge
+almoxarife_last_run_duration_seconds 12.345
+# HELP almoxarife_last_run_success Whether the last update got through without errors.
+# TYPE almoxarife_last_run_success gauge
+almoxarife_last_run_success 0
+# HELP almoxarife_plugins_installed Plugins installed by the last update.
+# TYPE almoxarife_plugins_installed gauge
+almoxarife_plugins_installed 1
+# HELP almoxarife_plugins_updated Plugins updated by the last update.
+# TYPE almoxarife_plugins_updated gauge
+almoxarife_plugins_updated 3
+# HELP almoxarife_plugins_failed Plugins the last update failed on.
+# TYPE almoxarife_plugins_failed gauge
+almoxarife_plugins_failed 2
+"
+    );
+}
+
+#[test]
+fn write_replaces_file() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("almoxarife.prom");
+    fs::write(&path, "stale").unwrap();
+
+    let metrics = Metrics {
+        success: true,
+        ..Metrics::default()
+    };
+
+    metrics.write(&path).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), metrics.textfile());
+    assert!(!temp_dir.path().join("almoxarife.prom.tmp").exists());
+}
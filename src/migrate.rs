@@ -0,0 +1,91 @@
+//! Versions of the `almoxarife.yaml` format, and the rewrites taking a file
+//! from each one to the next.
+
+use serde::Deserialize;
+
+/// The version of the format this Almoxarife reads. Files without a
+/// `version:` key predate versioning, and are in version 1.
+pub const CURRENT_VERSION: u64 = 1;
+
+/// The rewrites of the configuration file, the first one taking it from
+/// version 1 to version 2, and so on. Each one edits the text rather than
+/// reserializing it, so that comments and ordering survive.
+const MIGRATIONS: &[fn(&str) -> String] = &[];
+
+#[derive(Deserialize)]
+struct Versioned {
+    version: Option<u64>,
+}
+
+/// The format version of `source`, as told by its top level `version:` key.
+/// Gives `None` when even that can't be parsed, leaving the error to the
+/// parsing proper.
+pub fn version(source: &str) -> Option<u64> {
+    match serde_yaml::from_str::<Versioned>(source) {
+        Ok(versioned) => Some(versioned.version.unwrap_or(1)),
+        Err(_) => None,
+    }
+}
+
+/// Explains why a file in format `version` can't be read, if it can't.
+pub fn check(version: u64) -> Result<(), String> {
+    if version == 0 {
+        Err("format version 0 doesn't exist; the first one is 1".to_string())
+    } else if version < CURRENT_VERSION {
+        Err(format!(
+            "this file is in format version {version}, but Almoxarife now reads version {CURRENT_VERSION}; run `al migrate` to convert it"
+        ))
+    } else if version > CURRENT_VERSION {
+        Err(format!(
+            "this file is in format version {version}, but this Almoxarife only reads up to version {CURRENT_VERSION}; update Almoxarife to use it"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Rewrites `source` to the current format version, marking it with a
+/// `version:` key. Returns the version it was in along with the new text.
+pub fn migrate(source: &str) -> Result<(u64, String), String> {
+    let from = version(source).ok_or_else(|| "couldn't parse the file".to_string())?;
+
+    if from == 0 || from > CURRENT_VERSION {
+        check(from)?;
+    }
+
+    let migrated = MIGRATIONS[(from - 1) as usize..]
+        .iter()
+        .fold(source.to_string(), |source, migration| migration(&source));
+
+    Ok((from, set_version(&migrated, CURRENT_VERSION)))
+}
+
+/// Sets the top level `version:` key of `source` to `version`, adding it at
+/// the top if it's missing.
+fn set_version(source: &str, version: u64) -> String {
+    let mut found = false;
+
+    let lines: Vec<String> = source
+        .lines()
+        .map(|line| {
+            if line.starts_with("version:") {
+                found = true;
+                format!("version: {version}")
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+
+    let mut text = lines.join("\n");
+
+    if source.ends_with('\n') {
+        text.push('\n');
+    }
+
+    if found {
+        text
+    } else {
+        format!("version: {version}\n\n{text}")
+    }
+}
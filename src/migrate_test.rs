@@ -0,0 +1,39 @@
+use crate::migrate;
+use crate::migrate::CURRENT_VERSION;
+
+const CONFIG: &str = "# My plugins
+peneira:
+    location: https://github.com/gustavo-hms/peneira
+";
+
+#[test]
+fn versions() {
+    assert_eq!(migrate::version(CONFIG), Some(1));
+    assert_eq!(migrate::version("version: 3\nluar: {}\n"), Some(3));
+    assert_eq!(migrate::version("version: three\n"), None);
+}
+
+#[test]
+fn check_versions() {
+    assert!(migrate::check(CURRENT_VERSION).is_ok());
+    assert!(migrate::check(0).is_err());
+
+    let error = migrate::check(CURRENT_VERSION + 1).unwrap_err();
+    assert!(error.contains("update Almoxarife"));
+}
+
+#[test]
+fn migrate_unversioned() {
+    let (from, migrated) = migrate::migrate(CONFIG).unwrap();
+    assert_eq!(from, 1);
+    assert_eq!(migrated, format!("version: {CURRENT_VERSION}\n\n{CONFIG}"));
+
+    // Migrating again changes nothing.
+    assert_eq!(migrate::migrate(&migrated).unwrap(), (1, migrated));
+}
+
+#[test]
+fn migrate_newer_version() {
+    let source = format!("version: {}\n{CONFIG}", CURRENT_VERSION + 1);
+    assert!(migrate::migrate(&source).is_err());
+}
@@ -0,0 +1,85 @@
+use std::io;
+use std::process::Command;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::runner::Runner;
+
+/// How long the notifier gets, so that a stuck notification daemon doesn't
+/// hold a scheduled run up.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The message of the notification sent after an update.
+pub fn message(installed: usize, updated: usize, failed: usize) -> String {
+    if installed + updated + failed == 0 {
+        "All plugins are up to date".to_string()
+    } else {
+        format!("Plugins: {}", summary(installed, updated, failed))
+    }
+}
+
+/// A short summary of an update, like `3 updated, 1 failed`, fit for a
+/// modeline.
+pub fn summary(installed: usize, updated: usize, failed: usize) -> String {
+    let mut parts = Vec::new();
+
+    if installed > 0 {
+        parts.push(format!("{installed} installed"));
+    }
+
+    if updated > 0 {
+        parts.push(format!("{updated} updated"));
+    }
+
+    if failed > 0 {
+        parts.push(format!("{failed} failed"));
+    }
+
+    if parts.is_empty() {
+        "up to date".to_string()
+    } else {
+        parts.join(", ")
+    }
+}
+
+/// Sends a desktop notification, via `notify-send` (D-Bus) or, on macOS, via
+/// `osascript`.
+pub fn send(runner: &Runner, message: &str, failed: bool) -> io::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        osascript(message)
+    } else {
+        let mut command = Command::new("notify-send");
+        command
+            .args(["--app-name", "Almoxarife"])
+            .args(["--urgency", if failed { "critical" } else { "normal" }])
+            .arg("Almoxarife")
+            .arg(message);
+        command
+    };
+
+    let output = runner.output(&mut command, Some(Instant::now() + TIMEOUT))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "notifier exited with {}",
+            output.status
+        )))
+    }
+}
+
+/// An `osascript` command displaying `message`. The message is handed in as an
+/// argument, rather than quoted into the script, so that it's shown as is.
+pub fn osascript(message: &str) -> Command {
+    let mut command = Command::new("osascript");
+    command
+        .args(["-e", "on run argv"])
+        .args([
+            "-e",
+            "display notification (item 1 of argv) with title \"Almoxarife\"",
+        ])
+        .args(["-e", "end run"])
+        .arg(message);
+    command
+}
@@ -0,0 +1,55 @@
+use crate::notify;
+use crate::runner_test::FakeRunner;
+
+#[test]
+fn message() {
+    assert_eq!(notify::message(0, 0, 0), "All plugins are up to date");
+    assert_eq!(notify::message(0, 3, 0), "Plugins: 3 updated");
+    assert_eq!(
+        notify::message(1, 3, 2),
+        "Plugins: 1 installed, 3 updated, 2 failed"
+    );
+}
+
+#[test]
+fn summary() {
+    assert_eq!(notify::summary(0, 0, 0), "up to date");
+    assert_eq!(notify::summary(0, 3, 1), "3 updated, 1 failed");
+}
+
+#[test]
+fn osascript() {
+    let command = notify::osascript("Plugins: 1 \"updated\" \\ 2 falhou ✗");
+    let args: Vec<_> = command.get_args().collect();
+    assert_eq!(
+        args,
+        [
+            "-e",
+            "on run argv",
+            "-e",
+            "display notification (item 1 of argv) with title \"Almoxarife\"",
+            "-e",
+            "end run",
+            "Plugins: 1 \"updated\" \\ 2 falhou ✗",
+        ]
+    );
+}
+
+#[test]
+#[cfg(not(target_os = "macos"))]
+fn send() {
+    let fake = FakeRunner::new();
+
+    notify::send(&fake.runner(), "Plugins: 2 failed", true).unwrap();
+    assert_eq!(
+        fake.calls("notify-send"),
+        [[
+            "--app-name",
+            "Almoxarife",
+            "--urgency",
+            "critical",
+            "Almoxarife",
+            "Plugins: 2 failed"
+        ]]
+    );
+}
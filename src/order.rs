@@ -0,0 +1,107 @@
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+
+use crate::setup::Plugin;
+
+/// The order the configs of `plugins` go in the generated script, by name.
+/// When two configs set the same option, the later one wins.
+///
+/// A parent comes before its children, and a dependency before the plugins
+/// needing it. Apart from that, plugins go by their `order`, lowest first,
+/// and then as they appear in the configuration file. Dependencies the file
+/// doesn't list go right before the first plugin needing them, by name. The
+/// result only depends on the plugins, never on the order they got installed
+/// or updated in.
+pub fn sort(plugins: &[Plugin]) -> Vec<String> {
+    let index: HashMap<&str, usize> = plugins
+        .iter()
+        .enumerate()
+        .map(|(i, plugin)| (plugin.name.as_str(), i))
+        .collect();
+
+    // The plugins each one has to come after, and the ones waiting for it.
+    let mut pending = vec![0; plugins.len()];
+    let mut waiting: Vec<Vec<usize>> = vec![Vec::new(); plugins.len()];
+
+    for (i, plugin) in plugins.iter().enumerate() {
+        let parent = plugin.parent.iter().cloned();
+        let dependencies = plugin.manifest().dependencies.into_keys();
+
+        let before: BTreeSet<usize> = parent
+            .chain(dependencies)
+            .filter_map(|name| index.get(name.as_str()).copied())
+            .filter(|&j| j != i)
+            .collect();
+
+        pending[i] = before.len();
+
+        for j in before {
+            waiting[j].push(i);
+        }
+    }
+
+    // Plugins missing from the configuration file take the position of the
+    // first plugin needing them, going up dependencies of dependencies.
+    let mut positions: Vec<usize> = plugins
+        .iter()
+        .map(|plugin| plugin.position.unwrap_or(usize::MAX))
+        .collect();
+
+    for _ in 0..plugins.len() {
+        let mut changed = false;
+
+        for (i, plugin) in plugins.iter().enumerate() {
+            if plugin.position.is_some() {
+                continue;
+            }
+
+            let first = waiting[i].iter().map(|&j| positions[j]).min();
+
+            if let Some(first) = first.filter(|&first| first < positions[i]) {
+                positions[i] = first;
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let key = |i: usize| (plugins[i].order, positions[i], plugins[i].name.as_str(), i);
+
+    let mut ready: BTreeSet<_> = (0..plugins.len())
+        .filter(|&i| pending[i] == 0)
+        .map(key)
+        .collect();
+
+    let mut sorted = Vec::with_capacity(plugins.len());
+    let mut placed = vec![false; plugins.len()];
+
+    while sorted.len() < plugins.len() {
+        // Dependencies going around in a circle leave no plugin ready; the
+        // first of them goes anyway.
+        let next = match ready.pop_first() {
+            Some(next) => next,
+            None => (0..plugins.len())
+                .filter(|&i| !placed[i])
+                .map(key)
+                .min()
+                .expect("some plugin should be left"),
+        };
+
+        let i = next.3;
+        placed[i] = true;
+        sorted.push(plugins[i].name.clone());
+
+        for &j in &waiting[i] {
+            pending[j] = pending[j].saturating_sub(1);
+
+            if pending[j] == 0 && !placed[j] {
+                ready.insert(key(j));
+            }
+        }
+    }
+
+    sorted
+}
@@ -0,0 +1,128 @@
+use std::fs;
+use std::path::Path;
+
+use crate::order;
+use crate::setup::Kak;
+use crate::setup::Plugin;
+use crate::setup::Setup;
+
+const CONFIG: &[u8] = b"
+smarttab:
+    location: https://github.com/andreyorst/smarttab.kak
+    order: 10
+
+peneira:
+    location: https://github.com/gustavo-hms/peneira
+
+    peneira-filters:
+        location: https://github.com/gustavo-hms/peneira-filters
+        # Still after its parent.
+        order: -5
+
+luar:
+    location: https://github.com/gustavo-hms/luar
+
+kak-lsp:
+    location: https://github.com/kak-lsp/kak-lsp
+    order: -1
+";
+
+fn plugins(data_dir: &Path, config: &[u8]) -> Vec<Plugin> {
+    let setup = Setup {
+        almoxarife_data_dir: data_dir.into(),
+        ..Default::default()
+    };
+
+    setup.config_from_buffer(config).unwrap().active_plugins()
+}
+
+fn depend(data_dir: &Path, plugin: &str, dependency: &str) {
+    let repository = data_dir.join(plugin);
+    fs::create_dir_all(&repository).unwrap();
+    fs::write(
+        repository.join("almoxarife.toml"),
+        format!("dependencies.{dependency} = 'https://github.com/someone/{dependency}'"),
+    )
+    .unwrap();
+}
+
+#[test]
+fn sort_plugins() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    depend(temp_dir.path(), "peneira", "luar");
+
+    let mut plugins = plugins(temp_dir.path(), CONFIG);
+    let expected = ["kak-lsp", "luar", "peneira", "peneira-filters", "smarttab"];
+    assert_eq!(order::sort(&plugins), expected);
+
+    // Whatever order the plugins come in.
+    plugins.reverse();
+    assert_eq!(order::sort(&plugins), expected);
+    plugins.rotate_left(2);
+    assert_eq!(order::sort(&plugins), expected);
+}
+
+#[test]
+fn sort_dependencies_not_listed() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    depend(temp_dir.path(), "smarttab", "prelude");
+    depend(temp_dir.path(), "luar", "connect");
+
+    let config = b"
+smarttab:
+    location: https://github.com/andreyorst/smarttab.kak
+
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let setup = Setup {
+        almoxarife_data_dir: temp_dir.path().into(),
+        ..Default::default()
+    };
+
+    let config = setup.config_from_buffer(config.as_slice()).unwrap();
+    let plugins = config.active_plugins();
+
+    // Dependencies go right before the first plugin needing them.
+    assert_eq!(
+        order::sort(&plugins),
+        ["prelude", "smarttab", "connect", "luar"]
+    );
+}
+
+#[test]
+fn sort_circular_dependencies() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    depend(temp_dir.path(), "peneira", "luar");
+    depend(temp_dir.path(), "luar", "peneira");
+
+    let config = b"
+peneira:
+    location: https://github.com/gustavo-hms/peneira
+
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let mut plugins = plugins(temp_dir.path(), config);
+    assert_eq!(order::sort(&plugins), ["peneira", "luar"]);
+
+    plugins.reverse();
+    assert_eq!(order::sort(&plugins), ["peneira", "luar"]);
+}
+
+#[test]
+fn write_configs_in_order() {
+    let mut kak = Kak::with_buffer();
+    kak.write(b"prelude\n").unwrap();
+    kak.write_config("smarttab", "smarttab config\n".into());
+    kak.write_config("unknown", "unknown config\n".into());
+    kak.write_config("luar", "luar config\n".into());
+    kak.close(&["luar".into(), "smarttab".into()]).unwrap();
+
+    assert_eq!(
+        String::from_utf8_lossy(kak.bytes()),
+        "prelude\nluar config\nsmarttab config\nunknown config\n🧺"
+    );
+}
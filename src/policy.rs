@@ -0,0 +1,81 @@
+use serde::Deserialize;
+
+/// Restrictions on where plugins may be fetched from, set by the top level
+/// `url_policy` key. An empty list places no restriction.
+#[derive(Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct UrlPolicy {
+    /// The protocols plugins may be fetched with: `https`, `http`, `ssh`,
+    /// `git` or `file`, which also covers bare repositories given as paths.
+    #[serde(default)]
+    pub protocols: Vec<String>,
+    /// The hosts plugins may be fetched from. A host also allows its
+    /// subdomains. Restricting hosts doesn't restrict `file` URLs, which have
+    /// none.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// The hosts plugins may never be fetched from, even if allowed.
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl UrlPolicy {
+    /// Tells why `url` can't be fetched, if so.
+    pub fn check(&self, url: &str) -> Result<(), String> {
+        let Some((protocol, host)) = protocol_and_host(url) else {
+            return Err(format!("{url} isn't a URL Almoxarife understands"));
+        };
+
+        if !self.protocols.is_empty() && !self.protocols.iter().any(|p| p == protocol) {
+            return Err(format!(
+                "{url} uses {protocol}, but only {} is allowed",
+                self.protocols.join(", ")
+            ));
+        }
+
+        if protocol == "file" {
+            return Ok(());
+        }
+
+        if self.deny.iter().any(|denied| matches_host(host, denied)) {
+            return Err(format!("{url} is on a denied host"));
+        }
+
+        if !self.allow.is_empty() && !self.allow.iter().any(|allowed| matches_host(host, allowed)) {
+            return Err(format!("{url} isn't on an allowed host"));
+        }
+
+        Ok(())
+    }
+}
+
+fn matches_host(host: &str, pattern: &str) -> bool {
+    let host = host.to_ascii_lowercase();
+    let pattern = pattern.to_ascii_lowercase();
+    host == pattern || host.ends_with(&format!(".{pattern}"))
+}
+
+/// Splits a git URL into its protocol and host. The scp-like syntax, as in
+/// `git@github.com:user/repo`, is taken as `ssh`, and a plain path as `file`,
+/// with an empty host.
+pub fn protocol_and_host(url: &str) -> Option<(&str, &str)> {
+    let (protocol, rest) = match url.split_once("://") {
+        Some(("file", _)) => return Some(("file", "")),
+        Some((protocol, rest)) => (protocol, rest),
+        None => match url.split_once(':') {
+            Some((authority, _)) => ("ssh", authority),
+            None => return Some(("file", "")),
+        },
+    };
+
+    // Drop the user and the port, if any.
+    let authority = rest.split('/').next()?;
+    let host = authority.rsplit('@').next()?;
+    let host = host.split(':').next()?;
+
+    if host.is_empty() {
+        None
+    } else {
+        Some((protocol, host))
+    }
+}
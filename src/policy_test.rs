@@ -0,0 +1,72 @@
+use crate::policy::UrlPolicy;
+use crate::policy::protocol_and_host;
+
+#[test]
+fn protocols_and_hosts() {
+    assert_eq!(
+        protocol_and_host("https://github.com/gustavo-hms/peneira"),
+        Some(("https", "github.com"))
+    );
+    assert_eq!(
+        protocol_and_host("git@github.com:gustavo-hms/peneira.git"),
+        Some(("ssh", "github.com"))
+    );
+    assert_eq!(
+        protocol_and_host("ssh://git@git.example.com:2222/kak/plugin.git"),
+        Some(("ssh", "git.example.com"))
+    );
+    assert_eq!(
+        protocol_and_host("file:///srv/mirrors/peneira.git"),
+        Some(("file", ""))
+    );
+    assert_eq!(
+        protocol_and_host("/srv/mirrors/peneira.git"),
+        Some(("file", ""))
+    );
+    assert_eq!(protocol_and_host("https:///plugin"), None);
+}
+
+#[test]
+fn check_urls() {
+    let policy = UrlPolicy::default();
+    assert_eq!(policy.check("http://example.com/plugin"), Ok(()));
+
+    // Hosts don't restrict file URLs, which have none.
+    let policy = UrlPolicy {
+        allow: vec!["github.com".into()],
+        ..UrlPolicy::default()
+    };
+    assert_eq!(policy.check("file:///srv/mirrors/peneira.git"), Ok(()));
+
+    let policy = UrlPolicy {
+        protocols: vec!["https".into()],
+        allow: vec!["github.com".into(), "example.com".into()],
+        deny: vec!["untrusted.example.com".into()],
+    };
+
+    assert_eq!(
+        policy.check("https://github.com/gustavo-hms/peneira"),
+        Ok(())
+    );
+    assert_eq!(policy.check("https://git.example.com/plugin"), Ok(()));
+    assert_eq!(
+        policy.check("git@github.com:gustavo-hms/peneira"),
+        Err("git@github.com:gustavo-hms/peneira uses ssh, but only https is allowed".into())
+    );
+    assert_eq!(
+        policy.check("https://gitlab.com/someone/plugin"),
+        Err("https://gitlab.com/someone/plugin isn't on an allowed host".into())
+    );
+    assert_eq!(
+        policy.check("https://untrusted.example.com/plugin"),
+        Err("https://untrusted.example.com/plugin is on a denied host".into())
+    );
+    assert_eq!(
+        policy.check("file:///srv/mirrors/peneira.git"),
+        Err("file:///srv/mirrors/peneira.git uses file, but only https is allowed".into())
+    );
+    assert_eq!(
+        policy.check("https://notgithub.com/plugin"),
+        Err("https://notgithub.com/plugin isn't on an allowed host".into())
+    );
+}
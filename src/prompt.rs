@@ -0,0 +1,31 @@
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+/// Asks the user questions, reading the answers from `input`.
+pub struct Prompt<R: BufRead, W: Write> {
+    input: R,
+    pub output: W,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Prompt { input, output }
+    }
+
+    /// Asks a question, returning the answer without surrounding whitespace.
+    pub fn ask(&mut self, question: &str) -> io::Result<String> {
+        write!(self.output, "{question} ")?;
+        self.output.flush()?;
+
+        let mut answer = String::new();
+        self.input.read_line(&mut answer)?;
+        Ok(answer.trim().to_string())
+    }
+
+    /// Asks a yes or no question, where yes is the default answer.
+    pub fn confirm(&mut self, question: &str) -> io::Result<bool> {
+        let answer = self.ask(&format!("{question} [Y/n]"))?;
+        Ok(matches!(answer.as_str(), "" | "y" | "Y" | "yes"))
+    }
+}
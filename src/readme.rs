@@ -0,0 +1,146 @@
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+
+/// The extensions READMEs come with, in the order they're preferred.
+const EXTENSIONS: [&str; 6] = ["md", "markdown", "asciidoc", "adoc", "txt", ""];
+
+/// How many lines of a README `intro` keeps at most.
+const INTRO_LINES: usize = 20;
+
+/// The README of the checkout at `dir`, whatever its case and extension.
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    let mut readmes: Vec<(usize, PathBuf)> = fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?.to_lowercase();
+            let (stem, extension) = name.split_once('.').unwrap_or((&name, ""));
+
+            if stem != "readme" || !path.is_file() {
+                return None;
+            }
+
+            let rank = EXTENSIONS.iter().position(|&e| e == extension)?;
+            Some((rank, path))
+        })
+        .collect();
+
+    readmes.sort();
+    readmes.into_iter().next().map(|(_, path)| path)
+}
+
+/// The first section of a README, as plain text: what comes after its title
+/// (and badges) up to the next heading, or the first section with any text
+/// when the title is followed by a heading right away. Long sections are cut
+/// short, ending with `…`.
+pub fn intro(readme: &str) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    let mut code = false;
+    let mut source = readme.lines().peekable();
+
+    while let Some(line) = source.next() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            code = !code;
+            continue;
+        }
+
+        if code {
+            lines.push(format!("    {line}"));
+            continue;
+        }
+
+        // Setext headings are underlined by the line following them.
+        let underlined = source
+            .peek()
+            .is_some_and(|next| is_underline(next) && !trimmed.is_empty());
+
+        if underlined || is_heading(trimmed) {
+            if underlined {
+                source.next();
+            }
+
+            if lines.iter().any(|line| !line.is_empty()) {
+                break;
+            }
+
+            lines.clear();
+            continue;
+        }
+
+        if is_decoration(trimmed) || is_underline(trimmed) {
+            continue;
+        }
+
+        let text = plain(trimmed);
+
+        // Blank lines only separate paragraphs.
+        if text.is_empty() && lines.last().is_none_or(|last| last.is_empty()) {
+            continue;
+        }
+
+        lines.push(text);
+    }
+
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+
+    if lines.len() > INTRO_LINES {
+        lines.truncate(INTRO_LINES);
+        lines.push("…".to_string());
+    }
+
+    lines.join("\n")
+}
+
+/// Markdown and AsciiDoc headings, as in `## Usage` or `== Usage`.
+fn is_heading(line: &str) -> bool {
+    let marks = line.chars().take_while(|&c| c == '#' || c == '=').count();
+    marks > 0 && line[marks..].starts_with(' ')
+}
+
+/// The line under a setext heading, as in `-----`, which also makes a
+/// horizontal rule on its own.
+fn is_underline(line: &str) -> bool {
+    let line = line.trim();
+    line.len() >= 3 && (line.chars().all(|c| c == '=') || line.chars().all(|c| c == '-'))
+}
+
+/// Lines with nothing to read: badges, HTML tags and comments.
+fn is_decoration(line: &str) -> bool {
+    line.starts_with("[![") || line.starts_with("![") || line.starts_with('<')
+}
+
+/// `line` without Markdown markup: links keep their text, and images, bold
+/// and code marks are dropped.
+fn plain(line: &str) -> String {
+    let mut text = String::new();
+    let mut rest = line;
+
+    while let Some(start) = rest.find('[') {
+        let image = rest[..start].ends_with('!');
+        let link = rest[start..].find("](").and_then(|middle| {
+            let end = rest[start + middle..].find(')')?;
+            Some((start + middle, start + middle + end))
+        });
+
+        let Some((middle, end)) = link else {
+            break;
+        };
+
+        if image {
+            text.push_str(&rest[..start - 1]);
+        } else {
+            text.push_str(&rest[..start]);
+            text.push_str(&rest[start + 1..middle]);
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    text.push_str(rest);
+    text.replace("**", "").replace('`', "").trim().to_string()
+}
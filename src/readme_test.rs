@@ -0,0 +1,76 @@
+use std::fs;
+
+use crate::readme;
+
+#[test]
+fn find_readme() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    assert_eq!(readme::find(temp_dir.path()), None);
+
+    fs::write(temp_dir.path().join("readme.kak"), "").unwrap();
+    fs::write(temp_dir.path().join("README"), "").unwrap();
+    assert_eq!(
+        readme::find(temp_dir.path()),
+        Some(temp_dir.path().join("README"))
+    );
+
+    fs::write(temp_dir.path().join("Readme.md"), "").unwrap();
+    assert_eq!(
+        readme::find(temp_dir.path()),
+        Some(temp_dir.path().join("Readme.md"))
+    );
+}
+
+#[test]
+fn markdown_intro() {
+    let text = "# peneira
+
+[![CI](https://ci.example/badge.svg)](https://ci.example)
+
+A fuzzy finder crafted for **Kakoune**, built on
+[luar](https://github.com/gustavo-hms/luar).
+
+![screenshot](screenshot.png)
+
+```kak
+peneira-files
+```
+
+## Installation
+
+Clone it.
+";
+
+    assert_eq!(
+        readme::intro(text),
+        "A fuzzy finder crafted for Kakoune, built on\nluar.\n\n    peneira-files"
+    );
+}
+
+#[test]
+fn intro_after_heading() {
+    // The title is followed by a section right away, underlined.
+    let text = "peneira\n=======\n\nUsage\n-----\n\nRun `peneira-files`.\n\nOptions\n-------\n";
+    assert_eq!(readme::intro(text), "Run peneira-files.");
+
+    // AsciiDoc titles, and text before any title.
+    assert_eq!(
+        readme::intro("= peneira\n\nA fuzzy finder.\n"),
+        "A fuzzy finder."
+    );
+    assert_eq!(
+        readme::intro("A fuzzy finder.\n\n# Usage\n"),
+        "A fuzzy finder."
+    );
+    assert_eq!(readme::intro(""), "");
+}
+
+#[test]
+fn long_intro() {
+    let text: String = (1..=30).map(|line| format!("line {line}\n")).collect();
+    let intro = readme::intro(&text);
+    let lines: Vec<_> = intro.lines().collect();
+    assert_eq!(lines.len(), 21);
+    assert_eq!(lines[19], "line 20");
+    assert_eq!(lines[20], "…");
+}
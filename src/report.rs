@@ -0,0 +1,788 @@
+use std::cmp::Reverse;
+use std::env;
+use std::io;
+use std::io::IsTerminal;
+use std::io::Write;
+use std::iter;
+use std::mem;
+use std::process::Command;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::time::Duration;
+
+use colorized::Color;
+use colorized::Colors;
+
+use crate::time;
+
+/// What happened to a plugin during an update.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Outcome {
+    Disabled,
+    Installed,
+    /// Installed, but without any `.kak` file to load.
+    InstalledWithoutKakFiles,
+    Unchanged,
+    Updated,
+    /// Updated after upstream history was rewritten, resetting the checkout.
+    Rewritten,
+    Local,
+    Removed,
+    Failed,
+    /// Skipped because its parent plugin failed.
+    ParentFailed,
+    /// Left as it was because the run was interrupted.
+    Interrupted,
+    /// Left as it was because the update ran past `--max-time`.
+    TimedOut,
+}
+
+impl Outcome {
+    pub fn label(self) -> &'static str {
+        match self {
+            Outcome::Disabled => "disabled",
+            Outcome::Installed | Outcome::InstalledWithoutKakFiles => "installed",
+            Outcome::Unchanged => "unchanged",
+            Outcome::Updated => "updated",
+            Outcome::Rewritten => "rewritten",
+            Outcome::Local => "local",
+            Outcome::Removed => "removed",
+            Outcome::Failed => "failed",
+            Outcome::ParentFailed => "skipped",
+            Outcome::Interrupted => "interrupted",
+            Outcome::TimedOut => "timed out",
+        }
+    }
+
+    fn color(self) -> Colors {
+        match self {
+            Outcome::Disabled => Colors::BrightBlackFg,
+            Outcome::Installed | Outcome::Updated => Colors::GreenFg,
+            Outcome::InstalledWithoutKakFiles
+            | Outcome::Local
+            | Outcome::Rewritten
+            | Outcome::Interrupted
+            | Outcome::TimedOut => Colors::YellowFg,
+            Outcome::Unchanged => Colors::BlueFg,
+            Outcome::Removed => Colors::CyanFg,
+            Outcome::Failed | Outcome::ParentFailed => Colors::RedFg,
+        }
+    }
+}
+
+/// The revisions of a plugin before and after an update, when known.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Revisions<'a> {
+    pub old: Option<&'a str>,
+    pub new: Option<&'a str>,
+    /// The tag pointing at the new revision, if any.
+    pub tag: Option<&'a str>,
+    /// How long ago the newest upstream commit was made, for unchanged
+    /// plugins under `--verbose`.
+    pub last_commit_age: Option<Duration>,
+}
+
+impl<'a> Revisions<'a> {
+    /// The revisions of a freshly installed plugin.
+    pub fn new(revision: &'a str) -> Self {
+        Revisions {
+            old: None,
+            new: Some(revision),
+            tag: None,
+            last_commit_age: None,
+        }
+    }
+
+    /// Where a plugin just installed is, as in `at 1a2b3c4 (v1.2)`, or
+    /// nothing when unknown. Commit SHAs are shortened, as git does.
+    fn installed_at(&self) -> String {
+        let Some(revision) = self.new else {
+            return String::new();
+        };
+
+        let short = if revision.len() == 40 && revision.chars().all(|c| c.is_ascii_hexdigit()) {
+            &revision[..7]
+        } else {
+            revision
+        };
+
+        match self.tag {
+            Some(tag) if tag != revision => format!(" at {short} ({tag})"),
+            _ => format!(" at {short}"),
+        }
+    }
+}
+
+/// The commits a plugin received in an update.
+pub struct Change {
+    pub name: String,
+    /// One commit per line, as in `git log --oneline`.
+    pub log: String,
+    /// The versions the plugin went from and to, for plugins following tags.
+    pub versions: Option<(String, String)>,
+}
+
+/// Receives everything an update has to tell the user. The terminal is one
+/// frontend; others (JSON, a TUI) only need to implement this trait.
+///
+/// What a reporter is told comes in two kinds: the report proper (plugin
+/// statuses and changes), which goes to stdout, and diagnostics (warnings,
+/// errors and progress messages), which go to stderr. That way,
+/// `al | tee plugins.txt` captures the report while failures still show up on
+/// the terminal.
+///
+/// A reporter may be called from several threads at once, and must write each
+/// call out as a whole, so that lines never interleave.
+pub trait Reporter: Sync {
+    // The report.
+
+    /// `elapsed` is how long managing the plugin took, if it was managed at
+    /// all.
+    fn status(&self, name: &str, outcome: Outcome, revisions: Revisions, elapsed: Option<Duration>);
+
+    /// The plugin `name` was fetched, and its build commands started. Its
+    /// status comes once they're done.
+    fn building(&self, name: &str);
+
+    fn changes(&self, changes: &[Change]);
+
+    /// How long managing each plugin took.
+    fn timings(&self, timings: &[(String, Duration)]);
+
+    /// The update was skipped for the given reason.
+    fn skipped(&self, reason: &str);
+
+    // Diagnostics.
+
+    fn warnings(&self, warnings: &[String]);
+
+    fn error(&self, message: &str);
+
+    /// What Almoxarife is doing, like waiting for changes in watch mode.
+    fn progress(&self, message: &str);
+}
+
+/// Reports to the terminal, as aligned and colored lines.
+pub struct Terminal<O, E> {
+    out: Mutex<O>,
+    err: Mutex<E>,
+    /// Only print warnings and errors.
+    quiet: bool,
+    /// Also print the slowest plugins.
+    verbose: bool,
+    /// List plugins grouped by outcome, holding their statuses until the
+    /// changes are reported.
+    group_by_status: bool,
+    /// The name, outcome and details of each plugin.
+    held: Mutex<Vec<(String, Outcome, String)>>,
+    /// The width commit messages are fitted to, if any.
+    width: Option<usize>,
+    /// Where the Updates section goes when it doesn't fit on the screen.
+    pager: Option<Pager>,
+}
+
+impl Terminal<io::Stdout, io::Stderr> {
+    pub fn new(quiet: bool) -> Self {
+        Terminal::with_writers(io::stdout(), io::stderr(), quiet)
+    }
+}
+
+impl<O: Write + Send, E: Write + Send> Terminal<O, E> {
+    pub fn with_writers(out: O, err: E, quiet: bool) -> Self {
+        Terminal {
+            out: Mutex::new(out),
+            err: Mutex::new(err),
+            quiet,
+            verbose: false,
+            group_by_status: false,
+            held: Mutex::new(Vec::new()),
+            width: None,
+            pager: None,
+        }
+    }
+
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn group_by_status(mut self, group_by_status: bool) -> Self {
+        self.group_by_status = group_by_status;
+        self
+    }
+
+    pub fn width(mut self, width: Option<usize>) -> Self {
+        self.width = width;
+        self
+    }
+
+    pub fn pager(mut self, pager: Option<Pager>) -> Self {
+        self.pager = pager;
+        self
+    }
+
+    /// Writes the statuses held by `--group-by status`, under a header for
+    /// each group.
+    fn write_groups(&self) {
+        let mut held = mem::take(&mut *self.held.lock().unwrap_or_else(PoisonError::into_inner));
+
+        if held.is_empty() {
+            return;
+        }
+
+        held.sort();
+
+        let groups: Vec<String> = GROUPS
+            .iter()
+            .filter_map(|(header, outcomes)| {
+                let lines: String = held
+                    .iter()
+                    .filter(|(_, outcome, _)| outcomes.contains(outcome))
+                    .map(|(name, outcome, details)| status_line(name, *outcome, details))
+                    .collect();
+
+                if lines.is_empty() {
+                    return None;
+                }
+
+                let count = lines.lines().count();
+                let header = format!("{header} ({count}):").color(outcomes[0].color());
+                Some(format!("{header}\n\n{lines}"))
+            })
+            .collect();
+
+        write_all(&self.out, &groups.join("\n"));
+    }
+
+    #[cfg(test)]
+    pub fn into_writers(self) -> (O, E) {
+        (
+            self.out.into_inner().unwrap(),
+            self.err.into_inner().unwrap(),
+        )
+    }
+}
+
+/// The groups `--group-by status` lists plugins in, in order, along with the
+/// outcomes each one gathers.
+const GROUPS: [(&str, &[Outcome]); 8] = [
+    ("Updated", &[Outcome::Updated, Outcome::Rewritten]),
+    (
+        "Installed",
+        &[Outcome::Installed, Outcome::InstalledWithoutKakFiles],
+    ),
+    ("Removed", &[Outcome::Removed]),
+    ("Unchanged", &[Outcome::Unchanged]),
+    ("Local", &[Outcome::Local]),
+    ("Disabled", &[Outcome::Disabled]),
+    ("Skipped", &[Outcome::Interrupted, Outcome::TimedOut]),
+    ("Failed", &[Outcome::Failed, Outcome::ParentFailed]),
+];
+
+/// A plugin's name and outcome, aligned and colored, followed by `details`.
+fn status_line(name: &str, outcome: Outcome, details: &str) -> String {
+    let label = match outcome {
+        Outcome::Rewritten => "history rewritten, reset",
+        Outcome::ParentFailed => "skipped, parent failed",
+        _ => outcome.label(),
+    };
+
+    let label = label.color(outcome.color());
+    format!("{name:>20} {label}{details}\n")
+}
+
+/// How many plugins `--verbose` lists as the slowest ones.
+const SLOWEST: usize = 5;
+
+/// How many lines a commit message takes at most in the Updates section,
+/// once wrapped.
+const MESSAGE_LINES: usize = 2;
+
+/// The narrowest room commit messages are wrapped to. Below that, they're
+/// better left to the terminal.
+const MIN_MESSAGE_WIDTH: usize = 20;
+
+/// The size of the terminal stdout is, if it is one and it tells.
+fn window_size() -> Option<libc::winsize> {
+    let mut size = libc::winsize {
+        ws_row: 0,
+        ws_col: 0,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+
+    // SAFETY: TIOCGWINSZ only fills `size`, which outlives the call.
+    let result = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+    (result == 0).then_some(size)
+}
+
+/// The width of the terminal stdout is, if it is one: the one the terminal
+/// tells, or else `COLUMNS`.
+pub fn terminal_width() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+
+    match window_size() {
+        Some(size) if size.ws_col > 0 => Some(usize::from(size.ws_col)),
+        _ => env::var("COLUMNS").ok()?.parse().ok(),
+    }
+}
+
+/// The height of the terminal stdout is, if it is one: the one the terminal
+/// tells, or else `LINES`.
+fn terminal_height() -> Option<usize> {
+    if !io::stdout().is_terminal() {
+        return None;
+    }
+
+    match window_size() {
+        Some(size) if size.ws_row > 0 => Some(usize::from(size.ws_row)),
+        _ => env::var("LINES").ok()?.parse().ok(),
+    }
+}
+
+/// The program the Updates section goes through when it doesn't fit on the
+/// screen.
+pub struct Pager {
+    /// A shell command, as in `$PAGER`.
+    pub command: String,
+    /// How many lines fit on the screen.
+    pub height: usize,
+}
+
+impl Pager {
+    /// The pager of the terminal stdout is, if it is one: `$PAGER`, or else
+    /// `less`, as git does. An empty `PAGER`, or `cat`, turns paging off.
+    pub fn from_env() -> Option<Pager> {
+        let height = terminal_height()?;
+        let command = env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+        let command = command.trim();
+
+        if command.is_empty() || command == "cat" {
+            return None;
+        }
+
+        Some(Pager {
+            command: command.to_string(),
+            height,
+        })
+    }
+
+    /// Shows `text` through the pager, waiting for the user to quit it. Fails
+    /// only when the pager can't be run.
+    fn show(&self, text: &str) -> io::Result<()> {
+        // Unlike every other program, the pager is run outside the runner, on
+        // purpose: it's interactive, so it can't have a deadline, and it needs
+        // the user's whole environment, like TERM, which isolation would
+        // strip.
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(&self.command).stdin(Stdio::piped());
+
+        // Like git: let colors through, and quit at once if it all fits,
+        // unless the user chose otherwise.
+        if env::var_os("LESS").is_none() {
+            command.env("LESS", "FRX");
+        }
+
+        let mut child = command.spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            // Quitting the pager before reaching the end closes the pipe,
+            // which isn't a failure.
+            let _ = stdin.write_all(text.as_bytes());
+        }
+
+        // Whatever the pager exits with, the user has seen the text.
+        child.wait()?;
+        Ok(())
+    }
+}
+
+/// The characters of `text` that show on a terminal, along with their
+/// positions, leaving out ANSI escape sequences, like colors.
+fn visible_chars(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut chars = text.char_indices().peekable();
+
+    iter::from_fn(move || {
+        loop {
+            let (i, c) = chars.next()?;
+
+            if c != '\x1b' {
+                return Some((i, c));
+            }
+
+            // Control sequences, as in `ESC [ 3 2 m`, end with a character
+            // between `@` and `~`; other escapes take a single one.
+            if chars.next_if(|&(_, c)| c == '[').is_some() {
+                while chars.next_if(|&(_, c)| !('@'..='~').contains(&c)).is_some() {}
+            }
+
+            chars.next();
+        }
+    })
+}
+
+fn visible_width(text: &str) -> usize {
+    visible_chars(text).count()
+}
+
+/// Splits `text` after its first `width` visible characters, never inside an
+/// escape sequence.
+fn split_at_visible(text: &str, width: usize) -> (&str, &str) {
+    match visible_chars(text).nth(width) {
+        Some((i, _)) => text.split_at(i),
+        None => (text, ""),
+    }
+}
+
+/// Breaks `text` into lines of at most `width` visible characters, at spaces
+/// when it can, keeping the first `MESSAGE_LINES`. The last line kept ends
+/// with `…` when some text is left out.
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+
+    for word in text.split(' ') {
+        let mut word = word;
+
+        loop {
+            let room = if line.is_empty() {
+                width
+            } else {
+                width.saturating_sub(visible_width(&line) + 1)
+            };
+
+            if visible_width(word) <= room {
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+
+                line.push_str(word);
+                break;
+            }
+
+            if line.is_empty() {
+                // A word longer than a whole line.
+                let (head, tail) = split_at_visible(word, width);
+                lines.push(head.to_string());
+                word = tail;
+            } else {
+                lines.push(mem::take(&mut line));
+            }
+        }
+    }
+
+    lines.push(line);
+
+    if lines.len() > MESSAGE_LINES {
+        lines.truncate(MESSAGE_LINES);
+
+        if let Some(last) = lines.last_mut() {
+            let (head, _) = split_at_visible(last, width - 1);
+            *last = format!("{}…", head.trim_end());
+        }
+    }
+
+    lines
+}
+
+/// Writes `text` at once, holding the lock for the whole of it. There's
+/// nowhere left to report a failure to write to the terminal, so it's ignored.
+fn write_all<W: Write>(writer: &Mutex<W>, text: &str) {
+    let mut writer = writer.lock().unwrap_or_else(PoisonError::into_inner);
+    let _ = writer.write_all(text.as_bytes());
+    let _ = writer.flush();
+}
+
+impl<O: Write + Send, E: Write + Send> Reporter for Terminal<O, E> {
+    fn status(&self, name: &str, outcome: Outcome, revisions: Revisions, _: Option<Duration>) {
+        if self.quiet {
+            return;
+        }
+
+        // A fresh install is the one time there's no log telling what code
+        // came in.
+        let details = match (outcome, revisions.last_commit_age) {
+            (Outcome::Installed | Outcome::InstalledWithoutKakFiles, _) => revisions.installed_at(),
+            (Outcome::Unchanged, Some(age)) => format!(
+                " (last upstream commit {} ago)",
+                time::describe_duration(age)
+            ),
+            _ => String::new(),
+        };
+
+        if self.group_by_status {
+            let mut held = self.held.lock().unwrap_or_else(PoisonError::into_inner);
+            held.push((name.to_string(), outcome, details));
+        } else {
+            write_all(&self.out, &status_line(name, outcome, &details));
+        }
+    }
+
+    // A diagnostic rather than part of the report: the plugin's status comes
+    // later anyway.
+    fn building(&self, name: &str) {
+        if !self.quiet {
+            let label = "building".color(Colors::BrightBlackFg);
+            write_all(&self.err, &format!("{name:>20} {label}\n"));
+        }
+    }
+
+    fn changes(&self, changes: &[Change]) {
+        self.write_groups();
+
+        if changes.is_empty() || self.quiet {
+            return;
+        }
+
+        let changes: Vec<_> = changes
+            .iter()
+            .map(|change| {
+                let log: String = change
+                    .log
+                    .split("\n")
+                    .map(|line| match line.split_once(" ") {
+                        Some((revision, message)) => {
+                            let indent = visible_width(revision) + 1;
+                            let lines = match self.width {
+                                Some(width) if width >= indent + MIN_MESSAGE_WIDTH => {
+                                    wrap(message, width - indent)
+                                }
+                                _ => vec![message.to_string()],
+                            };
+
+                            let message = lines.join(&format!("\n{}", " ".repeat(indent)));
+                            format!("{} {message}\n", revision.color(Colors::BrightBlackFg))
+                        }
+
+                        None => line.to_string(),
+                    })
+                    .collect();
+
+                let name = change.name.color(Colors::GreenFg);
+
+                match &change.versions {
+                    Some((old, new)) => format!("{name} {old} → {new}:\n{log}"),
+                    None => format!("{name}:\n{log}"),
+                }
+            })
+            .collect();
+
+        let text = format!("\nUpdates:\n\n{}\n", changes.join("\n"));
+
+        if let Some(pager) = &self.pager
+            && text.lines().count() > pager.height
+        {
+            // Held, so that nothing else gets written while the pager is
+            // showing.
+            let out = self.out.lock().unwrap_or_else(PoisonError::into_inner);
+            let shown = pager.show(&text);
+            drop(out);
+
+            match shown {
+                Ok(()) => return,
+                Err(error) => write_all(&self.err, &format!("Couldn't run the pager: {error}\n")),
+            }
+        }
+
+        write_all(&self.out, &text);
+    }
+
+    fn timings(&self, timings: &[(String, Duration)]) {
+        if !self.verbose || self.quiet || timings.is_empty() {
+            return;
+        }
+
+        let mut timings = timings.to_vec();
+        timings.sort_by_key(|(_, elapsed)| Reverse(*elapsed));
+
+        let lines: String = timings
+            .iter()
+            .take(SLOWEST)
+            .map(|(name, elapsed)| {
+                let elapsed = format!("{:.1}s", elapsed.as_secs_f64());
+                format!("{name:>20} {}\n", elapsed.color(Colors::BrightBlackFg))
+            })
+            .collect();
+
+        write_all(&self.out, &format!("\nSlowest plugins:\n\n{lines}"));
+    }
+
+    fn skipped(&self, reason: &str) {
+        if !self.quiet {
+            write_all(&self.out, &format!("Skipping update: {reason}\n"));
+        }
+    }
+
+    fn warnings(&self, warnings: &[String]) {
+        if warnings.is_empty() {
+            return;
+        }
+
+        let mut text = format!("\n{}\n\n", "Warnings:".color(Colors::YellowFg));
+
+        for warning in warnings {
+            text.push_str(warning);
+            text.push('\n');
+        }
+
+        write_all(&self.err, &text);
+    }
+
+    fn error(&self, message: &str) {
+        write_all(&self.err, &format!("Error: {message}\n"));
+    }
+
+    fn progress(&self, message: &str) {
+        if !self.quiet {
+            let message = message.color(Colors::BrightBlackFg);
+            write_all(&self.err, &format!("\n{message}\n\n"));
+        }
+    }
+}
+
+/// The `--porcelain` format: one line per plugin, with tab separated fields
+/// `name`, `status`, `old revision`, `new revision`, `milliseconds`, the time
+/// managing the plugin took, and `tag`, for the plugins just installed, where
+/// unknown values are written as `-`.
+/// Scripts rely on it, so it must never change; new fields may only be
+/// appended.
+pub struct Porcelain<O, E> {
+    out: Mutex<O>,
+    err: Mutex<E>,
+}
+
+impl Porcelain<io::Stdout, io::Stderr> {
+    pub fn new() -> Self {
+        Porcelain::with_writers(io::stdout(), io::stderr())
+    }
+}
+
+impl<O: Write + Send, E: Write + Send> Porcelain<O, E> {
+    pub fn with_writers(out: O, err: E) -> Self {
+        Porcelain {
+            out: Mutex::new(out),
+            err: Mutex::new(err),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn into_writers(self) -> (O, E) {
+        (
+            self.out.into_inner().unwrap(),
+            self.err.into_inner().unwrap(),
+        )
+    }
+}
+
+impl<O: Write + Send, E: Write + Send> Reporter for Porcelain<O, E> {
+    fn status(
+        &self,
+        name: &str,
+        outcome: Outcome,
+        revisions: Revisions,
+        elapsed: Option<Duration>,
+    ) {
+        let old = revisions.old.unwrap_or("-");
+        let new = revisions.new.unwrap_or("-");
+        let tag = revisions.tag.unwrap_or("-");
+        let label = outcome.label();
+        let elapsed = elapsed.map_or("-".to_string(), |elapsed| elapsed.as_millis().to_string());
+
+        write_all(
+            &self.out,
+            &format!("{name}\t{label}\t{old}\t{new}\t{elapsed}\t{tag}\n"),
+        );
+    }
+
+    fn building(&self, _: &str) {}
+
+    fn changes(&self, _: &[Change]) {}
+
+    fn timings(&self, _: &[(String, Duration)]) {}
+
+    fn skipped(&self, _: &str) {}
+
+    fn warnings(&self, warnings: &[String]) {
+        let text: String = warnings
+            .iter()
+            .map(|warning| format!("warning: {warning}\n"))
+            .collect();
+
+        write_all(&self.err, &text);
+    }
+
+    fn error(&self, message: &str) {
+        write_all(&self.err, &format!("error: {message}\n"));
+    }
+
+    fn progress(&self, _: &str) {}
+}
+
+/// Forwards everything to another reporter, keeping a plain text transcript
+/// of the report proper, the statuses grouped by outcome and the changes, for
+/// the `almoxarife-report` command to show inside Kakoune.
+pub struct Transcript<'a> {
+    inner: &'a dyn Reporter,
+    copy: Terminal<Vec<u8>, io::Sink>,
+}
+
+impl<'a> Transcript<'a> {
+    pub fn new(inner: &'a dyn Reporter) -> Self {
+        Transcript {
+            inner,
+            copy: Terminal::with_writers(Vec::new(), io::sink(), false).group_by_status(true),
+        }
+    }
+
+    /// The report so far, without colors.
+    pub fn text(&self) -> String {
+        let out = self.copy.out.lock().unwrap_or_else(PoisonError::into_inner);
+        let text = String::from_utf8_lossy(&out);
+        visible_chars(&text).map(|(_, c)| c).collect()
+    }
+}
+
+impl Reporter for Transcript<'_> {
+    fn status(
+        &self,
+        name: &str,
+        outcome: Outcome,
+        revisions: Revisions,
+        elapsed: Option<Duration>,
+    ) {
+        self.copy.status(name, outcome, revisions, elapsed);
+        self.inner.status(name, outcome, revisions, elapsed);
+    }
+
+    fn building(&self, name: &str) {
+        self.inner.building(name);
+    }
+
+    fn changes(&self, changes: &[Change]) {
+        self.copy.changes(changes);
+        self.inner.changes(changes);
+    }
+
+    fn timings(&self, timings: &[(String, Duration)]) {
+        self.inner.timings(timings);
+    }
+
+    fn skipped(&self, reason: &str) {
+        self.inner.skipped(reason);
+    }
+
+    fn warnings(&self, warnings: &[String]) {
+        self.inner.warnings(warnings);
+    }
+
+    fn error(&self, message: &str) {
+        self.inner.error(message);
+    }
+
+    fn progress(&self, message: &str) {
+        self.inner.progress(message);
+    }
+}
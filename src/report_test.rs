@@ -0,0 +1,374 @@
+use std::fs;
+use std::time::Duration;
+
+use crate::report::Change;
+use crate::report::Outcome;
+use crate::report::Pager;
+use crate::report::Porcelain;
+use crate::report::Reporter;
+use crate::report::Revisions;
+use crate::report::Terminal;
+use crate::report::Transcript;
+
+#[test]
+fn terminal_status() {
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false);
+    terminal.status("peneira", Outcome::Updated, Revisions::default(), None);
+    terminal.status("luar", Outcome::Failed, Revisions::default(), None);
+    terminal.status(
+        "peneira-filters",
+        Outcome::ParentFailed,
+        Revisions::default(),
+        None,
+    );
+
+    let (out, err) = terminal.into_writers();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+
+    assert_eq!(lines.len(), 3);
+    assert!(lines[0].starts_with("             peneira "));
+    assert!(lines[0].contains("updated"));
+    assert!(lines[1].contains("failed"));
+    assert!(lines[2].contains("skipped, parent failed"));
+    assert!(err.is_empty());
+}
+
+#[test]
+fn terminal_group_by_status() {
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false).group_by_status(true);
+    terminal.status("search", Outcome::Disabled, Revisions::default(), None);
+    terminal.status("peneira", Outcome::Failed, Revisions::default(), None);
+    terminal.status("luar", Outcome::Updated, Revisions::default(), None);
+    terminal.status("auto-pairs", Outcome::Unchanged, Revisions::default(), None);
+    terminal.status("kak-lsp", Outcome::Rewritten, Revisions::default(), None);
+
+    let (out, _) = terminal.into_writers();
+    assert!(out.is_empty());
+
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false).group_by_status(true);
+    terminal.status("search", Outcome::Disabled, Revisions::default(), None);
+    terminal.status("peneira", Outcome::Failed, Revisions::default(), None);
+    terminal.status("luar", Outcome::Updated, Revisions::default(), None);
+    terminal.status("auto-pairs", Outcome::Unchanged, Revisions::default(), None);
+    terminal.status("kak-lsp", Outcome::Rewritten, Revisions::default(), None);
+    terminal.changes(&[]);
+
+    let (out, _) = terminal.into_writers();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().map(str::trim).collect();
+
+    assert_eq!(lines.len(), 16);
+    assert!(lines[0].contains("Updated (2):"));
+    assert!(lines[2].starts_with("kak-lsp "));
+    assert!(lines[3].starts_with("luar "));
+    assert!(lines[5].contains("Unchanged (1):"));
+    assert!(lines[7].starts_with("auto-pairs "));
+    assert!(lines[9].contains("Disabled (1):"));
+    assert!(lines[11].starts_with("search "));
+    assert!(lines[13].contains("Failed (1):"));
+    assert!(lines[15].starts_with("peneira "));
+}
+
+#[test]
+fn terminal_quiet() {
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), true);
+    terminal.status("peneira", Outcome::Installed, Revisions::default(), None);
+    terminal.changes(&[Change {
+        name: "peneira".into(),
+        log: "abc1234 Fix typo".into(),
+        versions: None,
+    }]);
+    terminal.warnings(&["peneira has no .kak files".into()]);
+
+    let (out, err) = terminal.into_writers();
+    assert!(out.is_empty());
+
+    let err = String::from_utf8(err).unwrap();
+    assert!(err.contains("Warnings:"));
+    assert!(err.ends_with("\n\npeneira has no .kak files\n"));
+}
+
+#[test]
+fn terminal_changes() {
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false);
+    terminal.changes(&[]);
+    terminal.changes(&[Change {
+        name: "peneira".into(),
+        log: "abc1234 Fix typo".into(),
+        versions: None,
+    }]);
+
+    let (out, _) = terminal.into_writers();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with("\nUpdates:\n\n"));
+    assert!(out.contains("abc1234"));
+    assert!(out.contains(" Fix typo\n"));
+}
+
+#[test]
+fn terminal_version_changes() {
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false);
+    terminal.changes(&[Change {
+        name: "peneira".into(),
+        log: "abc1234 Fix typo".into(),
+        versions: Some(("v1.1.0".into(), "v1.2.0".into())),
+    }]);
+
+    let (out, _) = terminal.into_writers();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(" v1.1.0 → v1.2.0:\n"));
+}
+
+#[test]
+fn terminal_installed_revisions() {
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false);
+    let sha = "3f2a1c9b8d7e6f5a4b3c2d1e0f9a8b7c6d5e4f3a";
+    terminal.status(
+        "peneira",
+        Outcome::Installed,
+        Revisions {
+            tag: Some("v1.2.0"),
+            ..Revisions::new(sha)
+        },
+        None,
+    );
+    terminal.status("luar", Outcome::Installed, Revisions::new("v0.3"), None);
+    terminal.status("search", Outcome::Unchanged, Revisions::new(sha), None);
+
+    let (out, _) = terminal.into_writers();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+    assert!(lines[0].ends_with(" at 3f2a1c9 (v1.2.0)"));
+    // Plugins following tags are at the tag already.
+    assert!(lines[1].ends_with(" at v0.3"));
+    assert!(!lines[2].contains(" at "));
+}
+
+#[test]
+fn terminal_commit_age() {
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false);
+    let revisions = Revisions {
+        last_commit_age: Some(Duration::from_secs(10 * 2592000)),
+        ..Revisions::new("abcdef")
+    };
+    terminal.status("peneira", Outcome::Unchanged, revisions, None);
+    terminal.status("luar", Outcome::Unchanged, Revisions::new("abcdef"), None);
+
+    let (out, _) = terminal.into_writers();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+    assert!(lines[0].ends_with(" (last upstream commit 10 months ago)"));
+    assert!(!lines[1].contains("last upstream commit"));
+}
+
+#[test]
+fn terminal_wraps_changes() {
+    let change = Change {
+        name: "peneira".into(),
+        log: "abc1234 Make the fuzzy matcher faster on long lists of files\n\
+              def5678 Fix \u{1b}[1mbold\u{1b}[0m typo\n\
+              0123456 A very long message which can't fit in two lines of this narrow pane, so it's cut short"
+            .into(),
+        versions: None,
+    };
+
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false).width(Some(40));
+    terminal.changes(std::slice::from_ref(&change));
+
+    let (out, _) = terminal.into_writers();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().skip(4).collect();
+    assert!(lines[0].ends_with(" Make the fuzzy matcher faster on"));
+    assert_eq!(lines[1], "        long lists of files");
+    // Escape sequences take no room.
+    assert!(lines[2].ends_with(" Fix \u{1b}[1mbold\u{1b}[0m typo"));
+    assert!(lines[3].ends_with(" A very long message which can't"));
+    assert_eq!(lines[4], "        fit in two lines of this narrow…");
+    assert_eq!(lines[5], "");
+    assert_eq!(lines.len(), 6);
+
+    // Unless wrapping is off.
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false);
+    terminal.changes(&[change]);
+
+    let (out, _) = terminal.into_writers();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains(" Make the fuzzy matcher faster on long lists of files\n"));
+}
+
+#[test]
+fn terminal_pages_long_changes() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let paged = temp_dir.path().join("paged");
+
+    let pager = |height| Pager {
+        command: format!("cat > '{}'", paged.display()),
+        height,
+    };
+
+    let change = Change {
+        name: "peneira".into(),
+        log: "abc1234 Make the fuzzy matcher faster\ndef5678 Fix typo".into(),
+        versions: None,
+    };
+
+    // Fitting on the screen, the changes are printed as usual.
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false).pager(Some(pager(24)));
+    terminal.changes(std::slice::from_ref(&change));
+
+    let (out, _) = terminal.into_writers();
+    assert!(String::from_utf8(out).unwrap().contains("Fix typo"));
+    assert!(!paged.exists());
+
+    // Otherwise, they go through the pager.
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false).pager(Some(pager(3)));
+    terminal.changes(&[change]);
+
+    let (out, err) = terminal.into_writers();
+    assert!(out.is_empty());
+    assert!(err.is_empty());
+
+    let paged = fs::read_to_string(paged).unwrap();
+    assert!(paged.starts_with("\nUpdates:\n\n"));
+    assert!(paged.contains("Fix typo"));
+}
+
+#[test]
+fn terminal_timings() {
+    let timings: Vec<_> = (1..=7)
+        .map(|i| (format!("plugin{i}"), Duration::from_millis(i * 100)))
+        .collect();
+
+    // Timings are only printed when verbose.
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false);
+    terminal.timings(&timings);
+    assert!(terminal.into_writers().0.is_empty());
+
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false).verbose(true);
+    terminal.timings(&timings);
+
+    let (out, _) = terminal.into_writers();
+    let out = String::from_utf8(out).unwrap();
+    let lines: Vec<_> = out.lines().collect();
+
+    assert_eq!(lines[1], "Slowest plugins:");
+    // Only the five slowest, the slowest first.
+    assert_eq!(lines.len(), 3 + 5);
+    assert!(lines[3].starts_with("             plugin7 "));
+    assert!(lines[3].contains("0.7s"));
+    assert!(lines[7].starts_with("             plugin3 "));
+}
+
+#[test]
+fn terminal_diagnostics_go_to_stderr() {
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false);
+    terminal.progress("Regenerating almoxarife.kak");
+    terminal.building("kak-lsp");
+    terminal.error("couldn't open almoxarife.yaml");
+    terminal.skipped("the last one was 2 hours ago");
+
+    let (out, err) = terminal.into_writers();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "Skipping update: the last one was 2 hours ago\n"
+    );
+
+    let err = String::from_utf8(err).unwrap();
+    assert!(err.contains("Regenerating almoxarife.kak"));
+    assert!(err.contains("             kak-lsp "));
+    assert!(err.contains("building"));
+    assert!(err.ends_with("Error: couldn't open almoxarife.yaml\n"));
+}
+
+#[test]
+fn porcelain() {
+    let porcelain = Porcelain::with_writers(Vec::new(), Vec::new());
+    porcelain.status(
+        "peneira",
+        Outcome::Installed,
+        Revisions {
+            tag: Some("v1.2"),
+            ..Revisions::new("abcdef")
+        },
+        None,
+    );
+    porcelain.status(
+        "luar",
+        Outcome::Updated,
+        Revisions {
+            old: Some("abcdef"),
+            new: Some("ghijkl"),
+            tag: None,
+            last_commit_age: None,
+        },
+        Some(Duration::from_millis(1250)),
+    );
+    porcelain.status("search", Outcome::Disabled, Revisions::default(), None);
+    porcelain.status(
+        "fzf-search",
+        Outcome::ParentFailed,
+        Revisions::default(),
+        None,
+    );
+    porcelain.changes(&[Change {
+        name: "luar".into(),
+        log: "ghijkl Fix typo".into(),
+        versions: None,
+    }]);
+    porcelain.warnings(&["peneira has no .kak files".into()]);
+
+    let (out, err) = porcelain.into_writers();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "peneira\tinstalled\t-\tabcdef\t-\tv1.2
+luar\tupdated\tabcdef\tghijkl\t1250\t-
+search\tdisabled\t-\t-\t-\t-
+fzf-search\tskipped\t-\t-\t-\t-
+"
+    );
+    assert_eq!(
+        String::from_utf8(err).unwrap(),
+        "warning: peneira has no .kak files\n"
+    );
+}
+
+#[test]
+fn transcript() {
+    let terminal = Terminal::with_writers(Vec::new(), Vec::new(), false);
+    let transcript = Transcript::new(&terminal);
+    transcript.status("peneira", Outcome::Updated, Revisions::default(), None);
+    transcript.status("luar", Outcome::Failed, Revisions::default(), None);
+    transcript.warnings(&["luar is slow".into()]);
+    transcript.changes(&[Change {
+        name: "peneira".into(),
+        log: "abc1234 Fix typo".into(),
+        versions: None,
+    }]);
+
+    // Grouped, and without colors.
+    assert_eq!(
+        transcript.text(),
+        "Updated (1):
+
+             peneira updated
+
+Failed (1):
+
+                luar failed
+
+Updates:
+
+peneira:
+abc1234 Fix typo
+
+"
+    );
+
+    // Everything reaches the terminal as usual.
+    let (out, err) = terminal.into_writers();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("peneira"));
+    assert!(out.contains("Fix typo"));
+    assert!(String::from_utf8(err).unwrap().contains("luar is slow"));
+}
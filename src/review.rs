@@ -0,0 +1,107 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::kakscript;
+use crate::setup::Plugin;
+
+/// How many files of a repository are listed by name before the rest are
+/// only counted.
+const LISTED_FILES: usize = 40;
+
+/// How much of a line with a shell block is shown.
+const SHOWN_LINE: usize = 100;
+
+/// What to look at before letting `plugin`, just cloned, run in Kakoune, as
+/// lines to print: its files, the Kakoune scripts among them, the shell blocks
+/// those run, and the build commands of its manifest.
+pub fn summary(plugin: &Plugin) -> io::Result<Vec<String>> {
+    let root = &plugin.repository_path;
+    let mut lines = vec![format!("{}, from {}", plugin.name, plugin.location)];
+
+    let mut files = Vec::new();
+    list_files(root, root, &mut files)?;
+    files.sort();
+
+    lines.push(format!("Files ({}):", files.len()));
+    lines.extend(
+        files
+            .iter()
+            .take(LISTED_FILES)
+            .map(|file| format!("  {file}")),
+    );
+
+    if files.len() > LISTED_FILES {
+        lines.push(format!("  and {} more", files.len() - LISTED_FILES));
+    }
+
+    let scripts = kakscript::kak_files(root)?;
+
+    if scripts.is_empty() {
+        lines.push("Kakoune scripts: none".to_string());
+    } else {
+        lines.push("Kakoune scripts, run when Kakoune starts:".to_string());
+        lines.extend(
+            scripts
+                .iter()
+                .map(|script| format!("  {}", relative(root, script))),
+        );
+    }
+
+    let mut blocks = Vec::new();
+
+    for script in &scripts {
+        let source = fs::read_to_string(script)?;
+
+        for block in kakscript::shell_blocks(&source) {
+            let line = source.lines().nth(block.line - 1).unwrap_or_default();
+            let line: String = line.trim().chars().take(SHOWN_LINE).collect();
+            blocks.push(format!(
+                "  {}:{}: {line}",
+                relative(root, script),
+                block.line
+            ));
+        }
+    }
+
+    if !blocks.is_empty() {
+        lines.push("Shell blocks in them:".to_string());
+        lines.extend(blocks);
+    }
+
+    let builds = plugin.manifest().build;
+
+    if !builds.is_empty() {
+        lines.push("Build commands, run after installing it:".to_string());
+        lines.extend(builds.iter().map(|build| format!("  {build}")));
+    }
+
+    Ok(lines)
+}
+
+/// Collects the files inside `dir`, relative to `root`, skipping hidden ones
+/// like `.git`.
+fn list_files(root: &Path, dir: &Path, files: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            list_files(root, &entry.path(), files)?;
+        } else {
+            files.push(relative(root, &entry.path()));
+        }
+    }
+
+    Ok(())
+}
+
+fn relative(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .to_string_lossy()
+        .into_owned()
+}
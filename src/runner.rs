@@ -0,0 +1,288 @@
+use std::env;
+use std::ffi::OsStr;
+use std::ffi::OsString;
+use std::fmt::Debug;
+use std::fmt::Formatter;
+use std::io;
+use std::io::ErrorKind;
+use std::io::Read;
+use std::io::Write;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Output;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use std::time::Instant;
+
+/// Runs the programs Almoxarife relies on, like git and kak, and tells what
+/// the environment they run in holds. Every subprocess goes through one, so
+/// that tests can hand in their own.
+pub trait CommandRunner: Send + Sync {
+    /// Runs `command` to completion, with `input` as its standard input, and
+    /// collects its output, unless `deadline` comes first: the program is then
+    /// killed, and the error is of kind `TimedOut`.
+    fn output(
+        &self,
+        command: &mut Command,
+        input: &[u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<Output>;
+
+    /// Runs `command` in the terminal, for the user to interact with, and
+    /// waits for it to exit.
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus>;
+
+    /// An environment variable, as the programs run see it.
+    fn var(&self, var: &str) -> Option<String>;
+
+    /// The names of the variables in the environment the programs run in.
+    fn var_names(&self) -> Vec<OsString>;
+}
+
+/// Runs programs in Almoxarife's own environment.
+pub struct System;
+
+impl CommandRunner for System {
+    fn output(
+        &self,
+        command: &mut Command,
+        input: &[u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<Output> {
+        let stdin = if input.is_empty() {
+            Stdio::null()
+        } else {
+            Stdio::piped()
+        };
+
+        let mut child = command
+            .stdin(stdin)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let stdout = child.stdout.take().map(drain);
+        let stderr = child.stderr.take().map(drain);
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(input)?;
+        }
+
+        let status = loop {
+            let Some(deadline) = deadline else {
+                break child.wait()?;
+            };
+
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+
+            let now = Instant::now();
+
+            if now >= deadline {
+                // The pipes aren't waited for: whatever the program started may
+                // still hold them open.
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(io::Error::new(ErrorKind::TimedOut, "ran out of time"));
+            }
+
+            thread::sleep((deadline - now).min(Duration::from_millis(20)));
+        };
+
+        let collect = |pipe: Option<JoinHandle<Vec<u8>>>| {
+            pipe.map(|reader| reader.join().unwrap_or_default())
+                .unwrap_or_default()
+        };
+
+        Ok(Output {
+            status,
+            stdout: collect(stdout),
+            stderr: collect(stderr),
+        })
+    }
+
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        command.status()
+    }
+
+    fn var(&self, var: &str) -> Option<String> {
+        env::var(var).ok()
+    }
+
+    fn var_names(&self) -> Vec<OsString> {
+        env::vars_os().map(|(name, _)| name).collect()
+    }
+}
+
+/// The variables programs keep from the environment when isolated, on top of
+/// the ones the user passes through: only what finding programs, files,
+/// Kakoune's sessions and SSH keys takes.
+pub const ISOLATED_ENV: [&str; 9] = [
+    "HOME",
+    "PATH",
+    "USER",
+    "LOGNAME",
+    "LANG",
+    "LC_ALL",
+    "TMPDIR",
+    "XDG_RUNTIME_DIR",
+    "SSH_AUTH_SOCK",
+];
+
+/// Keeps git from reading anything but the repository's own configuration.
+const GIT_CONFIG_ISOLATION: [(&str, &str); 2] = [
+    ("GIT_CONFIG_GLOBAL", "/dev/null"),
+    ("GIT_CONFIG_NOSYSTEM", "1"),
+];
+
+/// Runs programs through another runner, in a minimal environment: only the
+/// variables in `ISOLATED_ENV` and the ones passed through are kept, so that
+/// neither `GIT_*` variables nor a shell's startup files get in, and git
+/// ignores the user's and the system's configuration files.
+pub struct Isolated {
+    runner: Runner,
+    pass: Vec<String>,
+}
+
+impl Isolated {
+    pub fn new(runner: Runner, pass: Vec<String>) -> Isolated {
+        Isolated { runner, pass }
+    }
+
+    fn passes(&self, var: &OsStr) -> bool {
+        var.to_str().is_some_and(|var| {
+            ISOLATED_ENV.contains(&var) || self.pass.iter().any(|pass| pass == var)
+        })
+    }
+
+    /// Keeps `command` from getting the variables that don't pass, unless it
+    /// sets them itself.
+    fn isolate<'a>(&self, command: &'a mut Command) -> &'a mut Command {
+        let set: Vec<OsString> = command
+            .get_envs()
+            .map(|(var, _)| var.to_os_string())
+            .collect();
+
+        for var in self.runner.0.var_names() {
+            if !self.passes(&var) && !set.contains(&var) {
+                command.env_remove(var);
+            }
+        }
+
+        command.envs(GIT_CONFIG_ISOLATION)
+    }
+}
+
+impl CommandRunner for Isolated {
+    fn output(
+        &self,
+        command: &mut Command,
+        input: &[u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<Output> {
+        self.runner.0.output(self.isolate(command), input, deadline)
+    }
+
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        self.runner.0.status(self.isolate(command))
+    }
+
+    fn var(&self, var: &str) -> Option<String> {
+        if let Some((_, value)) = GIT_CONFIG_ISOLATION.iter().find(|(name, _)| *name == var) {
+            return Some(value.to_string());
+        }
+
+        if self.passes(OsStr::new(var)) {
+            self.runner.var(var)
+        } else {
+            None
+        }
+    }
+
+    fn var_names(&self) -> Vec<OsString> {
+        self.runner
+            .0
+            .var_names()
+            .into_iter()
+            .filter(|var| self.passes(var))
+            .chain(GIT_CONFIG_ISOLATION.iter().map(|(var, _)| var.into()))
+            .collect()
+    }
+}
+
+/// The runner of a setup, shared with each of its plugins.
+#[derive(Clone)]
+pub struct Runner(Arc<dyn CommandRunner>);
+
+impl Runner {
+    pub fn new(runner: impl CommandRunner + 'static) -> Runner {
+        Runner(Arc::new(runner))
+    }
+
+    /// Runs `command` to completion and collects its output, unless
+    /// `deadline` comes first: the program is then killed, and the error is
+    /// of kind `TimedOut`.
+    pub fn output(&self, command: &mut Command, deadline: Option<Instant>) -> io::Result<Output> {
+        self.0.output(command, &[], deadline)
+    }
+
+    /// Like [`Runner::output`], with `input` as the program's standard input.
+    pub fn output_with_input(
+        &self,
+        command: &mut Command,
+        input: &[u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<Output> {
+        self.0.output(command, input, deadline)
+    }
+
+    /// Runs `command` in the terminal, for the user to interact with.
+    pub fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        self.0.status(command)
+    }
+
+    pub fn var(&self, var: &str) -> Option<String> {
+        self.0.var(var)
+    }
+}
+
+/// A runner shared with whoever else holds it, like a test looking at the
+/// fake it handed in.
+impl<R: CommandRunner + 'static> From<Arc<R>> for Runner {
+    fn from(runner: Arc<R>) -> Runner {
+        Runner(runner)
+    }
+}
+
+impl Default for Runner {
+    fn default() -> Self {
+        Runner::new(System)
+    }
+}
+
+impl Debug for Runner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Runner")
+    }
+}
+
+/// How commands are run makes no difference to what a plugin is.
+impl PartialEq for Runner {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+/// Reads `pipe` to the end in the background, so that the program writing
+/// to it never blocks on a full pipe.
+fn drain(mut pipe: impl Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = Vec::new();
+        let _ = pipe.read_to_end(&mut bytes);
+        bytes
+    })
+}
@@ -0,0 +1,382 @@
+use std::collections::HashMap;
+use std::env;
+use std::ffi::OsString;
+use std::io;
+use std::io::ErrorKind;
+use std::os::unix::process::ExitStatusExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::process::ExitStatus;
+use std::process::Output;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::fakes_test;
+use crate::runner::CommandRunner;
+use crate::runner::Isolated;
+use crate::runner::Runner;
+use crate::runner::System;
+
+/// A runner for tests. Calls get the reply scripted for their program and
+/// arguments, if any; otherwise git, curl, kak and the other programs with a
+/// fake in `fakes_test` are faked, seeing only the variables set here, and
+/// the rest run for real, with these variables on top of the real
+/// environment. Every call is recorded.
+#[derive(Default)]
+pub struct FakeRunner {
+    env: Mutex<HashMap<&'static str, String>>,
+    replies: Mutex<Vec<Reply>>,
+    calls: Mutex<Vec<Vec<String>>>,
+}
+
+/// What the calls of `program` whose arguments include `args` do.
+struct Reply {
+    program: String,
+    args: Vec<String>,
+    outcome: Outcome,
+}
+
+enum Outcome {
+    Exit {
+        code: i32,
+        stdout: String,
+        stderr: String,
+    },
+    Hang,
+}
+
+/// A call handed to a fake program.
+pub struct Call<'a> {
+    pub args: Vec<String>,
+    /// The directory it runs in.
+    pub dir: PathBuf,
+    pub input: &'a [u8],
+    pub deadline: Option<Instant>,
+    command: &'a Command,
+    runner: &'a FakeRunner,
+}
+
+impl Call<'_> {
+    /// A variable of the call's environment: the ones set on its command, or
+    /// else the fake runner's.
+    pub fn var(&self, var: &str) -> Option<String> {
+        match self.command.get_envs().find(|(name, _)| *name == var) {
+            Some((_, value)) => value.map(|value| value.to_string_lossy().into_owned()),
+            None => self.runner.var(var),
+        }
+    }
+
+    /// Runs `args` for real, as the call would, like the `exec` of a shell
+    /// script.
+    pub fn exec(&self, args: &[String]) -> Output {
+        let mut command = Command::new(&args[0]);
+        command.args(&args[1..]).current_dir(&self.dir);
+
+        for (var, value) in self.command.get_envs() {
+            match value {
+                Some(value) => command.env(var, value),
+                None => command.env_remove(var),
+            };
+        }
+
+        self.runner
+            .run_for_real(&mut command, self.input, self.deadline)
+            .unwrap_or_else(|e| exit(127, "", &e.to_string()))
+    }
+}
+
+impl FakeRunner {
+    pub fn new() -> Arc<FakeRunner> {
+        FakeRunner::with_env(HashMap::new())
+    }
+
+    pub fn with_env(env: HashMap<&'static str, String>) -> Arc<FakeRunner> {
+        Arc::new(FakeRunner {
+            env: Mutex::new(env),
+            ..FakeRunner::default()
+        })
+    }
+
+    /// A runner handing the calls to this fake.
+    pub fn runner(self: &Arc<Self>) -> Runner {
+        Runner::from(Arc::clone(self))
+    }
+
+    pub fn set(&self, var: &'static str, value: String) {
+        self.env.lock().unwrap().insert(var, value);
+    }
+
+    pub fn unset(&self, var: &str) {
+        self.env.lock().unwrap().remove(var);
+    }
+
+    /// Has the calls of `program` whose arguments include `args`, one after
+    /// the other, exit with `code`, printing `stdout` and `stderr`. The latest
+    /// reply matching a call wins.
+    pub fn reply(&self, program: &str, args: &[&str], code: i32, stdout: &str, stderr: &str) {
+        self.script(
+            program,
+            args,
+            Outcome::Exit {
+                code,
+                stdout: stdout.into(),
+                stderr: stderr.into(),
+            },
+        );
+    }
+
+    /// Has the matching calls never exit, until killed at their deadline.
+    pub fn hang(&self, program: &str, args: &[&str]) {
+        self.script(program, args, Outcome::Hang);
+    }
+
+    /// Drops the replies scripted for `program`.
+    pub fn forget(&self, program: &str) {
+        self.replies
+            .lock()
+            .unwrap()
+            .retain(|reply| reply.program != program);
+    }
+
+    fn script(&self, program: &str, args: &[&str], outcome: Outcome) {
+        self.replies.lock().unwrap().push(Reply {
+            program: program.into(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            outcome,
+        });
+    }
+
+    /// The programs run so far, in order.
+    pub fn programs(&self) -> Vec<String> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|call| call[0].clone())
+            .collect()
+    }
+
+    /// The arguments of the calls of `program` so far, in order.
+    pub fn calls(&self, program: &str) -> Vec<Vec<String>> {
+        self.calls
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|call| call[0] == program)
+            .map(|call| call[1..].to_vec())
+            .collect()
+    }
+
+    fn run(
+        &self,
+        command: &mut Command,
+        input: &[u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<Output> {
+        let program = command.get_program().to_string_lossy().into_owned();
+        let args: Vec<String> = command
+            .get_args()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect();
+
+        let mut call = vec![program.clone()];
+        call.extend(args.iter().cloned());
+        self.calls.lock().unwrap().push(call);
+
+        let replies = self.replies.lock().unwrap();
+        let reply = replies.iter().rev().find(|reply| {
+            reply.program == program
+                && (reply.args.is_empty()
+                    || args
+                        .windows(reply.args.len())
+                        .any(|window| window == reply.args))
+        });
+
+        match reply.map(|reply| &reply.outcome) {
+            Some(Outcome::Exit {
+                code,
+                stdout,
+                stderr,
+            }) => return Ok(exit(*code, stdout, stderr)),
+            Some(Outcome::Hang) => {
+                let deadline = deadline.expect("a hanging program needs a deadline");
+                thread::sleep(deadline.saturating_duration_since(Instant::now()));
+                return Err(io::Error::new(ErrorKind::TimedOut, "ran out of time"));
+            }
+            None => {}
+        }
+
+        drop(replies);
+
+        let Some(fake) = fakes_test::program(&program) else {
+            return self.run_for_real(command, input, deadline);
+        };
+
+        let dir = match command.get_current_dir() {
+            Some(dir) => dir.to_path_buf(),
+            None => env::current_dir()?,
+        };
+
+        Ok(fake(&Call {
+            args,
+            dir,
+            input,
+            deadline,
+            command,
+            runner: self,
+        }))
+    }
+
+    fn run_for_real(
+        &self,
+        command: &mut Command,
+        input: &[u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<Output> {
+        for (var, value) in self.env.lock().unwrap().iter() {
+            if command.get_envs().all(|(name, _)| name != *var) {
+                command.env(var, value);
+            }
+        }
+
+        System.output(command, input, deadline)
+    }
+}
+
+impl CommandRunner for FakeRunner {
+    fn output(
+        &self,
+        command: &mut Command,
+        input: &[u8],
+        deadline: Option<Instant>,
+    ) -> io::Result<Output> {
+        self.run(command, input, deadline)
+    }
+
+    fn status(&self, command: &mut Command) -> io::Result<ExitStatus> {
+        Ok(self.run(command, &[], None)?.status)
+    }
+
+    fn var(&self, var: &str) -> Option<String> {
+        self.env.lock().unwrap().get(var).cloned()
+    }
+
+    fn var_names(&self) -> Vec<OsString> {
+        self.env
+            .lock()
+            .unwrap()
+            .keys()
+            .map(OsString::from)
+            .collect()
+    }
+}
+
+/// What a program exiting with `code` after printing `stdout` and `stderr`
+/// outputs.
+pub fn exit(code: i32, stdout: &str, stderr: &str) -> Output {
+    Output {
+        status: ExitStatus::from_raw(code << 8),
+        stdout: stdout.into(),
+        stderr: stderr.into(),
+    }
+}
+
+#[test]
+fn fake_runner() {
+    let fake = FakeRunner::new();
+    let runner = fake.runner();
+    fake.reply("git", &["status"], 1, "", "no git today");
+
+    let output = runner
+        .output(Command::new("git").args(["status", "--short"]), None)
+        .unwrap();
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "no git today");
+
+    // Calls the replies don't match go to the fakes.
+    let output = runner
+        .output(Command::new("git").args(["ls-remote", "origin"]), None)
+        .unwrap();
+    assert!(output.status.success());
+
+    fake.forget("git");
+    let output = runner
+        .output(Command::new("git").arg("status"), None)
+        .unwrap();
+    assert!(output.status.success());
+
+    fake.set("ALMOXARIFE_TEST_PROXY", String::new());
+    assert_eq!(runner.var("ALMOXARIFE_TEST_PROXY"), Some(String::new()));
+    fake.unset("ALMOXARIFE_TEST_PROXY");
+    assert_eq!(runner.var("ALMOXARIFE_TEST_PROXY"), None);
+
+    assert_eq!(fake.programs(), ["git", "git", "git"]);
+    assert_eq!(fake.calls("git")[1], ["ls-remote", "origin"]);
+
+    assert_eq!(Runner::default().var("ALMOXARIFE_NO_SUCH_VARIABLE"), None);
+}
+
+#[test]
+fn output_before_deadline() {
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let output = Runner::default()
+        .output_with_input(
+            Command::new("sh").args(["-c", "cat; echo 'no git today' >&2; exit 3"]),
+            b"status",
+            Some(deadline),
+        )
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "status");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "no git today\n");
+}
+
+#[test]
+fn output_past_deadline() {
+    let start = Instant::now();
+    let deadline = start + Duration::from_millis(100);
+    let error = Runner::default()
+        .output(Command::new("sleep").arg("30"), Some(deadline))
+        .unwrap_err();
+
+    assert_eq!(error.kind(), ErrorKind::TimedOut);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[test]
+fn isolated_runner() {
+    let mut env = HashMap::new();
+    env.insert("GIT_DIR", "/elsewhere".into());
+    env.insert("SSH_AUTH_SOCK", "/run/agent".into());
+    env.insert("https_proxy", "http://proxy:3128".into());
+    env.insert("BASH_ENV", "~/.aliases".into());
+
+    let runner = Runner::new(Isolated::new(
+        FakeRunner::with_env(env).runner(),
+        vec!["https_proxy".into()],
+    ));
+
+    let output = runner
+        .output(
+            Command::new("sh")
+                .arg("-c")
+                .arg("printf '%s|' \"$GIT_DIR\" \"$BASH_ENV\" \"$SSH_AUTH_SOCK\" \"$https_proxy\" \"$GIT_CONFIG_GLOBAL\" \"$ALMOXARIFE_BIN\"")
+                .env("ALMOXARIFE_BIN", "/bin"),
+            None,
+        )
+        .unwrap();
+
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "||/run/agent|http://proxy:3128|/dev/null|/bin|"
+    );
+
+    assert_eq!(runner.var("GIT_DIR"), None);
+    assert_eq!(runner.var("https_proxy"), Some("http://proxy:3128".into()));
+    assert_eq!(runner.var("GIT_CONFIG_NOSYSTEM"), Some("1".into()));
+    assert_eq!(runner.var("SSH_AUTH_SOCK"), Some("/run/agent".into()));
+}
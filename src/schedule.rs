@@ -0,0 +1,236 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+use crate::setup::Setup;
+
+/// How often scheduled updates run.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cadence {
+    Hourly,
+    Daily,
+    Weekly,
+}
+
+impl Cadence {
+    pub fn parse(cadence: &str) -> Option<Cadence> {
+        match cadence {
+            "hourly" => Some(Cadence::Hourly),
+            "daily" => Some(Cadence::Daily),
+            "weekly" => Some(Cadence::Weekly),
+            _ => None,
+        }
+    }
+
+    /// The name of the cadence, which is also understood by systemd's
+    /// `OnCalendar`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Cadence::Hourly => "hourly",
+            Cadence::Daily => "daily",
+            Cadence::Weekly => "weekly",
+        }
+    }
+
+    fn seconds(self) -> u64 {
+        match self {
+            Cadence::Hourly => 60 * 60,
+            Cadence::Daily => 24 * 60 * 60,
+            Cadence::Weekly => 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+const SYSTEMD_UNIT: &str = "almoxarife";
+const LAUNCHD_LABEL: &str = "io.github.gustavo-hms.almoxarife";
+
+/// The arguments scheduled runs are called with.
+const ARGS: [&str; 3] = ["--auto", "--quiet", "--notify"];
+
+/// Renders the systemd user service and timer units running Almoxarife.
+pub fn systemd_units(executable: &Path, path: &str, cadence: Cadence) -> (String, String) {
+    let service = format!(
+        "[Unit]
+Description=Update Kakoune plugins with Almoxarife
+
+[Service]
+Type=oneshot
+Environment=PATH={path}
+ExecStart={executable} {args}
+",
+        executable = executable.to_string_lossy(),
+        args = ARGS.join(" "),
+    );
+
+    let timer = format!(
+        "[Unit]
+Description=Update Kakoune plugins with Almoxarife {calendar}
+
+[Timer]
+OnCalendar={calendar}
+Persistent=true
+RandomizedDelaySec=10min
+
+[Install]
+WantedBy=timers.target
+",
+        calendar = cadence.name(),
+    );
+
+    (service, timer)
+}
+
+/// Renders the launchd agent running Almoxarife.
+pub fn launchd_plist(executable: &Path, path: &str, cadence: Cadence) -> String {
+    let args: String = ARGS
+        .iter()
+        .map(|arg| format!("\n        <string>{arg}</string>"))
+        .collect();
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>Label</key>
+    <string>{LAUNCHD_LABEL}</string>
+    <key>ProgramArguments</key>
+    <array>
+        <string>{executable}</string>{args}
+    </array>
+    <key>EnvironmentVariables</key>
+    <dict>
+        <key>PATH</key>
+        <string>{path}</string>
+    </dict>
+    <key>StartInterval</key>
+    <integer>{seconds}</integer>
+</dict>
+</plist>
+"#,
+        executable = executable.to_string_lossy(),
+        seconds = cadence.seconds(),
+    )
+}
+
+fn systemd_dir(setup: &Setup) -> PathBuf {
+    let config_dir = setup
+        .almoxarife_yaml_path
+        .parent()
+        .unwrap_or(Path::new("."));
+
+    config_dir.join("systemd/user")
+}
+
+fn launchd_plist_path(setup: &Setup) -> PathBuf {
+    // The configuration file lives in `~/.config` on macOS too, so we go up to
+    // the home directory.
+    let home = setup
+        .almoxarife_yaml_path
+        .parent()
+        .and_then(Path::parent)
+        .unwrap_or(Path::new("."));
+
+    home.join("Library/LaunchAgents")
+        .join(format!("{LAUNCHD_LABEL}.plist"))
+}
+
+fn run(setup: &Setup, command: &mut Command) -> io::Result<()> {
+    let output = setup.runner.output(command, None)?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} exited with {}: {}",
+            command.get_program().to_string_lossy(),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
+
+/// Installs and enables the scheduled job, returning the files written.
+pub fn install(setup: &Setup, cadence: Cadence) -> io::Result<Vec<PathBuf>> {
+    let executable = std::env::current_exe()?;
+    let path = setup.runner.var("PATH").unwrap_or_default();
+
+    if cfg!(target_os = "macos") {
+        let plist_path = launchd_plist_path(setup);
+        fs::create_dir_all(plist_path.parent().unwrap_or(Path::new(".")))?;
+
+        // Reloading is needed to pick up a changed cadence.
+        let _ = setup.runner.output(
+            Command::new("launchctl").arg("unload").arg(&plist_path),
+            None,
+        );
+
+        fs::write(&plist_path, launchd_plist(&executable, &path, cadence))?;
+        run(
+            setup,
+            Command::new("launchctl")
+                .args(["load", "-w"])
+                .arg(&plist_path),
+        )?;
+        return Ok(vec![plist_path]);
+    }
+
+    let dir = systemd_dir(setup);
+    fs::create_dir_all(&dir)?;
+
+    let (service, timer) = systemd_units(&executable, &path, cadence);
+    let service_path = dir.join(format!("{SYSTEMD_UNIT}.service"));
+    let timer_path = dir.join(format!("{SYSTEMD_UNIT}.timer"));
+    fs::write(&service_path, service)?;
+    fs::write(&timer_path, timer)?;
+
+    run(
+        setup,
+        Command::new("systemctl").args(["--user", "daemon-reload"]),
+    )?;
+    run(
+        setup,
+        Command::new("systemctl").args([
+            "--user",
+            "enable",
+            "--now",
+            &format!("{SYSTEMD_UNIT}.timer"),
+        ]),
+    )?;
+
+    Ok(vec![service_path, timer_path])
+}
+
+/// Disables and removes the scheduled job.
+pub fn remove(setup: &Setup) -> io::Result<()> {
+    if cfg!(target_os = "macos") {
+        let plist_path = launchd_plist_path(setup);
+        run(
+            setup,
+            Command::new("launchctl")
+                .args(["unload", "-w"])
+                .arg(&plist_path),
+        )?;
+        return fs::remove_file(plist_path);
+    }
+
+    run(
+        setup,
+        Command::new("systemctl").args([
+            "--user",
+            "disable",
+            "--now",
+            &format!("{SYSTEMD_UNIT}.timer"),
+        ]),
+    )?;
+
+    let dir = systemd_dir(setup);
+    fs::remove_file(dir.join(format!("{SYSTEMD_UNIT}.service")))?;
+    fs::remove_file(dir.join(format!("{SYSTEMD_UNIT}.timer")))?;
+    run(
+        setup,
+        Command::new("systemctl").args(["--user", "daemon-reload"]),
+    )
+}
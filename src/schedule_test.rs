@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::Path;
+
+use crate::runner_test::FakeRunner;
+use crate::schedule;
+use crate::schedule::Cadence;
+use crate::setup::Setup;
+
+#[test]
+fn parse_cadence() {
+    assert_eq!(Cadence::parse("weekly"), Some(Cadence::Weekly));
+    assert_eq!(Cadence::parse("monthly"), None);
+}
+
+#[test]
+fn systemd_units() {
+    let (service, timer) =
+        schedule::systemd_units(Path::new("/usr/bin/al"), "/usr/bin:/bin", Cadence::Daily);
+
+    assert_eq!(
+        service,
+        "[Unit]
+Description=Update Kakoune plugins with Almoxarife
+
+[Service]
+Type=oneshot
+Environment=PATH=/usr/bin:/bin
+ExecStart=/usr/bin/al --auto --quiet --notify
+"
+    );
+
+    assert_eq!(
+        timer,
+        "[Unit]
+Description=Update Kakoune plugins with Almoxarife daily
+
+[Timer]
+OnCalendar=daily
+Persistent=true
+RandomizedDelaySec=10min
+
+[Install]
+WantedBy=timers.target
+"
+    );
+}
+
+#[test]
+fn launchd_plist() {
+    let plist =
+        schedule::launchd_plist(Path::new("/usr/local/bin/al"), "/usr/bin", Cadence::Hourly);
+
+    assert!(plist.contains(
+        "        <string>/usr/local/bin/al</string>
+        <string>--auto</string>
+        <string>--quiet</string>
+        <string>--notify</string>"
+    ));
+    assert!(plist.contains("<key>StartInterval</key>\n    <integer>3600</integer>"));
+}
+
+#[test]
+#[cfg(not(target_os = "macos"))]
+fn install_and_remove_systemd_units() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let fake = FakeRunner::new();
+
+    let setup = Setup {
+        almoxarife_yaml_path: temp_dir.path().join("almoxarife.yaml"),
+        runner: fake.runner(),
+        ..Setup::default()
+    };
+
+    let dir = temp_dir.path().join("systemd/user");
+    let files = schedule::install(&setup, Cadence::Weekly).unwrap();
+    assert_eq!(
+        files,
+        [dir.join("almoxarife.service"), dir.join("almoxarife.timer")]
+    );
+    assert!(
+        fs::read_to_string(dir.join("almoxarife.timer"))
+            .unwrap()
+            .contains("OnCalendar=weekly")
+    );
+
+    schedule::remove(&setup).unwrap();
+    assert!(!dir.join("almoxarife.timer").exists());
+
+    let calls: Vec<String> = fake
+        .calls("systemctl")
+        .iter()
+        .map(|args| args.join(" "))
+        .collect();
+    assert_eq!(
+        calls,
+        [
+            "--user daemon-reload",
+            "--user enable --now almoxarife.timer",
+            "--user disable --now almoxarife.timer",
+            "--user daemon-reload",
+        ]
+    );
+}
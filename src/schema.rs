@@ -0,0 +1,342 @@
+/// The kind of value a plugin field holds.
+pub enum Kind {
+    String,
+    /// Kakoune commands, highlighted as such.
+    Kakscript,
+    Boolean,
+    Integer,
+    /// A list of strings.
+    StringList,
+    /// A map from strings to strings.
+    StringMap,
+}
+
+/// A field a plugin may set in `almoxarife.yaml`.
+pub struct Field {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub description: &'static str,
+}
+
+/// Every field known by the parser, the ones of `setup::PluginTree`. Any
+/// other key inside a plugin is taken as a child plugin.
+pub const PLUGIN_FIELDS: &[Field] = &[
+    Field {
+        name: "location",
+        kind: Kind::String,
+        description: "A repository URL or the full path of a local directory. file:// URLs and paths to bare repositories are cloned like any other repository.",
+    },
+    Field {
+        name: "file",
+        kind: Kind::String,
+        description: "The URL of a single .kak file, like a gist's raw URL, to download as the plugin instead of a repository. Replaces location.",
+    },
+    Field {
+        name: "sha256",
+        kind: Kind::String,
+        description: "The SHA-256 checksum the file downloaded from file has to match, in hexadecimal.",
+    },
+    Field {
+        name: "config",
+        kind: Kind::Kakscript,
+        description: "Kakscript code to configure the plugin.",
+    },
+    Field {
+        name: "disabled",
+        kind: Kind::Boolean,
+        description: "Whether this plugin (and its children) should be disabled.",
+    },
+    Field {
+        name: "lazy",
+        kind: Kind::Boolean,
+        description: "Whether the plugin should only be loaded on first use of one of its commands.",
+    },
+    Field {
+        name: "sessions",
+        kind: Kind::String,
+        description: "A regex the whole name of a Kakoune session has to match for the plugin to be loaded in it, like work-.*, to keep it out of the other sessions.",
+    },
+    Field {
+        name: "order",
+        kind: Kind::Integer,
+        description: "Moves the plugin's config later in the generated script, where it wins over the configs before it, the higher it is, or earlier, if negative. Parents still come before their children, and dependencies before the plugins needing them. 0 by default.",
+    },
+    Field {
+        name: "rev",
+        kind: Kind::String,
+        description: "A commit, tag or branch to pin the plugin to, instead of following the default branch.",
+    },
+    Field {
+        name: "tag_pattern",
+        kind: Kind::String,
+        description: "Follow the newest tag matching this glob, like v*, instead of the default branch. Ignored when rev is set.",
+    },
+    Field {
+        name: "remote",
+        kind: Kind::String,
+        description: "The name of the git remote the plugin is cloned from (origin by default).",
+    },
+    Field {
+        name: "remotes",
+        kind: Kind::StringMap,
+        description: "Further git remotes of the checkout, from their names to their URLs.",
+    },
+    Field {
+        name: "also_link_as",
+        kind: Kind::StringList,
+        description: "Further names to link the plugin as in the autoload directory, for plugins expecting a specific directory name.",
+    },
+    Field {
+        name: "after_update_kak",
+        kind: Kind::Kakscript,
+        description: "Kakoune commands to send to the running sessions after the plugin is updated, so that the update takes effect without a restart.",
+    },
+    Field {
+        name: "log_paths",
+        kind: Kind::StringList,
+        description: "The paths the changelog of an update is limited to, like [rc], to leave out changes to the CI or the docs.",
+    },
+    Field {
+        name: "features",
+        kind: Kind::StringList,
+        description: "Features of the plugin's almoxarife.toml to enable, like [fzf], each adding dependencies, build commands or configuration.",
+    },
+    Field {
+        name: "trusted",
+        kind: Kind::Boolean,
+        description: "Run the build commands of the plugin's almoxarife.toml unconfined, instead of inside bubblewrap's sandbox, which only lets them write to the checkout and the bin directory.",
+    },
+    Field {
+        name: "proxy",
+        kind: Kind::String,
+        description: "The HTTP or SOCKS proxy to fetch this plugin and its children through, like socks5://localhost:1080, or direct for none. Overrides the top level proxy.",
+    },
+    Field {
+        name: "upstream",
+        kind: Kind::String,
+        description: "The URL of the repository this plugin is a fork of. Forks of the same upstream, and the upstream itself, are cloned borrowing objects from each other's checkouts.",
+    },
+];
+
+/// Builds a JSON Schema describing the format of `almoxarife.yaml`, suitable
+/// for YAML language servers.
+pub fn json_schema() -> String {
+    let properties: Vec<_> = PLUGIN_FIELDS
+        .iter()
+        .map(|field| {
+            let kind = match field.kind {
+                Kind::String | Kind::Kakscript => r#""type": "string""#,
+                Kind::Boolean => r#""type": "boolean""#,
+                Kind::Integer => r#""type": "integer""#,
+                Kind::StringList => r#""type": "array", "items": { "type": "string" }"#,
+                Kind::StringMap => {
+                    r#""type": "object", "additionalProperties": { "type": "string" }"#
+                }
+            };
+
+            // Field names and descriptions are written by us, so they need no
+            // escaping.
+            format!(
+                r#"
+        "{name}": {{
+          {kind},
+          "description": "{description}"
+        }}"#,
+                name = field.name,
+                description = field.description,
+            )
+        })
+        .collect();
+
+    let properties = properties.join(",");
+
+    format!(
+        r##"{{
+  "$schema": "https://json-schema.org/draft/2020-12/schema",
+  "title": "almoxarife.yaml",
+  "description": "The configuration file of Almoxarife, a plugin manager for the Kakoune editor.",
+  "type": "object",
+  "minProperties": 1,
+  "properties": {{
+    "version": {{
+      "type": "integer",
+      "minimum": 1,
+      "description": "The version of this file's format (1 when missing). Run al migrate to convert a file from an older version."
+    }},
+    "auto_update_min_interval": {{
+      "type": "string",
+      "pattern": "^([0-9]+[smhdw])+$",
+      "description": "The minimum time between two automatic updates (like 30m, 12h or 2d). Updates started by --auto or --watch within this interval fetch nothing."
+    }},
+    "abandoned_after": {{
+      "type": "string",
+      "pattern": "^([0-9]+[smhdw])+$",
+      "description": "After updates, flag the plugins archived upstream (on GitHub and Codeberg) or whose upstream got no commits for longer than this (like 26w or 365d)."
+    }},
+    "prune_disabled_after": {{
+      "type": "string",
+      "pattern": "^([0-9]+[smhdw])+$",
+      "description": "On updates, delete the checkouts of plugins disabled for longer than this (like 30d). They're cloned again once enabled."
+    }},
+    "maintenance_interval": {{
+      "type": "string",
+      "pattern": "^([0-9]+[smhdw])+$",
+      "description": "After updates, compact the checkouts with git gc if they weren't in this long (like 1w). Run al maintain to compact them at any time."
+    }},
+    "clone_size_warning": {{
+      "type": "string",
+      "pattern": "^[0-9]+([KMG]B?)?$",
+      "description": "Ask before cloning (or warn, when not run from a terminal) a plugin whose repository is larger than this size (like 100M). Only GitHub and Codeberg report sizes."
+    }},
+    "url_policy": {{
+      "type": "object",
+      "additionalProperties": false,
+      "properties": {{
+        "protocols": {{
+          "type": "array",
+          "items": {{ "enum": ["https", "http", "ssh", "git", "file"] }},
+          "description": "The protocols plugins may be fetched with."
+        }},
+        "allow": {{
+          "type": "array",
+          "items": {{ "type": "string" }},
+          "description": "The hosts plugins may be fetched from, along with their subdomains."
+        }},
+        "deny": {{
+          "type": "array",
+          "items": {{ "type": "string" }},
+          "description": "The hosts plugins may never be fetched from."
+        }}
+      }},
+      "description": "Restricts where plugins may be fetched from. Violations are reported before anything is fetched."
+    }},
+    "max_connections_per_host": {{
+      "type": "integer",
+      "minimum": 1,
+      "description": "The most plugins fetched at once from the same host, for forges throttling many simultaneous connections. Unlimited by default."
+    }},
+    "max_parallel_builds": {{
+      "type": "integer",
+      "minimum": 1,
+      "description": "The most plugins whose manifests' build commands run at once, apart from fetches. One per CPU by default."
+    }},
+    "proxy": {{
+      "type": "string",
+      "pattern": "^(direct|(https?|socks4a?|socks5h?)://.+)$",
+      "description": "The HTTP or SOCKS proxy git fetches plugins through, like http://proxy.example.com:3128, or direct for none. Defaults to the one in https_proxy or all_proxy."
+    }},
+    "url_rewrites": {{
+      "type": "object",
+      "additionalProperties": {{ "type": "string", "minLength": 1 }},
+      "description": "URL prefixes replaced before fetching plugins, from the ones in the configuration to the ones to fetch from, like an internal mirror. The longest matching prefix wins."
+    }},
+    "defaults": {{
+      "type": "object",
+      "additionalProperties": false,
+      "properties": {{
+        "lazy": {{ "type": "boolean" }},
+        "tag_pattern": {{ "type": "string" }},
+        "remote": {{ "type": "string" }}
+      }},
+      "description": "Fields applied to every plugin not setting them itself. An empty tag_pattern in a plugin opts it out of the default one."
+    }},
+    "flag_breaking_changes": {{
+      "type": "boolean",
+      "description": "Flag the updates changing files like CHANGELOG.md or BREAKING, or with commit messages announcing a BREAKING CHANGE, to read about them before restarting Kakoune."
+    }},
+    "isolate_env": {{
+      "type": "boolean",
+      "description": "Run git, kak, build commands and hooks in a minimal environment, with no GIT_* variables nor shell startup files, and git ignoring the global and system configuration files."
+    }},
+    "pass_env": {{
+      "type": "array",
+      "items": {{ "type": "string" }},
+      "description": "The environment variables kept when isolate_env is set, besides HOME, PATH, USER, LOGNAME, LANG, LC_ALL, TMPDIR, XDG_RUNTIME_DIR and SSH_AUTH_SOCK."
+    }},
+    "review": {{
+      "type": "boolean",
+      "description": "Show the files, Kakoune scripts, shell blocks and build commands of every new plugin, and only install the ones accepted. New plugins are left out when not run from a terminal."
+    }},
+    "dotfiles_repo": {{
+      "type": "string",
+      "description": "The git repository keeping almoxarife.yaml, like ~/dotfiles. Successful updates and al freeze commit the file there when it changed, with a message listing the plugins installed and updated."
+    }},
+    "dotfiles_push": {{
+      "type": "boolean",
+      "description": "Push the commits to dotfiles_repo."
+    }},
+    "download_cache": {{
+      "type": "string",
+      "description": "A directory keeping the files of plugins with a sha256, like ~/Sync/almoxarife, so that machines sharing it, as over NFS or a synced folder, download each of them only once."
+    }},
+    "snippets": {{
+      "type": "object",
+      "additionalProperties": {{ "type": "string" }},
+      "description": "Kakoune code shared by several plugins' config, referenced as config: !snippet <name>, or on a line of its own inside a config block, as !snippet <name>."
+    }},
+    "hooks": {{
+      "type": "object",
+      "additionalProperties": false,
+      "properties": {{
+        "before_all": {{
+          "type": "string",
+          "description": "Run before anything is fetched. If it fails, the update is called off."
+        }},
+        "after_all": {{
+          "type": "string",
+          "description": "Run once the update is done, with the names of the plugins installed, updated and failed in ALMOXARIFE_INSTALLED, ALMOXARIFE_UPDATED and ALMOXARIFE_FAILED."
+        }},
+        "after_plugin_update": {{
+          "type": "string",
+          "description": "Run for each plugin installed or updated, with ALMOXARIFE_PLUGIN, ALMOXARIFE_PLUGIN_DIR, ALMOXARIFE_STATUS, ALMOXARIFE_OLD_REVISION and ALMOXARIFE_NEW_REVISION set."
+        }}
+      }},
+      "description": "Shell commands run around updates."
+    }},
+    "integration": {{
+      "enum": ["autoload", "source"],
+      "description": "How plugins are loaded: linked into Kakoune's autoload directory (the default), or sourced by plugins.kak, which your kakrc sources."
+    }}
+  }},
+  "additionalProperties": {{ "$ref": "#/$defs/plugin" }},
+  "$defs": {{
+    "plugin": {{
+      "type": "object",
+      "description": "A plugin. Keys other than the known fields are children plugins, loaded only after this one.",
+      "oneOf": [{{ "required": ["location"] }}, {{ "required": ["file"] }}],
+      "properties": {{{properties}
+      }},
+      "additionalProperties": {{ "$ref": "#/$defs/plugin" }}
+    }}
+  }}
+}}"##
+    )
+}
+
+/// Builds the Kakoune highlighters of `almoxarife.yaml`, where plugin fields
+/// stand out and the ones holding Kakoune commands are highlighted as such,
+/// and of the `config` of `almoxarife.toml` manifests, on top of Kakoune's
+/// own TOML highlighting.
+pub fn highlighters() -> String {
+    let names = |kakscript: bool| {
+        PLUGIN_FIELDS
+            .iter()
+            .filter(|field| matches!(field.kind, Kind::Kakscript) == kakscript)
+            .map(|field| field.name)
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+
+    let (kakscript, fields) = (names(true), names(false));
+
+    format!(
+        r#"add-highlighter shared/almoxarife regions
+add-highlighter shared/almoxarife/ region '^\s*({kakscript}):\s+\|' '^\s*[\w.-]+:' ref kakrc
+add-highlighter shared/almoxarife/ region '^\s*({kakscript}):[^\n]' '\n' ref kakrc
+add-highlighter shared/almoxarife/fields default-region regex '^\s*({fields}):' 1:keyword
+add-highlighter shared/almoxarife-manifest regions
+add-highlighter shared/almoxarife-manifest/ region '^\s*config\s*=\s*"""' '"""' ref kakrc
+add-highlighter shared/almoxarife-manifest/ region "^\s*config\s*=\s*'''" "'''" ref kakrc
+"#
+    )
+}
@@ -0,0 +1,95 @@
+use std::collections::HashSet;
+
+use serde_yaml::Value;
+
+use crate::schema;
+use crate::schema::PLUGIN_FIELDS;
+use crate::setup::PluginTree;
+use crate::setup::SETTINGS;
+
+#[test]
+fn json_schema_is_valid_json() {
+    // JSON is a subset of YAML.
+    let schema: Value = serde_yaml::from_str(&schema::json_schema()).unwrap();
+
+    assert_eq!(
+        schema["additionalProperties"]["$ref"],
+        Value::from("#/$defs/plugin")
+    );
+
+    assert_eq!(
+        schema["properties"]["auto_update_min_interval"]["type"],
+        "string"
+    );
+
+    let plugin = &schema["$defs"]["plugin"];
+    assert_eq!(plugin["additionalProperties"]["$ref"], "#/$defs/plugin");
+    assert_eq!(plugin["oneOf"][0]["required"][0], "location");
+    assert_eq!(plugin["oneOf"][1]["required"][0], "file");
+}
+
+#[test]
+fn json_schema_describes_all_settings() {
+    let schema: Value = serde_yaml::from_str(&schema::json_schema()).unwrap();
+    let properties: HashSet<&str> = schema["properties"]
+        .as_mapping()
+        .unwrap()
+        .keys()
+        .filter_map(Value::as_str)
+        .collect();
+
+    assert_eq!(properties, SETTINGS.iter().copied().collect());
+}
+
+#[test]
+fn plugin_fields_are_the_parsed_ones() {
+    let tree = serde_yaml::to_value(PluginTree::default()).unwrap();
+    let parsed: HashSet<&str> = tree
+        .as_mapping()
+        .unwrap()
+        .keys()
+        .filter_map(Value::as_str)
+        .collect();
+
+    assert_eq!(
+        parsed,
+        PLUGIN_FIELDS.iter().map(|field| field.name).collect()
+    );
+}
+
+#[test]
+fn json_schema_describes_all_fields() {
+    let schema: Value = serde_yaml::from_str(&schema::json_schema()).unwrap();
+    let properties = &schema["$defs"]["plugin"]["properties"];
+
+    for field in PLUGIN_FIELDS {
+        assert!(
+            properties[field.name]["description"].is_string(),
+            "missing field {}",
+            field.name
+        );
+    }
+
+    assert_eq!(properties["disabled"]["type"], "boolean");
+    assert_eq!(properties["config"]["type"], "string");
+    assert_eq!(
+        properties["remotes"]["additionalProperties"]["type"],
+        "string"
+    );
+    assert_eq!(properties["also_link_as"]["items"]["type"], "string");
+}
+
+#[test]
+fn highlighters_cover_all_fields() {
+    let highlighters = schema::highlighters();
+
+    for field in PLUGIN_FIELDS {
+        assert!(
+            highlighters.contains(&format!("|{}|", field.name))
+                || highlighters.contains(&format!("({}|", field.name))
+                || highlighters.contains(&format!("|{})", field.name)),
+            "missing field {}",
+            field.name
+        );
+    }
+}
@@ -0,0 +1,257 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::os::unix::fs::DirBuilderExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::process::Command;
+
+use serde::Deserialize;
+
+use crate::download::Download;
+use crate::setup::Setup;
+
+const LATEST_RELEASE: &str = "https://api.github.com/repos/gustavo-hms/almoxarife/releases/latest";
+
+#[derive(Debug, Deserialize)]
+pub struct Release {
+    pub tag_name: String,
+    #[serde(default)]
+    pub assets: Vec<Asset>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Asset {
+    pub name: String,
+    pub browser_download_url: String,
+}
+
+impl Release {
+    fn asset(&self, name: &str) -> Option<&Asset> {
+        self.assets.iter().find(|asset| asset.name == name)
+    }
+}
+
+/// What `al self-update` did.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    UpToDate,
+    /// Updated to the given version.
+    Updated(String),
+}
+
+/// The target triple releases are built for, if there's a release for this
+/// platform at all.
+pub fn target() -> Option<&'static str> {
+    match (env::consts::ARCH, env::consts::OS) {
+        ("x86_64", "linux") => Some("x86_64-unknown-linux-gnu"),
+        ("aarch64", "linux") => Some("aarch64-unknown-linux-gnu"),
+        ("x86_64", "macos") => Some("x86_64-apple-darwin"),
+        ("aarch64", "macos") => Some("aarch64-apple-darwin"),
+        _ => None,
+    }
+}
+
+/// The name of the release archive for `target`, as published by `dist`.
+pub fn archive_name(target: &str) -> String {
+    format!("almoxarife-{target}.tar.xz")
+}
+
+/// Whether the release tagged `tag` is newer than the `current` version, or
+/// `None` when either one isn't made of numbers only, like pre-releases.
+pub fn is_newer(tag: &str, current: &str) -> Option<bool> {
+    let parse = |version: &str| -> Option<Vec<u64>> {
+        version
+            .trim_start_matches('v')
+            .split('.')
+            .map(|part| part.parse().ok())
+            .collect()
+    };
+
+    Some(parse(tag)? > parse(current)?)
+}
+
+/// The checksum in a `.sha256` file, which may be followed by the file name.
+pub fn parse_checksum(text: &str) -> Option<&str> {
+    text.split_whitespace()
+        .next()
+        .filter(|checksum| checksum.len() == 64)
+}
+
+/// Replaces `executable` by `new` atomically, by renaming a copy of `new` put
+/// next to `executable`, so that a failure halfway leaves the old one alone.
+pub fn replace(executable: &Path, new: &Path) -> io::Result<()> {
+    let dir = executable
+        .parent()
+        .ok_or_else(|| io::Error::other("the executable has no parent directory"))?;
+
+    let staged = dir.join(format!(".al-{}.new", process::id()));
+    fs::copy(new, &staged)?;
+    fs::set_permissions(&staged, fs::Permissions::from_mode(0o755))?;
+
+    fs::rename(&staged, executable).inspect_err(|_| {
+        let _ = fs::remove_file(&staged);
+    })
+}
+
+/// Replaces the running executable by the one in the latest release, if it's
+/// newer than `current`, after checking the release archive against its
+/// published checksum. Releases aren't signed: the checksum, published next
+/// to the archive, only tells it wasn't corrupted on the way, not who built
+/// it.
+pub fn self_update(setup: &Setup, current: &str) -> io::Result<Outcome> {
+    let json = run(
+        setup,
+        "curl",
+        &["--silent", "--fail", "--location", LATEST_RELEASE],
+    )?;
+    let release: Release = serde_yaml::from_str(&json).map_err(io::Error::other)?;
+
+    match is_newer(&release.tag_name, current) {
+        Some(true) => {}
+        Some(false) => return Ok(Outcome::UpToDate),
+        None => {
+            return Err(io::Error::other(format!(
+                "can't tell whether the release {} is newer than {current}",
+                release.tag_name
+            )));
+        }
+    }
+
+    let target =
+        target().ok_or_else(|| io::Error::other("there are no releases for this platform"))?;
+    let name = archive_name(target);
+    let missing = |name: &str| io::Error::other(format!("the release has no {name}"));
+
+    let archive = release.asset(&name).ok_or_else(|| missing(&name))?;
+    let checksum_name = format!("{name}.sha256");
+    let checksum = release
+        .asset(&checksum_name)
+        .ok_or_else(|| missing(&checksum_name))?;
+
+    let dir = staging_dir(setup)?;
+
+    let result = install(
+        setup,
+        &dir,
+        &archive.browser_download_url,
+        &checksum.browser_download_url,
+        &env::current_exe()?,
+    );
+    let _ = fs::remove_dir_all(&dir);
+    result?;
+
+    Ok(Outcome::Updated(release.tag_name))
+}
+
+/// Creates the directory the release is downloaded and extracted into, in
+/// `XDG_RUNTIME_DIR` or else the temporary directory. Only the user can enter
+/// it, and it has to be new, so that nobody else can swap the executable
+/// before it's installed.
+pub fn staging_dir(setup: &Setup) -> io::Result<PathBuf> {
+    let dir = setup
+        .runner
+        .var("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(env::temp_dir)
+        .join(format!("almoxarife-self-update-{}", process::id()));
+
+    fs::DirBuilder::new()
+        .mode(0o700)
+        .create(&dir)
+        .map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("couldn't create {}: {e}", dir.to_string_lossy()),
+            )
+        })?;
+
+    Ok(dir)
+}
+
+/// Downloads the release archive at `archive_url` into `dir` and replaces
+/// `executable` by the `al` inside it. Nothing is extracted, let alone
+/// replaced, unless the archive matches the checksum at `checksum_url`.
+pub fn install(
+    setup: &Setup,
+    dir: &Path,
+    archive_url: &str,
+    checksum_url: &str,
+    executable: &Path,
+) -> io::Result<()> {
+    // Both into `dir`, which is new, so neither is ever found unchanged.
+    let download = |url, sha256, name: &str| {
+        let destination = dir.join(name);
+        let download = Download {
+            url,
+            runner: &setup.runner,
+            deadline: None,
+            proxy: None,
+            sha256,
+            cache: None,
+        };
+
+        download
+            .to(&destination, &dir.join(format!("{name}.etag")))
+            .map_err(io::Error::other)?;
+
+        Ok::<_, io::Error>(destination)
+    };
+
+    let published = fs::read_to_string(download(checksum_url, None, "release.tar.xz.sha256")?)?;
+    let published =
+        parse_checksum(&published).ok_or_else(|| io::Error::other("invalid checksum file"))?;
+
+    let archive = download(archive_url, Some(published), "release.tar.xz")?;
+
+    run(
+        setup,
+        "tar",
+        &[
+            "-xf",
+            &archive.to_string_lossy(),
+            "-C",
+            &dir.to_string_lossy(),
+        ],
+    )?;
+
+    let new = find_executable(dir)?
+        .ok_or_else(|| io::Error::other("the release archive has no al executable"))?;
+
+    replace(executable, &new)
+}
+
+/// Finds the `al` executable among the extracted files.
+fn find_executable(dir: &Path) -> io::Result<Option<PathBuf>> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            if let Some(found) = find_executable(&path)? {
+                return Ok(Some(found));
+            }
+        } else if path.file_name().is_some_and(|name| name == "al") {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+fn run(setup: &Setup, program: &str, args: &[&str]) -> io::Result<String> {
+    let output = setup
+        .runner
+        .output(Command::new(program).args(args), None)?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{program} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::os::unix::fs::PermissionsExt;
+
+use tempfile::TempDir;
+
+use crate::runner_test::FakeRunner;
+use crate::self_update::archive_name;
+use crate::self_update::is_newer;
+use crate::self_update::parse_checksum;
+use crate::self_update::replace;
+use crate::self_update::staging_dir;
+use crate::setup::Setup;
+
+#[test]
+fn compare_versions() {
+    assert_eq!(is_newer("v1.1.0", "1.0.0"), Some(true));
+    assert_eq!(is_newer("2.0.0", "1.9.9"), Some(true));
+    assert_eq!(is_newer("v1.0.10", "1.0.9"), Some(true));
+    assert_eq!(is_newer("v1.0.0", "1.0.0"), Some(false));
+    assert_eq!(is_newer("v0.9.0", "1.0.0"), Some(false));
+    assert_eq!(is_newer("v1.1.0-rc.1", "1.0.0"), None);
+    assert_eq!(is_newer("nightly", "1.0.0"), None);
+    assert_eq!(is_newer("v1.1.0", "1.0.0-dev"), None);
+}
+
+#[test]
+fn private_staging_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let fake = FakeRunner::with_env(HashMap::from([(
+        "XDG_RUNTIME_DIR",
+        temp_dir.path().to_string_lossy().into(),
+    )]));
+    let setup = Setup {
+        runner: fake.runner(),
+        ..Setup::default()
+    };
+
+    let dir = staging_dir(&setup).unwrap();
+    assert!(dir.starts_with(temp_dir.path()));
+    assert_eq!(
+        fs::metadata(&dir).unwrap().permissions().mode() & 0o777,
+        0o700
+    );
+
+    // A directory already there, maybe someone else's, isn't reused.
+    let error = staging_dir(&setup).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::AlreadyExists);
+}
+
+#[test]
+fn archive_names() {
+    assert_eq!(
+        archive_name("x86_64-unknown-linux-gnu"),
+        "almoxarife-x86_64-unknown-linux-gnu.tar.xz"
+    );
+}
+
+#[test]
+fn parse_checksums() {
+    let checksum = "a".repeat(64);
+
+    assert_eq!(
+        parse_checksum(&format!("{checksum}  almoxarife.tar.xz\n")),
+        Some(checksum.as_str())
+    );
+    assert_eq!(parse_checksum(&checksum), Some(checksum.as_str()));
+    assert_eq!(parse_checksum("404: Not Found"), None);
+    assert_eq!(parse_checksum(""), None);
+}
+
+#[test]
+fn replace_executable() {
+    let dir = TempDir::new().unwrap();
+    let executable = dir.path().join("al");
+    let new = dir.path().join("new");
+    fs::write(&executable, "old").unwrap();
+    fs::write(&new, "new").unwrap();
+
+    replace(&executable, &new).unwrap();
+
+    assert_eq!(fs::read_to_string(&executable).unwrap(), "new");
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+// Downloaded through the fake curl.
+#[test]
+#[cfg(not(feature = "http"))]
+fn install_checksum_mismatch() {
+    use crate::self_update::install;
+
+    let dir = TempDir::new().unwrap();
+    let executable = dir.path().join("al");
+    fs::write(&executable, "old").unwrap();
+    let download_dir = dir.path().join("download");
+    fs::create_dir(&download_dir).unwrap();
+
+    let fake = FakeRunner::new();
+    fake.set("ALMOXARIFE_TEST_DOWNLOAD", "tampered archive".into());
+    fake.set("ALMOXARIFE_TEST_CHECKSUM", "a".repeat(64));
+
+    let setup = Setup {
+        runner: fake.runner(),
+        ..Setup::default()
+    };
+
+    let error = install(
+        &setup,
+        &download_dir,
+        "https://github.com/gustavo-hms/almoxarife/releases/download/v2.0.0/almoxarife.tar.xz",
+        "https://github.com/gustavo-hms/almoxarife/releases/download/v2.0.0/almoxarife.tar.xz.sha256",
+        &executable,
+    )
+    .unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("doesn't match its sha256 checksum")
+    );
+    assert_eq!(fs::read_to_string(&executable).unwrap(), "old");
+    assert!(!download_dir.join("release.tar.xz").exists());
+}
@@ -0,0 +1,54 @@
+use std::io;
+use std::process::Command;
+
+use crate::setup::Setup;
+
+/// The names of the running Kakoune sessions, as told by `kak -l`.
+pub fn sessions(setup: &Setup) -> io::Result<Vec<String>> {
+    let output = setup.runner.output(Command::new("kak").arg("-l"), None)?;
+
+    if !output.status.success() {
+        return Err(io::Error::other("kak -l failed"));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        // Sessions whose server died are listed as `name (dead)`.
+        .filter(|line| !line.is_empty() && !line.ends_with("(dead)"))
+        .map(String::from)
+        .collect())
+}
+
+/// Sends `commands` to the Kakoune session `session`, via `kak -p`.
+pub fn send(setup: &Setup, session: &str, commands: &str) -> io::Result<()> {
+    let output = setup.runner.output_with_input(
+        Command::new("kak").args(["-p", session]),
+        commands.as_bytes(),
+        None,
+    )?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// The command setting `almoxarife_status` to `status`.
+pub fn status_script(status: &str) -> String {
+    format!(
+        "set-option global almoxarife_status '{}'\n",
+        status.replace('\'', "''")
+    )
+}
+
+/// The commands to send after an update, each plugin's wrapped in a `try`, so
+/// that a failing one doesn't keep the others from running.
+pub fn after_update_script<'a>(commands: impl IntoIterator<Item = &'a str>) -> String {
+    commands
+        .into_iter()
+        .map(|commands| format!("try %🧺\n{}\n🧺\n", commands.trim_end()))
+        .collect()
+}
@@ -0,0 +1,42 @@
+use std::collections::HashMap;
+use std::fs;
+
+use tempfile::TempDir;
+
+use crate::runner_test::FakeRunner;
+use crate::session;
+use crate::setup::Setup;
+
+#[test]
+fn send_to_sessions() {
+    let temp_dir = TempDir::new().unwrap();
+    let fake = FakeRunner::with_env(HashMap::from([(
+        "ALMOXARIFE_TEST_SESSIONS_DIR",
+        temp_dir.path().to_string_lossy().into(),
+    )]));
+    fake.reply("kak", &["-l"], 0, "work\n1234\nold (dead)\n", "");
+
+    let setup = Setup {
+        runner: fake.runner(),
+        ..Setup::default()
+    };
+
+    assert_eq!(session::sessions(&setup).unwrap(), vec!["work", "1234"]);
+
+    let script = session::after_update_script(["lsp-restart\n", "colorscheme gruvbox"]);
+    assert_eq!(
+        script,
+        "try %🧺\nlsp-restart\n🧺\ntry %🧺\ncolorscheme gruvbox\n🧺\n"
+    );
+
+    assert_eq!(
+        session::status_script("it's 1 failed"),
+        "set-option global almoxarife_status 'it''s 1 failed'\n"
+    );
+
+    session::send(&setup, "work", &script).unwrap();
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("work")).unwrap(),
+        script
+    );
+}
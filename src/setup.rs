@@ -1,6 +1,7 @@
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
 use std::collections::HashMap;
 use std::collections::HashSet;
-use std::env;
 use std::error;
 use std::ffi::OsStr;
 use std::fmt::Display;
@@ -14,20 +15,60 @@ use std::io;
 use std::io::Read;
 use std::io::Write;
 use std::iter;
+use std::mem;
 use std::os::unix;
 use std::os::unix::ffi::OsStrExt;
 use std::path::Path;
 use std::path::PathBuf;
 use std::process::Command;
-use std::process::Stdio;
 use std::result;
-use std::thread;
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::sync::PoisonError;
 use std::time::Duration;
+use std::time::Instant;
 
 use colorized::Color;
 use colorized::Colors;
 use serde::Deserialize;
 
+use crate::dev::Overrides;
+use crate::edit;
+use crate::forge;
+use crate::gitconfig;
+use crate::gitconfig::GitConfig;
+use crate::hooks::Hooks;
+use crate::kakscript;
+use crate::manifest::Manifest;
+use crate::migrate;
+use crate::policy::UrlPolicy;
+use crate::runner::Isolated;
+use crate::runner::Runner;
+use crate::schema;
+use crate::source;
+use crate::source::Source;
+use crate::state::Build;
+use crate::time;
+
+/// How many previous generations of `almoxarife.kak` (or `plugins.kak`) are
+/// kept for `al restore-script`.
+const BACKUPS: usize = 3;
+
+/// The directory inside the data directory where build hooks install the
+/// executables they build.
+const BIN: &str = "bin";
+
+/// How long Kakoune gets to tell where its runtime directory is.
+const KAKOUNE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The directory inside the data directory keeping each workspace's
+/// checkouts and `plugins.kak`.
+const WORKSPACES: &str = "workspaces";
+
+/// The git config key recording the branch a pinned checkout left, to be
+/// checked out again once it's unpinned.
+const BRANCH_KEY: &str = "almoxarife.branch";
+
 pub struct Setup {
     /// The path to `almoxarife.yaml`.
     pub almoxarife_yaml_path: PathBuf,
@@ -40,9 +81,39 @@ pub struct Setup {
     pub almoxarife_kak: PathBuf,
     /// The Kakoune's autoload directory.
     pub autoload_dir: PathBuf,
-    /// Custom environment variables the setup process will consider.
-    #[cfg(test)]
-    pub env: HashMap<&'static str, String>,
+    /// The path to the user's `kakrc`.
+    pub kakrc: PathBuf,
+    /// The file sourcing every plugin, when the integration is `source`.
+    pub plugins_kak: PathBuf,
+    /// The file where Almoxarife remembers things between runs.
+    pub state_path: PathBuf,
+    /// The file recording the plugins pointed to a local checkout by `al dev`.
+    pub dev_overrides_path: PathBuf,
+    /// The directory taking every write when Kakoune's configuration directory
+    /// is read-only, if any.
+    pub writable_root: Option<PathBuf>,
+    /// The workspace managed instead of the usual plugins, if any.
+    pub workspace: Option<String>,
+    /// Whether files in the way of the plugins' links, not put there by
+    /// Almoxarife, are replaced instead of reported.
+    pub force_adopt: bool,
+    /// When plugins not done yet are given up on, with `--max-time`.
+    pub deadline: Option<Instant>,
+    /// The last successful build of each plugin, so that unchanged plugins
+    /// aren't built again.
+    pub builds: BTreeMap<String, Build>,
+    /// Whether plugins are built again even if unchanged, with `--rebuild`.
+    pub rebuild: bool,
+    /// Whether the plugins the last update got done with, before it was
+    /// interrupted, are left alone, with `--resume`.
+    pub resume: bool,
+    /// Whether unchanged plugins tell how old their newest upstream commit
+    /// is, with `--verbose`.
+    pub commit_age: bool,
+    /// What the user's own git configuration says about fetching.
+    pub git_config: GitConfig,
+    /// What every external program goes through.
+    pub runner: Runner,
 }
 
 impl Default for Setup {
@@ -53,27 +124,39 @@ impl Default for Setup {
             autoload_plugins_dir: "~/.config/kak/autoload/almoxarife".into(),
             almoxarife_kak: "~/.config/kak/autoload/almoxarife/almoxarife.kak".into(),
             autoload_dir: "~/.config/kak/autoload".into(),
-            #[cfg(test)]
-            env: HashMap::default(),
+            kakrc: "~/.config/kak/kakrc".into(),
+            plugins_kak: "~/.config/kak/plugins.kak".into(),
+            state_path: "~/.local/state/almoxarife/state.yaml".into(),
+            dev_overrides_path: "~/.local/state/almoxarife/dev.yaml".into(),
+            writable_root: None,
+            workspace: None,
+            force_adopt: false,
+            deadline: None,
+            builds: BTreeMap::new(),
+            rebuild: false,
+            resume: false,
+            commit_age: false,
+            git_config: GitConfig::default(),
+            runner: Runner::default(),
         }
     }
 }
 
-fn get_var(environment: &HashMap<&str, String>, var: &str) -> Option<String> {
-    environment.get(var).cloned().or_else(|| env::var(var).ok())
-}
-
 impl Setup {
     pub fn new() -> Setup {
-        Setup::with_env(HashMap::new())
+        Setup::with_runner(Runner::default())
     }
 
-    pub fn with_env(env: HashMap<&'static str, String>) -> Setup {
-        let home = get_var(&env, "HOME").expect("could not read HOME environment variable");
+    /// A setup whose paths follow the environment `runner` tells about, and
+    /// which runs every program through it.
+    pub fn with_runner(runner: Runner) -> Setup {
+        let home = runner
+            .var("HOME")
+            .expect("could not read HOME environment variable");
 
         let home = Path::new(&home);
 
-        let config_dir = if let Some(config) = get_var(&env, "XDG_CONFIG_HOME") {
+        let config_dir = if let Some(config) = runner.var("XDG_CONFIG_HOME") {
             PathBuf::from(&config)
         } else {
             home.join(".config")
@@ -81,42 +164,284 @@ impl Setup {
 
         let almoxarife_yaml_path = config_dir.join("almoxarife.yaml");
 
-        let almoxarife_data_dir = if let Some(data) = get_var(&env, "XDG_DATA_HOME") {
+        let almoxarife_data_dir = if let Some(data) = runner.var("XDG_DATA_HOME") {
             PathBuf::from(&data).join("almoxarife")
         } else {
             home.join(".local/share/almoxarife")
         };
 
-        let autoload_dir = config_dir.join("kak/autoload");
-        let mut autoload_plugins_dir = autoload_dir.clone();
-        autoload_plugins_dir.push("almoxarife");
-        let almoxarife_kak = autoload_plugins_dir.join("almoxarife.kak");
+        let state_dir = if let Some(state) = runner.var("XDG_STATE_HOME") {
+            PathBuf::from(&state).join("almoxarife")
+        } else {
+            home.join(".local/state/almoxarife")
+        };
 
-        Setup {
+        let state_path = state_dir.join("state.yaml");
+        let dev_overrides_path = state_dir.join("dev.yaml");
+
+        // Kakoune itself honours KAKOUNE_CONFIG_DIR.
+        let kak_config_dir = if let Some(kak_config) = runner.var("KAKOUNE_CONFIG_DIR") {
+            PathBuf::from(&kak_config)
+        } else {
+            config_dir.join("kak")
+        };
+
+        let mut setup = Setup {
             almoxarife_yaml_path,
-            almoxarife_kak,
-            autoload_dir,
-            autoload_plugins_dir,
+            almoxarife_kak: PathBuf::new(),
+            autoload_dir: PathBuf::new(),
+            autoload_plugins_dir: PathBuf::new(),
             almoxarife_data_dir,
-            #[cfg(test)]
-            env,
+            kakrc: PathBuf::new(),
+            plugins_kak: PathBuf::new(),
+            state_path,
+            dev_overrides_path,
+            writable_root: None,
+            workspace: None,
+            force_adopt: false,
+            deadline: None,
+            builds: BTreeMap::new(),
+            rebuild: false,
+            resume: false,
+            commit_age: false,
+            git_config: GitConfig::default(),
+            runner,
+        };
+
+        setup.set_kak_config_dir(&kak_config_dir);
+        setup
+    }
+
+    /// A setup for `al generate`, confined to `out`, which takes the place of
+    /// the `almoxarife` directory inside `autoload`. Plugins are taken from
+    /// `sources`, where each one is in a directory named after it, and nothing
+    /// else is looked at, not even `HOME`.
+    pub fn for_generation(config: &Path, sources: &Path, out: &Path) -> Setup {
+        Setup {
+            almoxarife_yaml_path: config.into(),
+            almoxarife_data_dir: sources.into(),
+            autoload_plugins_dir: out.into(),
+            almoxarife_kak: out.join("almoxarife.kak"),
+            autoload_dir: out.into(),
+            kakrc: out.join("kakrc"),
+            plugins_kak: out.join("plugins.kak"),
+            // There's no state to keep nor overrides to read, and an empty
+            // path is never found.
+            state_path: PathBuf::new(),
+            dev_overrides_path: PathBuf::new(),
+            writable_root: None,
+            workspace: None,
+            force_adopt: false,
+            deadline: None,
+            builds: BTreeMap::new(),
+            rebuild: false,
+            resume: false,
+            commit_age: false,
+            git_config: GitConfig::default(),
+            runner: Runner::default(),
+        }
+    }
+
+    /// Makes the whole setup target a separate Kakoune configuration tree:
+    /// the configuration file and the autoload directory are the ones inside
+    /// `target`, and checkouts and state are kept in directories suffixed by
+    /// the target's name, apart from the usual ones.
+    pub fn set_target(&mut self, target: &Path) {
+        let suffix = target.file_name().unwrap_or_default().to_string_lossy();
+
+        self.set_kak_config_dir(target);
+        self.almoxarife_yaml_path = target.join("almoxarife.yaml");
+        self.almoxarife_data_dir
+            .set_file_name(format!("almoxarife-{suffix}"));
+
+        if let Some(state_dir) = self.state_path.parent() {
+            let state_dir = state_dir.with_file_name(format!("almoxarife-{suffix}"));
+            self.state_path = state_dir.join("state.yaml");
+            self.dev_overrides_path = state_dir.join("dev.yaml");
+        }
+    }
+
+    /// Directs every write to `root`, leaving Kakoune's configuration
+    /// directory alone: checkouts, state and `plugins.kak` are all kept there,
+    /// and plugins are sourced rather than linked into `autoload`.
+    pub fn set_writable_root(&mut self, root: &Path) {
+        self.almoxarife_data_dir = root.join("plugins");
+        self.autoload_plugins_dir = root.join("almoxarife");
+        self.almoxarife_kak = self.autoload_plugins_dir.join("almoxarife.kak");
+        self.plugins_kak = root.join("plugins.kak");
+        self.state_path = root.join("state.yaml");
+        self.dev_overrides_path = root.join("dev.yaml");
+        self.writable_root = Some(root.into());
+    }
+
+    /// Makes the whole setup manage the workspace `name`, a set of plugins
+    /// loaded on demand: the configuration file is `almoxarife-{name}.yaml`,
+    /// next to `almoxarife.yaml`, and checkouts, `plugins.kak` and state are
+    /// kept in directories of their own, out of `autoload`'s sight.
+    pub fn set_workspace(&mut self, name: &str) {
+        let dir = self.workspaces_dir().join(name);
+
+        self.almoxarife_yaml_path
+            .set_file_name(format!("almoxarife-{name}.yaml"));
+        self.almoxarife_data_dir = dir.join("plugins");
+        self.autoload_plugins_dir = dir.join("almoxarife");
+        self.almoxarife_kak = self.autoload_plugins_dir.join("almoxarife.kak");
+        self.plugins_kak = dir.join("plugins.kak");
+
+        if let Some(state_dir) = self.state_path.parent() {
+            let state_dir = state_dir.join(WORKSPACES).join(name);
+            self.state_path = state_dir.join("state.yaml");
+            self.dev_overrides_path = state_dir.join("dev.yaml");
+        }
+
+        self.workspace = Some(name.into());
+    }
+
+    /// Where the workspaces are kept, each in a directory named after it.
+    pub fn workspaces_dir(&self) -> PathBuf {
+        self.almoxarife_data_dir.join(WORKSPACES)
+    }
+
+    /// How plugins are handed to Kakoune: as configured, unless writes go to
+    /// a writable root, which `autoload` can't see, or plugins belong to a
+    /// workspace, which `autoload` mustn't load.
+    pub fn integration(&self, configured: Integration) -> Integration {
+        if self.writable_root.is_some() || self.workspace.is_some() {
+            Integration::Source
+        } else {
+            configured
+        }
+    }
+
+    /// The line the kakrc needs to load the plugins, when it doesn't have it
+    /// yet and plugins are sourced from a writable root.
+    pub fn missing_source_line(&self) -> Option<String> {
+        self.writable_root.as_ref()?;
+
+        let plugins_kak = self.plugins_kak.to_string_lossy();
+        let kakrc = fs::read_to_string(&self.kakrc).unwrap_or_default();
+
+        if kakrc.contains(&*plugins_kak) {
+            return None;
         }
+
+        // Single quotes are escaped by doubling them in Kakoune strings.
+        Some(format!("source '{}'", plugins_kak.replace('\'', "''")))
+    }
+
+    /// Points every path inside Kakoune's configuration directory to
+    /// `kak_config_dir`.
+    pub fn set_kak_config_dir(&mut self, kak_config_dir: &Path) {
+        self.kakrc = kak_config_dir.join("kakrc");
+        self.plugins_kak = kak_config_dir.join("plugins.kak");
+        self.autoload_dir = kak_config_dir.join("autoload");
+        self.autoload_plugins_dir = self.autoload_dir.join("almoxarife");
+        self.almoxarife_kak = self.autoload_plugins_dir.join("almoxarife.kak");
     }
 
     pub fn create_dirs(&self) -> Result<(), SetupError> {
+        let read_only_hint = |error| self.read_only_hint(error);
+
         if self.autoload_dir.metadata().is_err() {
-            fs::create_dir_all(&self.autoload_dir)?;
+            fs::create_dir_all(&self.autoload_dir).map_err(read_only_hint)?;
 
             self.link_runtime_dir()
                 .context("unable to detect Kakoune's runtime directory")?;
         }
 
-        if self.autoload_plugins_dir.metadata().is_ok() {
+        // What's already inside is kept, so that Kakoune finds the plugins
+        // even if the update is interrupted. Links are then fixed one by one,
+        // and the stale ones removed at the end.
+        fs::create_dir_all(&self.autoload_plugins_dir).map_err(read_only_hint)?;
+        self.create_data_dir()
+    }
+
+    /// Removes everything inside the `almoxarife` directory of `autoload`
+    /// but `almoxarife.kak` and the links in `keep`, like the links of
+    /// removed or disabled plugins.
+    pub fn remove_stale_links(&self, keep: &HashSet<&Path>) -> Result<(), SetupError> {
+        let entries = fs::read_dir(&self.autoload_plugins_dir).context(&format!(
+            "couldn't read {}",
+            self.autoload_plugins_dir.to_string_lossy()
+        ))?;
+
+        for entry in entries {
+            let path = entry?.path();
+
+            if path == self.almoxarife_kak || keep.contains(path.as_path()) {
+                continue;
+            }
+
+            if path.is_dir() && !path.is_symlink() {
+                fs::remove_dir_all(&path)?;
+            } else {
+                fs::remove_file(&path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Points to `--writable-root` when `error` comes from writing to a
+    /// read-only `autoload`.
+    fn read_only_hint(&self, error: io::Error) -> SetupError {
+        match error.kind() {
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
+                SetupError(format!(
+                    "{error}: {}; if it's read-only, use --writable-root <dir> to write elsewhere",
+                    self.autoload_dir.to_string_lossy()
+                ))
+            }
+            _ => error.into(),
+        }
+    }
+
+    /// Prepares the directories for the `source` integration, where the
+    /// autoload directory isn't used at all. Links left by the `autoload`
+    /// integration are removed, since they'd load plugins twice.
+    pub fn create_dirs_for_sourcing(&self) -> Result<(), SetupError> {
+        if self.autoload_plugins_dir.symlink_metadata().is_ok() {
             fs::remove_dir_all(&self.autoload_plugins_dir)?;
         }
 
-        fs::create_dir_all(&self.autoload_plugins_dir)?;
+        self.create_data_dir()
+    }
+
+    /// Describes how `path` overlaps the directories Almoxarife writes to, if
+    /// it's inside one of them or contains one. Links are followed when the
+    /// paths exist.
+    fn managed_overlap(&self, path: &Path) -> Option<String> {
+        let resolve = |path: &Path| path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let path = resolve(path);
+
+        [
+            &self.autoload_dir,
+            &self.autoload_plugins_dir,
+            &self.almoxarife_data_dir,
+        ]
+        .into_iter()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .find_map(|dir| {
+            let resolved = resolve(dir);
+            let dir = dir.to_string_lossy();
+
+            if path.starts_with(&resolved) {
+                Some(format!("is inside {dir}"))
+            } else if resolved.starts_with(&path) {
+                Some(format!("contains {dir}"))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Where build hooks install executables, given to them as
+    /// `$ALMOXARIFE_BIN`.
+    pub fn bin_dir(&self) -> PathBuf {
+        self.almoxarife_data_dir.join(BIN)
+    }
 
+    fn create_data_dir(&self) -> Result<(), SetupError> {
         if self.almoxarife_data_dir.metadata().is_err() {
             fs::create_dir_all(&self.almoxarife_data_dir)?;
         }
@@ -124,22 +449,17 @@ impl Setup {
         Ok(())
     }
 
+    #[allow(clippy::collapsible_if)]
     fn link_runtime_dir(&self) -> Result<(), SetupError> {
         let session_name = format!("almoxarife{}", RandomState::new().build_hasher().finish());
         let mut command = Command::new("kak");
         command
             .args(["-d", "-s", &session_name, "-E"])
-            .arg("echo -to-file /dev/stdout %val[runtime]")
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped());
+            .arg("echo -to-file /dev/stdout %val[runtime]; kill");
 
-        #[cfg(test)]
-        command.envs(&self.env);
-
-        let mut kakoune = command.spawn()?;
-        thread::sleep(Duration::from_millis(100));
-        kakoune.kill()?;
-        let output = kakoune.wait_with_output()?;
+        let output = self
+            .runner
+            .output(&mut command, Some(Instant::now() + KAKOUNE_TIMEOUT))?;
 
         if let Some(code) = output.status.code() {
             if code != 0 {
@@ -154,10 +474,179 @@ impl Setup {
         Ok(())
     }
 
-    pub fn create_kak_file_with_prelude(&self) -> Result<Kak<File>, SetupError> {
-        let mut kak = Kak::new(&self.almoxarife_kak)?;
-        kak.write_prelude()?;
-        Ok(kak)
+    /// Has every program run from now on, plugins' included, run in a minimal
+    /// environment, keeping only the variables in `pass` besides the usual
+    /// ones.
+    pub fn isolate(&mut self, pass: Vec<String>) {
+        self.runner = Runner::new(Isolated::new(self.runner.clone(), pass));
+    }
+
+    /// `path`, with a leading `~` standing for the user's home directory.
+    pub fn expand_home(&self, path: &Path) -> PathBuf {
+        match (path.strip_prefix("~"), self.runner.var("HOME")) {
+            (Ok(rest), Some(home)) => Path::new(&home).join(rest),
+            _ => path.into(),
+        }
+    }
+
+    /// The installed Kakoune version, as `kak -version` tells it, like
+    /// `Kakoune v2024.05.18`.
+    pub fn kakoune_version(&self) -> Option<String> {
+        let output = self
+            .runner
+            .output(Command::new("kak").arg("-version"), None)
+            .ok()?;
+
+        if !output.status.success() {
+            return None;
+        }
+
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    /// The proxy the environment sets for HTTPS, as curl would pick it, when
+    /// the configuration sets none. git prefers the `http.proxy` of the user's
+    /// git configuration to the environment, and so does Almoxarife.
+    pub fn environment_proxy(&self) -> Option<String> {
+        if self.git_config.proxy.is_some() {
+            return None;
+        }
+
+        ["https_proxy", "HTTPS_PROXY", "all_proxy", "ALL_PROXY"]
+            .into_iter()
+            .find_map(|var| self.runner.var(var))
+            .filter(|proxy| !proxy.is_empty())
+    }
+
+    /// Writes `almoxarife.kak`, keeping the previous one among the backups.
+    pub fn write_almoxarife_kak(&self, kak: &Kak<Vec<u8>>) -> Result<(), SetupError> {
+        self.replace_script(&self.almoxarife_kak, kak.bytes())
+            .context("couldn't create almoxarife.kak file")
+    }
+
+    /// Writes `plugins.kak` for the `source` integration: every `.kak` file of
+    /// the given repositories is sourced explicitly, followed by `kak`, which
+    /// holds the plugins' configs, like `almoxarife.kak` does. The
+    /// repositories are only listed once plugins are installed, so that new
+    /// ones are sourced too.
+    pub fn write_plugins_kak(
+        &self,
+        repositories: &[&Path],
+        kak: &Kak<Vec<u8>>,
+    ) -> Result<(), SetupError> {
+        let mut contents = plugins_kak_sources(repositories).into_bytes();
+        contents.extend_from_slice(kak.bytes());
+        self.replace_script(&self.plugins_kak, &contents)
+            .context("couldn't write plugins.kak file")
+    }
+
+    /// Replaces the generated `script` by `contents` through a temporary
+    /// file, renamed into place once complete, so that an interrupted update
+    /// leaves the previous script untouched. The temporary file doesn't end
+    /// in `.kak`, so Kakoune never loads it. The previous script is backed up
+    /// if it's any different.
+    fn replace_script(&self, script: &Path, contents: &[u8]) -> io::Result<()> {
+        if let Ok(previous) = fs::read(script)
+            && previous != contents
+        {
+            self.back_up(script, &previous)?;
+        }
+
+        let temporary = script.with_extension("kak.new");
+        fs::write(&temporary, contents)?;
+        fs::rename(&temporary, script)
+    }
+
+    /// The log of notable events of past updates, like force pushes, next to
+    /// the state.
+    pub fn history_path(&self) -> PathBuf {
+        self.state_path.with_file_name("history.log")
+    }
+
+    /// The script setting `almoxarife_status` to the result of the last
+    /// update, sourced by `almoxarife.kak`. There's none when there's no state
+    /// either.
+    pub fn status_path(&self) -> Option<PathBuf> {
+        self.state_path
+            .parent()
+            .filter(|dir| !dir.as_os_str().is_empty())
+            .map(|dir| dir.join("status.kak"))
+    }
+
+    /// The transcript of the last update, which the `almoxarife-report`
+    /// command shows inside Kakoune. There's none when there's no state
+    /// either.
+    pub fn report_path(&self) -> Option<PathBuf> {
+        self.status_path()
+            .map(|status| status.with_file_name("report.txt"))
+    }
+
+    /// The directory keeping the previous generations of the generated
+    /// scripts, next to the state. There's none when there's no state either.
+    fn backups_dir(&self) -> Option<PathBuf> {
+        let state_dir = self.state_path.parent()?;
+
+        if state_dir.as_os_str().is_empty() {
+            None
+        } else {
+            Some(state_dir.join("backups"))
+        }
+    }
+
+    /// The path of the backup of `script` from `generation` updates ago.
+    fn backup_path(dir: &Path, script: &Path, generation: usize) -> PathBuf {
+        let name = script.file_name().unwrap_or_default().to_string_lossy();
+        dir.join(format!("{name}.{generation}"))
+    }
+
+    /// Saves `contents` as the latest backup of `script`, shifting the older
+    /// ones and dropping the oldest.
+    fn back_up(&self, script: &Path, contents: &[u8]) -> io::Result<()> {
+        let Some(dir) = self.backups_dir() else {
+            return Ok(());
+        };
+
+        fs::create_dir_all(&dir)?;
+
+        for generation in (1..BACKUPS).rev() {
+            let backup = Setup::backup_path(&dir, script, generation);
+
+            if backup.exists() {
+                fs::rename(&backup, Setup::backup_path(&dir, script, generation + 1))?;
+            }
+        }
+
+        fs::write(Setup::backup_path(&dir, script, 1), contents)
+    }
+
+    /// Puts the latest backup of `script` back in its place, so that the
+    /// next restore goes one generation further back. Returns whether there
+    /// was any backup to restore.
+    pub fn restore_script(&self, script: &Path) -> Result<bool, SetupError> {
+        let Some(dir) = self.backups_dir() else {
+            return Ok(false);
+        };
+
+        let latest = Setup::backup_path(&dir, script, 1);
+
+        if !latest.exists() {
+            return Ok(false);
+        }
+
+        let temporary = script.with_extension("kak.new");
+        fs::copy(&latest, &temporary)?;
+        fs::rename(&temporary, script)?;
+        fs::remove_file(&latest)?;
+
+        for generation in 2..=BACKUPS {
+            let backup = Setup::backup_path(&dir, script, generation);
+
+            if backup.exists() {
+                fs::rename(&backup, Setup::backup_path(&dir, script, generation - 1))?;
+            }
+        }
+
+        Ok(true)
     }
 
     pub fn open_config_file(&self) -> Result<Config<'_>, SetupError> {
@@ -172,13 +661,293 @@ impl Setup {
 
 pub struct Config<'setup> {
     setup: &'setup Setup,
+    pub settings: Settings,
     plugins: HashMap<String, PluginTree>,
+    /// The plugins `al dev` points to local checkouts.
+    pub overrides: Overrides,
+    /// The line each plugin starts at in the configuration file.
+    positions: HashMap<String, usize>,
+}
+
+/// Top level keys of `almoxarife.yaml` configuring Almoxarife itself, rather
+/// than a plugin.
+#[derive(Debug, Default)]
+pub struct Settings {
+    /// The minimum time between two automatic updates.
+    pub auto_update_min_interval: Option<Duration>,
+    pub integration: Integration,
+    /// Repositories larger than this, in bytes, call for a warning before
+    /// being cloned.
+    pub clone_size_warning: Option<u64>,
+    pub url_policy: UrlPolicy,
+    /// The proxy git fetches plugins through, unless they set their own. Empty
+    /// for a direct connection.
+    pub proxy: Option<String>,
+    /// URL prefixes replaced before fetching, from the ones in the
+    /// configuration to the ones to fetch from, like an internal mirror.
+    pub url_rewrites: BTreeMap<String, String>,
+    /// The most plugins fetched at once from the same host, if limited.
+    pub max_connections_per_host: Option<usize>,
+    /// The most plugins built at once, one per CPU if unset.
+    pub max_parallel_builds: Option<usize>,
+    /// Plugins whose upstream got no commits for longer than this, or is
+    /// archived, are flagged after updates. Unset, nothing is checked.
+    pub abandoned_after: Option<Duration>,
+    /// The checkouts of plugins disabled for longer than this are deleted on
+    /// updates. Unset, they're kept.
+    pub prune_disabled_after: Option<Duration>,
+    /// How often the checkouts are compacted after updates. Unset, only
+    /// `al maintain` compacts them.
+    pub maintenance_interval: Option<Duration>,
+    /// Whether updates touching changelogs, or announcing breaking changes in
+    /// their commit messages, are flagged.
+    pub flag_breaking_changes: bool,
+    /// Whether git and hooks run in a minimal environment, instead of
+    /// inheriting Almoxarife's.
+    pub isolate_env: bool,
+    /// The variables kept in the environment when it's isolated, on top of
+    /// the few every program needs.
+    pub pass_env: Vec<String>,
+    /// Whether new plugins are shown to the user, who has to accept them,
+    /// before they're installed.
+    pub review: bool,
+    pub defaults: Defaults,
+    pub hooks: Hooks,
+    /// The git repository keeping the configuration file, where it's
+    /// committed after successful updates changing it.
+    pub dotfiles_repo: Option<PathBuf>,
+    /// Whether the commits to `dotfiles_repo` are pushed.
+    pub dotfiles_push: bool,
+    /// The directory keeping the files downloaded with a checksum, possibly
+    /// shared with other machines.
+    pub download_cache: Option<PathBuf>,
+}
+
+/// Plugin fields set once, under the top level `defaults` key, for every
+/// plugin not setting them itself.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Defaults {
+    pub lazy: Option<bool>,
+    /// An empty pattern in a plugin opts it out of a default one.
+    pub tag_pattern: Option<String>,
+    pub remote: Option<String>,
+}
+
+/// How plugins are handed to Kakoune.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Integration {
+    /// Plugins are linked into the autoload directory, next to
+    /// `almoxarife.kak`.
+    #[default]
+    Autoload,
+    /// Plugins are explicitly sourced by `plugins.kak`, which the user sources
+    /// from their kakrc.
+    Source,
+}
+
+/// The top level keys taken as settings rather than as plugins. Each one has
+/// to be parsed by `ConfigFile` and described by `schema::json_schema`.
+pub const SETTINGS: &[&str] = &[
+    "version",
+    "auto_update_min_interval",
+    "abandoned_after",
+    "prune_disabled_after",
+    "maintenance_interval",
+    "clone_size_warning",
+    "url_policy",
+    "url_rewrites",
+    "flag_breaking_changes",
+    "isolate_env",
+    "pass_env",
+    "review",
+    "defaults",
+    "hooks",
+    "snippets",
+    "dotfiles_repo",
+    "dotfiles_push",
+    "download_cache",
+    "max_connections_per_host",
+    "max_parallel_builds",
+    "proxy",
+    "integration",
+];
+
+/// Finds plugins named after a setting, which would otherwise be taken as the
+/// setting: top level keys in `SETTINGS` holding a `location`.
+fn plugins_named_as_settings(source: &str) -> Vec<&'static str> {
+    let Ok(keys) = serde_yaml::from_str::<BTreeMap<String, serde_yaml::Value>>(source) else {
+        return Vec::new();
+    };
+
+    SETTINGS
+        .iter()
+        .copied()
+        .filter(|setting| {
+            keys.get(*setting)
+                .is_some_and(|value| value.get("location").is_some())
+        })
+        .collect()
+}
+
+struct ConfigFile {
+    settings: Settings,
+    plugins: HashMap<String, PluginTree>,
+}
+
+impl<'de> Deserialize<'de> for ConfigFile {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        // A hand written visitor, instead of `#[serde(flatten)]`, so that
+        // errors inside plugins keep pointing at the offending line.
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = ConfigFile;
+
+            fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+                formatter.write_str("a map of plugins")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<ConfigFile, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut settings = Settings::default();
+                let mut plugins = HashMap::new();
+
+                while let Some(key) = map.next_key::<String>()? {
+                    // Only the keys in `SETTINGS` are settings, the ones
+                    // plugins are checked not to be named after.
+                    if !SETTINGS.contains(&key.as_str()) {
+                        let tree: PluginTree = map.next_value()?;
+                        tree.check_source(&key).map_err(serde::de::Error::custom)?;
+                        plugins.insert(key, tree);
+                    } else if key == "version" {
+                        // Already checked before parsing.
+                        let _: u64 = map.next_value()?;
+                    } else if key == "auto_update_min_interval" {
+                        let text: String = map.next_value()?;
+                        let interval =
+                            time::parse_duration(&text).map_err(serde::de::Error::custom)?;
+                        settings.auto_update_min_interval = Some(interval);
+                    } else if key == "abandoned_after" {
+                        let text: String = map.next_value()?;
+                        let after =
+                            time::parse_duration(&text).map_err(serde::de::Error::custom)?;
+                        settings.abandoned_after = Some(after);
+                    } else if key == "prune_disabled_after" {
+                        let text: String = map.next_value()?;
+                        let after =
+                            time::parse_duration(&text).map_err(serde::de::Error::custom)?;
+                        settings.prune_disabled_after = Some(after);
+                    } else if key == "maintenance_interval" {
+                        let text: String = map.next_value()?;
+                        let interval =
+                            time::parse_duration(&text).map_err(serde::de::Error::custom)?;
+                        settings.maintenance_interval = Some(interval);
+                    } else if key == "clone_size_warning" {
+                        let text: String = map.next_value()?;
+                        let size = forge::parse_size(&text).map_err(serde::de::Error::custom)?;
+                        settings.clone_size_warning = Some(size);
+                    } else if key == "url_policy" {
+                        settings.url_policy = map.next_value()?;
+                    } else if key == "url_rewrites" {
+                        let rewrites: BTreeMap<String, String> = map.next_value()?;
+
+                        if rewrites
+                            .iter()
+                            .any(|(from, to)| from.is_empty() || to.is_empty())
+                        {
+                            return Err(serde::de::Error::custom(
+                                "url_rewrites can't have empty URLs",
+                            ));
+                        }
+
+                        settings.url_rewrites = rewrites;
+                    } else if key == "flag_breaking_changes" {
+                        settings.flag_breaking_changes = map.next_value()?;
+                    } else if key == "isolate_env" {
+                        settings.isolate_env = map.next_value()?;
+                    } else if key == "pass_env" {
+                        settings.pass_env = map.next_value()?;
+                    } else if key == "review" {
+                        settings.review = map.next_value()?;
+                    } else if key == "defaults" {
+                        settings.defaults = map.next_value()?;
+                    } else if key == "hooks" {
+                        settings.hooks = map.next_value()?;
+                    } else if key == "snippets" {
+                        // Already pasted where they're referenced.
+                        let _: BTreeMap<String, String> = map.next_value()?;
+                    } else if key == "dotfiles_repo" {
+                        settings.dotfiles_repo = Some(map.next_value()?);
+                    } else if key == "dotfiles_push" {
+                        settings.dotfiles_push = map.next_value()?;
+                    } else if key == "download_cache" {
+                        settings.download_cache = Some(map.next_value()?);
+                    } else if key == "max_connections_per_host" {
+                        let limit: usize = map.next_value()?;
+
+                        if limit == 0 {
+                            return Err(serde::de::Error::custom(
+                                "max_connections_per_host must be at least 1",
+                            ));
+                        }
+
+                        settings.max_connections_per_host = Some(limit);
+                    } else if key == "max_parallel_builds" {
+                        let limit: usize = map.next_value()?;
+
+                        if limit == 0 {
+                            return Err(serde::de::Error::custom(
+                                "max_parallel_builds must be at least 1",
+                            ));
+                        }
+
+                        settings.max_parallel_builds = Some(limit);
+                    } else if key == "proxy" {
+                        let text: String = map.next_value()?;
+                        let proxy = parse_proxy(&text).map_err(serde::de::Error::custom)?;
+                        settings.proxy = Some(proxy);
+                    } else if key == "integration" {
+                        let integration: String = map.next_value()?;
+
+                        settings.integration = match integration.as_str() {
+                            "autoload" => Integration::Autoload,
+                            "source" => Integration::Source,
+                            _ => {
+                                return Err(serde::de::Error::custom(format!(
+                                    "unknown integration {integration:?}; use autoload or source"
+                                )));
+                            }
+                        };
+                    } else {
+                        unreachable!("the setting {key} isn't parsed");
+                    }
+                }
+
+                Ok(ConfigFile { settings, plugins })
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
 }
 
 impl<'setup> Config<'setup> {
     fn new(setup: &Setup) -> Result<Config<'_>, SetupError> {
         let file = File::open(&setup.almoxarife_yaml_path)?;
-        Config::from_reader(&file, setup)
+        let mut config = Config::from_reader(&file, setup)?;
+
+        config.overrides = Overrides::load(&setup.dev_overrides_path).context(&format!(
+            "couldn't read {}",
+            setup.dev_overrides_path.to_string_lossy()
+        ))?;
+
+        Ok(config)
     }
 
     fn from_reader<'r, R: 'r + ?Sized>(
@@ -188,11 +957,43 @@ impl<'setup> Config<'setup> {
     where
         &'r R: Read,
     {
-        let plugins: HashMap<String, PluginTree> =
-            serde_yaml::from_reader(reader).context(&format!(
-                "couldn't parse {}",
-                setup.almoxarife_yaml_path.to_string_lossy()
-            ))?;
+        // We keep the raw source around so that parsing errors can point to
+        // the offending line.
+        let mut source = String::new();
+        let mut reader = reader;
+        reader.read_to_string(&mut source).context(&format!(
+            "couldn't read {}",
+            setup.almoxarife_yaml_path.to_string_lossy()
+        ))?;
+
+        // Checked first, since a file in another version would likely give
+        // confusing errors.
+        if let Some(version) = migrate::version(&source) {
+            migrate::check(version).map_err(|error| {
+                SetupError(format!(
+                    "couldn't read {}: {error}",
+                    setup.almoxarife_yaml_path.to_string_lossy()
+                ))
+            })?;
+        }
+
+        let reserved = plugins_named_as_settings(&source);
+
+        if !reserved.is_empty() {
+            let reasons: Vec<String> = reserved
+                .iter()
+                .map(|name| format!("{name} is a setting, so it can't name a plugin"))
+                .collect();
+
+            return Err(SetupError(format!(
+                "plugins of {} need other names:\n{}",
+                setup.almoxarife_yaml_path.to_string_lossy(),
+                reasons.join("\n")
+            )));
+        }
+
+        let ConfigFile { settings, plugins } = parse_config(&source)
+            .map_err(|e| SetupError(describe_yaml_error(&e, &source, setup)))?;
 
         if plugins.is_empty() {
             return Err(SetupError(
@@ -200,7 +1001,63 @@ impl<'setup> Config<'setup> {
             ));
         }
 
-        Ok(Config { setup, plugins })
+        // Checked before anything is fetched, against the URLs git will
+        // actually fetch from, after the rewrites of the user's git
+        // configuration and of the file.
+        let mut rewrites = setup.git_config.url_rewrites.clone();
+        rewrites.extend(settings.url_rewrites.clone());
+
+        let violations: Vec<String> = plugins
+            .iter()
+            .flat_map(|(name, tree)| tree.urls(name))
+            .filter_map(|(name, url)| {
+                let error = settings
+                    .url_policy
+                    .check(&gitconfig::rewrite_url(&rewrites, url))
+                    .err()?;
+                Some(format!("{name}: {error}"))
+            })
+            .collect();
+
+        if !violations.is_empty() {
+            return Err(SetupError(format!(
+                "plugins break the url_policy of {}:\n{}",
+                setup.almoxarife_yaml_path.to_string_lossy(),
+                violations.join("\n")
+            )));
+        }
+
+        // Linking a directory Almoxarife cleans up could destroy it, and
+        // linking one containing the autoload directory would make a cycle.
+        let overlaps: Vec<String> = plugins
+            .iter()
+            .flat_map(|(name, tree)| tree.local_locations(name))
+            .filter_map(|(name, location)| {
+                let overlap = setup.managed_overlap(Path::new(location))?;
+                Some(format!("{name}: {location} {overlap}"))
+            })
+            .collect();
+
+        if !overlaps.is_empty() {
+            return Err(SetupError(format!(
+                "local plugins can't overlap the directories managed by Almoxarife:\n{}",
+                overlaps.join("\n")
+            )));
+        }
+
+        let positions = plugins
+            .iter()
+            .flat_map(|(name, tree)| iter::once(name.as_str()).chain(tree.list_children()))
+            .filter_map(|name| Some((name.to_string(), edit::line_of(&source, name, None)?)))
+            .collect();
+
+        Ok(Config {
+            setup,
+            settings,
+            plugins,
+            overrides: Overrides::default(),
+            positions,
+        })
     }
 
     pub fn disabled_plugins(&self) -> Vec<String> {
@@ -211,13 +1068,47 @@ impl<'setup> Config<'setup> {
             .collect()
     }
 
-    pub fn removed_plugins(&self) -> Result<Vec<PathBuf>, SetupError> {
-        let all_plugins: HashSet<&str> = self
-            .plugins
+    /// The disabled plugins with a config, which never runs.
+    pub fn unused_configs(&self) -> Vec<&str> {
+        self.plugins
+            .iter()
+            .flat_map(|(name, plugin)| plugin.unused_configs(name))
+            .collect()
+    }
+
+    /// The plugins left out along with a disabled ancestor, though not
+    /// disabled themselves, each with that ancestor.
+    pub fn skipped_children(&self) -> Vec<(&str, &str)> {
+        self.plugins
+            .iter()
+            .flat_map(|(name, plugin)| plugin.skipped_children(name))
+            .collect()
+    }
+
+    /// The names of all plugins in the configuration file, including disabled
+    /// ones.
+    pub fn plugin_names(&self) -> HashSet<&str> {
+        self.plugins
             .iter()
             .flat_map(|(name, plugin)| iter::once(name.as_str()).chain(plugin.list_children()))
+            .collect()
+    }
+
+    pub fn removed_plugins(&self) -> Result<Vec<PathBuf>, SetupError> {
+        // Dependencies brought in by manifests aren't in the configuration
+        // file, but are kept as long as some plugin needs them.
+        let dependencies: Vec<String> = self
+            .active_plugins()
+            .into_iter()
+            .filter(|plugin| plugin.required_by.is_some())
+            .map(|plugin| plugin.name)
             .collect();
 
+        let mut all_plugins = self.plugin_names();
+        all_plugins.extend(dependencies.iter().map(String::as_str));
+        all_plugins.insert(BIN);
+        all_plugins.insert(WORKSPACES);
+
         let removed = fs::read_dir(&self.setup.almoxarife_data_dir)
             .context(&format!(
                 "couldn't read {}",
@@ -232,35 +1123,498 @@ impl<'setup> Config<'setup> {
         Ok(removed)
     }
 
-    pub fn active_plugins(self) -> Vec<Plugin> {
-        self.plugins
-            .into_iter()
-            .flat_map(|(name, tree)| tree.plugins(name, None, self.setup))
-            .collect()
+    pub fn active_plugins(&self) -> Vec<Plugin> {
+        let proxy = self.proxy();
+        let defaults = &self.settings.defaults;
+
+        let upstreams: HashMap<String, String> = self
+            .plugins
+            .iter()
+            .flat_map(|(name, tree)| tree.upstreams(name))
+            .map(|(name, upstream)| (name.to_string(), upstream.to_string()))
+            .collect();
+
+        let mut plugins: Vec<Plugin> = self
+            .plugins
+            .iter()
+            .flat_map(|(name, tree)| {
+                tree.plugins(
+                    name.clone(),
+                    None,
+                    self.setup,
+                    &self.overrides,
+                    defaults,
+                    proxy.as_deref(),
+                )
+            })
+            .collect();
+
+        // Forks of the same upstream, and the upstream itself when it's a
+        // plugin too, borrow objects from each other's checkouts.
+        let families: Vec<Option<&str>> = plugins
+            .iter()
+            .map(|plugin| {
+                let upstream = upstreams.get(&plugin.name).map(String::as_str);
+                plugin
+                    .is_checkout()
+                    .then(|| upstream.unwrap_or(&plugin.location))
+            })
+            .collect();
+
+        let references: Vec<Vec<PathBuf>> = families
+            .iter()
+            .enumerate()
+            .map(|(i, family)| {
+                plugins
+                    .iter()
+                    .zip(&families)
+                    .enumerate()
+                    .filter(|(j, (_, other))| *j != i && family.is_some() && *other == family)
+                    .map(|(_, (plugin, _))| plugin.repository_path.clone())
+                    .collect()
+            })
+            .collect();
+
+        let download_cache = self
+            .settings
+            .download_cache
+            .as_deref()
+            .map(|dir| self.setup.expand_home(dir));
+
+        for (plugin, references) in plugins.iter_mut().zip(references) {
+            plugin.references = references;
+            plugin.flag_breaking_changes = self.settings.flag_breaking_changes;
+            plugin.url_rewrites = self.settings.url_rewrites.clone();
+            plugin.download_cache = download_cache.clone();
+            plugin.position = self.positions.get(&plugin.name).copied();
+        }
+
+        let dependencies = self.dependencies(&plugins);
+        plugins.extend(dependencies);
+        plugins
+    }
+
+    /// The plugins the manifests of the installed `plugins` depend on, which
+    /// are neither among them nor disabled in the configuration file. They're
+    /// set up like plugins with nothing but a location. Dependencies of the
+    /// dependencies already installed are followed too. When two manifests
+    /// disagree on where a dependency is, the first one wins.
+    pub fn dependencies(&self, plugins: &[Plugin]) -> Vec<Plugin> {
+        let proxy = self.proxy();
+
+        let mut known: HashSet<String> = plugins
+            .iter()
+            .map(|plugin| plugin.name.clone())
+            .chain(self.disabled_plugins())
+            .collect();
+
+        let mut dependencies: Vec<Plugin> = Vec::new();
+        let mut next = 0;
+
+        while let Some(requirer) = plugins
+            .get(next)
+            .or_else(|| dependencies.get(next - plugins.len()))
+        {
+            next += 1;
+
+            let name = requirer.name.clone();
+            let wanted = requirer.manifest().dependencies;
+
+            for (dependency, location) in wanted {
+                if !known.insert(dependency.clone()) {
+                    continue;
+                }
+
+                let tree = PluginTree {
+                    location,
+                    ..PluginTree::default()
+                };
+
+                let mut plugin = Plugin::new(
+                    dependency,
+                    &tree,
+                    None,
+                    self.setup,
+                    &self.overrides,
+                    &self.settings.defaults,
+                    proxy.as_deref(),
+                );
+
+                plugin.required_by = Some(name.clone());
+                plugin.flag_breaking_changes = self.settings.flag_breaking_changes;
+                plugin.url_rewrites = self.settings.url_rewrites.clone();
+                dependencies.push(plugin);
+            }
+        }
+
+        dependencies
     }
+
+    /// The proxy plugins are fetched through, unless they set their own.
+    fn proxy(&self) -> Option<String> {
+        self.settings
+            .proxy
+            .clone()
+            .or_else(|| self.setup.environment_proxy())
+    }
+}
+
+/// Parses the configuration file. Merge keys, as in `<<: *common`, and
+/// references to snippets, as in `!snippet lsp`, are only resolved in files
+/// using them, since going through a `Value` loses the location of errors.
+fn parse_config(source: &str) -> Result<ConfigFile, serde_yaml::Error> {
+    if !source.contains("<<") && !source.contains("!snippet") {
+        return serde_yaml::from_str(source);
+    }
+
+    let mut value: serde_yaml::Value = serde_yaml::from_str(source)?;
+    value.apply_merge()?;
+
+    let snippets: BTreeMap<String, String> = match value.get("snippets") {
+        Some(snippets) => serde_yaml::from_value(snippets.clone())?,
+        None => BTreeMap::new(),
+    };
+
+    expand_snippets(&mut value, &snippets)?;
+    serde_yaml::from_value(value)
 }
 
-#[derive(Debug, Deserialize)]
-struct PluginTree {
+/// Replaces the references to `snippets` in the `config` fields found in
+/// `value`: a whole field, as in `config: !snippet lsp`, or lines of their
+/// own inside one, which get the snippet indented like them.
+fn expand_snippets(
+    value: &mut serde_yaml::Value,
+    snippets: &BTreeMap<String, String>,
+) -> Result<(), serde_yaml::Error> {
+    let Some(mapping) = value.as_mapping_mut() else {
+        return Ok(());
+    };
+
+    let snippet = |name: &str| -> Result<&String, serde_yaml::Error> {
+        snippets.get(name.trim()).ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "there's no snippet named {:?} under snippets",
+                name.trim()
+            ))
+        })
+    };
+
+    for (key, value) in mapping.iter_mut() {
+        if key.as_str() != Some("config") {
+            expand_snippets(value, snippets)?;
+            continue;
+        }
+
+        match value {
+            serde_yaml::Value::Tagged(tagged) if tagged.tag == "snippet" => {
+                let name = tagged.value.as_str().unwrap_or_default();
+                *value = serde_yaml::Value::String(snippet(name)?.clone());
+            }
+
+            serde_yaml::Value::String(config) if config.contains("!snippet ") => {
+                let mut expanded = String::new();
+
+                for line in config.lines() {
+                    let indent = &line[..line.len() - line.trim_start().len()];
+
+                    match line.trim().strip_prefix("!snippet ") {
+                        Some(name) => {
+                            for line in snippet(name)?.lines() {
+                                expanded.push_str(indent);
+                                expanded.push_str(line);
+                                expanded.push('\n');
+                            }
+                        }
+
+                        None => {
+                            expanded.push_str(line);
+                            expanded.push('\n');
+                        }
+                    }
+                }
+
+                *config = expanded;
+            }
+
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+/// Renders a YAML parsing error as a message pointing to the line and column
+/// where the error happened, followed by a hint, when we know of a likely
+/// cause.
+fn describe_yaml_error(error: &serde_yaml::Error, source: &str, setup: &Setup) -> String {
+    let path = setup.almoxarife_yaml_path.to_string_lossy();
+    let message = error.to_string();
+
+    let Some(location) = error.location() else {
+        return format!("couldn't parse {path}: {message}");
+    };
+
+    // The message already ends with the location, which we show in our own
+    // format.
+    let message = match message.rsplit_once(" at line ") {
+        Some((message, _)) => message,
+        None => &message,
+    };
+
+    let line_number = location.line();
+    let line = source.lines().nth(line_number - 1).unwrap_or_default();
+    let gutter = " ".repeat(line_number.to_string().len());
+    let caret = " ".repeat(location.column() - 1);
+
+    let mut description = format!(
+        "couldn't parse {path} at line {line_number}, column {column}: {message}
+{gutter} |
+{line_number} | {line}
+{gutter} | {caret}^",
+        column = location.column(),
+    );
+
+    if let Some(hint) = yaml_error_hint(message, line) {
+        description.push_str("\nhint: ");
+        description.push_str(hint);
+    }
+
+    description
+}
+
+fn yaml_error_hint(message: &str, line: &str) -> Option<&'static str> {
+    let is_config = line.trim_start().starts_with("config:");
+
+    if message.starts_with("mapping values are not allowed") && is_config {
+        Some(
+            "config: blocks with `: `, `<` or quotes need quoting or `|`, as in\n    config: |\n      map global normal <c-p> ': my-command<ret>'",
+        )
+    } else if message.contains("cannot start any token") && line.contains('\t') {
+        Some("YAML doesn't allow tabs for indentation; use spaces instead")
+    } else if message.contains("cannot start any token") && is_config {
+        Some(
+            "config: values starting with `%`, `@` or `` ` `` need quoting or `|`, as in\n    config: |\n      %sh{ ... }",
+        )
+    } else if message.contains("expected struct PluginTree") {
+        Some(
+            "unknown fields inside a plugin are taken as child plugins; check the field names for typos",
+        )
+    } else if message.starts_with("did not find expected key") {
+        Some("check the indentation of this line and of the lines above it")
+    } else {
+        None
+    }
+}
+
+/// A plugin as written in `almoxarife.yaml`. Its fields are the ones of
+/// `schema::PLUGIN_FIELDS`.
+#[derive(Debug, Default, Deserialize)]
+#[cfg_attr(test, derive(serde::Serialize))]
+pub struct PluginTree {
+    /// Empty for plugins downloaded from `file`.
+    #[serde(default)]
     location: String,
+    /// The URL of the single `.kak` file the plugin is, instead of a
+    /// repository.
+    file: Option<String>,
+    /// The SHA-256 checksum of `file`.
+    sha256: Option<String>,
     #[serde(default)]
     config: String,
     #[serde(default)]
     disabled: bool,
+    lazy: Option<bool>,
+    sessions: Option<String>,
+    order: Option<i64>,
+    rev: Option<String>,
+    tag_pattern: Option<String>,
+    remote: Option<String>,
+    #[serde(default)]
+    remotes: BTreeMap<String, String>,
+    #[serde(default)]
+    also_link_as: Vec<String>,
+    #[serde(default)]
+    after_update_kak: String,
+    #[serde(default)]
+    log_paths: Vec<String>,
+    #[serde(default)]
+    features: Vec<String>,
+    #[serde(default)]
+    trusted: bool,
+    #[serde(default, deserialize_with = "deserialize_proxy")]
+    proxy: Option<String>,
+    upstream: Option<String>,
     #[serde(flatten)]
     children: HashMap<String, PluginTree>,
 }
 
+/// Checks the value of a `proxy:` key: the URL of an HTTP or SOCKS proxy, or
+/// `direct`, given as an empty proxy, which git takes as no proxy at all.
+fn parse_proxy(text: &str) -> Result<String, String> {
+    const SCHEMES: [&str; 6] = [
+        "http://",
+        "https://",
+        "socks4://",
+        "socks4a://",
+        "socks5://",
+        "socks5h://",
+    ];
+
+    if text == "direct" {
+        Ok(String::new())
+    } else if SCHEMES.iter().any(|scheme| text.starts_with(scheme)) {
+        Ok(text.to_string())
+    } else {
+        Err(format!(
+            "invalid proxy {text:?}; use an http://, https:// or socks5:// URL (socks4, socks4a and socks5h work too), or direct"
+        ))
+    }
+}
+
+fn deserialize_proxy<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    parse_proxy(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
 impl PluginTree {
-    fn plugins(&self, name: String, parent: Option<String>, setup: &Setup) -> Vec<Plugin> {
+    /// Checks that the plugin `name`, and each of its children, comes from
+    /// either a `location` or a `file`.
+    fn check_source(&self, name: &str) -> Result<(), String> {
+        match (self.location.is_empty(), &self.file) {
+            (true, None) => {
+                return Err(format!(
+                    "{name} needs a `location:` or a `file:` field; note that any unknown field inside a plugin is taken as a child plugin"
+                ));
+            }
+            (false, Some(_)) => {
+                return Err(format!(
+                    "{name} has both a `location:` and a `file:` field; keep only one"
+                ));
+            }
+            _ => {}
+        }
+
+        if let Some(sha256) = &self.sha256 {
+            if self.file.is_none() {
+                return Err(format!(
+                    "{name} has a `sha256:` field but no `file:`; only downloaded files are checked"
+                ));
+            }
+
+            if sha256.len() != 64 || !sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(format!(
+                    "{name}'s `sha256:` isn't a SHA-256 checksum: it takes 64 hexadecimal digits"
+                ));
+            }
+        }
+
+        self.children
+            .iter()
+            .try_for_each(|(name, child)| child.check_source(name))
+    }
+
+    fn plugins(
+        &self,
+        name: String,
+        parent: Option<String>,
+        setup: &Setup,
+        overrides: &Overrides,
+        defaults: &Defaults,
+        proxy: Option<&str>,
+    ) -> Vec<Plugin> {
         if self.disabled {
             return Vec::new();
         }
 
-        iter::once(Plugin::new(name.clone(), self, parent, setup))
-            .chain(self.children.iter().flat_map(move |(child_name, child)| {
-                child.plugins(child_name.clone(), Some(name.clone()), setup)
-            }))
+        // Children are usually hosted along with their parents, so they go
+        // through the same proxy unless they set their own.
+        let proxy = self.proxy.as_deref().or(proxy);
+
+        iter::once(Plugin::new(
+            name.clone(),
+            self,
+            parent,
+            setup,
+            overrides,
+            defaults,
+            proxy,
+        ))
+        .chain(self.children.iter().flat_map(move |(child_name, child)| {
+            child.plugins(
+                child_name.clone(),
+                Some(name.clone()),
+                setup,
+                overrides,
+                defaults,
+                proxy,
+            )
+        }))
+        .collect()
+    }
+
+    /// The URLs the enabled plugins of this tree are fetched from, along with
+    /// the plugins' names.
+    fn urls<'a>(&'a self, name: &'a str) -> Vec<(&'a str, &'a str)> {
+        if self.disabled {
+            return Vec::new();
+        }
+
+        let location = match &self.file {
+            Some(file) => Some(file.as_str()),
+            None => Some(self.location.as_str()).filter(|location| !is_local(location)),
+        };
+
+        location
+            .into_iter()
+            .chain(self.remotes.values().map(String::as_str))
+            .map(|url| (name, url))
+            .chain(
+                self.children
+                    .iter()
+                    .flat_map(|(name, child)| child.urls(name)),
+            )
+            .collect()
+    }
+
+    /// The locations of the enabled local plugins, along with their names.
+    fn local_locations<'a>(&'a self, name: &'a str) -> Vec<(&'a str, &'a str)> {
+        if self.disabled {
+            return Vec::new();
+        }
+
+        Some(self.location.as_str())
+            .filter(|location| self.file.is_none() && is_local(location))
+            .map(|location| (name, location))
+            .into_iter()
+            .chain(
+                self.children
+                    .iter()
+                    .flat_map(|(name, child)| child.local_locations(name)),
+            )
+            .collect()
+    }
+
+    /// The upstreams the enabled plugins declare themselves forks of, along
+    /// with their names.
+    fn upstreams<'a>(&'a self, name: &'a str) -> Vec<(&'a str, &'a str)> {
+        if self.disabled {
+            return Vec::new();
+        }
+
+        self.upstream
+            .as_deref()
+            .map(|upstream| (name, upstream))
+            .into_iter()
+            .chain(
+                self.children
+                    .iter()
+                    .flat_map(|(name, child)| child.upstreams(name)),
+            )
             .collect()
     }
 
@@ -271,6 +1625,50 @@ impl PluginTree {
             .collect()
     }
 
+    fn unused_configs<'a>(&'a self, name: &'a str) -> Vec<&'a str> {
+        let own = (self.disabled && !self.config.trim().is_empty()).then_some(name);
+
+        own.into_iter()
+            .chain(
+                self.children
+                    .iter()
+                    .flat_map(|(name, child)| child.unused_configs(name)),
+            )
+            .collect()
+    }
+
+    fn skipped_children<'a>(&'a self, name: &'a str) -> Vec<(&'a str, &'a str)> {
+        if !self.disabled {
+            return self
+                .children
+                .iter()
+                .flat_map(|(name, child)| child.skipped_children(name))
+                .collect();
+        }
+
+        let mut skipped = Vec::new();
+        let mut pending: Vec<(&str, &PluginTree)> = self
+            .children
+            .iter()
+            .map(|(name, child)| (name.as_str(), child))
+            .collect();
+
+        while let Some((child_name, child)) = pending.pop() {
+            if !child.disabled {
+                skipped.push((child_name, name));
+            }
+
+            pending.extend(
+                child
+                    .children
+                    .iter()
+                    .map(|(name, child)| (name.as_str(), child)),
+            );
+        }
+
+        skipped
+    }
+
     fn disabled_items<'a>(&'a self, current_plugin_name: &'a str) -> Vec<&'a str> {
         let mut disabled = Vec::new();
 
@@ -291,242 +1689,1190 @@ impl PluginTree {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Plugin {
     pub name: String,
     /// The parent of this plugin, if any.
     pub parent: Option<String>,
     /// Whether this plugin has children.
     pub has_children: bool,
+    /// The plugin whose manifest brought this one in, for plugins missing
+    /// from the configuration file.
+    pub required_by: Option<String>,
     /// Where the plugin is located (the URL of a git repo or a local folder).
     pub location: String,
     /// Whether the code is located in a local folder.
     pub is_local: bool,
+    /// Whether the plugin is a single file downloaded from `location`.
+    pub single_file: bool,
+    /// The SHA-256 checksum the downloaded file must have, in lowercase.
+    pub sha256: Option<String>,
+    /// Where files downloaded with a checksum are kept, to spare downloading
+    /// them again, here or on other machines sharing the directory.
+    pub download_cache: Option<PathBuf>,
     /// User defined configuration for the plugin.
     pub config: String,
+    /// Whether the plugin should only be loaded on first use of one of its
+    /// commands.
+    pub lazy: bool,
+    /// The pattern the names of the Kakoune sessions loading the plugin
+    /// match, if only some of them do.
+    pub sessions: Option<String>,
+    /// Moves the plugin's config later in the generated script, the higher
+    /// it is, or earlier, if negative. 0 unless set.
+    pub order: i64,
+    /// The line the plugin starts at in the configuration file, for plugins
+    /// listed there.
+    pub position: Option<usize>,
+    /// The revision the plugin is pinned to, if any.
+    pub rev: Option<String>,
+    /// The pattern, in git's glob syntax, of the tags the plugin follows
+    /// instead of its default branch, if any. `rev` takes precedence.
+    pub tag_pattern: Option<String>,
+    pub remotes: Remotes,
     /// The path to the folder containing the plugin's code.
     pub repository_path: PathBuf,
     /// The path inside `autoload` where a soft link of the plugin is.
     pub link_path: PathBuf,
-    // Custom environment variables the plugin setup will consider.
-    #[cfg(test)]
-    pub env: HashMap<&'static str, String>,
+    /// Further links of the plugin inside `autoload`, for plugins expecting a
+    /// specific directory name.
+    pub alias_link_paths: Vec<PathBuf>,
+    /// Kakoune commands to send to the running sessions after the plugin is
+    /// updated.
+    pub after_update_kak: String,
+    /// The paths the changelog of an update is limited to. Empty, every
+    /// commit is listed.
+    pub log_paths: Vec<String>,
+    /// The features of the plugin's manifest the user asked for.
+    pub features: Vec<String>,
+    /// Whether the plugin's build commands run outside the sandbox.
+    pub trusted: bool,
+    /// The proxy git reaches the plugin's remotes through, if any. Empty for a
+    /// direct connection, whatever the environment says.
+    pub proxy: Option<String>,
+    /// The URL prefixes git replaces before reaching the plugin's remotes,
+    /// from the ones in the configuration to the ones to use instead.
+    pub url_rewrites: BTreeMap<String, String>,
+    /// The checkouts of other forks of the same upstream, which the plugin
+    /// borrows objects from when cloned.
+    pub references: Vec<PathBuf>,
+    /// Whether updates are looked for hints of breaking changes.
+    pub flag_breaking_changes: bool,
+    /// Whether files in the way of the plugin's links, not put there by
+    /// Almoxarife, are replaced instead of reported.
+    pub force_adopt: bool,
+    /// When fetching the plugin is given up on, if ever.
+    pub deadline: Option<Instant>,
+    /// What the plugin was last built from, if it ever was.
+    pub last_build: Option<Build>,
+    /// Whether the plugin is built again even if unchanged since its last
+    /// build.
+    pub rebuild: bool,
+    /// Whether the plugin, if unchanged, tells when its newest upstream
+    /// commit was made.
+    pub commit_age: bool,
+    /// Whether the plugin was cloned ahead of the update for the user to
+    /// review, so that the update only has to report it installed.
+    pub reviewed: bool,
+    /// Where the plugin's build hooks install executables.
+    pub bin_dir: PathBuf,
+    pub runner: Runner,
+}
+
+/// The git remotes of a plugin's checkout.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Remotes {
+    /// The name of the remote the plugin is cloned from (git's default,
+    /// `origin`, if unset).
+    pub name: Option<String>,
+    /// Further remotes, by name, kept in sync with the configuration on every
+    /// update.
+    pub extra: BTreeMap<String, String>,
+}
+
+/// Whether `location` is a directory to be linked as is, rather than a
+/// repository to be cloned: anything with a URL scheme, like `https://`,
+/// `ssh://` or `file://`, is cloned, as are `git@` locations and bare
+/// repositories.
+fn is_local(location: &str) -> bool {
+    !has_scheme(location)
+        && !location.starts_with("git@")
+        && !is_bare_repository(Path::new(location))
+}
+
+/// Whether `location` starts with a URL scheme, as in `scheme://`.
+fn has_scheme(location: &str) -> bool {
+    let Some((scheme, _)) = location.split_once("://") else {
+        return false;
+    };
+
+    scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// The arguments of bubblewrap confining build commands: everything is
+/// read-only, but for devices, processes and a private `/tmp`. The network is
+/// left alone, since builds often download their own dependencies.
+const SANDBOX: [&str; 10] = [
+    "--ro-bind",
+    "/",
+    "/",
+    "--dev",
+    "/dev",
+    "--proc",
+    "/proc",
+    "--tmpfs",
+    "/tmp",
+    "--die-with-parent",
+];
+
+/// Whether the builds of a run are sandboxed, which bubblewrap is only asked
+/// about once, along with the untrusted plugins built unconfined because it
+/// can't.
+#[derive(Default)]
+pub struct Sandbox {
+    works: OnceLock<bool>,
+    unconfined: Mutex<Vec<String>>,
 }
 
-fn is_local(location: &str) -> bool {
-    !location.starts_with("https://")
-        && !location.starts_with("http://")
-        && !location.starts_with("git@")
-}
+impl Sandbox {
+    /// Whether `plugin` is built inside the sandbox. Untrusted plugins built
+    /// unconfined are recorded for `warning`.
+    fn confines(&self, plugin: &Plugin) -> bool {
+        if plugin.trusted {
+            return false;
+        }
+
+        if *self.works.get_or_init(|| sandbox_works(&plugin.runner)) {
+            return true;
+        }
+
+        self.unconfined
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(plugin.name.clone());
+
+        false
+    }
+
+    /// Tells about the untrusted plugins built unconfined since the last
+    /// warning, if any.
+    pub fn warning(&self) -> Option<String> {
+        let mut names = mem::take(
+            &mut *self
+                .unconfined
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+
+        if names.is_empty() {
+            return None;
+        }
+
+        names.sort();
+
+        Some(format!(
+            "{} built without a sandbox, since bubblewrap is missing or can't make sandboxes here; install it, or set `trusted: true` on the plugins you trust",
+            names.join(", ")
+        ))
+    }
+}
+
+/// Whether bubblewrap is installed and can make sandboxes here, which it
+/// can't in some containers.
+fn sandbox_works(runner: &Runner) -> bool {
+    runner
+        .output(Command::new("bwrap").args(SANDBOX).arg("true"), None)
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Whether `path` is a bare git repository, like a mirror on a network share.
+/// Those are cloned and pulled like remote repositories, instead of linked.
+fn is_bare_repository(path: &Path) -> bool {
+    path.join("HEAD").is_file() && path.join("objects").is_dir() && path.join("refs").is_dir()
+}
+
+/// The URL to clone the plugin at `location` from. Forges are given the
+/// `.git` suffix, while `file://` URLs and bare repositories are taken as
+/// they are.
+pub fn clone_url(location: &str) -> String {
+    if location.starts_with("file://") || is_bare_repository(Path::new(location)) {
+        location.to_string()
+    } else {
+        format!("{location}.git")
+    }
+}
+
+impl Plugin {
+    fn new(
+        name: String,
+        node: &PluginTree,
+        parent: Option<String>,
+        setup: &Setup,
+        overrides: &Overrides,
+        defaults: &Defaults,
+        proxy: Option<&str>,
+    ) -> Plugin {
+        let link_path = setup.autoload_plugins_dir.join(&name);
+        let last_build = setup.builds.get(&name).cloned();
+
+        // A plugin under development is used from its checkout, like a local
+        // one.
+        let (location, is_local, repository_path) = if let Some(checkout) = overrides.get(&name) {
+            (
+                checkout.to_string_lossy().to_string(),
+                true,
+                checkout.clone(),
+            )
+        } else if let Some(file) = &node.file {
+            (file.clone(), false, setup.almoxarife_data_dir.join(&name))
+        } else if is_local(&node.location) {
+            (node.location.clone(), true, PathBuf::from(&node.location))
+        } else {
+            (
+                node.location.clone(),
+                false,
+                setup.almoxarife_data_dir.join(&name),
+            )
+        };
+
+        Plugin {
+            name,
+            parent,
+            has_children: !node.children.is_empty(),
+            required_by: None,
+            config: node.config.clone(),
+            lazy: node.lazy.or(defaults.lazy).unwrap_or(false),
+            sessions: node
+                .sessions
+                .clone()
+                .filter(|sessions| !sessions.is_empty()),
+            order: node.order.unwrap_or(0),
+            position: None,
+            rev: node.rev.clone(),
+            tag_pattern: node
+                .tag_pattern
+                .as_ref()
+                .or(defaults.tag_pattern.as_ref())
+                .filter(|pattern| !pattern.is_empty())
+                .cloned(),
+            remotes: Remotes {
+                name: node.remote.clone().or_else(|| defaults.remote.clone()),
+                extra: node.remotes.clone(),
+            },
+            location,
+            is_local,
+            single_file: node.file.is_some() && !is_local,
+            sha256: node.sha256.as_deref().map(str::to_lowercase),
+            download_cache: None,
+            repository_path,
+            link_path,
+            alias_link_paths: node
+                .also_link_as
+                .iter()
+                .map(|alias| setup.autoload_plugins_dir.join(alias))
+                .collect(),
+            after_update_kak: node.after_update_kak.clone(),
+            log_paths: node.log_paths.clone(),
+            features: node.features.clone(),
+            trusted: node.trusted,
+            proxy: proxy.map(String::from),
+            url_rewrites: BTreeMap::new(),
+            references: Vec::new(),
+            flag_breaking_changes: false,
+            force_adopt: setup.force_adopt,
+            deadline: setup.deadline,
+            last_build,
+            rebuild: setup.rebuild,
+            commit_age: setup.commit_age,
+            reviewed: false,
+            bin_dir: setup.bin_dir(),
+            runner: setup.runner.clone(),
+        }
+    }
+
+    fn repository_path_exists(&self) -> bool {
+        fs::metadata(&self.repository_path).is_ok()
+    }
+
+    pub fn manage(self) -> Result<Status, PluginError> {
+        let status = self.fetch()?;
+
+        // A plugin skipped before it was ever installed has nothing to link.
+        if !matches!(status, Status::Skipped { config: None, .. }) {
+            self.symlink()?;
+        }
+
+        Ok(status)
+    }
+
+    /// Installs or updates the plugin, without linking it into `autoload`,
+    /// from the source handling it.
+    pub fn fetch(&self) -> Result<Status, PluginError> {
+        self.fetch_from(source::of(self))
+    }
+
+    /// Installs or updates the plugin from `source`, without linking it into
+    /// `autoload`.
+    pub fn fetch_from(&self, source: &dyn Source) -> Result<Status, PluginError> {
+        if self.past_deadline() {
+            return Ok(self.skipped());
+        }
+
+        let result = if self.repository_path_exists() {
+            source.update(self)
+        } else {
+            source.install(self)
+        };
+
+        match result {
+            // Failures past the deadline are the git processes killed for it.
+            Err(_) if self.past_deadline() => Ok(self.skipped()),
+            result => result,
+        }
+    }
+
+    /// What the plugin has to be built from, now that `status` fetched it,
+    /// if it has build commands to run. Fresh checkouts are always built;
+    /// others only when their revision or their build commands changed since
+    /// their last successful build, or with `--rebuild`.
+    pub fn pending_build(&self, status: &Status) -> Option<Build> {
+        let (revision, fresh) = match status {
+            Status::Installed { revision, .. } | Status::NoKakFiles { revision, .. } => {
+                (revision, true)
+            }
+            Status::Updated { new_revision, .. } => (new_revision, false),
+            Status::Unchanged {
+                revision: Some(revision),
+                ..
+            } => (revision, false),
+            _ => return None,
+        };
+
+        let build = Build {
+            revision: revision.clone(),
+            commands: self.manifest().build,
+        };
+
+        let built = !fresh && !self.rebuild && self.last_build.as_ref() == Some(&build);
+        (!build.commands.is_empty() && !built).then_some(build)
+    }
+
+    pub fn needs_build(&self, status: &Status) -> bool {
+        self.pending_build(status).is_some()
+    }
+
+    /// Runs the build commands of the plugin's manifest, if it needs to be
+    /// built now that `status` fetched it. Fetching doesn't, so that builds
+    /// can be scheduled apart. Untrusted plugins are built inside `sandbox`,
+    /// when bubblewrap can make one. A failed build leaves the plugin as it was
+    /// before, so that the next update tries again: new checkouts are deleted,
+    /// along with their links, and updated ones go back to their previous
+    /// revision.
+    pub fn build(&self, status: Status, sandbox: &Sandbox) -> Result<Status, PluginError> {
+        if !self.needs_build(&status) {
+            return Ok(status);
+        }
+
+        fs::create_dir_all(&self.bin_dir).map_err(|e| {
+            PluginError::Build(
+                self.name.clone(),
+                format!("couldn't create {}: {e}", self.bin_dir.to_string_lossy()),
+            )
+        })?;
+
+        let sandboxed = sandbox.confines(self);
+
+        let failure = self.manifest().build.iter().find_map(|build| {
+            let mut command = self.build_command(build, sandboxed);
+
+            match self.runner.output(&mut command, self.deadline) {
+                Ok(output) if output.status.success() => None,
+                Ok(output) => Some(format!(
+                    "{build:?} exited with status {}: {}",
+                    output.status.code().unwrap_or(-1),
+                    String::from_utf8_lossy(&output.stderr).trim_end()
+                )),
+                Err(error) => Some(format!("couldn't run {build:?}: {error}")),
+            }
+        });
+
+        let Some(failure) = failure else {
+            return Ok(status);
+        };
+
+        match &status {
+            Status::Updated { old_revision, .. } => {
+                let _ = self.git(
+                    &["reset", "--quiet", "--hard", old_revision],
+                    PluginError::Pull,
+                );
+            }
+            // Whatever the last successful build left is still there.
+            Status::Unchanged { .. } => (),
+            _ => {
+                let _ = fs::remove_dir_all(&self.repository_path);
+
+                for link_path in self.link_paths() {
+                    if fs::read_link(link_path).is_ok_and(|target| target == self.repository_path) {
+                        let _ = fs::remove_file(link_path);
+                    }
+                }
+            }
+        }
+
+        // Builds running past the deadline are killed for it.
+        if self.past_deadline() {
+            return Ok(self.skipped());
+        }
+
+        Err(PluginError::Build(self.name.clone(), failure))
+    }
+
+    /// The command running `build` in the checkout, inside bubblewrap's
+    /// sandbox if `sandboxed`: the whole system is read-only there, but for
+    /// the checkout, the bin directory and a private `/tmp`.
+    fn build_command(&self, build: &str, sandboxed: bool) -> Command {
+        let mut command = if sandboxed {
+            let mut command = Command::new("bwrap");
+            command
+                .args(SANDBOX)
+                .arg("--bind")
+                .args([&self.repository_path, &self.repository_path])
+                .arg("--bind")
+                .args([&self.bin_dir, &self.bin_dir])
+                .args(["--", "sh"]);
+            command
+        } else {
+            Command::new("sh")
+        };
+
+        command
+            .arg("-c")
+            .arg(build)
+            .current_dir(&self.repository_path)
+            .env("ALMOXARIFE_BIN", &self.bin_dir);
+
+        command
+    }
+
+    fn past_deadline(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// The plugin left as it was, with its configuration if it's installed.
+    fn skipped(&self) -> Status {
+        Status::Skipped {
+            name: self.name.clone(),
+            config: self.repository_path_exists().then(|| self.config()),
+        }
+    }
+
+    /// Activates an already installed plugin without reaching the network.
+    pub fn activate(self) -> Result<Status, PluginError> {
+        let status = self.inspect()?;
+        self.symlink()?;
+        Ok(status)
+    }
+
+    /// Like `activate`, but without linking the plugin into `autoload`.
+    pub fn inspect(&self) -> Result<Status, PluginError> {
+        let name = self.name.clone();
+
+        if !self.repository_path_exists() {
+            return Err(PluginError::Link(
+                name,
+                "the plugin is not installed".into(),
+            ));
+        }
+
+        let config = self.config();
+
+        if self.is_local {
+            Ok(Status::Local { name, config })
+        } else {
+            Ok(Status::Unchanged {
+                name,
+                config,
+                revision: None,
+                renamed_branch: None,
+                last_commit: None,
+            })
+        }
+    }
+
+    pub fn is_installed(&self) -> bool {
+        self.repository_path_exists()
+    }
+
+    /// Whether the plugin's files are a git checkout of Almoxarife's, rather
+    /// than a local folder or a downloaded file.
+    pub fn is_checkout(&self) -> bool {
+        !self.is_local && !self.single_file
+    }
+
+    /// Whether the repository provides any Kakoune script. A repository
+    /// without any usually means a typo in the plugin's URL.
+    pub fn has_kak_files(&self) -> bool {
+        kakscript::kak_files(&self.repository_path).is_ok_and(|files| !files.is_empty())
+    }
+
+    /// The modules the plugin's Kakoune scripts provide.
+    pub fn modules(&self) -> Vec<String> {
+        kakscript::kak_files(&self.repository_path)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|file| fs::read_to_string(file).ok())
+            .flat_map(|source| kakscript::modules(&source))
+            .collect()
+    }
+
+    /// The paths inside `autoload` the plugin is linked at.
+    pub fn link_paths(&self) -> impl Iterator<Item = &PathBuf> {
+        iter::once(&self.link_path).chain(&self.alias_link_paths)
+    }
+
+    /// Links the plugin into `autoload`. Links already pointing to the
+    /// checkout are left alone, and other links in their place are replaced.
+    /// Files and directories in their place, like a copy of the plugin
+    /// installed by hand, are only replaced with `--force-adopt`.
+    fn symlink(&self) -> Result<(), PluginError> {
+        for link_path in self.link_paths() {
+            let error = |e: io::Error| {
+                PluginError::Link(
+                    self.name.clone(),
+                    format!("{}: {}", e, link_path.to_string_lossy()),
+                )
+            };
+
+            match fs::read_link(link_path) {
+                Ok(target) if target == self.repository_path => continue,
+                Ok(_) => fs::remove_file(link_path).map_err(error)?,
+                Err(_) if link_path.symlink_metadata().is_err() => (),
+
+                Err(_) if !self.force_adopt => {
+                    return Err(PluginError::Link(
+                        self.name.clone(),
+                        format!(
+                            "{} conflicts with an existing file not managed by Almoxarife; \
+                            move it away, or run al --force-adopt to replace it",
+                            link_path.to_string_lossy()
+                        ),
+                    ));
+                }
+
+                Err(_) if link_path.is_dir() => fs::remove_dir_all(link_path).map_err(error)?,
+                Err(_) => fs::remove_file(link_path).map_err(error)?,
+            }
+
+            unix::fs::symlink(&self.repository_path, link_path).map_err(error)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn clone_repo(&self, url: &str) -> Result<(), PluginError> {
+        let mut command = self.git_command();
+        command.arg("clone");
+
+        if let Some(name) = &self.remotes.name {
+            command.args(["--origin", name]);
+        }
+
+        // The objects are copied over, so that deleting the other checkout
+        // doesn't break this one.
+        if let Some(reference) = self.references.iter().find(|path| path.exists()) {
+            command
+                .arg("--reference-if-able")
+                .arg(reference)
+                .arg("--dissociate");
+        }
+
+        command.arg(clone_url(url)).arg(&self.repository_path);
+
+        let output = self
+            .runner
+            .output(&mut command, self.deadline)
+            .map_err(|e| PluginError::Clone(self.name.clone(), e.to_string()))?;
+
+        match output.status.code() {
+            None | Some(0) => Ok(()),
+            Some(code) => Err(PluginError::Clone(
+                self.name.clone(),
+                git_failure(code, &output.stderr),
+            )),
+        }
+    }
+
+    /// Adds the extra remotes missing from the checkout, and points the
+    /// existing ones to the configured URLs.
+    pub fn sync_remotes(&self, error: fn(Name, Message) -> PluginError) -> Result<(), PluginError> {
+        for (name, url) in &self.remotes.extra {
+            match self.git(&["remote", "get-url", name], error) {
+                Ok(current) if current.trim() == url => (),
+                Ok(_) => {
+                    self.git(&["remote", "set-url", name, url], error)?;
+                }
+                Err(_) => {
+                    self.git(&["remote", "add", name, url], error)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Brings the repository, currently at `revision`, up to date. Returns
+    /// whether upstream history was rewritten.
+    pub fn update(&self, revision: &str) -> Result<bool, PluginError> {
+        match &self.rev {
+            // A pinned plugin only reaches the network if it's not at the
+            // right revision yet. Tags and branches are compared by the
+            // commit they name.
+            Some(rev) if self.resolve(rev).is_some_and(|commit| commit == revision) => Ok(false),
+
+            Some(rev) => {
+                self.git(&["fetch", "--quiet"], PluginError::Pull)?;
+                self.detach(rev, PluginError::Pull)?;
+                Ok(false)
+            }
+
+            None => match &self.tag_pattern {
+                Some(pattern) => {
+                    self.git(&["fetch", "--quiet", "--tags"], PluginError::Pull)?;
+                    let tag = self.newest_tag(pattern, PluginError::Pull)?;
 
-impl Plugin {
-    fn new(name: String, node: &PluginTree, parent: Option<String>, setup: &Setup) -> Plugin {
-        let link_path = setup.autoload_plugins_dir.join(&name);
+                    if revision != tag {
+                        self.detach(&tag, PluginError::Pull)?;
+                    }
 
-        let (is_local, repository_path) = if is_local(&node.location) {
-            (true, PathBuf::from(&node.location))
-        } else {
-            (false, setup.almoxarife_data_dir.join(&name))
-        };
+                    Ok(false)
+                }
 
-        Plugin {
-            name,
-            parent,
-            has_children: !node.children.is_empty(),
-            config: node.config.clone(),
-            location: node.location.clone(),
-            is_local,
-            repository_path,
-            link_path,
-            #[cfg(test)]
-            env: setup.env.clone(),
+                None => {
+                    self.reattach()?;
+                    self.pull()
+                }
+            },
         }
     }
 
-    fn repository_path_exists(&self) -> bool {
-        fs::metadata(&self.repository_path).is_ok()
+    /// The commit `rev` names in the checkout, if it's there already.
+    fn resolve(&self, rev: &str) -> Option<String> {
+        let commit = self
+            .git(
+                &[
+                    "rev-parse",
+                    "--verify",
+                    "--quiet",
+                    &format!("{rev}^{{commit}}"),
+                ],
+                PluginError::Pull,
+            )
+            .ok()?;
+
+        Some(commit.trim().to_string())
     }
 
-    pub fn manage(self) -> Result<Status, PluginError> {
-        let config = self.config();
-        let name = self.name.clone();
+    /// Checks out `rev`, for pinned plugins and plugins following tags. The
+    /// branch left behind is recorded in the checkout's git config, so that
+    /// it's checked out again once the plugin follows its branch anew.
+    pub fn detach(
+        &self,
+        rev: &str,
+        error: fn(Name, Message) -> PluginError,
+    ) -> Result<(), PluginError> {
+        if let Ok(branch) = self.git(&["symbolic-ref", "--quiet", "--short", "HEAD"], error) {
+            self.git(&["config", BRANCH_KEY, branch.trim()], error)?;
+        }
 
-        let status = match (self.is_local, self.repository_path_exists()) {
-            (true, true) => Status::Local { name, config },
+        self.git(&["checkout", "--quiet", "--detach", rev], error)?;
+        Ok(())
+    }
 
-            (true, false) => {
-                return Err(PluginError::Link(
-                    name,
-                    format!("the path {} is empty", self.location),
-                ))
+    /// Checks out the branch recorded by `detach` again, if the checkout is
+    /// still detached. Checkouts detached before branches were recorded go
+    /// back to the default branch of their remote.
+    fn reattach(&self) -> Result<(), PluginError> {
+        if self
+            .git(&["symbolic-ref", "--quiet", "HEAD"], PluginError::Pull)
+            .is_ok()
+        {
+            return Ok(());
+        }
+
+        let branch = match self.git(&["config", "--get", BRANCH_KEY], PluginError::Pull) {
+            Ok(branch) => branch.trim().to_string(),
+
+            Err(_) => {
+                let remote = self.remotes.name.as_deref().unwrap_or("origin");
+
+                // Detached by the user, since there's nothing to go back to.
+                let Ok(head) = self.git(
+                    &[
+                        "symbolic-ref",
+                        "--quiet",
+                        "--short",
+                        &format!("refs/remotes/{remote}/HEAD"),
+                    ],
+                    PluginError::Pull,
+                ) else {
+                    return Ok(());
+                };
+
+                let head = head.trim();
+                head.strip_prefix(&format!("{remote}/"))
+                    .unwrap_or(head)
+                    .to_string()
             }
+        };
 
-            (false, true) => match self.pull()? {
-                None => Status::Unchanged { name, config },
-                Some(log) => Status::Updated { name, log, config },
-            },
+        self.git(&["switch", "--quiet", &branch], PluginError::Pull)?;
+        let _ = self.git(&["config", "--unset", BRANCH_KEY], PluginError::Pull);
+        Ok(())
+    }
 
-            (false, false) => {
-                self.clone_repo(&self.location)?;
-                Status::Installed { name, config }
-            }
+    /// Makes the checkout follow the default branch of its remote, in case
+    /// upstream renamed it, as from `master` to `main`: the local branch is
+    /// renamed along, unless the user named it otherwise, and tracks the new
+    /// one. Returns the old and new names if it was renamed. Pinned plugins,
+    /// and plugins following tags, follow no branch.
+    pub fn follow_default_branch(&self) -> Result<Option<(String, String)>, PluginError> {
+        if self.rev.is_some() || self.tag_pattern.is_some() {
+            return Ok(None);
+        }
+
+        let remote = self.remotes.name.as_deref().unwrap_or("origin");
+
+        // Detached checkouts, or branches tracking nothing, were set up by
+        // the user, and are left alone.
+        let Ok(branches) = self.git(
+            &["rev-parse", "--abbrev-ref", "HEAD", "@{upstream}"],
+            PluginError::Pull,
+        ) else {
+            return Ok(None);
         };
 
-        self.symlink()?;
-        Ok(status)
+        let mut branches = branches.lines();
+        let (Some(local), Some(upstream)) = (branches.next(), branches.next()) else {
+            return Ok(None);
+        };
+
+        let Some(old) = upstream.strip_prefix(&format!("{remote}/")) else {
+            return Ok(None);
+        };
+
+        // A single round trip, fetching nothing. Remotes out of reach are
+        // left for the pull to report.
+        let Ok(head) = self.git(
+            &["ls-remote", "--symref", remote, "HEAD"],
+            PluginError::Pull,
+        ) else {
+            return Ok(None);
+        };
+
+        let new = head.lines().find_map(|line| {
+            line.strip_prefix("ref: refs/heads/")?
+                .strip_suffix("\tHEAD")
+        });
+
+        let Some(new) = new.filter(|&new| new != old) else {
+            return Ok(None);
+        };
+
+        self.git(&["fetch", "--quiet", remote, new], PluginError::Pull)?;
+
+        if local == old {
+            self.git(&["branch", "--quiet", "-m", old, new], PluginError::Pull)?;
+        }
+
+        let tracked = format!("{remote}/{new}");
+        self.git(
+            &["branch", "--quiet", "--set-upstream-to", &tracked],
+            PluginError::Pull,
+        )?;
+        self.git(&["remote", "set-head", remote, new], PluginError::Pull)?;
+
+        Ok(Some((old.to_string(), new.to_string())))
     }
 
-    fn symlink(&self) -> Result<(), PluginError> {
-        unix::fs::symlink(&self.repository_path, &self.link_path).map_err(|e| {
-            PluginError::Link(
+    /// The newest of the tags matching `pattern`, in version order.
+    pub fn newest_tag(
+        &self,
+        pattern: &str,
+        error: fn(Name, Message) -> PluginError,
+    ) -> Result<String, PluginError> {
+        let tags = self.git(
+            &["tag", "--list", "--sort=-version:refname", pattern],
+            error,
+        )?;
+
+        tags.lines().next().map(String::from).ok_or_else(|| {
+            error(
                 self.name.clone(),
-                format!("{}: {}", e, self.link_path.to_string_lossy()),
+                format!("there's no tag matching {pattern}"),
             )
         })
     }
 
-    fn clone_repo(&self, url: &str) -> Result<(), PluginError> {
-        let location = format!("{url}.git");
+    /// When the newest commit fetched from the plugin's remotes was made, in
+    /// seconds since the Unix epoch. Checkouts without remote branches, like
+    /// restored bundles, give their own newest commit instead.
+    pub fn last_upstream_commit(&self) -> Result<u64, PluginError> {
+        let time = |args: &[&str]| -> Result<Option<u64>, PluginError> {
+            let output = self.git(args, PluginError::Pull)?;
+            Ok(output.trim().parse().ok())
+        };
 
-        let mut command = Command::new("git");
-        command
-            .arg("clone")
-            .arg(location)
-            .arg(&self.repository_path)
-            .stdout(Stdio::null())
-            .stderr(Stdio::piped());
+        let upstream = time(&["log", "-1", "--format=%ct", "--remotes"])?;
 
-        #[cfg(test)]
-        command.envs(&self.env);
+        match upstream {
+            Some(upstream) => Ok(upstream),
+            None => time(&["log", "-1", "--format=%ct", "HEAD"])?.ok_or_else(|| {
+                PluginError::Pull(self.name.clone(), "the checkout has no commits".into())
+            }),
+        }
+    }
 
-        let output = command
-            .output()
-            .map_err(|e| PluginError::Clone(self.name.clone(), e.to_string()))?;
+    /// Whether the plugin follows the tags matching its `tag_pattern`.
+    pub fn follows_tags(&self) -> bool {
+        self.rev.is_none() && self.tag_pattern.is_some()
+    }
 
-        match output.status.code() {
-            None | Some(0) => Ok(()),
-            Some(code) => Err(PluginError::Clone(
-                self.name.clone(),
-                format!(
-                    "git exited with status {}: {}",
-                    code,
-                    String::from_utf8_lossy(&output.stderr)
-                ),
-            )),
+    /// The revision the plugin is at. Plugins following tags are at the tag,
+    /// when there's one right at the checked out commit, so that updates read
+    /// as version transitions.
+    pub fn current_version(&self) -> Result<String, PluginError> {
+        if self.follows_tags()
+            && let Some(tag) = self.current_tag()
+        {
+            return Ok(tag);
         }
+
+        self.current_revision()
     }
 
-    fn pull(&self) -> Result<Option<String>, PluginError> {
-        let old_revision = self.current_revision()?;
+    /// The tag right at the checked out commit, if any.
+    pub fn current_tag(&self) -> Option<String> {
+        self.git(
+            &["describe", "--tags", "--exact-match", "HEAD"],
+            PluginError::Pull,
+        )
+        .ok()
+        .map(|tag| tag.trim().to_string())
+    }
 
+    /// A git command reaching the plugin's remotes through its proxy, if any,
+    /// and at the URLs given by `url_rewrites`. Rewriting is left to git's
+    /// `insteadOf`, so that checkouts cloned before a rule was added follow it
+    /// too.
+    fn git_command(&self) -> Command {
         let mut command = Command::new("git");
+
+        if let Some(proxy) = &self.proxy {
+            command.arg("-c").arg(format!("http.proxy={proxy}"));
+        }
+
+        for (from, to) in &self.url_rewrites {
+            command.arg("-c").arg(format!("url.{to}.insteadOf={from}"));
+        }
+
         command
-            .arg("pull")
-            .current_dir(&self.repository_path)
-            .stdout(Stdio::null())
-            .stderr(Stdio::piped());
+    }
+
+    /// Runs git inside the plugin's repository, returning its output. Failures
+    /// are reported with the `error` variant.
+    pub fn git(
+        &self,
+        args: &[&str],
+        error: fn(Name, Message) -> PluginError,
+    ) -> Result<String, PluginError> {
+        let mut command = self.git_command();
+        command.current_dir(&self.repository_path).args(args);
+
+        let output = self
+            .runner
+            .output(&mut command, self.deadline)
+            .map_err(|e| error(self.name.clone(), e.to_string()))?;
+
+        match output.status.code() {
+            None | Some(0) => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+            Some(code) => Err(error(self.name.clone(), git_failure(code, &output.stderr))),
+        }
+    }
+
+    /// Compacts the checkout's object store, if git finds it worth it.
+    pub fn maintain(&self) -> Result<(), PluginError> {
+        self.git(&["gc", "--auto", "--quiet"], PluginError::Maintain)
+            .map(|_| ())
+    }
 
-        #[cfg(test)]
-        command.envs(&self.env);
+    /// Pulls the new commits of the branch. If upstream history was
+    /// rewritten, which can't be pulled, the checkout is reset to it instead,
+    /// and `true` returned.
+    #[allow(clippy::collapsible_if)]
+    fn pull(&self) -> Result<bool, PluginError> {
+        let mut command = self.git_command();
+        command
+            .args(["pull", "--ff-only"])
+            .current_dir(&self.repository_path);
 
-        let output = command
-            .output()
+        let output = self
+            .runner
+            .output(&mut command, self.deadline)
             .map_err(|e| PluginError::Pull(self.name.clone(), e.to_string()))?;
 
         if let Some(code) = output.status.code() {
             if code != 0 {
+                if self.history_rewritten() {
+                    self.git(
+                        &["reset", "--quiet", "--hard", "@{upstream}"],
+                        PluginError::Pull,
+                    )?;
+
+                    return Ok(true);
+                }
+
                 return Err(PluginError::Pull(
                     self.name.clone(),
-                    format!(
-                        "git exited with status {}: {}",
-                        code,
-                        String::from_utf8_lossy(&output.stderr)
-                    ),
+                    git_failure(code, &output.stderr),
                 ));
             }
         }
 
-        let new_revision = self.current_revision()?;
+        Ok(false)
+    }
+
+    /// Whether upstream history was rewritten since the last fetch: the
+    /// checkout was part of the branch before, but isn't anymore. A checkout
+    /// that was never part of it has commits of its own, which are better not
+    /// thrown away.
+    fn history_rewritten(&self) -> bool {
+        let is_ancestor = |upstream: &str| {
+            let mut command = self.git_command();
+            command.current_dir(&self.repository_path).args([
+                "merge-base",
+                "--is-ancestor",
+                "HEAD",
+                upstream,
+            ]);
+
+            self.runner
+                .output(&mut command, self.deadline)
+                .is_ok_and(|output| output.status.success())
+        };
 
-        if old_revision == new_revision {
-            return Ok(None);
-        }
+        !is_ancestor("@{upstream}") && is_ancestor("@{upstream}@{1}")
+    }
 
-        self.log(old_revision, new_revision).map(Some)
+    /// The plugin's manifest, if it ships one that can be read, with the
+    /// features the user asked for. Manifests that can't be read are reported
+    /// after updates.
+    pub fn manifest(&self) -> Manifest {
+        Manifest::load(&self.repository_path)
+            .ok()
+            .flatten()
+            .unwrap_or_default()
+            .with_features(&self.features)
     }
 
     pub fn config(&self) -> String {
+        let manifest = self.manifest();
+        let module = manifest.module.as_deref().unwrap_or(&self.name);
+
+        // The user's configuration comes last, so that it wins.
+        let config = match (manifest.config.trim_end(), self.config.as_str()) {
+            ("", config) => config.to_string(),
+            (recommended, "") => recommended.to_string(),
+            (recommended, config) => format!("{recommended}\n{config}"),
+        };
+
+        let config = if self.lazy {
+            self.lazy_config(module, &config)
+        } else {
+            self.eager_config(module, &config)
+        };
+
+        match &self.sessions {
+            Some(sessions) => self.session_config(sessions, &config),
+            None => config,
+        }
+    }
+
+    /// Generates the config of a plugin loaded right away.
+    fn eager_config(&self, module: &str, config: &str) -> String {
         match (&self.parent, self.has_children) {
             (None, false) => {
                 format!(
-                    "try %[ require-module {plugin} ]
+                    "try %[ require-module {module} ]
 {config}
-",
-                    plugin = self.name,
-                    config = self.config
+"
                 )
             }
 
+            // Children wait for a module named after the plugin, provided
+            // empty if the plugin's own module is named otherwise.
             (None, true) => format!(
-                "try %[ require-module {plugin} ] catch %[
+                "{own}try %[ require-module {plugin} ] catch %[
     provide-module {plugin} ''
     require-module {plugin}
 ]
 {config}
 ",
+                own = self.own_module(module, ""),
                 plugin = self.name,
-                config = self.config
             ),
 
             (Some(parent), false) => format!(
                 "hook -once global ModuleLoaded {parent} %[
-    try %[ require-module {plugin} ]
+    try %[ require-module {module} ]
     {config}
 ]
-",
-                plugin = self.name,
-                parent = parent,
-                config = self.config
+"
             ),
 
             (Some(parent), true) => format!(
                 "hook -once global ModuleLoaded {parent} %[
-    try %[ require-module {plugin} ] catch %[
+{own}    try %[ require-module {plugin} ] catch %[
         provide-module {plugin} ''
         require-module {plugin}
     ]
     {config}
 ]
 ",
+                own = self.own_module(module, "    "),
                 plugin = self.name,
-                parent = parent,
-                config = self.config
             ),
         }
     }
 
-    fn current_revision(&self) -> Result<String, PluginError> {
-        let mut command = Command::new("git");
+    /// Wraps `config` so that it only runs in the sessions whose name matches
+    /// `sessions`, as a whole. Kakoune matches the filters of hooks the same
+    /// way, so a user hook triggered with the session's name does the
+    /// matching, and is removed right after.
+    fn session_config(&self, sessions: &str, config: &str) -> String {
+        let group = format!("almoxarife-sessions-{}", self.name);
+        let sessions = sessions.replace('\'', "''");
+
+        format!(
+            "hook -group {group} global User 'almoxarife-session=(?:{sessions})' %[
+{config}]
+trigger-user-hook \"almoxarife-session=%val{{session}}\"
+remove-hooks global {group}
+"
+        )
+    }
+
+    /// Requires `module`, indented by `indent`, if it isn't named after the
+    /// plugin.
+    fn own_module(&self, module: &str, indent: &str) -> String {
+        if module == self.name {
+            String::new()
+        } else {
+            format!("{indent}try %[ require-module {module} ]\n")
+        }
+    }
+
+    /// Generates the config of a lazy plugin: instead of requiring its module
+    /// right away, every command the plugin defines is aliased to a stub that
+    /// requires the module and then re-dispatches the call. Once the module is
+    /// loaded, the aliases are removed and the user config is run.
+    ///
+    /// Top level `map` commands of the user config are still run at startup,
+    /// so that keys can trigger the loading.
+    ///
+    /// As with eager plugins, the parent is loaded first, and children wait
+    /// for a module named after the plugin.
+    fn lazy_config(&self, module: &str, config: &str) -> String {
+        let commands: Vec<String> = kakscript::kak_files(&self.repository_path)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|file| kakscript::read(file).ok())
+            .flat_map(|source| kakscript::declarations(&source))
+            .filter_map(|(_, declaration)| match declaration {
+                kakscript::Declaration::Command(command) => Some(command),
+                _ => None,
+            })
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        let (mappings, config): (Vec<&str>, Vec<&str>) = config.lines().partition(|line| {
+            !line.starts_with([' ', '\t'])
+                && matches!(
+                    kakscript::declarations(line).first(),
+                    Some((_, kakscript::Declaration::Mapping { .. }))
+                )
+        });
+
+        let unaliases: String = commands
+            .iter()
+            .map(|command| format!("    unalias global {command} almoxarife-lazy-{command}\n"))
+            .collect();
+
+        let parent = match &self.parent {
+            Some(parent) => format!("    try %[ require-module {parent} ]\n"),
+            None => String::new(),
+        };
+
+        let own = if self.has_children {
+            format!(
+                "{own}    try %[ require-module {plugin} ] catch %[
+        provide-module {plugin} ''
+        require-module {plugin}
+    ]
+",
+                own = self.own_module(module, "    "),
+                plugin = self.name,
+            )
+        } else {
+            format!("    try %[ require-module {module} ]\n")
+        };
+
+        let stubs: String = commands
+            .iter()
+            .map(|command| {
+                format!(
+                    "define-command -hidden -params .. almoxarife-lazy-{command} %[
+    unalias global {command} almoxarife-lazy-{command}
+{parent}{own}    {command} %arg{{@}}
+]
+alias global {command} almoxarife-lazy-{command}
+"
+                )
+            })
+            .collect();
+
+        let config: String = config.iter().map(|line| format!("    {line}\n")).collect();
+        let mappings: String = mappings.iter().map(|line| format!("{line}\n")).collect();
+
+        format!(
+            "hook -once global ModuleLoaded {module} %[
+{unaliases}{config}]
+{stubs}{mappings}"
+        )
+    }
+
+    #[allow(clippy::collapsible_if)]
+    pub fn current_revision(&self) -> Result<String, PluginError> {
+        let mut command = self.git_command();
         command
             .current_dir(&self.repository_path)
             .args(["rev-parse", "HEAD"]);
 
-        #[cfg(test)]
-        command.envs(&self.env);
-
-        let output = command
-            .output()
+        let output = self
+            .runner
+            .output(&mut command, self.deadline)
             .map_err(|e| PluginError::Pull(self.name.clone(), e.to_string()))?;
 
         if let Some(code) = output.status.code() {
             if code != 0 {
                 return Err(PluginError::Pull(
                     self.name.clone(),
-                    format!(
-                        "git exited with status {}: {}",
-                        code,
-                        String::from_utf8_lossy(&output.stderr)
-                    ),
+                    git_failure(code, &output.stderr),
                 ));
             }
         }
@@ -536,10 +2882,44 @@ impl Plugin {
         Ok(revision)
     }
 
-    fn log(&self, old_revision: String, new_revision: String) -> Result<String, PluginError> {
+    /// Looks for hints that the commits between the two revisions break
+    /// something: changes to files like `CHANGELOG.md`, or commit messages
+    /// announcing a `BREAKING CHANGE`, as in Conventional Commits. Failing to
+    /// look is no reason to fail the update, so it gives no hints.
+    pub fn breaking_changes(&self, old_revision: &str, new_revision: &str) -> Vec<String> {
+        let mut hints: Vec<String> = self
+            .git(
+                &["diff", "--name-only", old_revision, new_revision],
+                PluginError::Pull,
+            )
+            .unwrap_or_default()
+            .lines()
+            .filter(|file| {
+                Path::new(file)
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().to_uppercase())
+                    .is_some_and(|stem| CHANGE_NOTES.contains(&stem.as_str()))
+            })
+            .map(|file| format!("{file} changed"))
+            .collect();
+
+        let range = format!("{old_revision}..{new_revision}");
+        let messages = self
+            .git(&["log", &range, "--format=%B"], PluginError::Pull)
+            .unwrap_or_default();
+
+        if messages.contains("BREAKING CHANGE") || messages.contains("BREAKING-CHANGE") {
+            hints.push("a commit announces a breaking change".to_string());
+        }
+
+        hints
+    }
+
+    #[allow(clippy::collapsible_if)]
+    pub fn log(&self, old_revision: &str, new_revision: &str) -> Result<String, PluginError> {
         let range = format!("{old_revision}..{new_revision}");
 
-        let mut command = Command::new("git");
+        let mut command = self.git_command();
         command.current_dir(&self.repository_path).args([
             "log",
             &range,
@@ -548,22 +2928,20 @@ impl Plugin {
             "--reverse",
         ]);
 
-        #[cfg(test)]
-        command.envs(&self.env);
+        if !self.log_paths.is_empty() {
+            command.arg("--").args(&self.log_paths);
+        }
 
-        let output = command
-            .output()
+        let output = self
+            .runner
+            .output(&mut command, self.deadline)
             .map_err(|e| PluginError::Pull(self.name.clone(), e.to_string()))?;
 
         if let Some(code) = output.status.code() {
             if code != 0 {
                 return Err(PluginError::Pull(
                     self.name.clone(),
-                    format!(
-                        "git exited with status {}: {}",
-                        code,
-                        String::from_utf8_lossy(&output.stderr)
-                    ),
+                    git_failure(code, &output.stderr),
                 ));
             }
         }
@@ -572,20 +2950,136 @@ impl Plugin {
     }
 }
 
+/// The names, without extension, of the files whose changes hint at breaking
+/// changes.
+const CHANGE_NOTES: [&str; 6] = [
+    "BREAKING",
+    "CHANGELOG",
+    "CHANGES",
+    "MIGRATING",
+    "NEWS",
+    "UPGRADING",
+];
+
+/// Deletes the repository of a plugin removed from the configuration. A
+/// repository that is already gone counts as deleted.
+/// Children wait for a module named after their parent, which stands in for
+/// the parent's own module when that one is named otherwise. Warns about the
+/// parents among `plugins` whose modules are named otherwise but aren't
+/// required on their own, since their children then load before them, or,
+/// for lazy parents, never load at all.
+pub fn nesting_warnings(plugins: &[Plugin]) -> Vec<String> {
+    let mut children: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+
+    for plugin in plugins {
+        if let Some(parent) = &plugin.parent {
+            children.entry(parent).or_default().push(&plugin.name);
+        }
+    }
+
+    children
+        .into_iter()
+        .filter_map(|(parent, children)| {
+            let parent = plugins.iter().find(|plugin| plugin.name == parent)?;
+            let modules = parent.modules();
+            let manifest = parent.manifest();
+            let module = manifest.module.as_deref().unwrap_or(&parent.name);
+
+            // Without modules, there's nothing to tell them apart.
+            let loads = modules.is_empty()
+                || modules.iter().any(|provided| provided == module)
+                    && (!parent.lazy || module == parent.name);
+
+            if loads {
+                return None;
+            }
+
+            Some(format!(
+                "the children of {name} ({}) wait for module {name}, but {name} provides {}; name the plugin after its module in almoxarife.yaml",
+                children.join(", "),
+                modules.join(", "),
+                name = parent.name,
+            ))
+        })
+        .collect()
+}
+
+pub fn remove_repository(path: &Path) -> Result<Status, PluginError> {
+    let name: String = path
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into();
+
+    let error = match fs::remove_dir_all(path) {
+        Ok(_) => return Ok(Status::Deleted { name }),
+        Err(error) => error,
+    };
+
+    let path = path.to_string_lossy();
+
+    let message = match error.kind() {
+        io::ErrorKind::NotFound => return Ok(Status::Deleted { name }),
+
+        io::ErrorKind::PermissionDenied => {
+            format!("permission denied; check the permissions of {path}")
+        }
+
+        io::ErrorKind::ResourceBusy => {
+            format!("{path} is in use by another program")
+        }
+
+        io::ErrorKind::ReadOnlyFilesystem => {
+            format!("{path} is in a read-only file system")
+        }
+
+        _ => format!("{error} ({path})"),
+    };
+
+    Err(PluginError::Delete(name, message))
+}
+
 #[derive(Debug, PartialEq)]
 pub enum Status {
     Installed {
         name: String,
         config: String,
+        revision: String,
+        /// The tag pointing at `revision`, if any.
+        tag: Option<String>,
+    },
+    /// Installed, but the repository has no `.kak` file.
+    NoKakFiles {
+        name: String,
+        config: String,
+        revision: String,
+        tag: Option<String>,
     },
     Updated {
         name: String,
         log: String,
         config: String,
+        old_revision: String,
+        new_revision: String,
+        /// Upstream history was rewritten, by a force push, and the checkout
+        /// was reset to it.
+        history_rewritten: bool,
+        /// Hints the update has breaking changes, when they're looked for.
+        breaking_changes: Vec<String>,
+        /// The default branch upstream renamed, from its old name to its new
+        /// one, which the checkout follows now.
+        renamed_branch: Option<(String, String)>,
     },
     Unchanged {
         name: String,
         config: String,
+        /// Unknown when the plugin was activated without running git.
+        revision: Option<String>,
+        /// As in `Updated`: the revision may be the same under a new name.
+        renamed_branch: Option<(String, String)>,
+        /// When the newest upstream commit was made, in seconds since the
+        /// Unix epoch, if asked for.
+        last_commit: Option<u64>,
     },
     Local {
         name: String,
@@ -594,50 +3088,193 @@ pub enum Status {
     Deleted {
         name: String,
     },
+    /// Left alone because the run was interrupted before getting to it.
+    Interrupted {
+        name: String,
+    },
+    /// Left as it was because the update ran past `--max-time`. The
+    /// configuration is there if the plugin was installed already.
+    Skipped {
+        name: String,
+        config: Option<String>,
+    },
+}
+
+impl Status {
+    pub fn name(&self) -> &str {
+        match self {
+            Status::Installed { name, .. }
+            | Status::NoKakFiles { name, .. }
+            | Status::Updated { name, .. }
+            | Status::Unchanged { name, .. }
+            | Status::Local { name, .. }
+            | Status::Deleted { name }
+            | Status::Interrupted { name }
+            | Status::Skipped { name, .. } => name,
+        }
+    }
 }
 
-pub struct Kak<W: Write>(W);
+/// The `source` commands loading every `.kak` file of the given repositories.
+fn plugins_kak_sources(repositories: &[&Path]) -> String {
+    let mut sources = String::new();
 
-impl Kak<File> {
-    fn new(path: &Path) -> Result<Kak<File>, SetupError> {
-        let file = File::create(path).context("couldn't create almoxarife.kak file")?;
-        Ok(Kak(file))
+    for repository in repositories {
+        for file in kakscript::kak_files(repository).unwrap_or_default() {
+            // Single quotes are escaped by doubling them in Kakoune strings.
+            let file = file.to_string_lossy().replace('\'', "''");
+            sources.push_str(&format!("source '{file}'\n"));
+        }
     }
+
+    sources
+}
+
+/// Writes the script loading the plugins. The plugins' configs are kept
+/// until the script is closed, and then written in a stable order, whatever
+/// the order plugins got done in.
+pub struct Kak<W: Write> {
+    out: W,
+    configs: Vec<(String, String)>,
 }
 
-#[cfg(test)]
 impl Kak<Vec<u8>> {
     pub fn with_buffer() -> Self {
-        Kak(Vec::new())
+        Kak {
+            out: Vec::new(),
+            configs: Vec::new(),
+        }
     }
 
     pub fn bytes(&self) -> &[u8] {
-        &self.0
+        &self.out
     }
 }
 
 impl<W: Write> Kak<W> {
-    pub fn write_prelude(&mut self) -> Result<(), SetupError> {
-        let prelude = r"hook global KakBegin .* %🧺
-add-highlighter shared/almoxarife regions
-add-highlighter shared/almoxarife/ region '^\s*config:\s+\|' '^\s*\w+:' ref kakrc
-add-highlighter shared/almoxarife/ region '^\s*config:[^\n]' '\n' ref kakrc
-hook -group almoxarife global WinCreate .*almoxarife[.]yaml %{
+    /// Writes what comes before the plugins' configs. `status` is the file
+    /// setting `almoxarife_status`, if there's one to source, `report` the
+    /// transcript of the last update, if any, which `almoxarife-report` shows,
+    /// `bin` the directory of the executables plugins built, if any, which
+    /// `almoxarife_bin` tells plugins about, and `workspaces` the directory of
+    /// the workspaces `almoxarife-workspace` loads, if any.
+    pub fn write_prelude(
+        &mut self,
+        status: Option<&Path>,
+        report: Option<&Path>,
+        bin: Option<&Path>,
+        workspaces: Option<&Path>,
+    ) -> Result<(), SetupError> {
+        self.write(
+            b"declare-option -docstring 'the result of the last update by Almoxarife' str almoxarife_status\n",
+        )?;
+
+        if let Some(bin) = bin {
+            let option = format!(
+                "declare-option -docstring 'where plugins built by Almoxarife install their executables' str almoxarife_bin '{}'\n",
+                bin.to_string_lossy().replace('\'', "''")
+            );
+
+            self.write(option.as_bytes())?;
+        }
+
+        if let Some(status) = status {
+            let source = format!(
+                "try %{{ source '{}' }}\n",
+                status.to_string_lossy().replace('\'', "''")
+            );
+
+            self.write(source.as_bytes())?;
+        }
+
+        if let Some(report) = report {
+            let command = format!(
+                "define-command -docstring 'show what the last update by Almoxarife did' almoxarife-report %{{
+    edit -existing -readonly '{}'
+    rename-buffer -scratch *almoxarife-report*
+    set-option buffer filetype almoxarife-report
+}}
+",
+                report.to_string_lossy().replace('\'', "''")
+            );
+
+            self.write(command.as_bytes())?;
+        }
+
+        if let Some(workspaces) = workspaces {
+            // The option keeps the path from being parsed again as commands.
+            let command = format!(
+                "declare-option -hidden str almoxarife_workspaces '{}'
+define-command -params 1 -shell-script-candidates %{{ ls \"$kak_opt_almoxarife_workspaces\" }} -docstring 'almoxarife-workspace <name>: load the plugins of an Almoxarife workspace' almoxarife-workspace %{{
+    source \"%opt{{almoxarife_workspaces}}/%arg{{1}}/plugins.kak\"
+}}
+",
+                workspaces.to_string_lossy().replace('\'', "''")
+            );
+
+            self.write(command.as_bytes())?;
+        }
+
+        // Highlighting waits for Kakoune's own highlighters to be defined.
+        self.write("hook global KakBegin .* %🧺\n".as_bytes())?;
+        self.write(schema::highlighters().as_bytes())?;
+
+        let prelude = r"hook -group almoxarife global WinCreate .*almoxarife[.]yaml %{
     add-highlighter window/almoxarife ref almoxarife
     hook -once -always window WinClose .* %{ remove-highlighter window/almoxarife }
 }
+hook -group almoxarife global WinCreate .*almoxarife[.]toml %{
+    add-highlighter window/almoxarife-manifest ref almoxarife-manifest
+    hook -once -always window WinClose .* %{ remove-highlighter window/almoxarife-manifest }
+}
+add-highlighter shared/almoxarife-report group
+add-highlighter shared/almoxarife-report/ regex '^\S[^\n]*:$' 0:title
+add-highlighter shared/almoxarife-report/ regex '^ *\S+ (installed|updated|history rewritten, reset)\b' 1:string
+add-highlighter shared/almoxarife-report/ regex '^ *\S+ (failed|skipped, parent failed|interrupted|timed out)$' 1:Error
+add-highlighter shared/almoxarife-report/ regex '^([0-9a-f]{7,}) ' 1:comment
+hook -group almoxarife global WinSetOption filetype=almoxarife-report %{
+    add-highlighter window/almoxarife-report ref almoxarife-report
+    hook -once -always window WinSetOption filetype=.* %{ remove-highlighter window/almoxarife-report }
+}
 ";
         self.write(prelude.as_bytes())
     }
 
+    /// Writes what comes before the configs of a workspace's plugins, run
+    /// right away when the workspace is loaded, Kakoune being up by then.
+    pub fn write_workspace_prelude(&mut self) -> Result<(), SetupError> {
+        self.write("evaluate-commands %🧺\n".as_bytes())
+    }
+
     pub fn write(&mut self, data: &[u8]) -> Result<(), SetupError> {
-        self.0.write_all(data).context("error writing kak file")
+        self.out.write_all(data).context("error writing kak file")
     }
 
-    pub fn close(&mut self) -> Result<(), SetupError> {
-        self.0
-            .write_all("🧺".as_bytes())
-            .context("error writing kak file")
+    /// Keeps the config of the plugin `name` to write once the script is
+    /// closed.
+    pub fn write_config(&mut self, name: &str, config: String) {
+        self.configs.push((name.to_string(), config));
+    }
+
+    /// Writes the plugins' configs, following `order`, a list of plugin names
+    /// as `order::sort` gives it, and ends the script. Configs of plugins
+    /// missing from `order` go last, as they came.
+    pub fn close(&mut self, order: &[String]) -> Result<(), SetupError> {
+        let rank = |name: &str| {
+            order
+                .iter()
+                .position(|ordered| ordered == name)
+                .unwrap_or(order.len())
+        };
+
+        let mut configs = mem::take(&mut self.configs);
+        configs.sort_by_key(|(name, _)| rank(name));
+
+        for (_, config) in configs {
+            self.write(config.as_bytes())?;
+        }
+
+        self.write("🧺".as_bytes())
     }
 }
 
@@ -664,7 +3301,7 @@ impl From<serde_yaml::Error> for SetupError {
     }
 }
 
-trait Context<A> {
+pub trait Context<A> {
     fn context(self, message: &str) -> Result<A, SetupError>;
 }
 
@@ -677,6 +3314,54 @@ impl<A, E: error::Error> Context<A> for result::Result<A, E> {
     }
 }
 
+/// Separates the explanation of a git failure from git's own output, which
+/// is only shown with `--verbose`.
+const GIT_OUTPUT: &str = "\ngit's output: ";
+
+/// Describes git exiting with `code`, explaining the failure when it's a
+/// common one.
+fn git_failure(code: i32, stderr: &[u8]) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    let failure = format!("git exited with status {code}: {stderr}");
+
+    match explain_git_failure(&stderr) {
+        Some(explanation) => format!("{explanation}{GIT_OUTPUT}{failure}"),
+        None => failure,
+    }
+}
+
+/// What a common git failure means, and what to do about it, going by git's
+/// error output.
+pub fn explain_git_failure(stderr: &str) -> Option<&'static str> {
+    let stderr = stderr.to_lowercase();
+
+    let explanation = if stderr.contains("permission denied (publickey)") {
+        "the SSH key was refused — check that it's added to your account on the forge, or use an https URL"
+    } else if stderr.contains("host key verification failed") {
+        "the host's SSH key isn't known yet — connect to it once with ssh to accept it"
+    } else if stderr.contains("could not resolve host")
+        || stderr.contains("could not resolve hostname")
+    {
+        "couldn't find the host — check your network connection and the URL"
+    } else if stderr.contains("timed out") {
+        "the connection timed out — check your network connection"
+    } else if stderr.contains("authentication failed")
+        || stderr.contains("could not read username")
+        || stderr.contains("terminal prompts disabled")
+    {
+        "authentication required — the repository may be private, or the URL may be wrong"
+    } else if stderr.contains("repository not found")
+        || (stderr.contains("repository") && stderr.contains("not found"))
+        || stderr.contains("does not appear to be a git repository")
+    {
+        "repository not found — check the URL for typos"
+    } else {
+        return None;
+    };
+
+    Some(explanation)
+}
+
 type Name = String;
 type Message = String;
 
@@ -686,6 +3371,9 @@ pub enum PluginError {
     Pull(Name, Message),
     Link(Name, Message),
     Delete(Name, Message),
+    Maintain(Name, Message),
+    Build(Name, Message),
+    Verify(Name, Message),
 }
 
 impl PluginError {
@@ -695,44 +3383,54 @@ impl PluginError {
             PluginError::Pull(name, _) => name,
             PluginError::Link(name, _) => name,
             PluginError::Delete(name, _) => name,
+            PluginError::Maintain(name, _) => name,
+            PluginError::Build(name, _) => name,
+            PluginError::Verify(name, _) => name,
         }
     }
-}
-
-impl Display for PluginError {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            PluginError::Clone(name, message) => {
-                write!(
-                    f,
-                    "{}:\ncould not clone: {message}",
-                    name.color(Colors::RedFg)
-                )
-            }
 
-            PluginError::Pull(name, message) => {
-                write!(
-                    f,
-                    "{}:\ncould not update: {message}",
-                    name.color(Colors::RedFg)
-                )
+    /// The same error without git's own output, when it comes with an
+    /// explanation.
+    pub fn without_git_output(self) -> PluginError {
+        let brief = |message: Message| match message.split_once(GIT_OUTPUT) {
+            Some((explanation, _)) => {
+                format!("{explanation} (run with --verbose to see git's output)")
             }
+            None => message,
+        };
 
-            PluginError::Link(name, message) => {
-                write!(
-                    f,
-                    "{}:\ncould not activate: {message}",
-                    name.color(Colors::RedFg)
-                )
-            }
+        match self {
+            PluginError::Clone(name, message) => PluginError::Clone(name, brief(message)),
+            PluginError::Pull(name, message) => PluginError::Pull(name, brief(message)),
+            PluginError::Link(name, message) => PluginError::Link(name, brief(message)),
+            PluginError::Delete(name, message) => PluginError::Delete(name, brief(message)),
+            PluginError::Maintain(name, message) => PluginError::Maintain(name, brief(message)),
+            PluginError::Build(name, message) => PluginError::Build(name, brief(message)),
+            PluginError::Verify(name, message) => PluginError::Verify(name, brief(message)),
+        }
+    }
 
-            PluginError::Delete(name, message) => {
-                write!(
-                    f,
-                    "{}:\ncould not delete: {message}",
-                    name.color(Colors::RedFg)
-                )
-            }
+    /// What went wrong, without the plugin's name.
+    pub fn description(&self) -> String {
+        match self {
+            PluginError::Clone(_, message) => format!("could not clone: {message}"),
+            PluginError::Pull(_, message) => format!("could not update: {message}"),
+            PluginError::Link(_, message) => format!("could not activate: {message}"),
+            PluginError::Delete(_, message) => format!("could not delete: {message}"),
+            PluginError::Maintain(_, message) => format!("could not maintain: {message}"),
+            PluginError::Build(_, message) => format!("could not build: {message}"),
+            PluginError::Verify(_, message) => format!("could not verify: {message}"),
         }
     }
 }
+
+impl Display for PluginError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:\n{}",
+            self.plugin().color(Colors::RedFg),
+            self.description()
+        )
+    }
+}
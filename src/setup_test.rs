@@ -1,24 +1,37 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
-use std::env;
+use std::collections::HashSet;
 use std::fs;
+use std::os::unix;
 use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 use tempfile::TempDir;
 
+use crate::gitconfig::GitConfig;
+use crate::runner_test::FakeRunner;
+use crate::setup;
+use crate::setup::Integration;
 use crate::setup::Kak;
 use crate::setup::Plugin;
 use crate::setup::PluginError;
+use crate::setup::Remotes;
 use crate::setup::Setup;
 use crate::setup::Status;
 
 #[test]
 fn new_setup() {
-    let setup = Setup::with_env(
-        [
-            ("HOME", "custom-home".to_string()),
-            ("XDG_DATA_HOME", "custom-data".to_string()),
-            ("XDG_CONFIG_HOME", "custom-config".to_string()),
-        ]
-        .into(),
+    let setup = Setup::with_runner(
+        FakeRunner::with_env(
+            [
+                ("HOME", "custom-home".to_string()),
+                ("XDG_DATA_HOME", "custom-data".to_string()),
+                ("XDG_CONFIG_HOME", "custom-config".to_string()),
+            ]
+            .into(),
+        )
+        .runner(),
     );
 
     assert_eq!(
@@ -37,6 +50,161 @@ fn new_setup() {
     );
 }
 
+#[test]
+fn new_setup_state_and_kakoune_dirs() {
+    let mut setup = Setup::with_runner(
+        FakeRunner::with_env(
+            [
+                ("HOME", "custom-home".to_string()),
+                ("XDG_CONFIG_HOME", "custom-config".to_string()),
+                ("XDG_STATE_HOME", "custom-state".to_string()),
+                ("KAKOUNE_CONFIG_DIR", "custom-kak".to_string()),
+            ]
+            .into(),
+        )
+        .runner(),
+    );
+
+    assert_eq!(
+        setup.state_path,
+        Path::new("custom-state/almoxarife/state.yaml")
+    );
+    assert_eq!(setup.kakrc, Path::new("custom-kak/kakrc"));
+    assert_eq!(
+        setup.almoxarife_kak,
+        Path::new("custom-kak/autoload/almoxarife/almoxarife.kak")
+    );
+
+    setup.set_kak_config_dir(Path::new("other-kak"));
+    assert_eq!(setup.autoload_dir, Path::new("other-kak/autoload"));
+    assert_eq!(
+        setup.autoload_plugins_dir,
+        Path::new("other-kak/autoload/almoxarife")
+    );
+}
+
+#[test]
+fn setup_target() {
+    let mut setup = Setup::with_runner(
+        FakeRunner::with_env(
+            [
+                ("HOME", "custom-home".to_string()),
+                ("XDG_DATA_HOME", "custom-data".to_string()),
+                ("XDG_STATE_HOME", "custom-state".to_string()),
+            ]
+            .into(),
+        )
+        .runner(),
+    );
+
+    setup.set_target(Path::new("custom-home/kak-testing"));
+
+    assert_eq!(
+        setup.almoxarife_yaml_path,
+        Path::new("custom-home/kak-testing/almoxarife.yaml")
+    );
+    assert_eq!(
+        setup.autoload_plugins_dir,
+        Path::new("custom-home/kak-testing/autoload/almoxarife")
+    );
+    assert_eq!(
+        setup.almoxarife_data_dir,
+        Path::new("custom-data/almoxarife-kak-testing")
+    );
+    assert_eq!(
+        setup.state_path,
+        Path::new("custom-state/almoxarife-kak-testing/state.yaml")
+    );
+    assert_eq!(
+        setup.dev_overrides_path,
+        Path::new("custom-state/almoxarife-kak-testing/dev.yaml")
+    );
+}
+
+#[test]
+fn setup_writable_root() {
+    let dir = TempDir::new().unwrap();
+    let root = dir.path().join("al");
+    let mut setup = Setup {
+        kakrc: dir.path().join("kakrc"),
+        ..Setup::default()
+    };
+
+    assert_eq!(
+        setup.integration(Integration::Autoload),
+        Integration::Autoload
+    );
+    assert_eq!(setup.missing_source_line(), None);
+
+    setup.set_writable_root(&root);
+
+    assert_eq!(setup.almoxarife_data_dir, root.join("plugins"));
+    assert_eq!(setup.plugins_kak, root.join("plugins.kak"));
+    assert_eq!(setup.state_path, root.join("state.yaml"));
+    assert_eq!(setup.dev_overrides_path, root.join("dev.yaml"));
+    assert!(setup.autoload_plugins_dir.starts_with(&root));
+    assert_eq!(
+        setup.integration(Integration::Autoload),
+        Integration::Source
+    );
+
+    let line = format!("source '{}'", root.join("plugins.kak").to_string_lossy());
+    assert_eq!(setup.missing_source_line(), Some(line.clone()));
+
+    fs::write(&setup.kakrc, format!("{line}\n")).unwrap();
+    assert_eq!(setup.missing_source_line(), None);
+}
+
+#[test]
+fn setup_workspace() {
+    let mut setup = Setup::with_runner(
+        FakeRunner::with_env(
+            [
+                ("HOME", "custom-home".to_string()),
+                ("XDG_DATA_HOME", "custom-data".to_string()),
+                ("XDG_STATE_HOME", "custom-state".to_string()),
+            ]
+            .into(),
+        )
+        .runner(),
+    );
+
+    setup.set_workspace("writing");
+
+    assert_eq!(
+        setup.almoxarife_yaml_path,
+        Path::new("custom-home/.config/almoxarife-writing.yaml")
+    );
+    assert_eq!(
+        setup.almoxarife_data_dir,
+        Path::new("custom-data/almoxarife/workspaces/writing/plugins")
+    );
+    assert_eq!(
+        setup.plugins_kak,
+        Path::new("custom-data/almoxarife/workspaces/writing/plugins.kak")
+    );
+    assert_eq!(
+        setup.state_path,
+        Path::new("custom-state/almoxarife/workspaces/writing/state.yaml")
+    );
+    assert!(!setup.autoload_plugins_dir.starts_with(&setup.autoload_dir));
+    assert_eq!(
+        setup.integration(Integration::Autoload),
+        Integration::Source
+    );
+    // Loaded on demand, it never asks for a line in the kakrc.
+    assert_eq!(setup.missing_source_line(), None);
+
+    let mut kak = Kak::with_buffer();
+    kak.write_workspace_prelude().unwrap();
+    kak.write(b"set global an-option 19\n").unwrap();
+    kak.close(&[]).unwrap();
+    assert_eq!(
+        kak.bytes(),
+        "evaluate-commands %🧺\nset global an-option 19\n🧺".as_bytes()
+    );
+}
+
 #[test]
 fn create_dirs() {
     let temp_dir = TempDir::new().unwrap();
@@ -48,7 +216,7 @@ fn create_dirs() {
         almoxarife_data_dir: almoxarife_data_dir.clone(),
         autoload_dir: autoload_dir.clone(),
         autoload_plugins_dir: autoload_plugins_dir.clone(),
-        env: add_tests_executables_to_path(),
+        runner: FakeRunner::new().runner(),
         ..Default::default()
     };
 
@@ -65,6 +233,137 @@ fn create_dirs() {
     assert!(runtime_dir.metadata().is_ok());
 }
 
+#[test]
+fn create_dirs_keeps_links() {
+    let temp_dir = TempDir::new().unwrap();
+    let autoload_dir = temp_dir.path().join("autoload");
+    let autoload_plugins_dir = autoload_dir.join("almoxarife");
+    fs::create_dir_all(&autoload_plugins_dir).unwrap();
+    unix::fs::symlink(temp_dir.path(), autoload_plugins_dir.join("luar")).unwrap();
+
+    let setup = Setup {
+        almoxarife_data_dir: temp_dir.path().join("data"),
+        autoload_dir,
+        autoload_plugins_dir: autoload_plugins_dir.clone(),
+        ..Default::default()
+    };
+
+    setup.create_dirs().unwrap();
+    assert!(autoload_plugins_dir.join("luar").is_symlink());
+}
+
+#[test]
+fn remove_stale_links() {
+    let temp_dir = TempDir::new().unwrap();
+    let autoload_plugins_dir = temp_dir.path().join("almoxarife");
+    fs::create_dir_all(autoload_plugins_dir.join("old-directory")).unwrap();
+
+    for name in ["luar", "peneira"] {
+        unix::fs::symlink(temp_dir.path(), autoload_plugins_dir.join(name)).unwrap();
+    }
+
+    let setup = Setup {
+        almoxarife_kak: autoload_plugins_dir.join("almoxarife.kak"),
+        autoload_plugins_dir: autoload_plugins_dir.clone(),
+        ..Default::default()
+    };
+
+    fs::write(&setup.almoxarife_kak, "").unwrap();
+
+    let luar = autoload_plugins_dir.join("luar");
+    setup
+        .remove_stale_links(&HashSet::from([luar.as_path()]))
+        .unwrap();
+
+    let mut left: Vec<_> = fs::read_dir(&autoload_plugins_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name())
+        .collect();
+
+    left.sort();
+    assert_eq!(left, ["almoxarife.kak", "luar"]);
+}
+
+#[test]
+fn plugin_links_are_repaired() {
+    let temp_dir = TempDir::new().unwrap();
+    let repository_path = temp_dir.path().join("luar");
+    fs::create_dir(&repository_path).unwrap();
+
+    let link_dir = temp_dir.path().join("link");
+    fs::create_dir_all(link_dir.join("luar.kak")).unwrap();
+    unix::fs::symlink(temp_dir.path(), link_dir.join("luar")).unwrap();
+
+    let plugin = Plugin {
+        name: "luar".into(),
+        parent: None,
+        has_children: false,
+        required_by: None,
+        location: repository_path.to_string_lossy().into(),
+        is_local: true,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
+        config: String::new(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
+        repository_path: repository_path.clone(),
+        link_path: link_dir.join("luar"),
+        alias_link_paths: vec![link_dir.join("luar.kak")],
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: Default::default(),
+    };
+
+    // The directory in the way of luar.kak wasn't made by Almoxarife.
+    let alias = link_dir.join("luar.kak").to_string_lossy().into_owned();
+    assert_eq!(
+        plugin.clone().activate().unwrap_err(),
+        PluginError::Link(
+            "luar".into(),
+            format!(
+                "{alias} conflicts with an existing file not managed by Almoxarife; \
+                move it away, or run al --force-adopt to replace it"
+            )
+        )
+    );
+    assert!(link_dir.join("luar.kak").is_dir());
+
+    let plugin = Plugin {
+        force_adopt: true,
+        ..plugin
+    };
+
+    plugin.activate().unwrap();
+
+    assert_eq!(
+        fs::read_link(link_dir.join("luar")).unwrap(),
+        repository_path
+    );
+    assert_eq!(
+        fs::read_link(link_dir.join("luar.kak")).unwrap(),
+        repository_path
+    );
+}
+
 #[test]
 fn create_dirs_kakoune_error() {
     let temp_dir = TempDir::new().unwrap();
@@ -72,17 +371,14 @@ fn create_dirs_kakoune_error() {
     let autoload_plugins_dir = autoload_dir.join("almoxarife");
     let almoxarife_data_dir = temp_dir.path().join("data");
 
-    let mut env = add_tests_executables_to_path();
-    env.insert(
-        "ALMOXARIFE_TEST_FAIL",
-        "unable to bind listen socket".into(),
-    );
+    let fake = FakeRunner::new();
+    fake.reply("kak", &[], 1, "", "unable to bind listen socket");
 
     let setup = Setup {
         almoxarife_data_dir: almoxarife_data_dir.clone(),
         autoload_dir: autoload_dir.clone(),
         autoload_plugins_dir: autoload_plugins_dir.clone(),
-        env,
+        runner: fake.runner(),
         ..Default::default()
     };
 
@@ -96,24 +392,99 @@ fn create_dirs_kakoune_error() {
 #[test]
 fn write_kak_file() {
     let mut kak = Kak::with_buffer();
-    kak.write_prelude().unwrap();
+    kak.write_prelude(
+        Some(Path::new("/state/it's/status.kak")),
+        Some(Path::new("/state/it's/report.txt")),
+        Some(Path::new("/data/bin")),
+        Some(Path::new("/data/workspaces")),
+    )
+    .unwrap();
     kak.write(b"require-module a-plugin\n").unwrap();
     kak.write(b"set global an-option 19\n").unwrap();
-    kak.close().unwrap();
-    let expected = r"hook global KakBegin .* %🧺
+    kak.close(&[]).unwrap();
+    let expected = r#"declare-option -docstring 'the result of the last update by Almoxarife' str almoxarife_status
+declare-option -docstring 'where plugins built by Almoxarife install their executables' str almoxarife_bin '/data/bin'
+try %{ source '/state/it''s/status.kak' }
+define-command -docstring 'show what the last update by Almoxarife did' almoxarife-report %{
+    edit -existing -readonly '/state/it''s/report.txt'
+    rename-buffer -scratch *almoxarife-report*
+    set-option buffer filetype almoxarife-report
+}
+declare-option -hidden str almoxarife_workspaces '/data/workspaces'
+define-command -params 1 -shell-script-candidates %{ ls "$kak_opt_almoxarife_workspaces" } -docstring 'almoxarife-workspace <name>: load the plugins of an Almoxarife workspace' almoxarife-workspace %{
+    source "%opt{almoxarife_workspaces}/%arg{1}/plugins.kak"
+}
+hook global KakBegin .* %🧺
 add-highlighter shared/almoxarife regions
-add-highlighter shared/almoxarife/ region '^\s*config:\s+\|' '^\s*\w+:' ref kakrc
-add-highlighter shared/almoxarife/ region '^\s*config:[^\n]' '\n' ref kakrc
+add-highlighter shared/almoxarife/ region '^\s*(config|after_update_kak):\s+\|' '^\s*[\w.-]+:' ref kakrc
+add-highlighter shared/almoxarife/ region '^\s*(config|after_update_kak):[^\n]' '\n' ref kakrc
+add-highlighter shared/almoxarife/fields default-region regex '^\s*(location|file|sha256|disabled|lazy|sessions|order|rev|tag_pattern|remote|remotes|also_link_as|log_paths|features|trusted|proxy|upstream):' 1:keyword
+add-highlighter shared/almoxarife-manifest regions
+add-highlighter shared/almoxarife-manifest/ region '^\s*config\s*=\s*"""' '"""' ref kakrc
+add-highlighter shared/almoxarife-manifest/ region "^\s*config\s*=\s*'''" "'''" ref kakrc
 hook -group almoxarife global WinCreate .*almoxarife[.]yaml %{
     add-highlighter window/almoxarife ref almoxarife
     hook -once -always window WinClose .* %{ remove-highlighter window/almoxarife }
 }
+hook -group almoxarife global WinCreate .*almoxarife[.]toml %{
+    add-highlighter window/almoxarife-manifest ref almoxarife-manifest
+    hook -once -always window WinClose .* %{ remove-highlighter window/almoxarife-manifest }
+}
+add-highlighter shared/almoxarife-report group
+add-highlighter shared/almoxarife-report/ regex '^\S[^\n]*:$' 0:title
+add-highlighter shared/almoxarife-report/ regex '^ *\S+ (installed|updated|history rewritten, reset)\b' 1:string
+add-highlighter shared/almoxarife-report/ regex '^ *\S+ (failed|skipped, parent failed|interrupted|timed out)$' 1:Error
+add-highlighter shared/almoxarife-report/ regex '^([0-9a-f]{7,}) ' 1:comment
+hook -group almoxarife global WinSetOption filetype=almoxarife-report %{
+    add-highlighter window/almoxarife-report ref almoxarife-report
+    hook -once -always window WinSetOption filetype=.* %{ remove-highlighter window/almoxarife-report }
+}
 require-module a-plugin
 set global an-option 19
-🧺";
+🧺"#;
     assert_eq!(kak.bytes(), expected.as_bytes());
 }
 
+#[test]
+fn back_up_and_restore_kak_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let setup = Setup {
+        almoxarife_kak: temp_dir.path().join("almoxarife.kak"),
+        state_path: temp_dir.path().join("state/state.yaml"),
+        ..Default::default()
+    };
+
+    let write = |text: &str| {
+        let mut kak = Kak::with_buffer();
+        kak.write(text.as_bytes()).unwrap();
+        setup.write_almoxarife_kak(&kak).unwrap();
+    };
+
+    assert!(!setup.restore_script(&setup.almoxarife_kak).unwrap());
+
+    for text in ["first", "second", "second", "third", "fourth", "fifth"] {
+        write(text);
+    }
+
+    let backups = temp_dir.path().join("state/backups");
+    assert_eq!(
+        fs::read_to_string(backups.join("almoxarife.kak.1")).unwrap(),
+        "fourth"
+    );
+    assert_eq!(
+        fs::read_to_string(backups.join("almoxarife.kak.3")).unwrap(),
+        "second"
+    );
+    assert!(!backups.join("almoxarife.kak.4").exists());
+
+    for expected in ["fourth", "third", "second"] {
+        assert!(setup.restore_script(&setup.almoxarife_kak).unwrap());
+        assert_eq!(fs::read_to_string(&setup.almoxarife_kak).unwrap(), expected);
+    }
+
+    assert!(!setup.restore_script(&setup.almoxarife_kak).unwrap());
+}
+
 #[test]
 fn parse_yaml() {
     let file = b"
@@ -149,12 +520,39 @@ fn parse_yaml() {
                 name: "auto-pairs".into(),
                 parent: None,
                 has_children: false,
+                required_by: None,
                 location: "https://github.com/alexherbo2/auto-pairs.kak".into(),
                 is_local: false,
+                single_file: false,
+                sha256: None,
+                download_cache: None,
                 config: Default::default(),
+                lazy: false,
+                sessions: None,
+                order: 0,
+                position: Some(15),
+                rev: None,
+                tag_pattern: None,
+                remotes: Remotes::default(),
                 repository_path: "~/.local/share/almoxarife/auto-pairs".into(),
                 link_path: "~/.config/kak/autoload/almoxarife/auto-pairs".into(),
-                env: Default::default(),
+                alias_link_paths: Vec::new(),
+                after_update_kak: String::new(),
+                log_paths: Vec::new(),
+                features: Vec::new(),
+                trusted: false,
+                proxy: None,
+                url_rewrites: Default::default(),
+                references: Vec::new(),
+                flag_breaking_changes: false,
+                force_adopt: false,
+                deadline: None,
+                last_build: None,
+                rebuild: false,
+                commit_age: false,
+                reviewed: false,
+                bin_dir: "~/.local/share/almoxarife/bin".into(),
+                runner: Default::default(),
             },
         ),
         (
@@ -163,12 +561,39 @@ fn parse_yaml() {
                 name: "luar".into(),
                 parent: None,
                 has_children: true,
+                required_by: None,
                 location: "https://github.com/gustavo-hms/luar".into(),
                 is_local: false,
+                single_file: false,
+                sha256: None,
+                download_cache: None,
                 config: "set-option global luar_interpreter luajit".into(),
+                lazy: false,
+                sessions: None,
+                order: 0,
+                position: Some(2),
+                rev: None,
+                tag_pattern: None,
+                remotes: Remotes::default(),
                 repository_path: "~/.local/share/almoxarife/luar".into(),
                 link_path: "~/.config/kak/autoload/almoxarife/luar".into(),
-                env: Default::default(),
+                alias_link_paths: Vec::new(),
+                after_update_kak: String::new(),
+                log_paths: Vec::new(),
+                features: Vec::new(),
+                trusted: false,
+                proxy: None,
+                url_rewrites: Default::default(),
+                references: Vec::new(),
+                flag_breaking_changes: false,
+                force_adopt: false,
+                deadline: None,
+                last_build: None,
+                rebuild: false,
+                commit_age: false,
+                reviewed: false,
+                bin_dir: "~/.local/share/almoxarife/bin".into(),
+                runner: Default::default(),
             },
         ),
         (
@@ -177,12 +602,39 @@ fn parse_yaml() {
                 name: "peneira".into(),
                 parent: Some("luar".into()),
                 has_children: true,
+                required_by: None,
                 location: "/home/gustavo-hms/peneira".into(),
                 is_local: true,
+                single_file: false,
+                sha256: None,
+                download_cache: None,
                 config: Default::default(),
+                lazy: false,
+                sessions: None,
+                order: 0,
+                position: Some(6),
+                rev: None,
+                tag_pattern: None,
+                remotes: Remotes::default(),
                 repository_path: "/home/gustavo-hms/peneira".into(),
                 link_path: "~/.config/kak/autoload/almoxarife/peneira".into(),
-                env: Default::default(),
+                alias_link_paths: Vec::new(),
+                after_update_kak: String::new(),
+                log_paths: Vec::new(),
+                features: Vec::new(),
+                trusted: false,
+                proxy: None,
+                url_rewrites: Default::default(),
+                references: Vec::new(),
+                flag_breaking_changes: false,
+                force_adopt: false,
+                deadline: None,
+                last_build: None,
+                rebuild: false,
+                commit_age: false,
+                reviewed: false,
+                bin_dir: "~/.local/share/almoxarife/bin".into(),
+                runner: Default::default(),
             },
         ),
         (
@@ -191,12 +643,39 @@ fn parse_yaml() {
                 name: "peneira-filters".into(),
                 parent: Some("peneira".into()),
                 has_children: false,
+                required_by: None,
                 location: "https://codeberg.org/mbauhardt/peneira-filters".into(),
                 is_local: false,
+                single_file: false,
+                sha256: None,
+                download_cache: None,
                 config: "map global normal <c-p> ': peneira-filters-mode<ret>'\n".into(),
+                lazy: false,
+                sessions: None,
+                order: 0,
+                position: Some(10),
+                rev: None,
+                tag_pattern: None,
+                remotes: Remotes::default(),
                 repository_path: "~/.local/share/almoxarife/peneira-filters".into(),
                 link_path: "~/.config/kak/autoload/almoxarife/peneira-filters".into(),
-                env: Default::default(),
+                alias_link_paths: Vec::new(),
+                after_update_kak: String::new(),
+                log_paths: Vec::new(),
+                features: Vec::new(),
+                trusted: false,
+                proxy: None,
+                url_rewrites: Default::default(),
+                references: Vec::new(),
+                flag_breaking_changes: false,
+                force_adopt: false,
+                deadline: None,
+                last_build: None,
+                rebuild: false,
+                commit_age: false,
+                reviewed: false,
+                bin_dir: "~/.local/share/almoxarife/bin".into(),
+                runner: Default::default(),
             },
         ),
     ]
@@ -244,12 +723,39 @@ fn parse_yaml_disabled_plugin() {
                 name: "auto-pairs".into(),
                 parent: None,
                 has_children: false,
+                required_by: None,
                 location: "https://github.com/alexherbo2/auto-pairs.kak".into(),
                 is_local: false,
+                single_file: false,
+                sha256: None,
+                download_cache: None,
                 config: Default::default(),
+                lazy: false,
+                sessions: None,
+                order: 0,
+                position: Some(15),
+                rev: None,
+                tag_pattern: None,
+                remotes: Remotes::default(),
                 repository_path: "~/.local/share/almoxarife/auto-pairs".into(),
                 link_path: "~/.config/kak/autoload/almoxarife/auto-pairs".into(),
-                env: Default::default(),
+                alias_link_paths: Vec::new(),
+                after_update_kak: String::new(),
+                log_paths: Vec::new(),
+                features: Vec::new(),
+                trusted: false,
+                proxy: None,
+                url_rewrites: Default::default(),
+                references: Vec::new(),
+                flag_breaking_changes: false,
+                force_adopt: false,
+                deadline: None,
+                last_build: None,
+                rebuild: false,
+                commit_age: false,
+                reviewed: false,
+                bin_dir: "~/.local/share/almoxarife/bin".into(),
+                runner: Default::default(),
             },
         ),
         (
@@ -258,12 +764,39 @@ fn parse_yaml_disabled_plugin() {
                 name: "luar".into(),
                 parent: None,
                 has_children: true,
+                required_by: None,
                 location: "https://github.com/gustavo-hms/luar".into(),
                 is_local: false,
+                single_file: false,
+                sha256: None,
+                download_cache: None,
                 config: "set-option global luar_interpreter luajit".into(),
+                lazy: false,
+                sessions: None,
+                order: 0,
+                position: Some(2),
+                rev: None,
+                tag_pattern: None,
+                remotes: Remotes::default(),
                 repository_path: "~/.local/share/almoxarife/luar".into(),
                 link_path: "~/.config/kak/autoload/almoxarife/luar".into(),
-                env: Default::default(),
+                alias_link_paths: Vec::new(),
+                after_update_kak: String::new(),
+                log_paths: Vec::new(),
+                features: Vec::new(),
+                trusted: false,
+                proxy: None,
+                url_rewrites: Default::default(),
+                references: Vec::new(),
+                flag_breaking_changes: false,
+                force_adopt: false,
+                deadline: None,
+                last_build: None,
+                rebuild: false,
+                commit_age: false,
+                reviewed: false,
+                bin_dir: "~/.local/share/almoxarife/bin".into(),
+                runner: Default::default(),
             },
         ),
     ]
@@ -272,18 +805,6 @@ fn parse_yaml_disabled_plugin() {
     assert_eq!(plugins, expected);
 }
 
-fn add_tests_executables_to_path() -> HashMap<&'static str, String> {
-    let project_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
-    let project_dir = Path::new(&project_dir);
-    let path = std::env::var("PATH").unwrap();
-
-    [(
-        "PATH",
-        format!("{}:{path}", project_dir.join("tests").to_string_lossy()),
-    )]
-    .into()
-}
-
 #[test]
 fn plugin_update_clone() {
     let temp_dir = tempfile::tempdir().unwrap();
@@ -297,7 +818,7 @@ fn plugin_update_clone() {
 
     let url = "https://github.com/occivink/kakoune-phantom-selection";
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     env.insert("ALMOXARIFE_TEST_LOCATION", url.to_string() + ".git");
     env.insert(
         "ALMOXARIFE_TEST_REPO_PATH",
@@ -308,12 +829,39 @@ fn plugin_update_clone() {
         name: "kakoune-phantom-selection".into(),
         parent: None,
         has_children: false,
+        required_by: None,
         location: url.to_string(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "map global normal f ': phantom-selection-add-selection<ret>'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path: link_path.clone(),
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
     };
 
     let status = plugin.manage().unwrap();
@@ -324,7 +872,9 @@ fn plugin_update_clone() {
             config: r"try %[ require-module kakoune-phantom-selection ]
 map global normal f ': phantom-selection-add-selection<ret>'
 "
-            .into()
+            .into(),
+            revision: "abcdef".into(),
+            tag: None,
         }
     );
 
@@ -332,6 +882,167 @@ map global normal f ': phantom-selection-add-selection<ret>'
     assert!(link_path.metadata().is_ok());
 }
 
+#[test]
+fn git_remotes_on_the_filesystem() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let mirror = temp_dir.path().join("mirrors/peneira.git");
+    fs::create_dir_all(mirror.join("objects")).unwrap();
+    fs::create_dir_all(mirror.join("refs")).unwrap();
+    fs::write(mirror.join("HEAD"), "ref: refs/heads/master\n").unwrap();
+    let scripts = temp_dir.path().join("kak-scripts");
+    fs::create_dir_all(&scripts).unwrap();
+
+    let file = format!(
+        "
+luar:
+    location: file:///srv/mirrors/luar.git
+peneira:
+    location: {}
+mine:
+    location: {}
+kak-lsp:
+    location: ssh://git.example.com/kak-lsp
+smarttab:
+    location: git://git.example.com/smarttab.kak
+",
+        mirror.to_string_lossy(),
+        scripts.to_string_lossy()
+    );
+
+    let setup = Setup {
+        almoxarife_data_dir: temp_dir.path().join("data"),
+        ..Setup::default()
+    };
+
+    let config = setup.config_from_buffer(file.as_bytes()).unwrap();
+    let plugins: HashMap<_, _> = config
+        .active_plugins()
+        .into_iter()
+        .map(|plugin| (plugin.name.clone(), plugin))
+        .collect();
+
+    // Bare repositories and file:// URLs are cloned into the data directory.
+    assert!(!plugins["luar"].is_local);
+    assert_eq!(
+        plugins["luar"].repository_path,
+        temp_dir.path().join("data/luar")
+    );
+    assert!(!plugins["peneira"].is_local);
+    assert_eq!(
+        plugins["peneira"].repository_path,
+        temp_dir.path().join("data/peneira")
+    );
+    assert!(plugins["mine"].is_local);
+    assert_eq!(plugins["mine"].repository_path, scripts);
+
+    // As is anything with a URL scheme.
+    assert!(!plugins["kak-lsp"].is_local);
+    assert!(!plugins["smarttab"].is_local);
+    assert_eq!(
+        plugins["smarttab"].repository_path,
+        temp_dir.path().join("data/smarttab")
+    );
+
+    assert_eq!(
+        setup::clone_url("file:///srv/mirrors/luar.git"),
+        "file:///srv/mirrors/luar.git"
+    );
+    assert_eq!(
+        setup::clone_url(&mirror.to_string_lossy()),
+        mirror.to_string_lossy()
+    );
+    assert_eq!(
+        setup::clone_url("https://github.com/gustavo-hms/peneira"),
+        "https://github.com/gustavo-hms/peneira.git"
+    );
+}
+
+#[test]
+fn plugin_update_clone_with_aliases() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kak-lsp");
+
+    let link_dir = temp_dir.path().join("link");
+    fs::create_dir(&link_dir).unwrap();
+    let link_path = link_dir.join("kak-lsp");
+    let alias_link_path = link_dir.join("kakoune-lsp");
+
+    let plugin = Plugin {
+        name: "kak-lsp".into(),
+        parent: None,
+        has_children: false,
+        required_by: None,
+        location: "https://github.com/kakoune-lsp/kakoune-lsp".into(),
+        is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
+        config: "".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
+        repository_path: repository_path.clone(),
+        link_path: link_path.clone(),
+        alias_link_paths: vec![alias_link_path.clone()],
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::new().runner(),
+    };
+
+    plugin.manage().unwrap();
+
+    // Both links point to the same checkout.
+    assert_eq!(fs::read_link(&link_path).unwrap(), repository_path);
+    assert_eq!(fs::read_link(&alias_link_path).unwrap(), repository_path);
+}
+
+#[test]
+fn dev_overrides() {
+    let file = b"
+luar:
+    location: https://github.com/gustavo-hms/luar
+    peneira:
+        location: https://github.com/gustavo-hms/peneira
+";
+
+    let setup = Setup::default();
+    let mut config = setup.config_from_buffer(file.as_slice()).unwrap();
+    config
+        .overrides
+        .0
+        .insert("peneira".into(), PathBuf::from("/home/me/src/peneira"));
+
+    let plugins: HashMap<_, _> = config
+        .active_plugins()
+        .into_iter()
+        .map(|plugin| (plugin.name.clone(), plugin))
+        .collect();
+
+    let peneira = &plugins["peneira"];
+    assert!(peneira.is_local);
+    assert_eq!(peneira.location, "/home/me/src/peneira");
+    assert_eq!(peneira.repository_path, Path::new("/home/me/src/peneira"));
+    assert_eq!(peneira.parent.as_deref(), Some("luar"));
+    assert!(!plugins["luar"].is_local);
+}
+
 #[test]
 fn plugin_update_clone_plugin_with_parent() {
     let temp_dir = tempfile::tempdir().unwrap();
@@ -345,7 +1056,7 @@ fn plugin_update_clone_plugin_with_parent() {
 
     let url = "https://github.com/gustavo-hms/peneira";
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     env.insert("ALMOXARIFE_TEST_LOCATION", url.to_string() + ".git");
     env.insert(
         "ALMOXARIFE_TEST_REPO_PATH",
@@ -356,12 +1067,39 @@ fn plugin_update_clone_plugin_with_parent() {
         name: "peneira".into(),
         parent: Some("luar".into()),
         has_children: false,
+        required_by: None,
         location: url.to_string(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "set-option global peneira_files_command 'rg --files'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path: link_path.clone(),
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
     };
 
     let status = plugin.manage().unwrap();
@@ -374,7 +1112,9 @@ fn plugin_update_clone_plugin_with_parent() {
     set-option global peneira_files_command 'rg --files'
 ]
 "
-            .into()
+            .into(),
+            revision: "abcdef".into(),
+            tag: None,
         }
     );
 
@@ -395,7 +1135,7 @@ fn plugin_update_clone_plugin_with_children() {
 
     let url = "https://github.com/gustavo-hms/peneira";
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     env.insert("ALMOXARIFE_TEST_LOCATION", url.to_string() + ".git");
     env.insert(
         "ALMOXARIFE_TEST_REPO_PATH",
@@ -406,12 +1146,39 @@ fn plugin_update_clone_plugin_with_children() {
         name: "peneira".into(),
         parent: None,
         has_children: true,
+        required_by: None,
         location: url.to_string(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "set-option global peneira_files_command 'rg --files'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path: link_path.clone(),
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
     };
 
     let status = plugin.manage().unwrap();
@@ -425,7 +1192,9 @@ fn plugin_update_clone_plugin_with_children() {
 ]
 set-option global peneira_files_command 'rg --files'
 "
-            .into()
+            .into(),
+            revision: "abcdef".into(),
+            tag: None,
         }
     );
 
@@ -446,7 +1215,7 @@ fn plugin_update_clone_plugin_with_parent_and_children() {
 
     let url = "https://github.com/gustavo-hms/peneira";
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     env.insert("ALMOXARIFE_TEST_LOCATION", url.to_string() + ".git");
     env.insert(
         "ALMOXARIFE_TEST_REPO_PATH",
@@ -457,12 +1226,39 @@ fn plugin_update_clone_plugin_with_parent_and_children() {
         name: "peneira".into(),
         parent: Some("luar".into()),
         has_children: true,
+        required_by: None,
         location: url.to_string(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "set-option global peneira_files_command 'rg --files'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path: link_path.clone(),
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
     };
 
     let status = plugin.manage().unwrap();
@@ -478,7 +1274,9 @@ fn plugin_update_clone_plugin_with_parent_and_children() {
     set-option global peneira_files_command 'rg --files'
 ]
 "
-            .into()
+            .into(),
+            revision: "abcdef".into(),
+            tag: None,
         }
     );
 
@@ -487,9 +1285,8 @@ fn plugin_update_clone_plugin_with_parent_and_children() {
 }
 
 #[test]
-fn plugin_update_clone_unexpected_git_fail() {
+fn plugin_update_clone_no_kak_files() {
     let temp_dir = tempfile::tempdir().unwrap();
-
     let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
 
     let link_dir = temp_dir.path().join("link");
@@ -498,84 +1295,315 @@ fn plugin_update_clone_unexpected_git_fail() {
 
     let url = "https://github.com/occivink/kakoune-phantom-selection";
 
-    let mut env = add_tests_executables_to_path();
-    env.insert("ALMOXARIFE_TEST_FAIL", "unexpected error!".to_string());
-    env.insert("ALMOXARIFE_TEST_LOCATION", url.to_string() + ".git");
-    env.insert(
-        "ALMOXARIFE_TEST_REPO_PATH",
-        repository_path.to_string_lossy().into(),
-    );
+    let mut env = HashMap::new();
+    env.insert("ALMOXARIFE_TEST_NO_KAK_FILES", "1".into());
 
     let plugin = Plugin {
         name: "kakoune-phantom-selection".into(),
         parent: None,
         has_children: false,
+        required_by: None,
         location: url.to_string(),
         is_local: false,
-        config: "map global normal f ': phantom-selection-add-selection<ret>'".into(),
+        single_file: false,
+        sha256: None,
+        download_cache: None,
+        config: String::new(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path: link_path.clone(),
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
     };
 
-    let error = plugin.manage().unwrap_err();
+    let status = plugin.manage().unwrap();
     assert_eq!(
-        error,
-        PluginError::Clone(
-            "kakoune-phantom-selection".into(),
-            "git exited with status 1: unexpected error!".into()
-        )
+        status,
+        Status::NoKakFiles {
+            name: "kakoune-phantom-selection".into(),
+            config: "try %[ require-module kakoune-phantom-selection ]\n\n".into(),
+            revision: "abcdef".into(),
+            tag: None,
+        }
     );
+
+    // The plugin is still activated.
+    assert!(link_path.is_symlink());
 }
 
 #[test]
-fn plugin_update_clone_link_error() {
+fn plugin_update_clone_unexpected_git_fail() {
     let temp_dir = tempfile::tempdir().unwrap();
 
     let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
 
-    // By not creating the subdirectory `link`, we should trigger a linking
-    // error. If the error is not triggered, then we are not really executing
-    // the linking phase.
     let link_dir = temp_dir.path().join("link");
+    fs::create_dir(&link_dir).unwrap();
     let link_path = link_dir.join("kakoune-phantom-selection");
 
     let url = "https://github.com/occivink/kakoune-phantom-selection";
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     env.insert("ALMOXARIFE_TEST_LOCATION", url.to_string() + ".git");
     env.insert(
         "ALMOXARIFE_TEST_REPO_PATH",
         repository_path.to_string_lossy().into(),
     );
 
+    let fake = FakeRunner::with_env(env);
+    fake.reply("git", &["clone"], 1, "", "unexpected error!");
+
     let plugin = Plugin {
         name: "kakoune-phantom-selection".into(),
         parent: None,
         has_children: false,
+        required_by: None,
         location: url.to_string(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "map global normal f ': phantom-selection-add-selection<ret>'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path: link_path.clone(),
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: fake.runner(),
     };
 
     let error = plugin.manage().unwrap_err();
     assert_eq!(
         error,
-        PluginError::Link(
+        PluginError::Clone(
             "kakoune-phantom-selection".into(),
-            format!(
-                "No such file or directory (os error 2): {}",
-                link_path.to_string_lossy()
-            )
+            "git exited with status 1: unexpected error!".into()
         )
     );
 }
 
 #[test]
-fn plugin_update_pull_no_changes() {
+fn explain_git_failures() {
+    assert_eq!(
+        setup::explain_git_failure(
+            "Cloning into 'peneira'...\nremote: Repository not found.\nfatal: repository 'https://github.com/gustavo-hms/penera.git/' not found\n"
+        ),
+        Some("repository not found — check the URL for typos")
+    );
+    assert_eq!(
+        setup::explain_git_failure(
+            "fatal: unable to access 'https://gihub.com/a/b.git/': Could not resolve host: gihub.com\n"
+        ),
+        Some("couldn't find the host — check your network connection and the URL")
+    );
+    assert_eq!(
+        setup::explain_git_failure("git@github.com: Permission denied (publickey).\n"),
+        Some(
+            "the SSH key was refused — check that it's added to your account on the forge, or use an https URL"
+        )
+    );
+    assert_eq!(
+        setup::explain_git_failure(
+            "fatal: could not read Username for 'https://github.com': terminal prompts disabled\n"
+        ),
+        Some("authentication required — the repository may be private, or the URL may be wrong")
+    );
+    assert_eq!(
+        setup::explain_git_failure("error: Your local changes would be overwritten\n"),
+        None
+    );
+}
+
+#[test]
+fn plugin_update_clone_explained_git_fail() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let fake = FakeRunner::new();
+    fake.reply("git", &["clone"], 1, "", "remote: Repository not found.");
+
+    let plugin = Plugin {
+        name: "peneira".into(),
+        parent: None,
+        has_children: false,
+        required_by: None,
+        location: "https://github.com/gustavo-hms/penera".into(),
+        is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
+        config: String::new(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
+        repository_path: temp_dir.path().join("repo/peneira"),
+        link_path: temp_dir.path().join("link/peneira"),
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: fake.runner(),
+    };
+
+    let error = plugin.manage().unwrap_err();
+    assert_eq!(
+        error,
+        PluginError::Clone(
+            "peneira".into(),
+            "repository not found — check the URL for typos
+git's output: git exited with status 1: remote: Repository not found."
+                .into()
+        )
+    );
+
+    assert_eq!(
+        error.without_git_output(),
+        PluginError::Clone(
+            "peneira".into(),
+            "repository not found — check the URL for typos (run with --verbose to see git's output)"
+                .into()
+        )
+    );
+
+    // Failures without an explanation keep git's output.
+    let unexplained = PluginError::Pull("peneira".into(), "git exited with status 1: oops".into());
+    assert_eq!(
+        unexplained.without_git_output(),
+        PluginError::Pull("peneira".into(), "git exited with status 1: oops".into())
+    );
+}
+
+#[test]
+fn plugin_update_clone_link_error() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+
+    // By not creating the subdirectory `link`, we should trigger a linking
+    // error. If the error is not triggered, then we are not really executing
+    // the linking phase.
+    let link_dir = temp_dir.path().join("link");
+    let link_path = link_dir.join("kakoune-phantom-selection");
+
+    let url = "https://github.com/occivink/kakoune-phantom-selection";
+
+    let mut env = HashMap::new();
+    env.insert("ALMOXARIFE_TEST_LOCATION", url.to_string() + ".git");
+    env.insert(
+        "ALMOXARIFE_TEST_REPO_PATH",
+        repository_path.to_string_lossy().into(),
+    );
+
+    let plugin = Plugin {
+        name: "kakoune-phantom-selection".into(),
+        parent: None,
+        has_children: false,
+        required_by: None,
+        location: url.to_string(),
+        is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
+        config: "map global normal f ': phantom-selection-add-selection<ret>'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
+        repository_path,
+        link_path: link_path.clone(),
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
+    };
+
+    let error = plugin.manage().unwrap_err();
+    assert_eq!(
+        error,
+        PluginError::Link(
+            "kakoune-phantom-selection".into(),
+            format!(
+                "No such file or directory (os error 2): {}",
+                link_path.to_string_lossy()
+            )
+        )
+    );
+}
+
+#[test]
+fn plugin_update_pull_no_changes() {
     let temp_dir = tempfile::tempdir().unwrap();
 
     let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
@@ -587,7 +1615,7 @@ fn plugin_update_pull_no_changes() {
     fs::create_dir(&link_dir).unwrap();
     let link_path = link_dir.join("kakoune-phantom-selection");
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     // Test we are calling `git pull` from the right directory.
     env.insert(
         "ALMOXARIFE_TEST_CWD",
@@ -598,12 +1626,39 @@ fn plugin_update_pull_no_changes() {
         name: "kakoune-phantom-selection".into(),
         parent: None,
         has_children: false,
+        required_by: None,
         location: String::new(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "map global normal f ': phantom-selection-add-selection<ret>'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path,
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
     };
 
     let status = plugin.manage().unwrap();
@@ -614,7 +1669,10 @@ fn plugin_update_pull_no_changes() {
             config: r"try %[ require-module kakoune-phantom-selection ]
 map global normal f ': phantom-selection-add-selection<ret>'
 "
-            .into()
+            .into(),
+            revision: Some("abcdef".into()),
+            renamed_branch: None,
+            last_commit: None,
         }
     );
 }
@@ -632,7 +1690,7 @@ fn plugin_update_pull_updates_available() {
     fs::create_dir(&link_dir).unwrap();
     let link_path = link_dir.join("kakoune-phantom-selection");
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     // Test we are calling `git pull` from the right directory.
     env.insert(
         "ALMOXARIFE_TEST_CWD",
@@ -644,12 +1702,39 @@ fn plugin_update_pull_updates_available() {
         name: "kakoune-phantom-selection".into(),
         parent: None,
         has_children: false,
+        required_by: None,
         location: String::new(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "map global normal f ': phantom-selection-add-selection<ret>'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path,
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
     };
 
     let status = plugin.manage().unwrap();
@@ -661,7 +1746,340 @@ fn plugin_update_pull_updates_available() {
 map global normal f ': phantom-selection-add-selection<ret>'
 "
             .into(),
-            log: "abcdef Some change\nghijk Other change\n".into()
+            log: "abcdef Some change\nghijk Other change\n".into(),
+            old_revision: "abcdef".into(),
+            new_revision: "ghijkl".into(),
+            history_rewritten: false,
+            breaking_changes: Vec::new(),
+            renamed_branch: None,
+        }
+    );
+}
+
+#[test]
+fn plugin_update_follows_renamed_branch() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "abcdef");
+    plugin.rev = None;
+    fake.set("ALMOXARIFE_TEST_DEFAULT_BRANCH", "main".into());
+
+    let status = plugin.clone().manage().unwrap();
+    let Status::Unchanged { renamed_branch, .. } = status else {
+        panic!("unexpected status {status:?}");
+    };
+    assert_eq!(renamed_branch, Some(("master".into(), "main".into())));
+
+    let git = repository_path.join(".git");
+    assert_eq!(
+        fs::read_to_string(git.join("almoxarife-test-branch")).unwrap(),
+        "main\n"
+    );
+    assert_eq!(
+        fs::read_to_string(git.join("almoxarife-test-upstream")).unwrap(),
+        "origin/main\n"
+    );
+
+    // Once followed, there's nothing more to report.
+    let status = plugin.clone().manage().unwrap();
+    assert!(matches!(
+        status,
+        Status::Unchanged {
+            renamed_branch: None,
+            ..
+        }
+    ));
+
+    // Pinned plugins don't follow branches at all.
+    fake.set("ALMOXARIFE_TEST_DEFAULT_BRANCH", "trunk".into());
+    plugin.rev = Some("abcdef".into());
+    let status = plugin.clone().manage().unwrap();
+    assert!(matches!(
+        status,
+        Status::Unchanged {
+            renamed_branch: None,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn plugin_update_commit_age() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path, link_path, "abcdef");
+    plugin.rev = None;
+    fake.reply("git", &["--format=%ct", "--remotes"], 0, "1700000000\n", "");
+
+    // Only looked for when asked to.
+    let status = plugin.clone().manage().unwrap();
+    assert!(matches!(
+        status,
+        Status::Unchanged {
+            last_commit: None,
+            ..
+        }
+    ));
+
+    plugin.commit_age = true;
+    let status = plugin.manage().unwrap();
+    assert!(matches!(
+        status,
+        Status::Unchanged {
+            last_commit: Some(1700000000),
+            ..
+        }
+    ));
+}
+
+#[test]
+fn plugin_update_log_paths() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let mut plugin = pinned_plugin(repository_path, link_path, "ghijkl");
+    plugin.log_paths = vec!["rc".into(), "kakoune-phantom-selection.kak".into()];
+
+    let Status::Updated { log, .. } = plugin.manage().unwrap() else {
+        panic!("the plugin should be updated");
+    };
+
+    assert_eq!(log, "abcdef Change to rc kakoune-phantom-selection.kak\n");
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path, link_path, "ghijkl");
+    plugin.log_paths = vec!["rc".into()];
+    fake.reply("git", &["--", "rc"], 0, "", "");
+
+    let Status::Updated { log, .. } = plugin.manage().unwrap() else {
+        panic!("the plugin should be updated");
+    };
+
+    assert_eq!(log, "");
+}
+
+#[test]
+fn plugin_update_breaking_changes() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path, link_path, "ghijkl");
+    plugin.flag_breaking_changes = true;
+    fake.reply(
+        "git",
+        &["diff", "--name-only"],
+        0,
+        "rc/phantom.kak\nCHANGELOG.md\ndocs/news.txt\n",
+        "",
+    );
+    fake.reply(
+        "git",
+        &["--format=%B"],
+        0,
+        "Rename commands\n\nBREAKING CHANGE: phantom-add is now phantom-selection-add\n",
+        "",
+    );
+
+    let Status::Updated {
+        breaking_changes, ..
+    } = plugin.manage().unwrap()
+    else {
+        panic!("the plugin should be updated");
+    };
+
+    assert_eq!(
+        breaking_changes,
+        [
+            "CHANGELOG.md changed",
+            "docs/news.txt changed",
+            "a commit announces a breaking change"
+        ]
+    );
+}
+
+#[test]
+fn plugin_update_following_tags() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let repository_path = temp_dir.path().join("repo/peneira");
+    fs::create_dir_all(&repository_path).unwrap();
+
+    let link_dir = temp_dir.path().join("link");
+    fs::create_dir(&link_dir).unwrap();
+
+    let fake = FakeRunner::new();
+    fake.reply("git", &["tag"], 0, "v1.2.0\nv1.1.0\n", "");
+
+    let plugin = Plugin {
+        name: "peneira".into(),
+        parent: None,
+        has_children: false,
+        required_by: None,
+        location: String::new(),
+        is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
+        config: String::new(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: Some("v*".into()),
+        remotes: Remotes::default(),
+        repository_path: repository_path.clone(),
+        link_path: link_dir.join("peneira"),
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: fake.runner(),
+    };
+
+    let status = plugin.manage().unwrap();
+    assert_eq!(
+        status,
+        Status::Updated {
+            name: "peneira".into(),
+            config: "try %[ require-module peneira ]\n\n".into(),
+            log: "abcdef Some change\nghijk Other change\n".into(),
+            old_revision: "abcdef".into(),
+            new_revision: "v1.2.0".into(),
+            history_rewritten: false,
+            breaking_changes: Vec::new(),
+            renamed_branch: None,
+        }
+    );
+
+    assert_eq!(
+        fs::read_to_string(repository_path.join("checkout.txt")).unwrap(),
+        "v1.2.0\n"
+    );
+}
+
+#[test]
+fn plugin_update_no_longer_following_tags() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    plugin.rev = None;
+    plugin.tag_pattern = Some("v*".into());
+    fake.reply("git", &["tag"], 0, "v1.2.0\nv1.1.0\n", "");
+
+    plugin.clone().manage().unwrap();
+    assert_eq!(
+        fs::read_to_string(repository_path.join("checkout.txt")).unwrap(),
+        "v1.2.0\n"
+    );
+
+    plugin.tag_pattern = None;
+    plugin.manage().unwrap();
+    assert!(
+        !repository_path
+            .join(".git/almoxarife-test-detached")
+            .exists()
+    );
+    assert_eq!(
+        fs::read_to_string(repository_path.join(".git/almoxarife-test-branch")).unwrap(),
+        "master\n"
+    );
+}
+
+#[test]
+fn plugin_update_pull_history_rewritten() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let repository_path = temp_dir.path().join("repo/peneira");
+    fs::create_dir_all(&repository_path).unwrap();
+
+    let link_dir = temp_dir.path().join("link");
+    fs::create_dir(&link_dir).unwrap();
+
+    let mut env = HashMap::new();
+    env.insert("ALMOXARIFE_TEST_PULL_FAIL", "1".into());
+    env.insert("ALMOXARIFE_TEST_FORCE_PUSHED", "1".into());
+
+    let plugin = Plugin {
+        name: "peneira".into(),
+        parent: None,
+        has_children: false,
+        required_by: None,
+        location: String::new(),
+        is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
+        config: String::new(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
+        repository_path,
+        link_path: link_dir.join("peneira"),
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
+    };
+
+    let status = plugin.manage().unwrap();
+    assert_eq!(
+        status,
+        Status::Updated {
+            name: "peneira".into(),
+            config: "try %[ require-module peneira ]\n\n".into(),
+            log: "abcdef Some change\nghijk Other change\n".into(),
+            old_revision: "abcdef".into(),
+            new_revision: "rewritten".into(),
+            history_rewritten: true,
+            breaking_changes: Vec::new(),
+            renamed_branch: None,
         }
     );
 }
@@ -677,7 +2095,7 @@ fn plugin_update_pull_unexpected_git_pull_fail() {
     fs::create_dir(&link_dir).unwrap();
     let link_path = link_dir.join("kakoune-phantom-selection");
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     env.insert("ALMOXARIFE_TEST_PULL_FAIL", "unexpected error!".to_string());
     env.insert(
         "ALMOXARIFE_TEST_CWD",
@@ -688,12 +2106,39 @@ fn plugin_update_pull_unexpected_git_pull_fail() {
         name: "kakoune-phantom-selection".into(),
         parent: None,
         has_children: false,
+        required_by: None,
         location: String::new(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "map global normal f ': phantom-selection-add-selection<ret>'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path,
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
     };
 
     let error = plugin.manage().unwrap_err();
@@ -717,27 +2162,59 @@ fn plugin_update_pull_unexpected_git_rev_parse_fail() {
     fs::create_dir(&link_dir).unwrap();
     let link_path = link_dir.join("kakoune-phantom-selection");
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     env.insert("ALMOXARIFE_TEST_PLUGIN_UPDATE", "1".into());
-    env.insert(
-        "ALMOXARIFE_TEST_REV_PARSE_FAIL",
-        "unexpected error!".to_string(),
-    );
     env.insert(
         "ALMOXARIFE_TEST_CWD",
         repository_path.to_string_lossy().into(),
     );
 
+    let fake = FakeRunner::with_env(env);
+    fake.reply(
+        "git",
+        &["rev-parse", "HEAD"],
+        7,
+        "",
+        "can't retrieve commit SHA",
+    );
+
     let plugin = Plugin {
         name: "kakoune-phantom-selection".into(),
         parent: None,
         has_children: false,
+        required_by: None,
         location: String::new(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "map global normal f ': phantom-selection-add-selection<ret>'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path,
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: fake.runner(),
     };
 
     let error = plugin.manage().unwrap_err();
@@ -761,24 +2238,53 @@ fn plugin_update_pull_unexpected_git_log_fail() {
     fs::create_dir(&link_dir).unwrap();
     let link_path = link_dir.join("kakoune-phantom-selection");
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     env.insert("ALMOXARIFE_TEST_PLUGIN_UPDATE", "1".into());
-    env.insert("ALMOXARIFE_TEST_LOG_FAIL", "unexpected error!".to_string());
     env.insert(
         "ALMOXARIFE_TEST_CWD",
         repository_path.to_string_lossy().into(),
     );
 
+    let fake = FakeRunner::with_env(env);
+    fake.reply("git", &["log"], 8, "", "can't get log of changes");
+
     let plugin = Plugin {
         name: "kakoune-phantom-selection".into(),
         parent: None,
         has_children: false,
+        required_by: None,
         location: String::new(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "map global normal f ': phantom-selection-add-selection<ret>'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path,
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: fake.runner(),
     };
 
     let error = plugin.manage().unwrap_err();
@@ -804,7 +2310,7 @@ fn plugin_update_pull_link_error() {
     let link_dir = temp_dir.path().join("link");
     let link_path = link_dir.join("kakoune-phantom-selection");
 
-    let mut env = add_tests_executables_to_path();
+    let mut env = HashMap::new();
     env.insert(
         "ALMOXARIFE_TEST_CWD",
         repository_path.to_string_lossy().into(),
@@ -814,12 +2320,39 @@ fn plugin_update_pull_link_error() {
         name: "kakoune-phantom-selection".into(),
         parent: None,
         has_children: false,
+        required_by: None,
         location: String::new(),
         is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
         config: "map global normal f ': phantom-selection-add-selection<ret>'".into(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
         repository_path,
         link_path: link_path.clone(),
-        env,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: FakeRunner::with_env(env).runner(),
     };
 
     let error = plugin.manage().unwrap_err();
@@ -851,6 +2384,10 @@ fn plugin_remove() {
     let auto_pairs = temp_dir.path().join("auto-pairs");
     fs::create_dir_all(&auto_pairs).unwrap();
 
+    // Executables plugins built aren't a plugin, nor are workspaces.
+    fs::create_dir_all(temp_dir.path().join("bin")).unwrap();
+    fs::create_dir_all(temp_dir.path().join("workspaces")).unwrap();
+
     let file = b"
             luar:
                 location: https://github.com/gustavo-hms/luar
@@ -875,25 +2412,93 @@ fn plugin_remove() {
 }
 
 #[test]
-fn plugin_remove_missing_data_dir() {
+fn manifest_dependencies() {
     let temp_dir = tempfile::tempdir().unwrap();
+    let manifest = |name: &str, manifest: &str| {
+        let path = temp_dir.path().join(name);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("almoxarife.toml"), manifest).unwrap();
+    };
 
-    let luar = temp_dir.path().join("luar");
-    fs::create_dir_all(&luar).unwrap();
+    manifest(
+        "peneira",
+        "[dependencies]\nluar = 'https://github.com/gustavo-hms/luar'\nauto-pairs = 'https://github.com/alexherbo2/auto-pairs.kak'\n",
+    );
+    manifest(
+        "luar",
+        "[dependencies]\nfzf = 'https://github.com/junegunn/fzf'\npeneira = 'https://elsewhere.com/peneira'\nkak-lsp = 'https://github.com/kakoune-lsp/kakoune-lsp'\n",
+    );
+    fs::create_dir_all(temp_dir.path().join("unneeded")).unwrap();
 
-    let peneira = temp_dir.path().join("peneira");
-    fs::create_dir_all(&peneira).unwrap();
+    let file = b"
+peneira:
+    location: https://github.com/gustavo-hms/peneira
 
-    let peneira_filters = temp_dir.path().join("peneira-filters");
-    fs::create_dir_all(&peneira_filters).unwrap();
+auto-pairs:
+    location: https://github.com/alexherbo2/auto-pairs.kak
+    disabled: true
 
-    let auto_pairs = temp_dir.path().join("auto-pairs");
-    fs::create_dir_all(&auto_pairs).unwrap();
+kak-lsp:
+    location: https://github.com/kakoune-lsp/kakoune-lsp
+";
 
-    let file = b"
-            luar:
-                location: https://github.com/gustavo-hms/luar
-                config: set-option global luar_interpreter luajit
+    let setup = Setup {
+        almoxarife_data_dir: temp_dir.path().into(),
+        ..Default::default()
+    };
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let mut plugins = config.active_plugins();
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let required: Vec<_> = plugins
+        .iter()
+        .map(|plugin| (plugin.name.as_str(), plugin.required_by.as_deref()))
+        .collect();
+
+    assert_eq!(
+        required,
+        vec![
+            ("fzf", Some("luar")),
+            ("kak-lsp", None),
+            ("luar", Some("peneira")),
+            ("peneira", None),
+        ]
+    );
+
+    assert_eq!(plugins[0].location, "https://github.com/junegunn/fzf");
+    assert_eq!(plugins[0].repository_path, temp_dir.path().join("fzf"));
+
+    // Dependencies of plugins not yet installed are only known once they are.
+    let [fzf] = config.dependencies(&plugins[1..]).try_into().unwrap();
+    assert_eq!(fzf.name, "fzf");
+    assert!(config.dependencies(&plugins).is_empty());
+
+    // Dependencies are kept as long as some plugin needs them.
+    let [removed] = config.removed_plugins().unwrap().try_into().unwrap();
+    assert_eq!(removed, temp_dir.path().join("unneeded"));
+}
+
+#[test]
+fn plugin_remove_missing_data_dir() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    let luar = temp_dir.path().join("luar");
+    fs::create_dir_all(&luar).unwrap();
+
+    let peneira = temp_dir.path().join("peneira");
+    fs::create_dir_all(&peneira).unwrap();
+
+    let peneira_filters = temp_dir.path().join("peneira-filters");
+    fs::create_dir_all(&peneira_filters).unwrap();
+
+    let auto_pairs = temp_dir.path().join("auto-pairs");
+    fs::create_dir_all(&auto_pairs).unwrap();
+
+    let file = b"
+            luar:
+                location: https://github.com/gustavo-hms/luar
+                config: set-option global luar_interpreter luajit
 
                 peneira:
                     location: /home/gustavo-hms/peneira
@@ -911,3 +2516,1562 @@ fn plugin_remove_missing_data_dir() {
         "couldn't read ~/.local/share/almoxarife: No such file or directory (os error 2)"
     );
 }
+
+#[test]
+fn parse_yaml_error_snippet() {
+    let file = b"
+luar:
+    location: https://github.com/gustavo-hms/luar
+    config: map global normal <c-p> ': peneira-files<ret>'
+";
+
+    let setup = Setup::default();
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "couldn't parse ~/.config/almoxarife.yaml at line 4, column 38: mapping values are not allowed in this context
+  |
+4 |     config: map global normal <c-p> ': peneira-files<ret>'
+  |                                      ^
+hint: config: blocks with `: `, `<` or quotes need quoting or `|`, as in
+    config: |
+      map global normal <c-p> ': my-command<ret>'"
+    );
+}
+
+#[test]
+fn parse_yaml_error_unknown_field() {
+    let file = b"
+luar:
+    location: https://github.com/gustavo-hms/luar
+    conifg: set-option global luar_interpreter luajit
+";
+
+    let setup = Setup::default();
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        r#"couldn't parse ~/.config/almoxarife.yaml at line 3, column 5: luar: invalid type: string "set-option global luar_interpreter luajit", expected struct PluginTree
+  |
+3 |     location: https://github.com/gustavo-hms/luar
+  |     ^
+hint: unknown fields inside a plugin are taken as child plugins; check the field names for typos"#
+    );
+}
+
+#[test]
+fn parse_yaml_newer_version() {
+    let setup = Setup::default();
+
+    let error = setup
+        .config_from_buffer(b"version: 99\nluar:\n    colors: [blue]\n")
+        .err()
+        .unwrap()
+        .to_string();
+
+    assert!(error.contains("format version 99"));
+
+    let config = setup
+        .config_from_buffer(
+            b"version: 1\nluar:\n    location: https://github.com/gustavo-hms/luar\n",
+        )
+        .unwrap();
+
+    assert_eq!(config.plugin_names(), HashSet::from(["luar"]));
+}
+
+#[test]
+fn parse_single_file_plugins() {
+    let file = b"
+smarttab:
+    file: https://gist.githubusercontent.com/someone/abc/raw/smarttab.kak
+";
+
+    let setup = Setup::default();
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let [plugin] = config.active_plugins().try_into().unwrap();
+    assert!(plugin.single_file);
+    assert!(!plugin.is_local);
+    assert_eq!(
+        plugin.location,
+        "https://gist.githubusercontent.com/someone/abc/raw/smarttab.kak"
+    );
+    assert_eq!(
+        plugin.repository_path,
+        PathBuf::from("~/.local/share/almoxarife/smarttab")
+    );
+
+    let error = setup
+        .config_from_buffer(b"luar:\n    config: set-option global luar_interpreter luajit\n")
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(error.contains("luar needs a `location:` or a `file:` field"));
+
+    let error = setup
+        .config_from_buffer(
+            b"luar:\n    location: https://github.com/gustavo-hms/luar\n    file: https://example.com/luar.kak\n",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(error.contains("luar has both a `location:` and a `file:` field"));
+
+    let error = setup
+        .config_from_buffer(
+            b"luar:\n    location: https://github.com/gustavo-hms/luar\n    sha256: 0123\n",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(error.contains("luar has a `sha256:` field but no `file:`"));
+
+    let error = setup
+        .config_from_buffer(
+            b"smarttab:\n    file: https://example.com/smarttab.kak\n    sha256: 0123\n",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+    assert!(error.contains("smarttab's `sha256:` isn't a SHA-256 checksum"));
+
+    let config = setup
+        .config_from_buffer(
+            format!(
+                "smarttab:\n    file: https://example.com/smarttab.kak\n    sha256: {}\n",
+                "AB".repeat(32)
+            )
+            .as_bytes(),
+        )
+        .unwrap();
+    assert_eq!(config.active_plugins()[0].sha256, Some("ab".repeat(32)));
+}
+
+#[test]
+fn parse_settings() {
+    let file = b"
+auto_update_min_interval: 6h
+abandoned_after: 52w
+prune_disabled_after: 30d
+maintenance_interval: 1w
+flag_breaking_changes: true
+dotfiles_repo: ~/dotfiles
+dotfiles_push: true
+download_cache: /srv/almoxarife
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let setup = Setup::default();
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(
+        config.settings.auto_update_min_interval,
+        Some(Duration::from_secs(6 * 3600))
+    );
+    assert_eq!(
+        config.settings.abandoned_after,
+        Some(Duration::from_secs(52 * 7 * 24 * 3600))
+    );
+    assert_eq!(
+        config.settings.prune_disabled_after,
+        Some(Duration::from_secs(30 * 24 * 3600))
+    );
+    assert_eq!(
+        config.settings.maintenance_interval,
+        Some(Duration::from_secs(7 * 24 * 3600))
+    );
+    assert!(config.settings.flag_breaking_changes);
+    assert_eq!(
+        config.settings.dotfiles_repo,
+        Some(PathBuf::from("~/dotfiles"))
+    );
+    assert!(config.settings.dotfiles_push);
+    assert!(
+        config
+            .active_plugins()
+            .iter()
+            .all(|plugin| plugin.download_cache == Some(PathBuf::from("/srv/almoxarife")))
+    );
+    assert_eq!(config.plugin_names(), HashSet::from(["luar"]));
+    assert!(
+        config
+            .active_plugins()
+            .iter()
+            .all(|plugin| plugin.flag_breaking_changes)
+    );
+
+    let file = b"
+auto_update_min_interval: often
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert!(
+        error
+            .to_string()
+            .contains(r#"invalid duration "often"; use something like 30m, 12h or 2d"#)
+    );
+}
+
+#[test]
+fn parse_proxies() {
+    let file = b"
+proxy: http://proxy.example.com:3128
+luar:
+    location: https://github.com/gustavo-hms/luar
+
+    peneira:
+        location: https://github.com/gustavo-hms/peneira
+
+internal:
+    location: https://git.example.com/internal
+    proxy: direct
+
+tor:
+    location: https://github.com/someone/tor
+    proxy: socks5h://localhost:9050
+";
+
+    let setup = Setup::default();
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let proxies: HashMap<_, _> = config
+        .active_plugins()
+        .into_iter()
+        .map(|plugin| (plugin.name, plugin.proxy))
+        .collect();
+
+    let proxy = |proxy: &str| Some(proxy.to_string());
+
+    assert_eq!(
+        proxies,
+        HashMap::from([
+            ("luar".into(), proxy("http://proxy.example.com:3128")),
+            ("peneira".into(), proxy("http://proxy.example.com:3128")),
+            ("internal".into(), proxy("")),
+            ("tor".into(), proxy("socks5h://localhost:9050")),
+        ])
+    );
+
+    let file = b"
+luar:
+    location: https://github.com/gustavo-hms/luar
+    proxy: proxy.example.com
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert!(
+        error
+            .to_string()
+            .contains(r#"invalid proxy "proxy.example.com""#)
+    );
+}
+
+#[test]
+fn parse_max_connections_per_host() {
+    let setup = Setup::default();
+    let file = b"
+max_connections_per_host: 4
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(config.settings.max_connections_per_host, Some(4));
+
+    let file = b"
+max_connections_per_host: 0
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert!(
+        error
+            .to_string()
+            .contains("max_connections_per_host must be at least 1")
+    );
+
+    let file = b"
+max_parallel_builds: 2
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(config.settings.max_parallel_builds, Some(2));
+    assert_eq!(config.settings.max_connections_per_host, None);
+}
+
+#[test]
+fn parse_safety_settings() {
+    let setup = Setup::default();
+
+    let file = b"
+isolate_env: true
+pass_env: [SSH_ASKPASS, https_proxy]
+review: true
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert!(config.settings.isolate_env);
+    assert_eq!(config.settings.pass_env, vec!["SSH_ASKPASS", "https_proxy"]);
+    assert!(config.settings.review);
+
+    let config = setup.config_from_buffer(b"luar: {location: x}".as_slice());
+    assert!(!config.unwrap().settings.isolate_env);
+}
+
+#[test]
+fn environment_proxy() {
+    let setup = Setup::with_runner(
+        FakeRunner::with_env(
+            [
+                ("HOME", "custom-home".to_string()),
+                ("all_proxy", "socks5://localhost:1080".to_string()),
+            ]
+            .into(),
+        )
+        .runner(),
+    );
+
+    let file = b"
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let plugins = config.active_plugins();
+    assert_eq!(plugins[0].proxy.as_deref(), Some("socks5://localhost:1080"));
+
+    let file = b"
+proxy: direct
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let plugins = config.active_plugins();
+    assert_eq!(plugins[0].proxy.as_deref(), Some(""));
+}
+
+#[test]
+fn parse_defaults() {
+    let file = b"
+defaults:
+    lazy: true
+    tag_pattern: v*
+    remote: upstream
+
+luar:
+    location: https://github.com/gustavo-hms/luar
+    lazy: false
+
+    peneira:
+        location: https://github.com/gustavo-hms/peneira
+        tag_pattern: ''
+
+auto-pairs:
+    location: https://github.com/alexherbo2/auto-pairs.kak
+    remote: origin
+";
+
+    let setup = Setup::default();
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let plugins: HashMap<_, _> = config
+        .active_plugins()
+        .into_iter()
+        .map(|plugin| {
+            let fields = (plugin.lazy, plugin.tag_pattern, plugin.remotes.name);
+            (plugin.name, fields)
+        })
+        .collect();
+
+    let some = |text: &str| Some(text.to_string());
+
+    assert_eq!(
+        plugins,
+        HashMap::from([
+            ("luar".into(), (false, some("v*"), some("upstream"))),
+            ("peneira".into(), (true, None, some("upstream"))),
+            ("auto-pairs".into(), (true, some("v*"), some("origin"))),
+        ])
+    );
+
+    let file = b"
+defaults:
+    depth: 1
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert!(error.to_string().contains("unknown field `depth`"));
+}
+
+#[test]
+fn parse_merge_keys() {
+    let file = b"
+luar: &common
+    location: https://github.com/gustavo-hms/luar
+    after_update_kak: lsp-restart
+
+kak-lsp:
+    <<: *common
+    location: https://github.com/kakoune-lsp/kakoune-lsp
+";
+
+    let setup = Setup::default();
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let plugins = config.active_plugins();
+    let kak_lsp = plugins.iter().find(|p| p.name == "kak-lsp").unwrap();
+    assert_eq!(kak_lsp.after_update_kak, "lsp-restart");
+}
+
+#[test]
+fn parse_snippets() {
+    let file = b"
+snippets:
+    lsp: |
+        lsp-enable-window
+        set-option window lsp_auto_highlight_references true
+
+luar:
+    location: https://github.com/gustavo-hms/luar
+    config: !snippet lsp
+
+peneira:
+    location: https://github.com/gustavo-hms/peneira
+    config: |
+        hook global WinSetOption filetype=rust %{
+            !snippet lsp
+        }
+";
+
+    let setup = Setup::default();
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(config.plugin_names(), HashSet::from(["luar", "peneira"]));
+
+    let plugins = config.active_plugins();
+    let config = |name: &str| {
+        let plugin = plugins.iter().find(|p| p.name == name).unwrap();
+        plugin.config.clone()
+    };
+
+    assert_eq!(
+        config("luar"),
+        "lsp-enable-window\nset-option window lsp_auto_highlight_references true\n"
+    );
+    assert_eq!(
+        config("peneira"),
+        "hook global WinSetOption filetype=rust %{
+    lsp-enable-window
+    set-option window lsp_auto_highlight_references true
+}
+"
+    );
+
+    let file = b"
+luar:
+    location: https://github.com/gustavo-hms/luar
+    config: !snippet lsp
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert!(
+        error
+            .to_string()
+            .contains(r#"there's no snippet named "lsp" under snippets"#)
+    );
+}
+
+#[test]
+fn nesting_warnings() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let checkout = |name: &str, script: &str| {
+        let path = temp_dir.path().join(name);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join(format!("{name}.kak")), script).unwrap();
+    };
+
+    checkout("kak-lsp", "provide-module lsp %{\n}\n");
+    checkout("luar", "provide-module luar %{\n}\n");
+    checkout("auto-pairs", "define-command auto-pairs-enable %{}\n");
+
+    let setup = Setup {
+        almoxarife_data_dir: temp_dir.path().into(),
+        ..Setup::default()
+    };
+
+    let file = b"
+kak-lsp:
+    location: https://github.com/kakoune-lsp/kakoune-lsp
+    lsp-snippets:
+        location: https://github.com/me/lsp-snippets
+luar:
+    location: https://github.com/gustavo-hms/luar
+    peneira:
+        location: https://github.com/gustavo-hms/peneira
+auto-pairs:
+    location: https://github.com/alexherbo2/auto-pairs.kak
+    surround:
+        location: https://github.com/alexherbo2/surround.kak
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(
+        setup::nesting_warnings(&config.active_plugins()),
+        [
+            "the children of kak-lsp (lsp-snippets) wait for module kak-lsp, but kak-lsp provides lsp; name the plugin after its module in almoxarife.yaml"
+        ]
+    );
+
+    // A manifest naming the module has it required before the children load.
+    fs::write(
+        temp_dir.path().join("kak-lsp").join("almoxarife.toml"),
+        "module = 'lsp'",
+    )
+    .unwrap();
+
+    assert!(setup::nesting_warnings(&config.active_plugins()).is_empty());
+}
+
+#[test]
+fn parse_clone_size_warning() {
+    let setup = Setup::default();
+    let file = b"
+clone_size_warning: 100M
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(config.settings.clone_size_warning, Some(100 * 1024 * 1024));
+    assert_eq!(config.plugin_names(), HashSet::from(["luar"]));
+
+    let file = b"
+clone_size_warning: big
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert!(error.to_string().contains(r#"invalid size "big""#));
+}
+
+#[test]
+fn plugins_named_as_settings() {
+    let setup = Setup::default();
+    let file = b"
+proxy: direct
+hooks:
+    location: https://github.com/someone/hooks.kak
+review:
+    location: https://github.com/someone/review.kak
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert_eq!(
+        error.to_string(),
+        "plugins of ~/.config/almoxarife.yaml need other names:
+review is a setting, so it can't name a plugin
+hooks is a setting, so it can't name a plugin"
+    );
+}
+
+#[test]
+fn every_setting_is_parsed() {
+    let setup = Setup::default();
+    let file = b"
+version: 1
+auto_update_min_interval: 12h
+abandoned_after: 52w
+prune_disabled_after: 4w
+maintenance_interval: 1w
+clone_size_warning: 100M
+url_policy:
+    protocols: [https]
+url_rewrites:
+    https://github.com/: https://mirror.example.com/
+flag_breaking_changes: true
+isolate_env: true
+pass_env: [SSH_AUTH_SOCK]
+review: true
+defaults:
+    lazy: true
+hooks:
+    after_all: echo done
+snippets:
+    greet: echo hi
+dotfiles_repo: ~/dotfiles
+dotfiles_push: true
+download_cache: ~/Sync/almoxarife
+max_connections_per_host: 4
+max_parallel_builds: 2
+proxy: direct
+integration: source
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let keys: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_slice(file).unwrap();
+    let mut keys: HashSet<&str> = keys.keys().map(String::as_str).collect();
+    keys.remove("luar");
+    assert_eq!(keys, setup::SETTINGS.iter().copied().collect());
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(config.plugin_names(), HashSet::from(["luar"]));
+    assert_eq!(config.settings.max_parallel_builds, Some(2));
+    assert_eq!(config.settings.integration, Integration::Source);
+}
+
+#[test]
+fn url_policy_violations() {
+    let setup = Setup::default();
+    let file = b"
+url_policy:
+    protocols: [https]
+    allow: [github.com]
+luar:
+    location: https://github.com/gustavo-hms/luar
+    remotes:
+        fork: git@github.com:me/luar
+    peneira:
+        location: https://gitlab.com/gustavo-hms/peneira
+mine:
+    location: /home/me/kak-scripts
+old:
+    location: http://example.com/old
+    disabled: true
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    let error = error.to_string();
+    assert!(error.contains("luar: git@github.com:me/luar uses ssh, but only https is allowed"));
+    assert!(
+        error.contains("peneira: https://gitlab.com/gustavo-hms/peneira isn't on an allowed host")
+    );
+    // Local and disabled plugins are never fetched.
+    assert!(!error.contains("mine"));
+    assert!(!error.contains("old"));
+
+    let file = b"
+url_policy:
+    protocols: [https]
+    allow: [github.com]
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(config.settings.url_policy.allow, vec!["github.com"]);
+    assert_eq!(config.plugin_names(), HashSet::from(["luar"]));
+}
+
+#[test]
+fn parse_url_rewrites() {
+    let setup = Setup::default();
+    let file = b"
+url_rewrites:
+    https://github.com/: https://mirror.internal/github/
+    https://github.com/gustavo-hms/: https://mirror.internal/mine/
+url_policy:
+    allow: [mirror.internal]
+luar:
+    location: https://github.com/gustavo-hms/luar
+phantom:
+    location: https://github.com/occivink/kakoune-phantom-selection
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(config.settings.url_rewrites.len(), 2);
+
+    let plugins = config.active_plugins();
+    assert_eq!(plugins[0].url_rewrites, plugins[1].url_rewrites);
+    assert_eq!(plugins[0].url_rewrites.len(), 2);
+
+    // The policy applies to the URLs actually fetched.
+    let file = b"
+url_policy:
+    allow: [mirror.internal]
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert!(error.to_string().contains("isn't on an allowed host"));
+
+    let file = b"
+url_rewrites:
+    https://github.com/: ''
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert!(
+        error
+            .to_string()
+            .contains("url_rewrites can't have empty URLs")
+    );
+}
+
+#[test]
+fn user_git_config() {
+    let mut setup = Setup::with_runner(
+        FakeRunner::with_env(
+            [
+                ("HOME", "custom-home".to_string()),
+                ("https_proxy", "socks5://localhost:1080".to_string()),
+            ]
+            .into(),
+        )
+        .runner(),
+    );
+    setup.git_config = GitConfig {
+        url_rewrites: BTreeMap::from([(
+            "https://github.com/".into(),
+            "https://mirror.internal/github/".into(),
+        )]),
+        proxy: Some("http://proxy.example.com:3128".into()),
+    };
+
+    // The policy sees through the user's own rewrites.
+    let file = b"
+url_policy:
+    allow: [mirror.internal]
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let plugins = config.active_plugins();
+    // git picks its own proxy over the environment's.
+    assert_eq!(plugins[0].proxy, None);
+    // git follows the user's rules by itself.
+    assert!(plugins[0].url_rewrites.is_empty());
+
+    // Rewrites in the file come after the user's ones.
+    let file = b"
+url_rewrites:
+    https://github.com/: https://elsewhere.com/
+url_policy:
+    allow: [mirror.internal]
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert!(
+        error
+            .to_string()
+            .contains("https://elsewhere.com/gustavo-hms/luar isn't on an allowed host")
+    );
+}
+
+#[test]
+fn local_locations_overlapping_managed_dirs() {
+    let setup = Setup::default();
+    let file = b"
+mine:
+    location: ~/.config/kak/autoload/my-scripts
+copy:
+    location: ~/.local/share/almoxarife/luar
+luar:
+    location: https://github.com/gustavo-hms/luar
+    kak-config:
+        location: ~/.config
+old:
+    location: ~/.config/kak/autoload/old
+    disabled: true
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    let error = error.to_string();
+    assert!(
+        error.contains("mine: ~/.config/kak/autoload/my-scripts is inside ~/.config/kak/autoload")
+    );
+    assert!(
+        error.contains("copy: ~/.local/share/almoxarife/luar is inside ~/.local/share/almoxarife")
+    );
+    assert!(error.contains("kak-config: ~/.config contains ~/.config/kak/autoload"));
+    // Disabled plugins are never linked.
+    assert!(!error.contains("old"));
+
+    let file = b"
+mine:
+    location: ~/code/kak-scripts
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(config.plugin_names(), HashSet::from(["mine"]));
+}
+
+#[test]
+fn parse_integration() {
+    let setup = Setup::default();
+    let file = b"
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(config.settings.integration, Integration::Autoload);
+
+    let file = b"
+integration: source
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    assert_eq!(config.settings.integration, Integration::Source);
+
+    let file = b"
+integration: symlinks
+luar:
+    location: https://github.com/gustavo-hms/luar
+";
+
+    let error = setup.config_from_buffer(file.as_slice()).err().unwrap();
+    assert!(
+        error
+            .to_string()
+            .contains(r#"unknown integration "symlinks"; use autoload or source"#)
+    );
+}
+
+#[test]
+fn write_plugins_kak() {
+    let temp_dir = TempDir::new().unwrap();
+    let luar = temp_dir.path().join("luar");
+    fs::create_dir_all(luar.join("rc")).unwrap();
+    fs::write(luar.join("rc/luar.kak"), "").unwrap();
+    fs::write(luar.join("README.md"), "").unwrap();
+    let peneira = temp_dir.path().join("it's peneira");
+    fs::create_dir_all(&peneira).unwrap();
+    fs::write(peneira.join("peneira.kak"), "").unwrap();
+
+    let setup = Setup {
+        plugins_kak: temp_dir.path().join("plugins.kak"),
+        ..Setup::default()
+    };
+
+    let mut kak = Kak::with_buffer();
+    kak.write(b"try %[ require-module luar ]\n").unwrap();
+    setup.write_plugins_kak(&[&luar, &peneira], &kak).unwrap();
+
+    let dir = temp_dir.path().to_string_lossy();
+    assert_eq!(
+        fs::read_to_string(&setup.plugins_kak).unwrap(),
+        format!(
+            "source '{dir}/luar/rc/luar.kak'
+source '{dir}/it''s peneira/peneira.kak'
+try %[ require-module luar ]
+"
+        )
+    );
+}
+
+#[test]
+fn create_dirs_for_sourcing() {
+    let temp_dir = TempDir::new().unwrap();
+    let autoload_plugins_dir = temp_dir.path().join("autoload/almoxarife");
+    fs::create_dir_all(&autoload_plugins_dir).unwrap();
+
+    let setup = Setup {
+        autoload_plugins_dir: autoload_plugins_dir.clone(),
+        almoxarife_data_dir: temp_dir.path().join("data"),
+        ..Setup::default()
+    };
+
+    setup.create_dirs_for_sourcing().unwrap();
+    assert!(!autoload_plugins_dir.exists());
+    assert!(setup.almoxarife_data_dir.is_dir());
+}
+
+#[test]
+fn plugin_lazy_config() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("peneira");
+    fs::create_dir_all(repository_path.join("rc")).unwrap();
+    fs::write(
+        repository_path.join("rc/peneira.kak"),
+        "provide-module peneira %{
+    define-command -params 1 peneira-files %{ nop }
+    define-command peneira %{ nop }
+}",
+    )
+    .unwrap();
+
+    let plugin = Plugin {
+        name: "peneira".into(),
+        parent: None,
+        has_children: false,
+        required_by: None,
+        location: "https://github.com/gustavo-hms/peneira".into(),
+        is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
+        config: "set-option global peneira_files_command 'rg --files'
+map global normal <c-p> ': peneira-files<ret>'
+hook global WinCreate .* %{
+    map window normal <c-f> ': peneira<ret>'
+}
+"
+        .into(),
+        lazy: true,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: None,
+        tag_pattern: None,
+        remotes: Remotes::default(),
+        repository_path,
+        link_path: Default::default(),
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir: PathBuf::new(),
+        runner: Default::default(),
+    };
+
+    assert_eq!(
+        plugin.config(),
+        "hook -once global ModuleLoaded peneira %[
+    unalias global peneira almoxarife-lazy-peneira
+    unalias global peneira-files almoxarife-lazy-peneira-files
+    set-option global peneira_files_command 'rg --files'
+    hook global WinCreate .* %{
+        map window normal <c-f> ': peneira<ret>'
+    }
+]
+define-command -hidden -params .. almoxarife-lazy-peneira %[
+    unalias global peneira almoxarife-lazy-peneira
+    try %[ require-module peneira ]
+    peneira %arg{@}
+]
+alias global peneira almoxarife-lazy-peneira
+define-command -hidden -params .. almoxarife-lazy-peneira-files %[
+    unalias global peneira-files almoxarife-lazy-peneira-files
+    try %[ require-module peneira ]
+    peneira-files %arg{@}
+]
+alias global peneira-files almoxarife-lazy-peneira-files
+map global normal <c-p> ': peneira-files<ret>'
+"
+    );
+}
+
+#[test]
+fn plugin_lazy_config_with_children() {
+    let temp_dir = tempfile::tempdir().unwrap();
+
+    for (plugin, source) in [
+        (
+            "kak-lsp",
+            "provide-module lsp %{\n    define-command lsp-enable %{ nop }\n}",
+        ),
+        (
+            "lsp-extras",
+            "provide-module lsp-extras %{\n    define-command lsp-extras %{ nop }\n}",
+        ),
+    ] {
+        let repository_path = temp_dir.path().join(plugin);
+        fs::create_dir_all(&repository_path).unwrap();
+        fs::write(repository_path.join(format!("{plugin}.kak")), source).unwrap();
+        fs::write(
+            repository_path.join("almoxarife.toml"),
+            if plugin == "kak-lsp" {
+                "module = 'lsp'"
+            } else {
+                ""
+            },
+        )
+        .unwrap();
+    }
+
+    let file = b"
+kak-lsp:
+    location: https://github.com/kakoune-lsp/kakoune-lsp
+    lazy: true
+
+    lsp-extras:
+        location: https://github.com/someone/lsp-extras
+        lazy: true
+";
+
+    let setup = Setup {
+        almoxarife_data_dir: temp_dir.path().into(),
+        ..Setup::default()
+    };
+
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let plugins = config.active_plugins();
+    let parent = plugins.iter().find(|p| p.name == "kak-lsp").unwrap();
+    let child = plugins.iter().find(|p| p.name == "lsp-extras").unwrap();
+
+    // The children of kak-lsp wait for a module named after it.
+    assert_eq!(
+        parent.config(),
+        "hook -once global ModuleLoaded lsp %[
+    unalias global lsp-enable almoxarife-lazy-lsp-enable
+]
+define-command -hidden -params .. almoxarife-lazy-lsp-enable %[
+    unalias global lsp-enable almoxarife-lazy-lsp-enable
+    try %[ require-module lsp ]
+    try %[ require-module kak-lsp ] catch %[
+        provide-module kak-lsp ''
+        require-module kak-lsp
+    ]
+    lsp-enable %arg{@}
+]
+alias global lsp-enable almoxarife-lazy-lsp-enable
+"
+    );
+
+    // And lsp-extras loads its parent first.
+    assert_eq!(
+        child.config(),
+        "hook -once global ModuleLoaded lsp-extras %[
+    unalias global lsp-extras almoxarife-lazy-lsp-extras
+]
+define-command -hidden -params .. almoxarife-lazy-lsp-extras %[
+    unalias global lsp-extras almoxarife-lazy-lsp-extras
+    try %[ require-module kak-lsp ]
+    try %[ require-module lsp-extras ]
+    lsp-extras %arg{@}
+]
+alias global lsp-extras almoxarife-lazy-lsp-extras
+"
+    );
+}
+
+#[test]
+fn plugin_sessions_config() {
+    let file = b"
+            kak-lsp:
+                location: https://github.com/kakoune-lsp/kakoune-lsp
+                sessions: work-.*|it's
+                config: lsp-enable
+            ";
+
+    let setup = Setup::default();
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let [plugin] = config.active_plugins().try_into().unwrap();
+    assert_eq!(plugin.sessions.as_deref(), Some("work-.*|it's"));
+
+    assert_eq!(
+        plugin.config(),
+        "hook -group almoxarife-sessions-kak-lsp global User 'almoxarife-session=(?:work-.*|it''s)' %[
+try %[ require-module kak-lsp ]
+lsp-enable
+]
+trigger-user-hook \"almoxarife-session=%val{session}\"
+remove-hooks global almoxarife-sessions-kak-lsp
+"
+    );
+}
+
+pub fn pinned_plugin(repository_path: PathBuf, link_path: PathBuf, rev: &str) -> Plugin {
+    pinned_plugin_with_fake(repository_path, link_path, rev).0
+}
+
+/// A pinned plugin, along with the fake running its programs, which expects
+/// them to run inside the checkout.
+pub fn pinned_plugin_with_fake(
+    repository_path: PathBuf,
+    link_path: PathBuf,
+    rev: &str,
+) -> (Plugin, Arc<FakeRunner>) {
+    let bin_dir = repository_path.with_file_name("bin");
+    let fake = FakeRunner::with_env(HashMap::from([(
+        "ALMOXARIFE_TEST_CWD",
+        repository_path.to_string_lossy().into(),
+    )]));
+
+    let plugin = Plugin {
+        name: "kakoune-phantom-selection".into(),
+        parent: None,
+        has_children: false,
+        required_by: None,
+        location: "https://github.com/occivink/kakoune-phantom-selection".into(),
+        is_local: false,
+        single_file: false,
+        sha256: None,
+        download_cache: None,
+        config: String::new(),
+        lazy: false,
+        sessions: None,
+        order: 0,
+        position: None,
+        rev: Some(rev.into()),
+        tag_pattern: None,
+        remotes: Remotes::default(),
+        repository_path,
+        link_path,
+        alias_link_paths: Vec::new(),
+        after_update_kak: String::new(),
+        log_paths: Vec::new(),
+        features: Vec::new(),
+        trusted: false,
+        proxy: None,
+        url_rewrites: Default::default(),
+        references: Vec::new(),
+        flag_breaking_changes: false,
+        force_adopt: false,
+        deadline: None,
+        last_build: None,
+        rebuild: false,
+        commit_age: false,
+        reviewed: false,
+        bin_dir,
+        runner: fake.runner(),
+    };
+
+    (plugin, fake)
+}
+
+#[test]
+fn plugin_update_pinned_unchanged() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (plugin, fake) = pinned_plugin_with_fake(repository_path, link_path, "abc");
+    // A pinned plugin at the right revision shouldn't touch the network.
+    fake.set("ALMOXARIFE_TEST_PULL_FAIL", "unexpected fetch".into());
+
+    let status = plugin.manage().unwrap();
+    assert!(matches!(status, Status::Unchanged { .. }));
+}
+
+#[test]
+fn plugin_update_pinned_checkout() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let plugin = pinned_plugin(repository_path.clone(), link_path, "ghijkl");
+
+    let status = plugin.manage().unwrap();
+    assert_eq!(
+        status,
+        Status::Updated {
+            name: "kakoune-phantom-selection".into(),
+            log: "abcdef Some change\nghijk Other change\n".into(),
+            config: "try %[ require-module kakoune-phantom-selection ]\n\n".into(),
+            old_revision: "abcdef".into(),
+            new_revision: "ghijkl".into(),
+            history_rewritten: false,
+            breaking_changes: Vec::new(),
+            renamed_branch: None,
+        }
+    );
+
+    assert_eq!(
+        fs::read_to_string(repository_path.join("checkout.txt")).unwrap(),
+        "ghijkl\n"
+    );
+}
+
+#[test]
+fn plugin_update_unpinned() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let mut plugin = pinned_plugin(repository_path.clone(), link_path, "ghijkl");
+    plugin.clone().manage().unwrap();
+    assert!(
+        repository_path
+            .join(".git/almoxarife-test-detached")
+            .exists()
+    );
+
+    // Once the pin is gone, the branch left behind is checked out again, so
+    // that it can be pulled.
+    plugin.rev = None;
+    let status = plugin.clone().manage().unwrap();
+    let Status::Updated {
+        old_revision,
+        new_revision,
+        ..
+    } = status
+    else {
+        panic!("unexpected status {status:?}");
+    };
+    assert_eq!(
+        (old_revision.as_str(), new_revision.as_str()),
+        ("ghijkl", "abcdef")
+    );
+    assert!(
+        !repository_path
+            .join(".git/almoxarife-test-detached")
+            .exists()
+    );
+    assert_eq!(
+        fs::read_to_string(repository_path.join(".git/almoxarife-test-branch")).unwrap(),
+        "master\n"
+    );
+
+    // And then it's pulled as any other.
+    let status = plugin.manage().unwrap();
+    assert!(matches!(status, Status::Unchanged { .. }));
+}
+
+#[test]
+fn plugin_update_unpinned_without_record() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(repository_path.join(".git")).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    // Detached before branches were recorded.
+    fs::write(repository_path.join(".git/almoxarife-test-detached"), "").unwrap();
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    plugin.rev = None;
+    fake.set("ALMOXARIFE_TEST_DEFAULT_BRANCH", "main".into());
+
+    plugin.manage().unwrap();
+    assert_eq!(
+        fs::read_to_string(repository_path.join(".git/almoxarife-test-branch")).unwrap(),
+        "main\n"
+    );
+}
+
+#[test]
+fn plugin_update_pinned_fetch_fail() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (plugin, fake) = pinned_plugin_with_fake(repository_path, link_path, "ghijkl");
+    fake.set("ALMOXARIFE_TEST_PULL_FAIL", "unexpected error!".into());
+
+    let error = plugin.manage().unwrap_err();
+    assert_eq!(
+        error,
+        PluginError::Pull(
+            "kakoune-phantom-selection".into(),
+            "git exited with status 5: can't fetch changes".into()
+        )
+    );
+}
+
+#[test]
+fn plugin_clone_pinned() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    fake.unset("ALMOXARIFE_TEST_CWD");
+
+    let status = plugin.manage().unwrap();
+    assert!(matches!(status, Status::Installed { .. }));
+    assert_eq!(
+        fs::read_to_string(repository_path.join("checkout.txt")).unwrap(),
+        "ghijkl\n"
+    );
+}
+
+#[test]
+fn plugin_clone_tagged() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path, link_path, "ghijkl");
+    plugin.rev = None;
+    fake.unset("ALMOXARIFE_TEST_CWD");
+    fake.reply("git", &["describe"], 0, "v1.2\n", "");
+
+    // The commit installed, along with the tag naming it.
+    let status = plugin.manage().unwrap();
+    let Status::Installed { revision, tag, .. } = status else {
+        panic!("unexpected status {status:?}");
+    };
+    assert_eq!(revision, "abcdef");
+    assert_eq!(tag.as_deref(), Some("v1.2"));
+}
+
+#[test]
+fn plugin_clone_through_proxy() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    fake.unset("ALMOXARIFE_TEST_CWD");
+    plugin.proxy = Some("socks5://localhost:1080".into());
+    fake.set("ALMOXARIFE_TEST_PROXY", "socks5://localhost:1080".into());
+
+    let status = plugin.manage().unwrap();
+    assert!(matches!(status, Status::Installed { .. }));
+}
+
+#[test]
+fn plugin_clone_through_url_rewrite() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    fake.unset("ALMOXARIFE_TEST_CWD");
+    plugin.url_rewrites = BTreeMap::from([(
+        "https://github.com/".into(),
+        "https://mirror.internal/github/".into(),
+    )]);
+    fake.set(
+        "ALMOXARIFE_TEST_LOCATION",
+        "https://mirror.internal/github/occivink/kakoune-phantom-selection.git".into(),
+    );
+
+    let status = plugin.manage().unwrap();
+    assert!(matches!(status, Status::Installed { .. }));
+}
+
+#[test]
+fn upstream_references() {
+    let file = b"
+luar:
+    location: https://github.com/gustavo-hms/luar
+luar-fork:
+    location: https://github.com/me/luar
+    upstream: https://github.com/gustavo-hms/luar
+luar-other-fork:
+    location: https://github.com/someone/luar
+    upstream: https://github.com/gustavo-hms/luar
+peneira:
+    location: https://github.com/gustavo-hms/peneira
+";
+
+    let setup = Setup::default();
+    let config = setup.config_from_buffer(file.as_slice()).unwrap();
+    let data_dir = &setup.almoxarife_data_dir;
+
+    let references: HashMap<_, _> = config
+        .active_plugins()
+        .into_iter()
+        .map(|plugin| {
+            let mut references = plugin.references;
+            references.sort();
+            (plugin.name, references)
+        })
+        .collect();
+
+    assert_eq!(
+        references,
+        HashMap::from([
+            (
+                "luar".into(),
+                vec![data_dir.join("luar-fork"), data_dir.join("luar-other-fork")]
+            ),
+            (
+                "luar-fork".into(),
+                vec![data_dir.join("luar"), data_dir.join("luar-other-fork")]
+            ),
+            (
+                "luar-other-fork".into(),
+                vec![data_dir.join("luar"), data_dir.join("luar-fork")]
+            ),
+            ("peneira".into(), Vec::new()),
+        ])
+    );
+}
+
+#[test]
+fn plugin_clone_with_reference() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+    let sibling = temp_dir.path().join("repo/phantom-selection-fork");
+    fs::create_dir_all(&sibling).unwrap();
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    fake.unset("ALMOXARIFE_TEST_CWD");
+    plugin.references = vec![temp_dir.path().join("repo/missing"), sibling.clone()];
+
+    let status = plugin.manage().unwrap();
+    assert!(matches!(status, Status::Installed { .. }));
+    assert_eq!(
+        fs::read_to_string(repository_path.join(".git/reference")).unwrap(),
+        format!("{}\n", sibling.to_string_lossy())
+    );
+}
+
+#[test]
+fn plugin_update_direct_connection() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (plugin, fake) = pinned_plugin_with_fake(repository_path, link_path, "ghijkl");
+    fake.set("ALMOXARIFE_TEST_PROXY", String::new());
+
+    let error = plugin.manage().err().unwrap();
+    assert!(error.description().contains("wrong proxy: unset"));
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let mut plugin = pinned_plugin(repository_path, link_path, "ghijkl");
+    fake.set("ALMOXARIFE_TEST_PROXY", String::new());
+    plugin.proxy = Some(String::new());
+
+    let status = plugin.manage().unwrap();
+    assert!(matches!(status, Status::Updated { .. }));
+}
+
+#[test]
+fn plugin_last_upstream_commit() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    fs::write(repository_path.join("commit-time.txt"), "1600000000\n").unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (plugin, fake) = pinned_plugin_with_fake(repository_path, link_path, "abc");
+    assert_eq!(plugin.last_upstream_commit(), Ok(1600000000));
+
+    fake.reply("git", &["--format=%ct", "--remotes"], 0, "1700000000\n", "");
+    assert_eq!(plugin.last_upstream_commit(), Ok(1700000000));
+}
+
+#[test]
+fn plugin_activate() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    fs::create_dir_all(&repository_path).unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (plugin, fake) = pinned_plugin_with_fake(repository_path, link_path.clone(), "abc");
+    // Activating must not run git at all.
+    fake.reply("git", &[], 1, "", "unexpected git");
+
+    let status = plugin.activate().unwrap();
+    assert!(matches!(status, Status::Unchanged { .. }));
+    assert!(link_path.is_symlink());
+}
+
+#[test]
+fn plugin_activate_not_installed() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let plugin = pinned_plugin(repository_path, link_path, "abc");
+    assert_eq!(
+        plugin.activate().unwrap_err(),
+        PluginError::Link(
+            "kakoune-phantom-selection".into(),
+            "the plugin is not installed".into()
+        )
+    );
+}
+
+#[test]
+fn remove_repository() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("peneira");
+    fs::create_dir_all(path.join("rc")).unwrap();
+
+    assert_eq!(
+        setup::remove_repository(&path),
+        Ok(Status::Deleted {
+            name: "peneira".into()
+        })
+    );
+    assert!(!path.exists());
+
+    // Already gone, as when the directory was deleted by hand.
+    assert_eq!(
+        setup::remove_repository(&path),
+        Ok(Status::Deleted {
+            name: "peneira".into()
+        })
+    );
+}
+
+#[test]
+fn plugin_clone_remotes() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "abc");
+    fake.unset("ALMOXARIFE_TEST_CWD");
+    plugin.rev = None;
+    plugin.remotes = Remotes {
+        name: Some("upstream".into()),
+        extra: [("fork".into(), "git@github.com:me/kps".into())].into(),
+    };
+
+    let status = plugin.manage().unwrap();
+    assert!(matches!(status, Status::Installed { .. }));
+
+    let remotes = repository_path.join(".git/remotes");
+    assert_eq!(
+        fs::read_to_string(remotes.join("upstream")).unwrap(),
+        "https://github.com/occivink/kakoune-phantom-selection.git\n"
+    );
+    assert_eq!(
+        fs::read_to_string(remotes.join("fork")).unwrap(),
+        "git@github.com:me/kps\n"
+    );
+    assert!(!remotes.join("origin").exists());
+}
+
+#[test]
+fn plugin_update_syncs_remotes() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let remotes = repository_path.join(".git/remotes");
+    fs::create_dir_all(&remotes).unwrap();
+    fs::write(remotes.join("fork"), "git@github.com:old/kps\n").unwrap();
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let mut plugin = pinned_plugin(repository_path, link_path, "abc");
+    plugin.remotes.extra = [
+        ("fork".into(), "git@github.com:me/kps".into()),
+        ("mirror".into(), "https://codeberg.org/me/kps".into()),
+    ]
+    .into();
+
+    let status = plugin.manage().unwrap();
+    assert!(matches!(status, Status::Unchanged { .. }));
+    assert_eq!(
+        fs::read_to_string(remotes.join("fork")).unwrap(),
+        "git@github.com:me/kps\n"
+    );
+    assert_eq!(
+        fs::read_to_string(remotes.join("mirror")).unwrap(),
+        "https://codeberg.org/me/kps\n"
+    );
+}
@@ -0,0 +1,292 @@
+use std::fs;
+
+use crate::download::Download;
+use crate::setup::Plugin;
+use crate::setup::PluginError;
+use crate::setup::Status;
+
+/// Where the files of a plugin come from, and how they're brought up to date.
+/// A new way of fetching plugins is a new source in `SOURCES`.
+pub trait Source: Sync {
+    /// Whether the source is the one bringing in `plugin`.
+    fn handles(&self, plugin: &Plugin) -> bool;
+
+    /// Brings in the files of a plugin that isn't installed.
+    fn install(&self, plugin: &Plugin) -> Result<Status, PluginError>;
+
+    /// Brings the files of an installed plugin up to date.
+    fn update(&self, plugin: &Plugin) -> Result<Status, PluginError>;
+}
+
+/// The sources, in the order they're asked whether they handle a plugin. git
+/// handles whatever the others don't, so it comes last.
+const SOURCES: &[&dyn Source] = &[&LocalSource, &FileSource, &ReviewedSource, &GitSource];
+
+/// The source bringing in `plugin`.
+pub fn of(plugin: &Plugin) -> &'static dyn Source {
+    SOURCES
+        .iter()
+        .copied()
+        .find(|source| source.handles(plugin))
+        .unwrap_or(&GitSource)
+}
+
+/// Plugins taken from a folder of the user's, which Almoxarife never changes.
+pub struct LocalSource;
+
+impl Source for LocalSource {
+    fn handles(&self, plugin: &Plugin) -> bool {
+        plugin.is_local
+    }
+
+    fn install(&self, plugin: &Plugin) -> Result<Status, PluginError> {
+        Err(PluginError::Link(
+            plugin.name.clone(),
+            format!("the path {} is empty", plugin.location),
+        ))
+    }
+
+    fn update(&self, plugin: &Plugin) -> Result<Status, PluginError> {
+        Ok(Status::Local {
+            name: plugin.name.clone(),
+            config: plugin.config(),
+        })
+    }
+}
+
+/// Plugins which are a single `.kak` file, downloaded over HTTP into a
+/// directory of their own. The file's git hash is their revision, and the
+/// ETag of the last download spares downloading it again while unchanged.
+pub struct FileSource;
+
+impl Source for FileSource {
+    fn handles(&self, plugin: &Plugin) -> bool {
+        plugin.single_file
+    }
+
+    fn install(&self, plugin: &Plugin) -> Result<Status, PluginError> {
+        let result = fs::create_dir_all(&plugin.repository_path)
+            .map_err(|e| {
+                PluginError::Clone(
+                    plugin.name.clone(),
+                    format!(
+                        "couldn't create {}: {e}",
+                        plugin.repository_path.to_string_lossy()
+                    ),
+                )
+            })
+            .and_then(|_| download(plugin, PluginError::Clone))
+            .and_then(|_| file_installed(plugin));
+
+        if result.is_err() {
+            let _ = fs::remove_dir_all(&plugin.repository_path);
+        }
+
+        result
+    }
+
+    fn update(&self, plugin: &Plugin) -> Result<Status, PluginError> {
+        // Downloaded for review already.
+        if plugin.reviewed {
+            return file_installed(plugin);
+        }
+
+        let old_revision = file_revision(plugin, PluginError::Pull)?;
+        download(plugin, PluginError::Pull)?;
+        let new_revision = file_revision(plugin, PluginError::Pull)?;
+        let name = plugin.name.clone();
+
+        if old_revision == new_revision {
+            return Ok(Status::Unchanged {
+                name,
+                config: plugin.config(),
+                revision: Some(new_revision),
+                renamed_branch: None,
+                last_commit: None,
+            });
+        }
+
+        let short = new_revision.get(..7).unwrap_or(&new_revision);
+
+        Ok(Status::Updated {
+            name,
+            log: format!("{short} {} changed upstream\n", file_name(plugin)),
+            config: plugin.config(),
+            old_revision,
+            new_revision,
+            history_rewritten: false,
+            breaking_changes: Vec::new(),
+            renamed_branch: None,
+        })
+    }
+}
+
+/// The name the file of `plugin` is saved as: the last part of its URL, if
+/// it's a Kakoune script, or else the plugin's name, so that Kakoune loads
+/// it anyway.
+fn file_name(plugin: &Plugin) -> String {
+    let path = plugin.location.split(['?', '#']).next().unwrap_or_default();
+
+    match path.rsplit_once('/') {
+        Some((_, name)) if name.ends_with(".kak") => name.to_string(),
+        _ => format!("{}.kak", plugin.name),
+    }
+}
+
+/// Downloads the file of `plugin` into its directory, unless the server
+/// tells, from the ETag of the last download, that it didn't change.
+fn download(plugin: &Plugin, error: fn(String, String) -> PluginError) -> Result<(), PluginError> {
+    let dir = &plugin.repository_path;
+
+    Download {
+        url: &plugin.location,
+        runner: &plugin.runner,
+        deadline: plugin.deadline,
+        proxy: plugin.proxy.as_deref(),
+        sha256: plugin.sha256.as_deref(),
+        cache: plugin.download_cache.as_deref(),
+    }
+    .to(&dir.join(file_name(plugin)), &dir.join(".etag"))
+    .map(|_| ())
+    .map_err(|e| error(plugin.name.clone(), e))
+}
+
+/// The git hash of the file of `plugin`, which stands for its revision.
+fn file_revision(
+    plugin: &Plugin,
+    error: fn(String, String) -> PluginError,
+) -> Result<String, PluginError> {
+    let hash = plugin.git(&["hash-object", "--", &file_name(plugin)], error)?;
+    Ok(hash.trim().to_string())
+}
+
+/// The status of the plugin whose file was just downloaded.
+fn file_installed(plugin: &Plugin) -> Result<Status, PluginError> {
+    Ok(Status::Installed {
+        name: plugin.name.clone(),
+        config: plugin.config(),
+        revision: file_revision(plugin, PluginError::Clone)?,
+        tag: None,
+    })
+}
+
+/// Plugins cloned ahead of the update for the user to review, and already
+/// installed by the time the update gets to them.
+pub struct ReviewedSource;
+
+impl Source for ReviewedSource {
+    fn handles(&self, plugin: &Plugin) -> bool {
+        plugin.reviewed
+    }
+
+    fn install(&self, plugin: &Plugin) -> Result<Status, PluginError> {
+        GitSource.install(plugin)
+    }
+
+    fn update(&self, plugin: &Plugin) -> Result<Status, PluginError> {
+        installed(plugin)
+    }
+}
+
+/// Plugins cloned from a git repository.
+pub struct GitSource;
+
+impl Source for GitSource {
+    fn handles(&self, _: &Plugin) -> bool {
+        true
+    }
+
+    fn install(&self, plugin: &Plugin) -> Result<Status, PluginError> {
+        let result = clone(plugin);
+
+        // A checkout left halfway, by a failure or an interrupted run, would
+        // pass for an installed plugin on the next run.
+        if result.is_err() && plugin.repository_path.exists() {
+            let _ = fs::remove_dir_all(&plugin.repository_path);
+        }
+
+        result
+    }
+
+    fn update(&self, plugin: &Plugin) -> Result<Status, PluginError> {
+        plugin.sync_remotes(PluginError::Pull)?;
+        let old_revision = plugin.current_version()?;
+        let renamed_branch = plugin.follow_default_branch()?;
+        let history_rewritten = plugin.update(&old_revision)?;
+        let new_revision = plugin.current_version()?;
+        let name = plugin.name.clone();
+
+        if old_revision == new_revision {
+            let last_commit = plugin
+                .commit_age
+                .then(|| plugin.last_upstream_commit().ok())
+                .flatten();
+
+            return Ok(Status::Unchanged {
+                name,
+                config: plugin.config(),
+                revision: Some(new_revision),
+                renamed_branch,
+                last_commit,
+            });
+        }
+
+        let breaking_changes = if plugin.flag_breaking_changes {
+            plugin.breaking_changes(&old_revision, &new_revision)
+        } else {
+            Vec::new()
+        };
+
+        Ok(Status::Updated {
+            name,
+            log: plugin.log(&old_revision, &new_revision)?,
+            config: plugin.config(),
+            old_revision,
+            new_revision,
+            history_rewritten,
+            breaking_changes,
+            renamed_branch,
+        })
+    }
+}
+
+/// Clones the plugin and checks out the revision it asks for.
+fn clone(plugin: &Plugin) -> Result<Status, PluginError> {
+    plugin.clone_repo(&plugin.location)?;
+
+    if let Some(rev) = &plugin.rev {
+        plugin.detach(rev, PluginError::Clone)?;
+    } else if let Some(pattern) = &plugin.tag_pattern {
+        let tag = plugin.newest_tag(pattern, PluginError::Clone)?;
+        plugin.detach(&tag, PluginError::Clone)?;
+    }
+
+    plugin.sync_remotes(PluginError::Clone)?;
+    installed(plugin)
+}
+
+/// The status of the plugin just cloned.
+fn installed(plugin: &Plugin) -> Result<Status, PluginError> {
+    // The config is only generated after cloning, because lazy plugins
+    // need their sources to generate it.
+    let name = plugin.name.clone();
+    let config = plugin.config();
+    let revision = plugin.current_version()?;
+    let tag = plugin.current_tag();
+
+    if plugin.has_kak_files() {
+        Ok(Status::Installed {
+            name,
+            config,
+            revision,
+            tag,
+        })
+    } else {
+        Ok(Status::NoKakFiles {
+            name,
+            config,
+            revision,
+            tag,
+        })
+    }
+}
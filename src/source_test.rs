@@ -0,0 +1,401 @@
+use std::fs;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::setup::Plugin;
+use crate::setup::PluginError;
+use crate::setup::Sandbox;
+use crate::setup::Status;
+use crate::setup_test::pinned_plugin;
+use crate::setup_test::pinned_plugin_with_fake;
+use crate::source::Source;
+use crate::state::Build;
+
+/// A source recording what it was asked, instead of running anything.
+#[derive(Default)]
+struct FakeSource {
+    calls: Mutex<Vec<&'static str>>,
+    /// The `almoxarife.toml` of the plugins installed, if any.
+    manifest: Option<&'static str>,
+}
+
+impl Source for FakeSource {
+    fn handles(&self, _: &Plugin) -> bool {
+        true
+    }
+
+    fn install(&self, plugin: &Plugin) -> Result<Status, PluginError> {
+        self.calls.lock().unwrap().push("install");
+        fs::create_dir_all(&plugin.repository_path).unwrap();
+
+        if let Some(manifest) = self.manifest {
+            fs::write(plugin.repository_path.join("almoxarife.toml"), manifest).unwrap();
+        }
+
+        Ok(Status::Installed {
+            name: plugin.name.clone(),
+            config: String::new(),
+            revision: "abcdef".into(),
+            tag: None,
+        })
+    }
+
+    fn update(&self, plugin: &Plugin) -> Result<Status, PluginError> {
+        self.calls.lock().unwrap().push("update");
+
+        Ok(Status::Unchanged {
+            name: plugin.name.clone(),
+            config: String::new(),
+            revision: Some("abcdef".into()),
+            renamed_branch: None,
+            last_commit: None,
+        })
+    }
+}
+
+#[test]
+fn fetch_from_source() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+    let plugin = pinned_plugin(repository_path, link_path, "ghijkl");
+    let source = FakeSource::default();
+
+    let status = plugin.fetch_from(&source).unwrap();
+    assert!(matches!(status, Status::Installed { .. }));
+
+    let status = plugin.fetch_from(&source).unwrap();
+    assert!(matches!(status, Status::Unchanged { .. }));
+
+    assert_eq!(*source.calls.lock().unwrap(), vec!["install", "update"]);
+}
+
+#[test]
+fn local_source() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("mine");
+    let link_path = temp_dir.path().join("link");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    plugin.is_local = true;
+    plugin.location = repository_path.to_string_lossy().into();
+
+    let error = plugin.fetch().err().unwrap();
+    assert!(matches!(error, PluginError::Link(..)));
+    assert!(error.description().contains("is empty"));
+
+    // The folder is never touched, so git is never run.
+    fs::create_dir_all(&repository_path).unwrap();
+
+    let status = plugin.fetch().unwrap();
+    assert!(matches!(status, Status::Local { .. }));
+    assert!(fake.programs().is_empty());
+}
+
+// Downloaded through the fake curl.
+#[test]
+#[cfg(not(feature = "http"))]
+fn file_source() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/smarttab");
+    let link_path = temp_dir.path().join("smarttab");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    plugin.rev = None;
+    plugin.single_file = true;
+    plugin.location = "https://gist.githubusercontent.com/someone/abc/raw/smarttab.kak".into();
+    fake.set(
+        "ALMOXARIFE_TEST_DOWNLOAD",
+        "define-command smarttab %{}".into(),
+    );
+    fake.set("ALMOXARIFE_TEST_ETAG", "\"1\"".into());
+
+    let status = plugin.fetch().unwrap();
+    assert!(matches!(status, Status::Installed { .. }));
+
+    let file = repository_path.join("smarttab.kak");
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "define-command smarttab %{}"
+    );
+
+    // The server tells, from the ETag, that nothing changed.
+    let status = plugin.fetch().unwrap();
+    assert!(matches!(status, Status::Unchanged { .. }));
+
+    // Or the file is downloaded again, and compared.
+    fake.set("ALMOXARIFE_TEST_ETAG", "\"2\"".into());
+    let status = plugin.fetch().unwrap();
+    assert!(matches!(status, Status::Unchanged { .. }));
+
+    fake.set(
+        "ALMOXARIFE_TEST_DOWNLOAD",
+        "define-command smarttab %{ nop }".into(),
+    );
+    fake.set("ALMOXARIFE_TEST_ETAG", "\"3\"".into());
+    let Status::Updated { log, .. } = plugin.fetch().unwrap() else {
+        panic!("the plugin wasn't updated");
+    };
+    assert!(log.ends_with(" smarttab.kak changed upstream\n"));
+
+    // A failed download leaves the file as it was.
+    fake.set("ALMOXARIFE_TEST_DOWNLOAD_FAIL", "1".into());
+    let error = plugin.fetch().err().unwrap();
+    assert!(matches!(error, PluginError::Pull(..)));
+    assert!(error.description().contains("404"));
+    assert_eq!(
+        fs::read_to_string(&file).unwrap(),
+        "define-command smarttab %{ nop }"
+    );
+
+    // And a failed install leaves nothing.
+    fs::remove_dir_all(&repository_path).unwrap();
+    let error = plugin.fetch().err().unwrap();
+    assert!(matches!(error, PluginError::Clone(..)));
+    assert!(!repository_path.exists());
+}
+
+#[test]
+fn reviewed_source() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    plugin.reviewed = true;
+    fs::create_dir_all(&repository_path).unwrap();
+    fs::write(repository_path.join("phantom.kak"), "").unwrap();
+
+    // Cloned for review, it's only reported installed, looking up its
+    // revision and tag.
+    let status = plugin.fetch().unwrap();
+    assert!(matches!(status, Status::Installed { .. }));
+    assert_eq!(fake.programs(), vec!["git", "git"]);
+}
+
+#[test]
+fn failed_installs_leave_nothing() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    fake.unset("ALMOXARIFE_TEST_CWD");
+    fake.reply(
+        "git",
+        &["rev-parse", "HEAD"],
+        7,
+        "",
+        "can't retrieve commit SHA",
+    );
+
+    let error = plugin.fetch().err().unwrap();
+    assert!(error.description().contains("can't retrieve commit SHA"));
+    assert!(!repository_path.exists());
+}
+
+#[test]
+fn skipped_past_deadline() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+
+    let (mut plugin, fake) =
+        pinned_plugin_with_fake(repository_path.clone(), link_path.clone(), "ghijkl");
+    plugin.deadline = Some(Instant::now());
+
+    let status = plugin.clone().manage().unwrap();
+    assert_eq!(
+        status,
+        Status::Skipped {
+            name: "kakoune-phantom-selection".into(),
+            config: None,
+        }
+    );
+    assert!(fake.programs().is_empty());
+    assert!(!link_path.exists());
+
+    // A clone running past the deadline is stopped, and leaves nothing.
+    plugin.deadline = Some(Instant::now() + Duration::from_millis(200));
+    fake.unset("ALMOXARIFE_TEST_CWD");
+    fake.hang("git", &["clone"]);
+
+    let status = plugin.fetch().unwrap();
+    assert!(matches!(status, Status::Skipped { config: None, .. }));
+    assert!(!repository_path.exists());
+}
+
+#[test]
+fn build_after_install() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kakoune-phantom-selection");
+    let link_path = temp_dir.path().join("kakoune-phantom-selection");
+    let plugin = pinned_plugin(repository_path.clone(), link_path, "ghijkl");
+
+    let source = FakeSource {
+        manifest: Some("build = 'touch built'"),
+        ..FakeSource::default()
+    };
+
+    // Fetching alone builds nothing, so that builds can be scheduled apart.
+    let status = plugin.fetch_from(&source).unwrap();
+    assert!(matches!(status, Status::Installed { .. }));
+    assert!(plugin.needs_build(&status));
+    assert!(!repository_path.join("built").exists());
+
+    let status = plugin.build(status, &Sandbox::default()).unwrap();
+    assert!(matches!(status, Status::Installed { .. }));
+    assert!(repository_path.join("built").exists());
+
+    // Nothing is built when nothing changed since the last build.
+    let mut plugin = plugin;
+    plugin.last_build = Some(Build {
+        revision: "abcdef".into(),
+        commands: vec!["touch built".into()],
+    });
+
+    fs::remove_file(repository_path.join("built")).unwrap();
+    let status = plugin.fetch_from(&source).unwrap();
+    assert!(matches!(status, Status::Unchanged { .. }));
+    assert!(!plugin.needs_build(&status));
+    plugin.build(status, &Sandbox::default()).unwrap();
+    assert!(!repository_path.join("built").exists());
+
+    // Unless asked to.
+    plugin.rebuild = true;
+    let status = plugin.fetch_from(&source).unwrap();
+    assert!(plugin.needs_build(&status));
+    plugin.rebuild = false;
+
+    // Or the build commands changed.
+    fs::write(
+        repository_path.join("almoxarife.toml"),
+        "build = ['touch built', 'touch done']",
+    )
+    .unwrap();
+
+    let status = plugin.fetch_from(&source).unwrap();
+    assert_eq!(
+        plugin.pending_build(&status),
+        Some(Build {
+            revision: "abcdef".into(),
+            commands: vec!["touch built".into(), "touch done".into()],
+        })
+    );
+    assert!(matches!(
+        plugin.build(status, &Sandbox::default()),
+        Ok(Status::Unchanged { .. })
+    ));
+    assert!(repository_path.join("done").exists());
+
+    // A failed build leaves nothing, so that the next update tries again.
+    fs::remove_dir_all(&repository_path).unwrap();
+
+    let source = FakeSource {
+        manifest: Some("build = 'echo no compiler >&2; exit 2'"),
+        ..FakeSource::default()
+    };
+
+    let status = plugin.fetch_from(&source).unwrap();
+    let error = plugin.build(status, &Sandbox::default()).err().unwrap();
+    assert_eq!(
+        error,
+        PluginError::Build(
+            "kakoune-phantom-selection".into(),
+            "\"echo no compiler >&2; exit 2\" exited with status 2: no compiler".into()
+        )
+    );
+    assert!(!repository_path.exists());
+}
+
+#[test]
+fn build_into_bin_dir() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kak-lsp");
+    let link_path = temp_dir.path().join("kak-lsp");
+    let plugin = pinned_plugin(repository_path, link_path, "ghijkl");
+
+    let source = FakeSource {
+        manifest: Some("build = 'touch \"$ALMOXARIFE_BIN/kak-lsp\"'"),
+        ..FakeSource::default()
+    };
+
+    let status = plugin.fetch_from(&source).unwrap();
+    plugin.build(status, &Sandbox::default()).unwrap();
+    assert!(temp_dir.path().join("repo/bin/kak-lsp").exists());
+}
+
+#[test]
+fn build_in_sandbox() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let repository_path = temp_dir.path().join("repo/kak-lsp");
+    let link_path = temp_dir.path().join("kak-lsp");
+    let (mut plugin, fake) = pinned_plugin_with_fake(repository_path.clone(), link_path, "ghijkl");
+    let bwrap_calls = || -> Vec<String> {
+        fake.calls("bwrap")
+            .iter()
+            .map(|args| args.join(" "))
+            .collect()
+    };
+
+    let source = FakeSource {
+        manifest: Some("build = 'touch built'"),
+        ..FakeSource::default()
+    };
+
+    // Bubblewrap is tried first, and then runs the build with write access
+    // to the checkout and the bin directory only.
+    let sandbox = Sandbox::default();
+    let status = plugin.fetch_from(&source).unwrap();
+    plugin.build(status, &sandbox).unwrap();
+    assert!(repository_path.join("built").exists());
+
+    let [probe, build] = bwrap_calls().try_into().unwrap();
+    assert!(probe.starts_with("--ro-bind / / "));
+    assert!(probe.ends_with(" true"));
+
+    let repository = repository_path.to_string_lossy();
+    let bin = temp_dir.path().join("repo/bin");
+    assert!(build.contains(&format!("--bind {repository} {repository} ")));
+    assert!(build.contains(&format!("--bind {} {} ", bin.display(), bin.display())));
+    assert!(build.ends_with("-- sh -c touch built"));
+
+    // Bubblewrap is only tried once per run.
+    plugin.rebuild = true;
+    let status = plugin.fetch_from(&source).unwrap();
+    plugin.build(status, &sandbox).unwrap();
+    assert_eq!(bwrap_calls().len(), 3);
+    assert_eq!(sandbox.warning(), None);
+
+    // Trusted plugins are built unconfined.
+    plugin.trusted = true;
+    let status = plugin.fetch_from(&source).unwrap();
+    plugin.build(status, &sandbox).unwrap();
+    assert_eq!(bwrap_calls().len(), 3);
+    assert_eq!(sandbox.warning(), None);
+
+    // And so is every plugin where bubblewrap can't make sandboxes, which
+    // is worth a warning.
+    plugin.trusted = false;
+    fake.reply(
+        "bwrap",
+        &[],
+        1,
+        "",
+        "bwrap: No permissions to create new namespace",
+    );
+    fs::remove_file(repository_path.join("built")).unwrap();
+    let sandbox = Sandbox::default();
+    let status = plugin.fetch_from(&source).unwrap();
+    plugin.build(status, &sandbox).unwrap();
+    assert!(repository_path.join("built").exists());
+    assert_eq!(
+        sandbox.warning().unwrap(),
+        format!(
+            "{} built without a sandbox, since bubblewrap is missing or can't make sandboxes here; install it, or set `trusted: true` on the plugins you trust",
+            plugin.name
+        )
+    );
+    assert_eq!(sandbox.warning(), None);
+}
@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+/// What Almoxarife remembers between runs.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct State {
+    /// When the last update without failures finished, in seconds since the
+    /// Unix epoch.
+    #[serde(default)]
+    pub last_successful_run: Option<u64>,
+    /// A summary of the last update without failures.
+    #[serde(default)]
+    pub last_result: String,
+    /// When the last update finished, whether it failed or not.
+    #[serde(default)]
+    pub last_run: Option<u64>,
+    /// A summary of the last update, whether it failed or not.
+    #[serde(default)]
+    pub last_run_result: String,
+    /// When forges were last asked which plugins are archived.
+    #[serde(default)]
+    pub last_archive_check: Option<u64>,
+    /// The plugins archived upstream, as of the last check.
+    #[serde(default)]
+    pub archived: Vec<String>,
+    /// Since when each disabled plugin has been disabled, in seconds since
+    /// the Unix epoch.
+    #[serde(default)]
+    pub disabled_since: BTreeMap<String, u64>,
+    /// When the checkouts were last compacted.
+    #[serde(default)]
+    pub last_maintenance: Option<u64>,
+    /// The last successful build of each plugin with build commands.
+    #[serde(default)]
+    pub builds: BTreeMap<String, Build>,
+    /// What each plugin was installed at, for those installed since this was
+    /// recorded.
+    #[serde(default)]
+    pub installs: BTreeMap<String, Install>,
+    /// The plugins the last update got done with, when it was interrupted or
+    /// some plugins failed, for `al --resume` to leave alone.
+    #[serde(default)]
+    pub progress: BTreeMap<String, Done>,
+}
+
+/// What a plugin was built from.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Build {
+    pub revision: String,
+    pub commands: Vec<String>,
+}
+
+/// What a plugin got when it was installed.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Install {
+    pub revision: String,
+    /// The tag pointing at `revision`, if any.
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// When, in seconds since the Unix epoch.
+    pub at: u64,
+}
+
+/// What an update did to a plugin it got done with.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Done {
+    /// Unset for plugins just installed.
+    #[serde(default)]
+    pub old_revision: Option<String>,
+    pub new_revision: String,
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// The commits the update brought, as listed in its report.
+    #[serde(default)]
+    pub log: String,
+    /// What the plugin was built from, if the update built it.
+    #[serde(default)]
+    pub build: Option<Build>,
+}
+
+impl State {
+    /// Loads the state from `path`. A missing or unreadable file gives an empty
+    /// state, since the state is only a cache.
+    pub fn load(path: &Path) -> State {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|yaml| serde_yaml::from_str(&yaml).ok())
+            .unwrap_or_default()
+    }
+
+    /// How long ago the last successful update finished, if less than
+    /// `interval` ago.
+    pub fn recent_run(&self, now: u64, interval: Duration) -> Option<Duration> {
+        let elapsed = now.checked_sub(self.last_successful_run?)?;
+        (elapsed < interval.as_secs()).then(|| Duration::from_secs(elapsed))
+    }
+
+    /// Whether the checkouts weren't compacted in the last `interval`.
+    pub fn maintenance_due(&self, now: u64, interval: Duration) -> bool {
+        self.last_maintenance
+            .is_none_or(|last| now.saturating_sub(last) >= interval.as_secs())
+    }
+
+    /// Records `disabled` as the plugins disabled as of `now`. Plugins enabled
+    /// again are forgotten, and those already disabled keep their date.
+    pub fn track_disabled(&mut self, disabled: &[String], now: u64) {
+        self.disabled_since
+            .retain(|name, _| disabled.contains(name));
+
+        for name in disabled {
+            self.disabled_since.entry(name.clone()).or_insert(now);
+        }
+    }
+
+    /// The plugins among `disabled` which have been disabled for at least
+    /// `after` as of `now`.
+    pub fn disabled_for<'a>(
+        &self,
+        disabled: &'a [String],
+        after: Duration,
+        now: u64,
+    ) -> Vec<&'a str> {
+        disabled
+            .iter()
+            .filter(|name| {
+                self.disabled_since
+                    .get(*name)
+                    .is_some_and(|since| now.saturating_sub(*since) >= after.as_secs())
+            })
+            .map(String::as_str)
+            .collect()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let yaml = serde_yaml::to_string(self).map_err(io::Error::other)?;
+        fs::write(path, yaml)
+    }
+}
+
+/// Appends `events` to the history log at `path`, one per line, each prefixed
+/// by `now`, in seconds since the Unix epoch.
+pub fn record(path: &Path, now: u64, events: &[String]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut log = OpenOptions::new().create(true).append(true).open(path)?;
+    let lines: String = events
+        .iter()
+        .map(|event| format!("{now} {event}\n"))
+        .collect();
+    log.write_all(lines.as_bytes())
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::Duration;
+
+use crate::state;
+use crate::state::Build;
+use crate::state::Done;
+use crate::state::Install;
+use crate::state::State;
+
+#[test]
+fn save_and_load() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("state/state.yaml");
+
+    let state = State {
+        last_successful_run: Some(1700000000),
+        last_result: "Plugins: 2 updated".into(),
+        last_run: Some(1700003600),
+        last_run_result: "Plugins: 1 failed".into(),
+        last_archive_check: Some(1700000000),
+        archived: vec!["kakoune-old".into()],
+        disabled_since: BTreeMap::from([("search".into(), 1690000000)]),
+        last_maintenance: Some(1690000000),
+        builds: BTreeMap::from([(
+            "kak-lsp".into(),
+            Build {
+                revision: "abc123".into(),
+                commands: vec!["cargo build --release".into()],
+            },
+        )]),
+        installs: BTreeMap::from([(
+            "peneira".into(),
+            Install {
+                revision: "def456".into(),
+                tag: Some("v1.2".into()),
+                at: 1690000000,
+            },
+        )]),
+        progress: BTreeMap::from([(
+            "luar".into(),
+            Done {
+                old_revision: Some("abc123".into()),
+                new_revision: "def456".into(),
+                tag: None,
+                log: "def456 Fix the interpreter detection".into(),
+                build: None,
+            },
+        )]),
+    };
+
+    state.save(&path).unwrap();
+    assert_eq!(State::load(&path), state);
+}
+
+#[test]
+fn load_missing_or_broken() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("state.yaml");
+    assert_eq!(State::load(&path), State::default());
+
+    fs::write(&path, "last_successful_run: [").unwrap();
+    assert_eq!(State::load(&path), State::default());
+}
+
+#[test]
+fn recent_run() {
+    let state = State {
+        last_successful_run: Some(1000),
+        ..State::default()
+    };
+
+    let hour = Duration::from_secs(3600);
+    assert_eq!(state.recent_run(1600, hour), Some(Duration::from_secs(600)));
+    assert_eq!(state.recent_run(4600, hour), None);
+    assert_eq!(State::default().recent_run(1600, hour), None);
+}
+
+#[test]
+fn maintenance_due() {
+    let state = State {
+        last_maintenance: Some(1000),
+        ..State::default()
+    };
+
+    let hour = Duration::from_secs(3600);
+    assert!(!state.maintenance_due(1600, hour));
+    assert!(state.maintenance_due(4600, hour));
+    assert!(State::default().maintenance_due(1600, hour));
+}
+
+#[test]
+fn track_disabled() {
+    let mut state = State::default();
+    state.track_disabled(&["search".into(), "luar".into()], 1000);
+    state.track_disabled(&["search".into(), "peneira".into()], 2000);
+
+    assert_eq!(
+        state.disabled_since,
+        BTreeMap::from([("peneira".into(), 2000), ("search".into(), 1000)])
+    );
+
+    let disabled = ["search".to_string(), "peneira".into(), "auto-pairs".into()];
+    let hour = Duration::from_secs(3600);
+    assert_eq!(state.disabled_for(&disabled, hour, 4600), ["search"]);
+    assert_eq!(
+        state.disabled_for(&disabled, hour, 5600),
+        ["search", "peneira"]
+    );
+}
+
+#[test]
+fn record_history() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let path = temp_dir.path().join("state/history.log");
+
+    state::record(&path, 1700000000, &["peneira: reset".into()]).unwrap();
+    state::record(&path, 1700000060, &["luar: reset".into()]).unwrap();
+
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "1700000000 peneira: reset\n1700000060 luar: reset\n"
+    );
+}
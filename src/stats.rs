@@ -0,0 +1,94 @@
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::forge;
+use crate::time;
+
+/// Aggregate numbers about the plugins, as told by `al stats`.
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    pub enabled: usize,
+    pub disabled: usize,
+    /// The enabled plugins taken from local folders.
+    pub local: usize,
+    /// The bytes taken by the checkouts.
+    pub disk_usage: u64,
+    /// When the last update finished, and a summary of it.
+    pub last_run: Option<(u64, String)>,
+    /// When the last update without failures finished.
+    pub last_successful_run: Option<u64>,
+    /// The plugins whose upstream got no commits for longer than the
+    /// threshold, along with the time of their newest commit.
+    pub stale: Vec<(String, u64)>,
+}
+
+impl Stats {
+    /// Describes the numbers, one per line, as of `now`. `stale_after` is the
+    /// threshold the stale plugins were picked with.
+    pub fn render(&self, now: u64, stale_after: Duration) -> String {
+        let ago = |then: u64| {
+            let elapsed = Duration::from_secs(now.saturating_sub(then));
+            format!("{} ago", time::describe_duration(elapsed))
+        };
+
+        let mut text = format!(
+            "Plugins: {} ({} enabled, {} disabled), {} of them local
+Disk usage: {}
+",
+            self.enabled + self.disabled,
+            self.enabled,
+            self.disabled,
+            self.local,
+            forge::describe_size(self.disk_usage),
+        );
+
+        match &self.last_run {
+            Some((time, result)) => {
+                text.push_str(&format!("Last update: {} ({result})\n", ago(*time)))
+            }
+            None => text.push_str("Last update: never\n"),
+        }
+
+        if let Some((last_run, _)) = self.last_run
+            && self.last_successful_run != Some(last_run)
+        {
+            let successful = self.last_successful_run.map_or("never".to_string(), ago);
+            text.push_str(&format!("Last update without failures: {successful}\n"));
+        }
+
+        if !self.stale.is_empty() {
+            text.push_str(&format!(
+                "Possibly abandoned, without upstream commits for over {}:\n",
+                time::describe_duration(stale_after)
+            ));
+
+            for (name, time) in &self.stale {
+                text.push_str(&format!("{name:>20} last commit {}\n", ago(*time)));
+            }
+        }
+
+        text
+    }
+}
+
+/// The bytes taken by the files under `path`, not following links. Whatever
+/// can't be read is left out.
+pub fn disk_usage(path: &Path) -> u64 {
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+
+    if !metadata.is_dir() {
+        return metadata.len();
+    }
+
+    fs::read_dir(path)
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .map(|entry| disk_usage(&entry.path()))
+                .sum()
+        })
+        .unwrap_or(0)
+}
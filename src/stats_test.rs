@@ -0,0 +1,57 @@
+use std::fs;
+use std::os::unix;
+use std::time::Duration;
+
+use crate::stats;
+use crate::stats::Stats;
+
+const YEAR: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+#[test]
+fn disk_usage() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let plugin = temp_dir.path().join("peneira");
+    fs::create_dir_all(plugin.join("rc")).unwrap();
+    fs::write(plugin.join("peneira.kak"), [0; 1000]).unwrap();
+    fs::write(plugin.join("rc/filters.kak"), [0; 24]).unwrap();
+    unix::fs::symlink("/usr", plugin.join("usr")).unwrap();
+
+    // The link itself is counted, but not what it points to.
+    let link = fs::symlink_metadata(plugin.join("usr")).unwrap().len();
+    assert_eq!(stats::disk_usage(temp_dir.path()), 1024 + link);
+    assert_eq!(stats::disk_usage(&temp_dir.path().join("missing")), 0);
+}
+
+#[test]
+fn render() {
+    let now = 1_700_000_000;
+
+    let stats = Stats {
+        enabled: 12,
+        disabled: 2,
+        local: 3,
+        disk_usage: 48 << 20,
+        last_run: Some((now - 3 * 3600, "Plugins: 1 failed".into())),
+        last_successful_run: Some(now - 2 * 86400),
+        stale: vec![("old-plugin".into(), now - 2 * YEAR.as_secs())],
+    };
+
+    assert_eq!(
+        stats.render(now, YEAR),
+        "Plugins: 14 (12 enabled, 2 disabled), 3 of them local
+Disk usage: 48 MB
+Last update: 3 hours ago (Plugins: 1 failed)
+Last update without failures: 2 days ago
+Possibly abandoned, without upstream commits for over 1 year:
+          old-plugin last commit 2 years ago
+"
+    );
+
+    assert_eq!(
+        Stats::default().render(now, YEAR),
+        "Plugins: 0 (0 enabled, 0 disabled), 0 of them local
+Disk usage: 0 bytes
+Last update: never
+"
+    );
+}
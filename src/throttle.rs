@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+use std::sync::Condvar;
+use std::sync::Mutex;
+use std::sync::PoisonError;
+use std::thread;
+
+use crate::policy;
+use crate::setup::Sandbox;
+
+/// What a run may do at once, and where its builds run.
+pub struct Limits {
+    pub hosts: HostLimits,
+    pub builds: BuildLimits,
+    pub sandbox: Sandbox,
+}
+
+/// Limits how many plugins are fetched at once from the same host, so that
+/// updating dozens of plugins hosted on the same forge doesn't get throttled.
+/// Plugins on different hosts don't wait for each other.
+pub struct HostLimits {
+    /// The most connections to a single host at once, if limited.
+    limit: Option<usize>,
+    /// The number of connections open to each host.
+    open: Mutex<HashMap<String, usize>>,
+    released: Condvar,
+}
+
+impl HostLimits {
+    pub fn new(limit: Option<usize>) -> Self {
+        HostLimits {
+            limit,
+            open: Mutex::new(HashMap::new()),
+            released: Condvar::new(),
+        }
+    }
+
+    /// Waits until a connection to the host of `url` is allowed. The
+    /// connection is taken as closed when the returned permit is dropped.
+    /// Local paths and `file://` URLs have no host, and never wait.
+    pub fn acquire(&self, url: &str) -> Permit<'_> {
+        let host = match (self.limit, policy::protocol_and_host(url)) {
+            (Some(limit), Some((_, host))) if !host.is_empty() => {
+                let host = host.to_ascii_lowercase();
+                let mut open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+
+                while open.get(&host).copied().unwrap_or(0) >= limit {
+                    open = self
+                        .released
+                        .wait(open)
+                        .unwrap_or_else(PoisonError::into_inner);
+                }
+
+                *open.entry(host.clone()).or_insert(0) += 1;
+                Some(host)
+            }
+
+            _ => None,
+        };
+
+        Permit { limits: self, host }
+    }
+}
+
+/// Permission to connect to a host, given back when dropped.
+pub struct Permit<'a> {
+    limits: &'a HostLimits,
+    host: Option<String>,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let Some(host) = &self.host else {
+            return;
+        };
+
+        let mut open = self
+            .limits
+            .open
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(count) = open.get_mut(host) {
+            *count -= 1;
+        }
+
+        self.limits.released.notify_all();
+    }
+}
+
+/// Limits how many plugins are built at once. Builds are CPU bound, unlike
+/// fetches, so they're limited apart: a long compilation doesn't hold back
+/// fetches, and many fetches don't hold back builds.
+pub struct BuildLimits {
+    limit: usize,
+    /// The number of builds running.
+    running: Mutex<usize>,
+    finished: Condvar,
+}
+
+impl BuildLimits {
+    /// Limits builds to `limit` at once, or to one per CPU.
+    pub fn new(limit: Option<usize>) -> Self {
+        let limit = limit.unwrap_or_else(|| thread::available_parallelism().map_or(1, usize::from));
+
+        BuildLimits {
+            limit,
+            running: Mutex::new(0),
+            finished: Condvar::new(),
+        }
+    }
+
+    /// Waits until another build is allowed. The build is taken as finished
+    /// when the returned permit is dropped.
+    pub fn acquire(&self) -> BuildPermit<'_> {
+        let mut running = self.running.lock().unwrap_or_else(PoisonError::into_inner);
+
+        while *running >= self.limit {
+            running = self
+                .finished
+                .wait(running)
+                .unwrap_or_else(PoisonError::into_inner);
+        }
+
+        *running += 1;
+        BuildPermit { limits: self }
+    }
+}
+
+/// Permission to build a plugin, given back when dropped.
+pub struct BuildPermit<'a> {
+    limits: &'a BuildLimits,
+}
+
+impl Drop for BuildPermit<'_> {
+    fn drop(&mut self) {
+        let mut running = self
+            .limits
+            .running
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        *running -= 1;
+        self.limits.finished.notify_all();
+    }
+}
@@ -0,0 +1,74 @@
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use crate::throttle::BuildLimits;
+use crate::throttle::HostLimits;
+
+/// Fetches every URL from its own thread, returning the most fetches that
+/// were running at once.
+fn most_at_once(limits: &HostLimits, urls: &[&str]) -> usize {
+    let running = AtomicUsize::new(0);
+    let most = AtomicUsize::new(0);
+
+    thread::scope(|s| {
+        for url in urls {
+            s.spawn(|| {
+                let _permit = limits.acquire(url);
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    most.into_inner()
+}
+
+#[test]
+fn limits_connections_per_host() {
+    let urls = [
+        "https://github.com/gustavo-hms/luar",
+        "https://github.com/gustavo-hms/peneira",
+        "git@github.com:alexherbo2/auto-pairs.kak",
+        "https://GitHub.com/occivink/kakoune-phantom-selection",
+    ];
+
+    assert_eq!(most_at_once(&HostLimits::new(Some(2)), &urls), 2);
+    assert_eq!(most_at_once(&HostLimits::new(None), &urls), 4);
+}
+
+#[test]
+fn hosts_dont_wait_for_each_other() {
+    let urls = [
+        "https://github.com/gustavo-hms/luar",
+        "https://codeberg.org/mbauhardt/peneira-filters",
+        "/home/gustavo-hms/peneira",
+        "file:///srv/mirrors/kakoune-lsp",
+    ];
+
+    assert_eq!(most_at_once(&HostLimits::new(Some(1)), &urls), 4);
+}
+
+#[test]
+fn limits_builds() {
+    let limits = BuildLimits::new(Some(2));
+    let running = AtomicUsize::new(0);
+    let most = AtomicUsize::new(0);
+
+    thread::scope(|s| {
+        for _ in 0..5 {
+            s.spawn(|| {
+                let _permit = limits.acquire();
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                most.fetch_max(now, Ordering::SeqCst);
+                thread::sleep(Duration::from_millis(20));
+                running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+    });
+
+    assert_eq!(most.into_inner(), 2);
+}
@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use crate::time;
+
+#[test]
+fn parse_duration() {
+    assert_eq!(time::parse_duration("90s"), Ok(Duration::from_secs(90)));
+    assert_eq!(time::parse_duration("30m"), Ok(Duration::from_secs(1800)));
+    assert_eq!(time::parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+    assert_eq!(time::parse_duration("2d"), Ok(Duration::from_secs(172800)));
+    assert_eq!(time::parse_duration("1w"), Ok(Duration::from_secs(604800)));
+    assert_eq!(time::parse_duration("0s"), Ok(Duration::ZERO));
+}
+
+#[test]
+fn parse_invalid_duration() {
+    for text in ["", "12", "h", "3 hours", "-1h"] {
+        assert_eq!(
+            time::parse_duration(text),
+            Err(format!(
+                "invalid duration {text:?}; use something like 30m, 12h or 2d"
+            ))
+        );
+    }
+}
+
+#[test]
+fn describe_duration() {
+    assert_eq!(time::describe_duration(Duration::from_secs(1)), "1 second");
+    assert_eq!(
+        time::describe_duration(Duration::from_secs(750)),
+        "12 minutes"
+    );
+    assert_eq!(
+        time::describe_duration(Duration::from_secs(7200)),
+        "2 hours"
+    );
+    assert_eq!(
+        time::describe_duration(Duration::from_secs(5 * 2592000)),
+        "5 months"
+    );
+    assert_eq!(
+        time::describe_duration(Duration::from_secs(3 * 31536000)),
+        "3 years"
+    );
+}
@@ -0,0 +1,251 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+use std::process::Command;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::adopt;
+use crate::edit;
+use crate::setup;
+use crate::setup::Plugin;
+use crate::setup::Setup;
+
+/// The file, inside a vendor directory, listing what it holds.
+pub const MANIFEST: &str = "manifest.yaml";
+
+/// What `al vendor` exported, for `al restore` to consume.
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Manifest {
+    pub plugins: Vec<Entry>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct Entry {
+    pub name: String,
+    pub location: String,
+    /// The commit the plugin was at when exported.
+    pub revision: String,
+    /// Where the plugin is, relative to the vendor directory: either a plain
+    /// working tree or, if it ends with `.bundle`, a git bundle.
+    pub path: PathBuf,
+}
+
+impl Entry {
+    fn is_bundle(&self) -> bool {
+        self.path
+            .extension()
+            .is_some_and(|extension| extension == "bundle")
+    }
+}
+
+/// Exports the installed plugins to `dir`, along with the configuration file
+/// and the generated kak file. Plugins are copied as working trees without
+/// their `.git` directory or, with `full_history`, as git bundles. Local
+/// plugins aren't exported, since their locations are paths on this machine.
+pub fn vendor(
+    setup: &Setup,
+    plugins: &[Plugin],
+    dir: &Path,
+    full_history: bool,
+) -> io::Result<Manifest> {
+    fs::create_dir_all(dir.join("plugins"))?;
+    let mut manifest = Manifest::default();
+
+    for plugin in plugins {
+        if !plugin.is_checkout() || !plugin.is_installed() {
+            continue;
+        }
+
+        let revision = git(setup, &plugin.repository_path, &["rev-parse", "HEAD"])?;
+
+        let path = if full_history {
+            let path = PathBuf::from(format!("plugins/{}.bundle", plugin.name));
+            let bundle = dir.join(&path).to_string_lossy().to_string();
+            git(
+                setup,
+                &plugin.repository_path,
+                &["bundle", "create", &bundle, "--all"],
+            )?;
+            path
+        } else {
+            let path = PathBuf::from(format!("plugins/{}", plugin.name));
+            adopt::copy_dir(&plugin.repository_path, &dir.join(&path), &[".git"])?;
+            path
+        };
+
+        manifest.plugins.push(Entry {
+            name: plugin.name.clone(),
+            location: plugin.location.clone(),
+            revision,
+            path,
+        });
+    }
+
+    fs::copy(&setup.almoxarife_yaml_path, dir.join("almoxarife.yaml"))?;
+
+    for kak in [&setup.almoxarife_kak, &setup.plugins_kak] {
+        if let Some(name) = kak.file_name()
+            && kak.exists()
+        {
+            fs::copy(kak, dir.join(name))?;
+        }
+    }
+
+    let yaml = serde_yaml::to_string(&manifest).map_err(io::Error::other)?;
+    fs::write(dir.join(MANIFEST), yaml)?;
+
+    Ok(manifest)
+}
+
+/// Writes a single tar `archive` holding every plugin as a git bundle, along
+/// with the configuration file, where each plugin is pinned to the revision it
+/// was bundled at.
+pub fn bundle(setup: &Setup, plugins: &[Plugin], archive: &Path) -> io::Result<Manifest> {
+    let archive = path::absolute(archive)?;
+    let staging = staging_dir()?;
+
+    let result = vendor(setup, plugins, &staging, true).and_then(|manifest| {
+        lock(&staging.join("almoxarife.yaml"), &manifest)?;
+        let archive = archive.to_string_lossy();
+        let staging = staging.to_string_lossy();
+        tar(setup, &["-cf", &archive, "-C", &staging, "."])?;
+        Ok(manifest)
+    });
+
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+/// Pins every plugin of the configuration file at `yaml` to its revision in
+/// `manifest`.
+fn lock(yaml: &Path, manifest: &Manifest) -> io::Result<()> {
+    let mut source = fs::read_to_string(yaml)?;
+
+    for entry in &manifest.plugins {
+        if let Some(edited) = edit::set_field(&source, &entry.name, "rev", &entry.revision) {
+            source = edited;
+        }
+    }
+
+    fs::write(yaml, source)
+}
+
+/// Installs the plugins exported by `vendor` or `bundle` into the data
+/// directory, returning their names. `from` is either a vendor directory or an
+/// archive. Plugins already installed are left alone. The configuration file is
+/// copied too, unless there's one already.
+pub fn restore(setup: &Setup, from: &Path) -> io::Result<Vec<String>> {
+    if !from.is_file() {
+        return restore_dir(setup, from);
+    }
+
+    let archive = path::absolute(from)?;
+    let staging = staging_dir()?;
+
+    let result = tar(
+        setup,
+        &[
+            "-xf",
+            &archive.to_string_lossy(),
+            "-C",
+            &staging.to_string_lossy(),
+        ],
+    )
+    .and_then(|_| restore_dir(setup, &staging));
+
+    let _ = fs::remove_dir_all(&staging);
+    result
+}
+
+fn restore_dir(setup: &Setup, dir: &Path) -> io::Result<Vec<String>> {
+    let manifest = fs::read_to_string(dir.join(MANIFEST))?;
+    let manifest: Manifest = serde_yaml::from_str(&manifest).map_err(io::Error::other)?;
+
+    fs::create_dir_all(&setup.almoxarife_data_dir)?;
+    let mut restored = Vec::new();
+
+    for entry in manifest.plugins {
+        let destination = setup.almoxarife_data_dir.join(&entry.name);
+
+        if destination.exists() {
+            continue;
+        }
+
+        let source = dir.join(&entry.path);
+
+        if entry.is_bundle() {
+            let source = source.to_string_lossy();
+            let destination = destination.to_string_lossy();
+            git(setup, dir, &["clone", "--quiet", &source, &destination])?;
+
+            // Point the checkout to where future updates will come from, as if
+            // it had been cloned from there.
+            let destination = Path::new(destination.as_ref());
+            let url = setup::clone_url(&entry.location);
+            git(setup, destination, &["remote", "set-url", "origin", &url])?;
+            git(
+                setup,
+                destination,
+                &["checkout", "--quiet", &entry.revision],
+            )?;
+        } else {
+            adopt::copy_dir(&source, &destination, &[])?;
+        }
+
+        restored.push(entry.name);
+    }
+
+    if !setup.almoxarife_yaml_path.exists() {
+        if let Some(parent) = setup.almoxarife_yaml_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::copy(dir.join("almoxarife.yaml"), &setup.almoxarife_yaml_path)?;
+    }
+
+    Ok(restored)
+}
+
+/// Creates an empty directory to assemble or unpack an archive in.
+fn staging_dir() -> io::Result<PathBuf> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let count = COUNT.fetch_add(1, Ordering::Relaxed);
+    let dir = env::temp_dir().join(format!("almoxarife-{}-{count}", process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Runs git inside `dir`, returning its trimmed output.
+fn git(setup: &Setup, dir: &Path, args: &[&str]) -> io::Result<String> {
+    run(setup, "git", dir, args)
+}
+
+fn tar(setup: &Setup, args: &[&str]) -> io::Result<String> {
+    run(setup, "tar", &env::temp_dir(), args)
+}
+
+fn run(setup: &Setup, program: &str, dir: &Path, args: &[&str]) -> io::Result<String> {
+    let output = setup
+        .runner
+        .output(Command::new(program).args(args).current_dir(dir), None)?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{program} {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
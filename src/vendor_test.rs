@@ -0,0 +1,155 @@
+use std::fs;
+use std::path::Path;
+
+use tempfile::TempDir;
+
+use crate::runner_test::FakeRunner;
+use crate::setup::Setup;
+use crate::vendor;
+use crate::vendor::Entry;
+
+const CONFIG: &str = "peneira:
+    location: https://github.com/gustavo-hms/peneira
+mine:
+    location: /home/me/kak-scripts
+";
+
+fn setup(root: &Path) -> Setup {
+    Setup {
+        almoxarife_yaml_path: root.join("config/almoxarife.yaml"),
+        almoxarife_data_dir: root.join("data"),
+        almoxarife_kak: root.join("autoload/almoxarife/almoxarife.kak"),
+        plugins_kak: root.join("plugins.kak"),
+        runner: FakeRunner::new().runner(),
+        ..Setup::default()
+    }
+}
+
+fn installed_setup(root: &Path) -> Setup {
+    let setup = setup(root);
+    fs::create_dir_all(root.join("config")).unwrap();
+    fs::write(&setup.almoxarife_yaml_path, CONFIG).unwrap();
+
+    let peneira = setup.almoxarife_data_dir.join("peneira");
+    fs::create_dir_all(peneira.join(".git")).unwrap();
+    fs::create_dir_all(peneira.join("rc")).unwrap();
+    fs::write(peneira.join("rc/peneira.kak"), "provide-module peneira %{}").unwrap();
+
+    fs::create_dir_all(root.join("autoload/almoxarife")).unwrap();
+    fs::write(&setup.almoxarife_kak, "hook global KakBegin .* %🧺🧺").unwrap();
+    setup
+}
+
+#[test]
+fn vendor_working_trees() {
+    let temp_dir = TempDir::new().unwrap();
+    let setup = installed_setup(temp_dir.path());
+    let plugins = setup.open_config_file().unwrap().active_plugins();
+    let dir = temp_dir.path().join("vendor");
+
+    let manifest = vendor::vendor(&setup, &plugins, &dir, false).unwrap();
+
+    // The local plugin isn't exported.
+    assert_eq!(
+        manifest.plugins,
+        vec![Entry {
+            name: "peneira".into(),
+            location: "https://github.com/gustavo-hms/peneira".into(),
+            revision: "abcdef".into(),
+            path: "plugins/peneira".into(),
+        }]
+    );
+
+    assert!(dir.join("plugins/peneira/rc/peneira.kak").exists());
+    assert!(!dir.join("plugins/peneira/.git").exists());
+    assert_eq!(
+        fs::read_to_string(dir.join("almoxarife.yaml")).unwrap(),
+        CONFIG
+    );
+    assert!(dir.join("almoxarife.kak").exists());
+    assert!(dir.join(vendor::MANIFEST).exists());
+}
+
+#[test]
+fn vendor_and_restore() {
+    let temp_dir = TempDir::new().unwrap();
+    let setup = installed_setup(&temp_dir.path().join("here"));
+    let plugins = setup.open_config_file().unwrap().active_plugins();
+    let dir = temp_dir.path().join("vendor");
+    vendor::vendor(&setup, &plugins, &dir, false).unwrap();
+
+    let elsewhere = self::setup(&temp_dir.path().join("elsewhere"));
+    let restored = vendor::restore(&elsewhere, &dir).unwrap();
+
+    assert_eq!(restored, vec!["peneira"]);
+    assert!(
+        elsewhere
+            .almoxarife_data_dir
+            .join("peneira/rc/peneira.kak")
+            .exists()
+    );
+    assert_eq!(
+        fs::read_to_string(&elsewhere.almoxarife_yaml_path).unwrap(),
+        CONFIG
+    );
+
+    // Restoring again leaves the installed plugins alone.
+    assert!(vendor::restore(&elsewhere, &dir).unwrap().is_empty());
+}
+
+#[test]
+fn vendor_and_restore_bundles() {
+    let temp_dir = TempDir::new().unwrap();
+    let setup = installed_setup(&temp_dir.path().join("here"));
+    let plugins = setup.open_config_file().unwrap().active_plugins();
+    let dir = temp_dir.path().join("vendor");
+
+    let manifest = vendor::vendor(&setup, &plugins, &dir, true).unwrap();
+    assert_eq!(
+        manifest.plugins[0].path,
+        Path::new("plugins/peneira.bundle")
+    );
+    assert!(dir.join("plugins/peneira.bundle").exists());
+
+    let elsewhere = self::setup(&temp_dir.path().join("elsewhere"));
+    vendor::restore(&elsewhere, &dir).unwrap();
+
+    let checkout = elsewhere.almoxarife_data_dir.join("peneira");
+    assert_eq!(
+        fs::read_to_string(checkout.join(".git/remotes/origin")).unwrap(),
+        "https://github.com/gustavo-hms/peneira.git\n"
+    );
+    assert_eq!(
+        fs::read_to_string(checkout.join("checkout.txt")).unwrap(),
+        "abcdef\n"
+    );
+}
+
+#[test]
+fn bundle_and_restore_archive() {
+    let temp_dir = TempDir::new().unwrap();
+    let setup = installed_setup(&temp_dir.path().join("here"));
+    let plugins = setup.open_config_file().unwrap().active_plugins();
+    let archive = temp_dir.path().join("plugins.tar");
+
+    let manifest = vendor::bundle(&setup, &plugins, &archive).unwrap();
+    assert_eq!(manifest.plugins.len(), 1);
+    assert!(archive.is_file());
+
+    let elsewhere = self::setup(&temp_dir.path().join("elsewhere"));
+    let restored = vendor::restore(&elsewhere, &archive).unwrap();
+
+    assert_eq!(restored, vec!["peneira"]);
+    assert!(elsewhere.almoxarife_data_dir.join("peneira/.git").exists());
+
+    // The restored configuration pins plugins to the bundled revisions.
+    assert_eq!(
+        fs::read_to_string(&elsewhere.almoxarife_yaml_path).unwrap(),
+        "peneira:
+    location: https://github.com/gustavo-hms/peneira
+    rev: abcdef
+mine:
+    location: /home/me/kak-scripts
+"
+    );
+}
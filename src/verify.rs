@@ -0,0 +1,119 @@
+use std::fmt::Display;
+use std::fmt::Formatter;
+
+use crate::setup::Plugin;
+use crate::setup::PluginError;
+
+/// How a checkout differs from what the configuration file pins and the last
+/// update left.
+#[derive(Debug, PartialEq)]
+pub enum Drift {
+    /// There's no checkout at all.
+    Missing,
+    /// Files tracked by git were changed.
+    Modified,
+    /// The checkout isn't at the revision its plugin is pinned to, as after a
+    /// pull by hand.
+    Moved { revision: String, pinned: String },
+    /// The checkout has commits its upstream branch doesn't.
+    LocalCommits(usize),
+}
+
+impl Display for Drift {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Drift::Missing => write!(f, "missing; run al to install it"),
+            Drift::Modified => write!(f, "has changed files"),
+            Drift::Moved { revision, pinned } => {
+                write!(f, "at {}, pinned to {}", short(revision), short(pinned))
+            }
+            Drift::LocalCommits(1) => write!(f, "has a commit of its own"),
+            Drift::LocalCommits(count) => write!(f, "has {count} commits of its own"),
+        }
+    }
+}
+
+/// The ways the checkout of `plugin` drifted, none if it's as expected.
+/// Plugins that aren't git checkouts Almoxarife manages, like local ones,
+/// never drift.
+pub fn drift(plugin: &Plugin) -> Result<Vec<Drift>, PluginError> {
+    if !plugin.is_checkout() {
+        return Ok(Vec::new());
+    }
+
+    if !plugin.is_installed() {
+        return Ok(vec![Drift::Missing]);
+    }
+
+    let mut drift = Vec::new();
+
+    // Untracked files are left out: build commands leave their products
+    // around.
+    let status = plugin.git(
+        &["status", "--porcelain", "--untracked-files=no"],
+        PluginError::Verify,
+    )?;
+
+    if !status.trim().is_empty() {
+        drift.push(Drift::Modified);
+    }
+
+    let revision = plugin.current_revision()?;
+
+    if let Some(pinned) = pinned_revision(plugin)? {
+        if revision != pinned {
+            drift.push(Drift::Moved { revision, pinned });
+        }
+    } else if plugin.tag_pattern.is_none() {
+        let count = plugin.git(
+            &["rev-list", "--count", "@{upstream}..HEAD"],
+            PluginError::Verify,
+        )?;
+
+        match count.trim().parse() {
+            Ok(0) | Err(_) => (),
+            Ok(count) => drift.push(Drift::LocalCommits(count)),
+        }
+    }
+
+    Ok(drift)
+}
+
+/// Resets the checkout of `plugin` to what it should be: the revision it's
+/// pinned to, its upstream branch, or, for plugins following tags, the tag
+/// it's at. Changed files and commits of its own are thrown away.
+pub fn fix(plugin: &Plugin) -> Result<(), PluginError> {
+    let target = match pinned_revision(plugin)? {
+        Some(pinned) => pinned,
+        None if plugin.tag_pattern.is_some() => "HEAD".to_string(),
+        None => "@{upstream}".to_string(),
+    };
+
+    plugin
+        .git(&["reset", "--quiet", "--hard", &target], PluginError::Pull)
+        .map(|_| ())
+}
+
+/// The commit the plugin is pinned to by its `rev`, if any.
+fn pinned_revision(plugin: &Plugin) -> Result<Option<String>, PluginError> {
+    let Some(rev) = &plugin.rev else {
+        return Ok(None);
+    };
+
+    let commit = plugin.git(
+        &[
+            "rev-parse",
+            "--verify",
+            "--quiet",
+            &format!("{rev}^{{commit}}"),
+        ],
+        PluginError::Verify,
+    )?;
+
+    Ok(Some(commit.trim().to_string()))
+}
+
+/// The abbreviated form of a commit hash.
+fn short(revision: &str) -> &str {
+    revision.get(..7).unwrap_or(revision)
+}
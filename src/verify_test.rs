@@ -0,0 +1,73 @@
+use std::fs;
+
+use crate::setup::Plugin;
+use crate::setup_test::pinned_plugin;
+use crate::verify;
+use crate::verify::Drift;
+
+fn plugin(dir: &tempfile::TempDir, rev: &str) -> Plugin {
+    let repository_path = dir.path().join("kakoune-phantom-selection");
+    fs::create_dir_all(repository_path.join(".git")).unwrap();
+    pinned_plugin(repository_path, dir.path().join("link"), rev)
+}
+
+#[test]
+fn pinned_drift() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let plugin = plugin(&temp_dir, "abcdef");
+    assert_eq!(verify::drift(&plugin), Ok(Vec::new()));
+
+    let git = plugin.repository_path.join(".git");
+    fs::write(git.join("almoxarife-test-status"), " M rc/plugin.kak\n").unwrap();
+    assert_eq!(verify::drift(&plugin), Ok(vec![Drift::Modified]));
+
+    let mut plugin = plugin;
+    plugin.rev = Some("v1.0".into());
+    let drift = verify::drift(&plugin).unwrap();
+    assert_eq!(
+        drift,
+        vec![
+            Drift::Modified,
+            Drift::Moved {
+                revision: "abcdef".into(),
+                pinned: "v1.0".into()
+            }
+        ]
+    );
+    assert_eq!(drift[1].to_string(), "at abcdef, pinned to v1.0");
+
+    verify::fix(&plugin).unwrap();
+    assert_eq!(verify::drift(&plugin), Ok(Vec::new()));
+}
+
+#[test]
+fn branch_drift() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let mut plugin = plugin(&temp_dir, "");
+    plugin.rev = None;
+
+    let git = plugin.repository_path.join(".git");
+    fs::write(git.join("almoxarife-test-ahead"), "2\n").unwrap();
+    let drift = verify::drift(&plugin).unwrap();
+    assert_eq!(drift, vec![Drift::LocalCommits(2)]);
+    assert_eq!(drift[0].to_string(), "has 2 commits of its own");
+
+    verify::fix(&plugin).unwrap();
+    assert_eq!(verify::drift(&plugin), Ok(Vec::new()));
+    assert_eq!(
+        fs::read_to_string(plugin.repository_path.join("checkout.txt")).unwrap(),
+        "rewritten\n"
+    );
+}
+
+#[test]
+fn missing_and_local() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let mut plugin = plugin(&temp_dir, "abcdef");
+    fs::remove_dir_all(&plugin.repository_path).unwrap();
+    assert_eq!(verify::drift(&plugin), Ok(vec![Drift::Missing]));
+
+    // Local checkouts are the user's own business.
+    plugin.is_local = true;
+    assert_eq!(verify::drift(&plugin), Ok(Vec::new()));
+}
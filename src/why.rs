@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+
+use crate::setup::Plugin;
+
+/// Why the plugin `name` is among `plugins`, the active ones, as lines to
+/// print: where it comes from, the plugins depending on it, and the ones that
+/// would break if it were removed. `None` if it isn't among them.
+pub fn explain(plugins: &[Plugin], name: &str) -> Option<Vec<String>> {
+    let plugin = plugins.iter().find(|plugin| plugin.name == name)?;
+
+    let dependencies: HashMap<&str, Vec<String>> = plugins
+        .iter()
+        .map(|plugin| {
+            let names = plugin.manifest().dependencies.into_keys().collect();
+            (plugin.name.as_str(), names)
+        })
+        .collect();
+
+    let dependents = |name: &str| -> Vec<&str> {
+        let mut dependents: Vec<&str> = plugins
+            .iter()
+            .map(|plugin| plugin.name.as_str())
+            .filter(|dependent| dependencies[dependent].iter().any(|d| d == name))
+            .collect();
+        dependents.sort();
+        dependents
+    };
+
+    let mut lines = vec![match (&plugin.parent, &plugin.required_by) {
+        (Some(parent), _) => format!(
+            "{name} is configured in almoxarife.yaml, as a child of {parent}, which it loads after"
+        ),
+        (None, Some(dependent)) => format!(
+            "{name} isn't in almoxarife.yaml; it's installed because the almoxarife.toml of {dependent} depends on it"
+        ),
+        (None, None) => format!("{name} is configured in almoxarife.yaml"),
+    }];
+
+    let direct = dependents(name);
+
+    if !direct.is_empty() {
+        lines.push(format!("Depended on by: {}", direct.join(", ")));
+    }
+
+    // Children only load once their parents do, and dependents need their
+    // dependencies, so both break along with what they rely on.
+    let mut broken: BTreeMap<&str, String> = BTreeMap::new();
+    let mut queue = vec![name];
+
+    while let Some(current) = queue.pop() {
+        let children = plugins
+            .iter()
+            .filter(|plugin| plugin.parent.as_deref() == Some(current))
+            .map(|plugin| (plugin.name.as_str(), format!("a child of {current}")));
+
+        let dependents = dependents(current)
+            .into_iter()
+            .map(|dependent| (dependent, format!("depends on {current}")));
+
+        for (plugin, reason) in children.chain(dependents) {
+            if plugin != name && !broken.contains_key(plugin) {
+                broken.insert(plugin, reason);
+                queue.push(plugin);
+            }
+        }
+    }
+
+    if broken.is_empty() {
+        lines.push("Removing it would break nothing else".to_string());
+    } else {
+        lines.push("Removing it would break:".to_string());
+
+        for (plugin, reason) in broken {
+            lines.push(format!("  {plugin} ({reason})"));
+        }
+    }
+
+    Some(lines)
+}
@@ -0,0 +1,77 @@
+use std::fs;
+
+use crate::setup::Setup;
+use crate::why;
+
+#[test]
+fn explain_plugins() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let manifest = |name: &str, manifest: &str| {
+        let path = temp_dir.path().join(name);
+        fs::create_dir_all(&path).unwrap();
+        fs::write(path.join("almoxarife.toml"), manifest).unwrap();
+    };
+
+    manifest(
+        "peneira",
+        "dependencies.luar = 'https://github.com/gustavo-hms/luar'",
+    );
+    manifest(
+        "kak-fzf",
+        "dependencies.peneira = 'https://github.com/gustavo-hms/peneira'",
+    );
+
+    let file = b"
+peneira:
+    location: https://github.com/gustavo-hms/peneira
+
+    peneira-filters:
+        location: https://github.com/gustavo-hms/peneira-filters
+
+kak-fzf:
+    location: https://github.com/andreyorst/fzf.kak
+
+auto-pairs:
+    location: https://github.com/alexherbo2/auto-pairs.kak
+";
+
+    let setup = Setup {
+        almoxarife_data_dir: temp_dir.path().into(),
+        ..Default::default()
+    };
+
+    let plugins = setup
+        .config_from_buffer(file.as_slice())
+        .unwrap()
+        .active_plugins();
+
+    assert_eq!(
+        why::explain(&plugins, "luar").unwrap(),
+        vec![
+            "luar isn't in almoxarife.yaml; it's installed because the almoxarife.toml of peneira depends on it",
+            "Depended on by: peneira",
+            "Removing it would break:",
+            "  kak-fzf (depends on peneira)",
+            "  peneira (depends on luar)",
+            "  peneira-filters (a child of peneira)",
+        ]
+    );
+
+    assert_eq!(
+        why::explain(&plugins, "peneira-filters").unwrap(),
+        vec![
+            "peneira-filters is configured in almoxarife.yaml, as a child of peneira, which it loads after",
+            "Removing it would break nothing else",
+        ]
+    );
+
+    assert_eq!(
+        why::explain(&plugins, "auto-pairs").unwrap(),
+        vec![
+            "auto-pairs is configured in almoxarife.yaml",
+            "Removing it would break nothing else",
+        ]
+    );
+
+    assert_eq!(why::explain(&plugins, "kak-lsp"), None);
+}
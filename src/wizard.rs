@@ -0,0 +1,317 @@
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+
+use colorized::Color;
+use colorized::Colors;
+
+use crate::adopt;
+use crate::prompt::Prompt;
+use crate::setup::Setup;
+
+/// A plugin to be written to a freshly created `almoxarife.yaml`.
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub location: String,
+    pub config: String,
+}
+
+/// Well known plugins offered to new users.
+pub const STARTER_PLUGINS: &[(&str, &str, &str)] = &[
+    (
+        "auto-pairs",
+        "https://github.com/alexherbo2/auto-pairs.kak",
+        "auto-close pairs of characters",
+    ),
+    (
+        "kakoune-phantom-selection",
+        "https://github.com/occivink/kakoune-phantom-selection",
+        "work on multiple selections one at a time",
+    ),
+    (
+        "kakoune-state-save",
+        "https://gitlab.com/Screwtapello/kakoune-state-save",
+        "save registers between sessions",
+    ),
+    (
+        "kakoune-gdb",
+        "https://github.com/occivink/kakoune-gdb",
+        "gdb integration",
+    ),
+    (
+        "luar",
+        "https://github.com/gustavo-hms/luar",
+        "script Kakoune using Lua",
+    ),
+];
+
+/// Guides the user through the creation of `almoxarife.yaml`.
+pub struct Wizard<'setup, R: BufRead, W: Write> {
+    setup: &'setup Setup,
+    prompt: Prompt<R, W>,
+}
+
+impl<'setup, R: BufRead, W: Write> Wizard<'setup, R, W> {
+    pub fn new(setup: &'setup Setup, input: R, output: W) -> Self {
+        Wizard {
+            setup,
+            prompt: Prompt::new(input, output),
+        }
+    }
+
+    /// Runs the wizard, returning the contents of the configuration file, or
+    /// `None` if the user gave up.
+    pub fn run(&mut self) -> io::Result<Option<String>> {
+        writeln!(
+            self.prompt.output,
+            "{}\n",
+            "Welcome to Almoxarife!".color(Colors::GreenFg)
+        )?;
+
+        match self.setup.kakoune_version() {
+            Some(version) => writeln!(self.prompt.output, "Found {version}.")?,
+            None => {
+                writeln!(self.prompt.output, "Kakoune was not found in your PATH.")?;
+
+                if !self.prompt.confirm("Continue anyway?")? {
+                    return Ok(None);
+                }
+            }
+        }
+
+        writeln!(
+            self.prompt.output,
+            "
+Almoxarife will use the following paths:
+
+ configuration file: {}
+ plugins' checkouts: {}
+ Kakoune's autoload: {}
+
+You can change them with the XDG_CONFIG_HOME and XDG_DATA_HOME environment
+variables.
+",
+            self.setup.almoxarife_yaml_path.to_string_lossy(),
+            self.setup.almoxarife_data_dir.to_string_lossy(),
+            self.setup.autoload_dir.to_string_lossy(),
+        )?;
+
+        if !self.prompt.confirm("Proceed with these paths?")? {
+            return Ok(None);
+        }
+
+        let mut entries = Vec::new();
+
+        if let Ok(kakrc) = fs::read_to_string(&self.setup.kakrc) {
+            let imported = plug_kak_plugins(&kakrc);
+
+            if !imported.is_empty() {
+                let names: Vec<_> = imported.iter().map(|entry| entry.name.as_str()).collect();
+                writeln!(
+                    self.prompt.output,
+                    "\nYour kakrc installs the following plugins with plug.kak: {}",
+                    names.join(", ")
+                )?;
+
+                if self.prompt.confirm("Import them?")? {
+                    entries = imported;
+                }
+            }
+        }
+
+        writeln!(self.prompt.output, "\nSome plugins you may like:\n")?;
+
+        for (i, (name, _, description)) in STARTER_PLUGINS.iter().enumerate() {
+            writeln!(self.prompt.output, " {}. {name}: {description}", i + 1)?;
+        }
+
+        let answer = self
+            .prompt
+            .ask("\nPick any by number (e.g. 1 3), or leave empty:")?;
+
+        for choice in answer.split([' ', ',']).filter(|choice| !choice.is_empty()) {
+            let Some((name, location, _)) = choice
+                .parse::<usize>()
+                .ok()
+                .and_then(|i| STARTER_PLUGINS.get(i.checked_sub(1)?))
+            else {
+                writeln!(self.prompt.output, "Ignoring unknown choice {choice}")?;
+                continue;
+            };
+
+            if !entries.iter().any(|entry| entry.name == *name) {
+                entries.push(Entry {
+                    name: name.to_string(),
+                    location: location.to_string(),
+                    config: String::new(),
+                });
+            }
+        }
+
+        if entries.is_empty() {
+            writeln!(
+                self.prompt.output,
+                "\nNo plugins chosen. Run `al --config` to add some."
+            )?;
+            return Ok(None);
+        }
+
+        Ok(Some(render_config(&entries)))
+    }
+}
+
+/// Finds the plugins installed with plug.kak in a kakrc, along with their
+/// `config` blocks.
+pub fn plug_kak_plugins(kakrc: &str) -> Vec<Entry> {
+    let mut entries = Vec::new();
+    let mut rest = kakrc;
+
+    while let Some(start) = find_plug_command(rest) {
+        rest = &rest[start + "plug".len()..];
+
+        let Some((repo, after)) = quoted(rest.trim_start()) else {
+            continue;
+        };
+
+        rest = after;
+
+        // plug.kak manages itself.
+        if repo.ends_with("plug.kak") {
+            continue;
+        }
+
+        let location = if repo.contains("://") || repo.starts_with("git@") {
+            adopt::location_from_url(repo)
+        } else {
+            format!("https://github.com/{repo}")
+        };
+
+        let name = location
+            .trim_end_matches('/')
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .trim_end_matches(".kak")
+            .to_string();
+
+        // The config block, if any, must come before the next command. It may
+        // follow `config` or stand on its own, as the last argument. The
+        // blocks of `do`, run in the shell after updates, and of `defer` and
+        // `demand` aren't configs.
+        let mut config = String::new();
+
+        loop {
+            let command_end = rest.find('\n').unwrap_or(rest.len());
+
+            let Some(position) = rest[..command_end].find("%{") else {
+                break;
+            };
+
+            let words: Vec<&str> = rest[..position].split_whitespace().collect();
+            let body = &rest[position + "%{".len()..];
+            let body_end = balanced_end(body);
+
+            if !matches!(words.as_slice(), [.., "do"] | [.., "defer" | "demand", _]) {
+                config = dedent(&body[..body_end]);
+            }
+
+            rest = body.get(body_end + 1..).unwrap_or_default();
+        }
+
+        entries.push(Entry {
+            name,
+            location,
+            config,
+        });
+    }
+
+    entries
+}
+
+/// Finds the next `plug` command at the beginning of a line.
+fn find_plug_command(source: &str) -> Option<usize> {
+    let mut offset = 0;
+
+    for line in source.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+
+        if trimmed.starts_with("plug ") || trimmed.starts_with("plug\t") {
+            return Some(offset + line.len() - trimmed.len());
+        }
+
+        offset += line.len();
+    }
+
+    None
+}
+
+fn quoted(text: &str) -> Option<(&str, &str)> {
+    let quote = text.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+    let end = text[1..].find(quote)? + 1;
+    Some((&text[1..end], &text[end + 1..]))
+}
+
+/// The position of the brace closing a block whose opening brace was just
+/// consumed.
+fn balanced_end(body: &str) -> usize {
+    let mut depth = 0;
+
+    for (i, c) in body.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return i,
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+
+    body.len()
+}
+
+fn dedent(block: &str) -> String {
+    let lines: Vec<&str> = block
+        .trim_matches('\n')
+        .lines()
+        .map(|line| line.trim_end())
+        .collect();
+
+    let indentation = lines
+        .iter()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| line.get(indentation..).unwrap_or_default())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Renders the entries in the format of `almoxarife.yaml`.
+pub fn render_config(entries: &[Entry]) -> String {
+    entries
+        .iter()
+        .map(|entry| {
+            let mut yaml = format!("{}:\n  location: {}\n", entry.name, entry.location);
+
+            if !entry.config.is_empty() {
+                yaml.push_str("  config: |\n");
+
+                for line in entry.config.lines() {
+                    if line.is_empty() {
+                        yaml.push('\n');
+                    } else {
+                        yaml.push_str(&format!("    {line}\n"));
+                    }
+                }
+            }
+
+            yaml
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
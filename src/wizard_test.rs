@@ -0,0 +1,167 @@
+use std::fs;
+
+use crate::runner_test::FakeRunner;
+use crate::setup::Setup;
+use crate::wizard;
+use crate::wizard::Entry;
+use crate::wizard::Wizard;
+
+#[test]
+fn plug_kak_plugins() {
+    let kakrc = r#"
+source "%val{config}/plugins/plug.kak/rc/plug.kak"
+plug "andreyorst/plug.kak" noload
+
+plug "occivink/kakoune-phantom-selection" config %{
+    map global normal f ': phantom-selection-add-selection<ret>'
+    hook global WinCreate .* %{
+        nop
+    }
+}
+
+set-option global tabstop 4
+  plug 'alexherbo2/auto-pairs.kak'
+plug "https://gitlab.com/Screwtapello/kakoune-state-save.git"
+
+plug "andreyorst/smarttab.kak" %{
+    set-option global softtabstop 4
+}
+
+plug "kak-lsp/kak-lsp" do %{
+    cargo install --locked --force --path .
+} %{
+    lsp-enable
+}
+
+plug "alexherbo2/prelude.kak" demand prelude %{
+    nop
+}
+"#;
+
+    assert_eq!(
+        wizard::plug_kak_plugins(kakrc),
+        [
+            Entry {
+                name: "kakoune-phantom-selection".into(),
+                location: "https://github.com/occivink/kakoune-phantom-selection".into(),
+                config: "map global normal f ': phantom-selection-add-selection<ret>'
+hook global WinCreate .* %{
+    nop
+}"
+                .into()
+            },
+            Entry {
+                name: "auto-pairs".into(),
+                location: "https://github.com/alexherbo2/auto-pairs.kak".into(),
+                config: String::new(),
+            },
+            Entry {
+                name: "kakoune-state-save".into(),
+                location: "https://gitlab.com/Screwtapello/kakoune-state-save".into(),
+                config: String::new(),
+            },
+            Entry {
+                name: "smarttab".into(),
+                location: "https://github.com/andreyorst/smarttab.kak".into(),
+                config: "set-option global softtabstop 4".into(),
+            },
+            Entry {
+                name: "kak-lsp".into(),
+                location: "https://github.com/kak-lsp/kak-lsp".into(),
+                config: "lsp-enable".into(),
+            },
+            Entry {
+                name: "prelude".into(),
+                location: "https://github.com/alexherbo2/prelude.kak".into(),
+                config: String::new(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn render_config() {
+    let entries = [
+        Entry {
+            name: "kakoune-phantom-selection".into(),
+            location: "https://github.com/occivink/kakoune-phantom-selection".into(),
+            config: "map global normal f ': phantom-selection-add-selection<ret>'\n\nnop".into(),
+        },
+        Entry {
+            name: "auto-pairs".into(),
+            location: "https://github.com/alexherbo2/auto-pairs.kak".into(),
+            config: String::new(),
+        },
+    ];
+
+    let yaml = wizard::render_config(&entries);
+    assert_eq!(
+        yaml,
+        "kakoune-phantom-selection:
+  location: https://github.com/occivink/kakoune-phantom-selection
+  config: |
+    map global normal f ': phantom-selection-add-selection<ret>'
+
+    nop
+
+auto-pairs:
+  location: https://github.com/alexherbo2/auto-pairs.kak
+"
+    );
+
+    // The rendered file must be understood by the parser.
+    let setup = Setup::default();
+    let config = setup.config_from_buffer(yaml.as_bytes()).unwrap();
+    assert_eq!(config.active_plugins().len(), 2);
+}
+
+#[test]
+fn wizard_imports_and_picks_plugins() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let kakrc = temp_dir.path().join("kakrc");
+    fs::write(&kakrc, "plug \"occivink/kakoune-gdb\"\n").unwrap();
+
+    let setup = Setup {
+        kakrc,
+        runner: FakeRunner::new().runner(),
+        ..Default::default()
+    };
+
+    // Accept the paths, import from plug.kak and pick the first starter plugin
+    // plus an invalid choice.
+    let input = "\ny\n1 42\n";
+
+    let mut output = Vec::new();
+    let yaml = Wizard::new(&setup, input.as_bytes(), &mut output)
+        .run()
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(
+        yaml,
+        "kakoune-gdb:
+  location: https://github.com/occivink/kakoune-gdb
+
+auto-pairs:
+  location: https://github.com/alexherbo2/auto-pairs.kak
+"
+    );
+
+    assert!(String::from_utf8_lossy(&output).contains("Ignoring unknown choice 42"));
+}
+
+#[test]
+fn wizard_refused_paths() {
+    let setup = Setup {
+        runner: FakeRunner::new().runner(),
+        ..Default::default()
+    };
+
+    let mut output = Vec::new();
+    let yaml = Wizard::new(&setup, "n\n".as_bytes(), &mut output)
+        .run()
+        .unwrap();
+
+    assert_eq!(yaml, None);
+    assert!(String::from_utf8_lossy(&output).contains("Found Kakoune v2024.05.18."));
+}